@@ -0,0 +1,312 @@
+// The Win32 calls managing the lifetime of a process, behind a trait so they
+// can be replaced by a fake in tests (see the `test-util` feature).
+
+use crate::{
+    attributes::{
+        AttributeList, PROC_THREAD_ATTRIBUTE_HANDLE_LIST, PROC_THREAD_ATTRIBUTE_PROTECTION_LEVEL,
+        PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE,
+    },
+    wait::{deadline, remaining_millis, wait_result},
+    wide, Error, Result,
+};
+use std::{
+    ffi::{c_void, OsStr},
+    fmt, io,
+    mem::size_of,
+    os::windows::io::RawHandle,
+    path::Path,
+    time::Duration,
+};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        System::Threading::{
+            CreateProcessAsUserW, CreateProcessW, GetExitCodeProcess, TerminateProcess,
+            WaitForSingleObject, CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT,
+            PROCESS_CREATION_FLAGS, PROCESS_INFORMATION, STARTF_USESTDHANDLES, STARTUPINFOEXW,
+            STARTUPINFOW, STARTUPINFOW_FLAGS,
+        },
+    },
+};
+
+/// What to create with [`ProcessApi::create_process`].
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessSpec<'a> {
+    /// The command line of the process.
+    pub command_line: &'a OsStr,
+    /// The program to execute, found from the command line if `None`.
+    pub application_name: Option<&'a Path>,
+    /// The working directory of the process, the one of the calling process
+    /// if `None`.
+    pub current_directory: Option<&'a Path>,
+    /// Whether the process inherits the inheritable handles.
+    pub inherit_handles: bool,
+    /// The only handles the process inherits, given in the attribute list of
+    /// the extended startup information. With `inherit_handles`, the process
+    /// inherits all the inheritable handles if `None`.
+    pub handle_list: Option<&'a [RawHandle]>,
+    /// The process creation flags.
+    pub creation_flags: u32,
+    /// The flags of the startup information, other than
+    /// `STARTF_USESTDHANDLES`.
+    pub startup_flags: u32,
+    /// The title of the startup information, an AppUserModelID with the
+    /// `STARTF_TITLEISAPPID` flag.
+    pub title: Option<&'a OsStr>,
+    /// The window station and desktop of the process, like
+    /// `winsta0\default`, the ones of the calling process if `None`.
+    pub desktop: Option<&'a OsStr>,
+    /// The hotkey of the process with the `STARTF_USEHOTKEY` flag, given in
+    /// place of the standard input.
+    pub hotkey: Option<u16>,
+    /// The `lpReserved2` block of the startup information, read by the C
+    /// runtime of the process.
+    pub reserved2: Option<&'a [u8]>,
+    /// The protection level of the process, a `PROTECTION_LEVEL_*` value
+    /// given in the attribute list of the extended startup information.
+    pub protection_level: Option<u32>,
+    /// The primary token the process runs with, the token of the calling
+    /// process if `None`.
+    pub token: Option<RawHandle>,
+    /// The environment block of the process, a sequence of `name=value`
+    /// wide strings terminated by an empty string. The environment of the
+    /// calling process if `None`.
+    pub environment: Option<&'a [u16]>,
+    /// The handles of the standard input, output and error, if they are
+    /// configured.
+    pub stdio: Option<[RawHandle; 3]>,
+    /// The pseudoconsole (`HPCON`) the process is attached to, given in the
+    /// attribute list of the extended startup information. The process
+    /// doesn't get the standard handles of the calling process then.
+    pub pseudoconsole: Option<RawHandle>,
+}
+
+/// A process created by [`ProcessApi::create_process`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawProcess {
+    /// The handle of the process.
+    pub process: RawHandle,
+    /// The handle of the main thread of the process.
+    pub thread: RawHandle,
+    /// The identifier of the process.
+    pub pid: u32,
+    /// The identifier of the main thread of the process.
+    pub thread_id: u32,
+}
+
+/// The calls managing the lifetime of a process.
+///
+/// The children spawned with [`Command::spawn`][crate::Command::spawn] use
+/// the Win32 API. A fake implementation, like
+/// [`MockProcessApi`][crate::MockProcessApi], can be given to
+/// [`Command::spawn_with_api`][crate::Command::spawn_with_api] to test the
+/// code handling the children without spawning processes.
+///
+/// Only spawning, waiting, killing and closing the handles go through this
+/// trait. The other methods of [`Child`][crate::Child] use the Win32 API with
+/// the process handle.
+///
+/// Requires the `test-util` feature to be implemented outside of this crate.
+pub trait ProcessApi: fmt::Debug + Send + Sync {
+    /// Creates a process.
+    fn create_process(&self, spec: &ProcessSpec<'_>) -> Result<RawProcess>;
+
+    /// Terminates a process, which exits with `exit_code`.
+    fn terminate(&self, process: RawHandle, exit_code: u32) -> Result<()>;
+
+    /// Waits for a process to exit, returning `false` if `timeout` elapsed
+    /// first. `None` waits forever.
+    fn wait(&self, process: RawHandle, timeout: Option<Duration>) -> Result<bool>;
+
+    /// Returns the exit code of a process, `STATUS_PENDING` (`259`) if it is
+    /// still running.
+    fn get_exit_code(&self, process: RawHandle) -> Result<u32>;
+
+    /// Closes a handle returned by [`create_process`][ProcessApi::create_process].
+    fn close_handle(&self, handle: RawHandle);
+}
+
+// The Win32 API.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Win32ProcessApi;
+
+impl ProcessApi for Win32ProcessApi {
+    fn create_process(&self, spec: &ProcessSpec<'_>) -> Result<RawProcess> {
+        let mut startup_information_ex = STARTUPINFOEXW::default();
+        let startup_information = &mut startup_information_ex.StartupInfo;
+        let mut process_information = PROCESS_INFORMATION::default();
+        let mut creation_flags = PROCESS_CREATION_FLAGS(spec.creation_flags);
+
+        startup_information.cb = size_of::<STARTUPINFOW>() as u32;
+        startup_information.dwFlags = STARTUPINFOW_FLAGS(spec.startup_flags);
+
+        if let Some([stdin, stdout, stderr]) = spec.stdio {
+            startup_information.dwFlags |= STARTF_USESTDHANDLES;
+            startup_information.hStdInput = handle(stdin);
+            startup_information.hStdOutput = handle(stdout);
+            startup_information.hStdError = handle(stderr);
+        } else if spec.pseudoconsole.is_some() {
+            // Null standard handles, so the process uses the ones of the
+            // pseudoconsole instead of the ones of the calling process.
+            startup_information.dwFlags |= STARTF_USESTDHANDLES;
+        }
+
+        if let Some(reserved2) = spec.reserved2 {
+            startup_information.cbReserved2 = reserved2.len() as u16;
+            startup_information.lpReserved2 = reserved2.as_ptr() as *mut u8;
+        }
+
+        if let Some(hotkey) = spec.hotkey {
+            startup_information.hStdInput = HANDLE(hotkey as isize);
+        }
+
+        // Convert command to a wide string with a null terminator.
+        let mut command_wide = wide::to_wide(spec.command_line)?;
+
+        // The wide strings have to live until the process is created.
+        let mut title_wide = spec.title.map(wide::to_wide).transpose()?;
+
+        if let Some(title_wide) = &mut title_wide {
+            startup_information.lpTitle = PWSTR(title_wide.as_mut_ptr());
+        }
+
+        let mut desktop_wide = spec.desktop.map(wide::to_wide).transpose()?;
+
+        if let Some(desktop_wide) = &mut desktop_wide {
+            startup_information.lpDesktop = PWSTR(desktop_wide.as_mut_ptr());
+        }
+
+        // The attributes and their values have to live until the process is
+        // created too.
+        let mut attributes = None;
+        let count = spec.protection_level.is_some() as u32
+            + spec.handle_list.is_some() as u32
+            + spec.pseudoconsole.is_some() as u32;
+
+        if count > 0 {
+            let list = attributes.insert(AttributeList::new(count)?);
+
+            if let Some(protection_level) = &spec.protection_level {
+                unsafe { list.set(PROC_THREAD_ATTRIBUTE_PROTECTION_LEVEL, protection_level)? };
+            }
+
+            if let Some(handle_list) = spec.handle_list {
+                unsafe { list.set_slice(PROC_THREAD_ATTRIBUTE_HANDLE_LIST, handle_list)? };
+            }
+
+            if let Some(pseudoconsole) = spec.pseudoconsole {
+                unsafe { list.set_handle(PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE, pseudoconsole)? };
+            }
+
+            startup_information.cb = size_of::<STARTUPINFOEXW>() as u32;
+            startup_information_ex.lpAttributeList = list.as_raw();
+            creation_flags |= EXTENDED_STARTUPINFO_PRESENT;
+        }
+
+        let environment = spec.environment.map(|block| {
+            creation_flags |= CREATE_UNICODE_ENVIRONMENT;
+            block.as_ptr() as *const c_void
+        });
+
+        let application_name_wide = spec
+            .application_name
+            .map(|path| wide::to_wide(path.as_os_str()))
+            .transpose()?;
+        let application_name_ptr = application_name_wide
+            .as_ref()
+            .map_or(std::ptr::null(), |wide_path| wide_path.as_ptr());
+
+        let current_directory_wide = spec
+            .current_directory
+            .map(|path| wide::to_wide(path.as_os_str()))
+            .transpose()?;
+        let current_directory_ptr = current_directory_wide
+            .as_ref()
+            .map_or(std::ptr::null(), |wide_path| wide_path.as_ptr());
+
+        let res = unsafe {
+            match spec.token {
+                Some(token) => CreateProcessAsUserW(
+                    handle(token),
+                    PCWSTR(application_name_ptr),
+                    PWSTR(command_wide.as_mut_ptr()),
+                    None,
+                    None,
+                    spec.inherit_handles,
+                    creation_flags,
+                    environment,
+                    PCWSTR(current_directory_ptr),
+                    &startup_information_ex.StartupInfo,
+                    &mut process_information,
+                ),
+                None => CreateProcessW(
+                    PCWSTR(application_name_ptr),
+                    PWSTR(command_wide.as_mut_ptr()),
+                    None,
+                    None,
+                    spec.inherit_handles,
+                    creation_flags,
+                    environment,
+                    PCWSTR(current_directory_ptr),
+                    &startup_information_ex.StartupInfo,
+                    &mut process_information,
+                ),
+            }
+        };
+
+        match res {
+            Ok(()) => {
+                #[cfg(feature = "leak-check")]
+                crate::leak_check::opened(2);
+
+                Ok(RawProcess {
+                    process: process_information.hProcess.0 as RawHandle,
+                    thread: process_information.hThread.0 as RawHandle,
+                    pid: process_information.dwProcessId,
+                    thread_id: process_information.dwThreadId,
+                })
+            }
+            Err(_) => Err(Error::CreationFailed(io::Error::last_os_error())),
+        }
+    }
+
+    fn terminate(&self, process: RawHandle, exit_code: u32) -> Result<()> {
+        unsafe {
+            TerminateProcess(handle(process), exit_code)
+                .map_err(|_| Error::KillFailed(io::Error::last_os_error()))
+        }
+    }
+
+    fn wait(&self, process: RawHandle, timeout: Option<Duration>) -> Result<bool> {
+        // Rounded up, so a timeout below a millisecond isn't a poll.
+        let millis = remaining_millis(deadline(timeout));
+        let res = unsafe { WaitForSingleObject(handle(process), millis) };
+
+        wait_result(res, 1).map(|index| index.is_some())
+    }
+
+    fn get_exit_code(&self, process: RawHandle) -> Result<u32> {
+        let mut exit_code: u32 = 0;
+
+        unsafe {
+            GetExitCodeProcess(handle(process), &mut exit_code as *mut u32)
+                .map_err(|_| Error::GetExitCodeFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok(exit_code)
+    }
+
+    fn close_handle(&self, handle: RawHandle) {
+        unsafe {
+            let _ = CloseHandle(self::handle(handle));
+        }
+
+        #[cfg(feature = "leak-check")]
+        crate::leak_check::closed(1);
+    }
+}
+
+fn handle(handle: RawHandle) -> HANDLE {
+    HANDLE(handle as isize)
+}
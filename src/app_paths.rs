@@ -0,0 +1,169 @@
+// Resolving a program with the `App Paths` registry key, like the Run dialog
+// and `ShellExecuteExW` do, when `CreateProcessW` doesn't find it.
+//
+// The key of a program is `<name>.exe`, under the key of the current user,
+// then the one of the machine. Its default value is the path of the program,
+// and its `Path` value a directory to append to the `PATH` of the child.
+
+use crate::{os_code, validation, wide, Child, Command, Error, ProcessApi, Result};
+use std::{
+    ffi::{c_void, OsStr, OsString},
+    os::windows::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::ERROR_FILE_NOT_FOUND,
+        System::Registry::{
+            RegGetValueW, HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, RRF_RT_REG_EXPAND_SZ,
+            RRF_RT_REG_SZ,
+        },
+    },
+};
+
+const APP_PATHS: &str = r"SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths";
+
+// A program registered in `App Paths`.
+struct AppPath {
+    program: PathBuf,
+    path: Option<OsString>,
+}
+
+impl Command {
+    // Create the child like `create_child`, looking up the program in the
+    // `App Paths` key if it isn't found and `resolve_app_paths` is enabled.
+    // If it isn't registered either, the original error is returned.
+    pub(crate) fn create_child_resolving_app_paths(
+        &mut self,
+        api: Arc<dyn ProcessApi>,
+        capture: bool,
+    ) -> Result<Child> {
+        let err = match self.create_child(Arc::clone(&api), capture) {
+            Err(Error::CreationFailed(err))
+                if os_code(&err) == ERROR_FILE_NOT_FOUND.0
+                    && self.resolve_app_paths
+                    && self.application_name.is_none() =>
+            {
+                Error::CreationFailed(err)
+            }
+            child => return child,
+        };
+
+        let app_path = match lookup(&validation::program_and_args(&self.command).0) {
+            Some(app_path) => app_path,
+            None => return Err(err),
+        };
+
+        // The command is left unchanged, so it can be spawned again.
+        let mut command = self.clone();
+        command.application_name = Some(app_path.program);
+
+        if let Some(dir) = app_path.path {
+            let mut path = command
+                .environment
+                .var(OsStr::new("PATH"))
+                .unwrap_or_default();
+
+            if !path.is_empty() {
+                path.push(";");
+            }
+            path.push(dir);
+
+            command.env("PATH", path);
+        }
+
+        command.create_child(api, capture)
+    }
+}
+
+// Look up a program given by its name, like `winword`. The paths, even
+// relative, aren't looked up. The registry errors are ignored, the program
+// isn't found.
+fn lookup(program: &OsStr) -> Option<AppPath> {
+    if program.is_empty()
+        || program
+            .encode_wide()
+            .any(|unit| [b'\\', b'/', b':'].map(u16::from).contains(&unit))
+    {
+        return None;
+    }
+
+    let mut name = program.to_os_string();
+
+    if Path::new(program).extension().is_none() {
+        name.push(".exe");
+    }
+
+    let subkey = wide::to_wide(Path::new(APP_PATHS).join(name).as_os_str()).ok()?;
+
+    [HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE]
+        .into_iter()
+        .find_map(|root| {
+            let program = query_string(root, &subkey, PCWSTR::null())?;
+            let path = query_string(root, &subkey, w!("Path")).filter(|path| !path.is_empty());
+
+            Some(AppPath {
+                program: PathBuf::from(unquote(program)),
+                path,
+            })
+        })
+}
+
+// Read a string value, with its environment variables expanded.
+fn query_string(root: HKEY, subkey: &[u16], value: PCWSTR) -> Option<OsString> {
+    let flags = RRF_RT_REG_SZ | RRF_RT_REG_EXPAND_SZ;
+    let mut size = 0;
+
+    unsafe {
+        if RegGetValueW(
+            root,
+            PCWSTR(subkey.as_ptr()),
+            value,
+            flags,
+            None,
+            None,
+            Some(&mut size),
+        )
+        .is_err()
+        {
+            return None;
+        }
+
+        let mut buffer = vec![0u16; (size as usize).div_ceil(2)];
+
+        if RegGetValueW(
+            root,
+            PCWSTR(subkey.as_ptr()),
+            value,
+            flags,
+            None,
+            Some(buffer.as_mut_ptr() as *mut c_void),
+            Some(&mut size),
+        )
+        .is_err()
+        {
+            return None;
+        }
+
+        let len = buffer
+            .iter()
+            .position(|&unit| unit == 0)
+            .unwrap_or(buffer.len());
+
+        Some(wide::from_wide(&buffer[..len]))
+    }
+}
+
+// The default value is sometimes quoted, like a command line.
+fn unquote(program: OsString) -> OsString {
+    let wide = program.encode_wide().collect::<Vec<_>>();
+
+    match wide.as_slice() {
+        [quote, inner @ .., end] if *quote == b'"' as u16 && *end == b'"' as u16 => {
+            wide::from_wide(inner)
+        }
+        _ => program,
+    }
+}
@@ -0,0 +1,174 @@
+// Asynchronous reading of the output of child processes.
+//
+// Each stream is read by a dedicated thread sending chunks over a bounded
+// channel, which works with any runtime. The channel gives backpressure: when
+// the chunks aren't consumed, the thread stops reading and the child
+// eventually blocks on writing, like it would with a blocking read.
+
+use crate::{ChildStderr, ChildStdout};
+use std::{
+    fmt,
+    io::{self, Read},
+    pin::Pin,
+    task::{Context, Poll},
+    thread,
+};
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::mpsc,
+};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+const CHANNEL_CAPACITY: usize = 16;
+
+/// An asynchronous reader of the standard output of a child process.
+///
+/// Returned by [`ChildStdout::into_async`]. Requires the `tokio` feature.
+#[derive(Debug)]
+pub struct AsyncChildStdout(AsyncPipeReader);
+
+/// An asynchronous reader of the standard error of a child process.
+///
+/// Returned by [`ChildStderr::into_async`]. Requires the `tokio` feature.
+#[derive(Debug)]
+pub struct AsyncChildStderr(AsyncPipeReader);
+
+impl ChildStdout {
+    /// Converts the standard output into an asynchronous reader.
+    ///
+    /// The pipe is read on a background thread, so no thread of the runtime
+    /// is blocked. The reader returns the end of file once the child (and any
+    /// process that inherited the pipe) has exited.
+    ///
+    /// Requires the `tokio` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Stdio};
+    /// use tokio::io::{AsyncBufReadExt, BufReader};
+    ///
+    /// # async fn run() {
+    /// let mut child = Command::new("cargo.exe build")
+    ///     .stdout(Stdio::piped())
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// let stdout = child.stdout.take().unwrap().into_async();
+    /// let mut lines = BufReader::new(stdout).lines();
+    ///
+    /// while let Some(line) = lines.next_line().await.unwrap() {
+    ///     println!("cargo: {}", line);
+    /// }
+    ///
+    /// child.wait_async().await.unwrap();
+    /// # }
+    /// ```
+    pub fn into_async(self) -> AsyncChildStdout {
+        AsyncChildStdout(AsyncPipeReader::new(self))
+    }
+}
+
+impl ChildStderr {
+    /// Converts the standard error into an asynchronous reader.
+    ///
+    /// See [`ChildStdout::into_async`] for more information.
+    ///
+    /// Requires the `tokio` feature.
+    pub fn into_async(self) -> AsyncChildStderr {
+        AsyncChildStderr(AsyncPipeReader::new(self))
+    }
+}
+
+impl AsyncRead for AsyncChildStdout {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.0.poll_read(cx, buf)
+    }
+}
+
+impl AsyncRead for AsyncChildStderr {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.0.poll_read(cx, buf)
+    }
+}
+
+struct AsyncPipeReader {
+    receiver: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl fmt::Debug for AsyncPipeReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncPipeReader")
+            .field("buffered", &(self.chunk.len() - self.position))
+            .finish()
+    }
+}
+
+impl AsyncPipeReader {
+    fn new(mut reader: impl Read + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+        thread::spawn(move || loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+
+            let res = match reader.read(&mut chunk) {
+                // End of file.
+                Ok(0) => break,
+                Ok(len) => {
+                    chunk.truncate(len);
+                    Ok(chunk)
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => Err(err),
+            };
+
+            let failed = res.is_err();
+
+            // Stop reading if the reader has been dropped.
+            if sender.blocking_send(res).is_err() || failed {
+                break;
+            }
+        });
+
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.position < self.chunk.len() {
+                let len = buf.remaining().min(self.chunk.len() - self.position);
+
+                buf.put_slice(&self.chunk[self.position..self.position + len]);
+                self.position += len;
+
+                return Poll::Ready(Ok(()));
+            }
+
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(err)),
+                // The thread stopped at the end of file, nothing is put in
+                // `buf`.
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
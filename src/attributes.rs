@@ -0,0 +1,115 @@
+// The attribute list of the extended startup information, for the options of
+// `CreateProcessW` that aren't flags.
+
+use crate::{Error, Result};
+use std::{
+    ffi::c_void,
+    io,
+    mem::{size_of, size_of_val},
+    os::windows::io::RawHandle,
+};
+use windows::Win32::System::Threading::{
+    DeleteProcThreadAttributeList, InitializeProcThreadAttributeList, UpdateProcThreadAttribute,
+    LPPROC_THREAD_ATTRIBUTE_LIST,
+};
+
+// `ProcThreadAttributeValue(ProcThreadAttributeProtectionLevel, FALSE, TRUE,
+// FALSE)`.
+pub(crate) const PROC_THREAD_ATTRIBUTE_PROTECTION_LEVEL: usize = 0x2000B;
+
+// `ProcThreadAttributeValue(ProcThreadAttributeHandleList, FALSE, TRUE,
+// FALSE)`.
+pub(crate) const PROC_THREAD_ATTRIBUTE_HANDLE_LIST: usize = 0x20002;
+
+// `ProcThreadAttributeValue(ProcThreadAttributePseudoConsole, FALSE, TRUE,
+// FALSE)`.
+pub(crate) const PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE: usize = 0x20016;
+
+// An initialized attribute list. The values given to `set` aren't copied,
+// they have to live until the process is created.
+pub(crate) struct AttributeList {
+    // A buffer of `usize` for the alignment of the list.
+    buffer: Vec<usize>,
+}
+
+impl AttributeList {
+    pub(crate) fn new(count: u32) -> Result<Self> {
+        let mut size = 0;
+
+        unsafe {
+            // The first call fails with `ERROR_INSUFFICIENT_BUFFER` and
+            // returns the size of the list.
+            let _ = InitializeProcThreadAttributeList(
+                LPPROC_THREAD_ATTRIBUTE_LIST(std::ptr::null_mut()),
+                count,
+                0,
+                &mut size,
+            );
+
+            let mut list = Self {
+                buffer: vec![0; size.div_ceil(size_of::<usize>())],
+            };
+
+            if InitializeProcThreadAttributeList(list.as_raw(), count, 0, &mut size).is_err() {
+                let err = io::Error::last_os_error();
+
+                // Don't delete a list that isn't initialized.
+                list.buffer.clear();
+
+                return Err(Error::AttributeFailed(err));
+            }
+
+            Ok(list)
+        }
+    }
+
+    // Set `attribute` to `value`.
+    //
+    // Safety: `value` must stay valid until the process is created and have the
+    // type expected by `attribute`.
+    pub(crate) unsafe fn set<T>(&mut self, attribute: usize, value: *const T) -> Result<()> {
+        self.set_raw(attribute, value as *const c_void, size_of::<T>())
+    }
+
+    // Set `attribute` to the array `values`, like a list of handles.
+    //
+    // Safety: `values` must stay valid until the process is created and have
+    // the type expected by `attribute`.
+    pub(crate) unsafe fn set_slice<T>(&mut self, attribute: usize, values: &[T]) -> Result<()> {
+        self.set_raw(
+            attribute,
+            values.as_ptr() as *const c_void,
+            size_of_val(values),
+        )
+    }
+
+    // Set `attribute` to a handle given in place of a pointer, like the
+    // pseudoconsole of the process.
+    //
+    // Safety: `handle` must stay valid until the process is created.
+    pub(crate) unsafe fn set_handle(&mut self, attribute: usize, handle: RawHandle) -> Result<()> {
+        self.set_raw(attribute, handle as *const c_void, size_of::<RawHandle>())
+    }
+
+    unsafe fn set_raw(
+        &mut self,
+        attribute: usize,
+        value: *const c_void,
+        size: usize,
+    ) -> Result<()> {
+        UpdateProcThreadAttribute(self.as_raw(), 0, attribute, Some(value), size, None, None)
+            .map_err(|_| Error::AttributeFailed(io::Error::last_os_error()))
+    }
+
+    pub(crate) fn as_raw(&mut self) -> LPPROC_THREAD_ATTRIBUTE_LIST {
+        LPPROC_THREAD_ATTRIBUTE_LIST(self.buffer.as_mut_ptr() as *mut c_void)
+    }
+}
+
+impl Drop for AttributeList {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            unsafe { DeleteProcThreadAttributeList(self.as_raw()) }
+        }
+    }
+}
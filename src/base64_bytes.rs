@@ -0,0 +1,16 @@
+// The byte buffers serialized as base64 strings, with `#[serde(with)]`. The
+// output of a process can contain any bytes, and a string is more compact than
+// an array of numbers in the text formats.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+pub(crate) fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(bytes))
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+
+    STANDARD.decode(encoded).map_err(D::Error::custom)
+}
@@ -0,0 +1,127 @@
+use crate::{
+    wait::{deadline, wait_any_handle},
+    Child, ExitStatus, Result,
+};
+use std::time::Duration;
+
+/// A set of child processes observed as they exit.
+///
+/// Each child is identified by the [`Token`] returned when it is inserted.
+/// Tokens are never reused by a set, so they stay valid as children are
+/// removed. Children can be inserted between calls to
+/// [`next_exit`][ChildSet::next_exit], to replace the ones that exited for
+/// example.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{ChildSet, Command};
+///
+/// let mut workers = ChildSet::new();
+///
+/// for _ in 0..4 {
+///     workers.insert(Command::new("worker.exe").spawn().unwrap());
+/// }
+///
+/// while let Some((token, status)) = workers.next_exit(None).unwrap() {
+///     println!("worker {:?} exited with {}", token, status);
+///
+///     if !status.success() {
+///         workers.insert(Command::new("worker.exe").spawn().unwrap());
+///     }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ChildSet {
+    children: Vec<(Token, Child)>,
+    next_token: u64,
+}
+
+/// The identifier of a child in a [`ChildSet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Token(u64);
+
+impl ChildSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a child to the set, returning its token.
+    pub fn insert(&mut self, child: Child) -> Token {
+        let token = Token(self.next_token);
+
+        self.next_token += 1;
+        self.children.push((token, child));
+
+        token
+    }
+
+    /// Removes a child from the set, returning it if it is still in the set.
+    ///
+    /// The child isn't killed nor waited on.
+    pub fn remove(&mut self, token: Token) -> Option<Child> {
+        let index = self.children.iter().position(|(t, _)| *t == token)?;
+
+        Some(self.children.remove(index).1)
+    }
+
+    /// Returns the child with the given token, if it is still in the set.
+    pub fn get(&self, token: Token) -> Option<&Child> {
+        self.children
+            .iter()
+            .find(|(t, _)| *t == token)
+            .map(|(_, child)| child)
+    }
+
+    /// Returns the number of children in the set.
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Returns `true` if the set doesn't contain any child.
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Waits for a child of the set to exit, removing it from the set and
+    /// returning its token and exit status.
+    ///
+    /// `Ok(None)` is returned if `timeout` elapses first, or right away if the
+    /// set is empty. A `None` timeout waits forever. A child that has already
+    /// been waited on is returned right away. See [`wait_any`][crate::wait_any]
+    /// for more information.
+    pub fn next_exit(&mut self, timeout: Option<Duration>) -> Result<Option<(Token, ExitStatus)>> {
+        let index = match self
+            .children
+            .iter()
+            .position(|(_, child)| child.exit_status.get().is_some())
+        {
+            Some(index) => Some(index),
+            None => {
+                let handles = self
+                    .children
+                    .iter()
+                    .map(|(_, child)| child.process_information.hProcess)
+                    .collect::<Vec<_>>();
+
+                if handles.is_empty() {
+                    return Ok(None);
+                }
+
+                wait_any_handle(&handles, deadline(timeout))?
+            }
+        };
+
+        match index {
+            Some(index) => {
+                // The process has exited, this doesn't block.
+                let status = self.children[index].1.wait()?;
+                let (token, _) = self.children.remove(index);
+
+                Ok(Some((token, status)))
+            }
+            None => Ok(None),
+        }
+    }
+}
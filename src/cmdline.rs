@@ -0,0 +1,388 @@
+//! Building and splitting command lines.
+//!
+//! A process on Windows receives its command line as a single string, the
+//! program splits it into arguments itself. Most programs use the rules of
+//! the Microsoft C runtime, which are also the rules of
+//! [`CommandLineToArgvW`](https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-commandlinetoargvw).
+//! The functions of this module quote the arguments according to these
+//! rules, so the program receives them unchanged, and split command lines
+//! like the program would.
+//!
+//! Note that `cmd.exe` and the batch files don't follow these rules.
+
+use crate::{Error, Result};
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    slice,
+};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{LocalFree, HLOCAL},
+        UI::Shell::CommandLineToArgvW,
+    },
+};
+
+const QUOTE: u16 = b'"' as u16;
+const BACKSLASH: u16 = b'\\' as u16;
+const SPACE: u16 = b' ' as u16;
+
+/// Quotes an argument so it is parsed as a single argument, unchanged.
+///
+/// The argument is returned as is when it doesn't need to be quoted. Otherwise
+/// it is enclosed in quotes, the quotes it contains and the backslashes that
+/// precede them being escaped with backslashes.
+///
+/// This doesn't apply to the program, which is the first token of the command
+/// line and is parsed with different rules, see [`build_command_line`].
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::quote_argument;
+///
+/// assert_eq!(quote_argument("simple".as_ref()), "simple");
+/// assert_eq!(quote_argument("with space".as_ref()), r#""with space""#);
+/// assert_eq!(quote_argument(r#"say "hi""#.as_ref()), r#""say \"hi\"""#);
+/// assert_eq!(quote_argument(r"C:\dir with space\".as_ref()), r#""C:\dir with space\\""#);
+/// assert_eq!(quote_argument("".as_ref()), r#""""#);
+/// ```
+pub fn quote_argument(arg: &OsStr) -> OsString {
+    let mut quoted = Vec::new();
+
+    push_argument(&mut quoted, arg);
+
+    OsString::from_wide(&quoted)
+}
+
+/// Builds a command line from a program and its arguments.
+///
+/// The arguments are quoted with [`quote_argument`]. The program is enclosed
+/// in quotes if it is empty or contains whitespace, since its first token
+/// ends at the first whitespace (or at the next quote if it starts with a
+/// quote). A program cannot contain quotes, which aren't valid in a path.
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::build_command_line;
+///
+/// let command_line = build_command_line(
+///     r"C:\Program Files\Git\bin\git.exe".as_ref(),
+///     &["commit".as_ref(), "-m".as_ref(), "fix the \"quoting\"".as_ref()],
+/// );
+///
+/// assert_eq!(
+///     command_line,
+///     r#""C:\Program Files\Git\bin\git.exe" commit -m "fix the \"quoting\"""#,
+/// );
+/// ```
+///
+/// The command line can be given to [`Command::new`][crate::Command::new].
+pub fn build_command_line(program: &OsStr, args: &[&OsStr]) -> OsString {
+    let mut command_line = Vec::new();
+    let program = program.encode_wide().collect::<Vec<_>>();
+
+    if program.is_empty() || program.iter().copied().any(is_whitespace) {
+        command_line.push(QUOTE);
+        command_line.extend(program);
+        command_line.push(QUOTE);
+    } else {
+        command_line.extend(program);
+    }
+
+    for arg in args {
+        command_line.push(SPACE);
+        push_argument(&mut command_line, arg);
+    }
+
+    OsString::from_wide(&command_line)
+}
+
+/// Splits a command line into the arguments the program receives, the first
+/// one being the program.
+///
+/// An empty command line gives no arguments. A command line containing a
+/// null character returns an [`InvalidInput`][Error::InvalidInput] error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::cmdline;
+///
+/// let args = cmdline::split(r#""C:\Program Files\tool.exe" --name "a b" c"#.as_ref()).unwrap();
+///
+/// assert_eq!(args, [r"C:\Program Files\tool.exe", "--name", "a b", "c"]);
+/// ```
+///
+/// Equivalent to the [`CommandLineToArgvW`][command-line-to-argv-w] function.
+///
+/// [command-line-to-argv-w]: https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-commandlinetoargvw
+pub fn split(command_line: &OsStr) -> Result<Vec<OsString>> {
+    let mut command_line_wide = command_line.encode_wide().collect::<Vec<_>>();
+
+    if command_line_wide.contains(&0) {
+        return Err(Error::InvalidInput(
+            "the command line contains a null character",
+        ));
+    }
+
+    // `CommandLineToArgvW` returns the path of the current executable for an
+    // empty command line.
+    if command_line_wide.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    command_line_wide.push(0);
+
+    unsafe {
+        let mut count = 0;
+        let argv = CommandLineToArgvW(PCWSTR(command_line_wide.as_ptr()), &mut count);
+
+        if argv.is_null() {
+            return Err(Error::SplitCommandLineFailed(io::Error::last_os_error()));
+        }
+
+        let args = slice::from_raw_parts(argv, count.max(0) as usize)
+            .iter()
+            .map(|arg| OsString::from_wide(arg.as_wide()))
+            .collect();
+
+        let _ = LocalFree(HLOCAL(argv as isize));
+
+        Ok(args)
+    }
+}
+
+/// Splits the program from the arguments of a command line, returning the
+/// program and the rest of the command line.
+///
+/// The program is parsed with its own rules: it ends at the next quote if it
+/// starts with a quote, at the first whitespace otherwise. Backslashes don't
+/// escape anything in the program. The arguments are returned unchanged, the
+/// whitespace before them being removed.
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::cmdline;
+///
+/// let (program, args) = cmdline::program_and_args(r#""C:\Program Files\tool.exe" --name "a b""#.as_ref());
+///
+/// assert_eq!(program, r"C:\Program Files\tool.exe");
+/// assert_eq!(args, r#"--name "a b""#);
+/// ```
+pub fn program_and_args(command_line: &OsStr) -> (OsString, OsString) {
+    let command_line = command_line.encode_wide().collect::<Vec<_>>();
+
+    let (program, rest) = match command_line.split_first() {
+        Some((&QUOTE, rest)) => match rest.iter().position(|&unit| unit == QUOTE) {
+            Some(end) => (&rest[..end], &rest[end + 1..]),
+            // The program is the rest of the command line.
+            None => (rest, &[][..]),
+        },
+        _ => {
+            let end = command_line
+                .iter()
+                .position(|&unit| is_whitespace(unit))
+                .unwrap_or(command_line.len());
+
+            command_line.split_at(end)
+        }
+    };
+
+    let args_start = rest
+        .iter()
+        .position(|&unit| !is_whitespace(unit))
+        .unwrap_or(rest.len());
+
+    (
+        OsString::from_wide(program),
+        OsString::from_wide(&rest[args_start..]),
+    )
+}
+
+fn push_argument(command_line: &mut Vec<u16>, arg: &OsStr) {
+    let arg = arg.encode_wide().collect::<Vec<_>>();
+
+    if !arg.is_empty() && !arg.iter().any(|&unit| is_whitespace(unit) || unit == QUOTE) {
+        command_line.extend(arg);
+        return;
+    }
+
+    command_line.push(QUOTE);
+
+    let mut backslashes = 0;
+
+    for unit in arg {
+        match unit {
+            BACKSLASH => backslashes += 1,
+            QUOTE => {
+                // The backslashes before a quote are escaped, then the quote.
+                command_line.extend(std::iter::repeat(BACKSLASH).take(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                command_line.extend(std::iter::repeat(BACKSLASH).take(backslashes));
+                backslashes = 0;
+            }
+        }
+
+        if unit != BACKSLASH {
+            command_line.push(unit);
+        }
+    }
+
+    // The backslashes before the closing quote are escaped.
+    command_line.extend(std::iter::repeat(BACKSLASH).take(backslashes * 2));
+    command_line.push(QUOTE);
+}
+
+pub(crate) fn is_whitespace(unit: u16) -> bool {
+    unit == SPACE || unit == b'\t' as u16 || unit == b'\n' as u16 || unit == 0x0b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Arguments whose quoting is easy to get wrong.
+    const ADVERSARIAL: &[&str] = &[
+        "",
+        " ",
+        "   ",
+        "\t",
+        "a  b",
+        " leading and trailing ",
+        "tab\there",
+        "line\nbreak",
+        "trailing\\",
+        "trailing\\\\",
+        r"C:\dir with space\",
+        r"\\server\share\",
+        r"a\b",
+        "\"",
+        "\"\"",
+        "a\"b",
+        "\"quoted\"",
+        "\\\"",
+        "\\\\\"",
+        "a\\\\\\\"b",
+        "\"a b\\\"",
+        "--flag=\"x y\"",
+    ];
+
+    fn round_trip(args: &[&str]) -> Vec<OsString> {
+        let args = args.iter().map(OsStr::new).collect::<Vec<_>>();
+        let command_line = build_command_line(OsStr::new("prog.exe"), &args);
+
+        let mut split = split(&command_line).unwrap();
+        assert_eq!(split.remove(0), "prog.exe", "{command_line:?}");
+        split
+    }
+
+    #[test]
+    fn each_argument_round_trips() {
+        for &arg in ADVERSARIAL {
+            assert_eq!(round_trip(&[arg]), [arg], "{arg:?}");
+        }
+    }
+
+    #[test]
+    fn all_arguments_round_trip_together() {
+        assert_eq!(round_trip(ADVERSARIAL), ADVERSARIAL);
+
+        // Empty arguments between the others aren't lost.
+        assert_eq!(
+            round_trip(&["", "a", "", "", "b", ""]),
+            ["", "a", "", "", "b", ""]
+        );
+    }
+
+    fn program_and_args_of(command_line: &str) -> (OsString, OsString) {
+        program_and_args(OsStr::new(command_line))
+    }
+
+    #[test]
+    fn split_of_an_empty_command_line_is_empty() {
+        assert!(split(OsStr::new("")).unwrap().is_empty());
+        assert!(matches!(
+            split(OsStr::new("prog.exe a\0b")),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn split_keeps_a_quoted_program_with_spaces() {
+        assert_eq!(
+            split(OsStr::new(r#""C:\Program Files\tool.exe" --name "a b" c"#)).unwrap(),
+            [r"C:\Program Files\tool.exe", "--name", "a b", "c"]
+        );
+        // The backslash before the closing quote of the program isn't an
+        // escape.
+        assert_eq!(
+            split(OsStr::new(r#""C:\dir\" arg"#)).unwrap(),
+            [r"C:\dir\", "arg"]
+        );
+    }
+
+    #[test]
+    fn split_keeps_unicode_arguments() {
+        assert_eq!(
+            split(OsStr::new("прог.exe héllo \"日本 語\" 😀")).unwrap(),
+            ["прог.exe", "héllo", "日本 語", "😀"]
+        );
+    }
+
+    #[test]
+    fn program_and_args_of_an_empty_command_line_are_empty() {
+        assert_eq!(program_and_args_of(""), ("".into(), "".into()));
+        assert_eq!(program_and_args_of("   "), ("".into(), "".into()));
+    }
+
+    #[test]
+    fn program_and_args_keep_a_quoted_program_with_spaces() {
+        assert_eq!(
+            program_and_args_of(r#""C:\Program Files\tool.exe"  --name "a b""#),
+            (
+                r"C:\Program Files\tool.exe".into(),
+                r#"--name "a b""#.into()
+            )
+        );
+        assert_eq!(
+            program_and_args_of(r#""C:\Program Files\tool.exe""#),
+            (r"C:\Program Files\tool.exe".into(), "".into())
+        );
+        // An unterminated quote runs to the end.
+        assert_eq!(
+            program_and_args_of(r#""C:\Program Files\tool.exe --name"#),
+            (r"C:\Program Files\tool.exe --name".into(), "".into())
+        );
+        assert_eq!(
+            program_and_args_of(r#""C:\dir\" arg"#),
+            (r"C:\dir\".into(), "arg".into())
+        );
+        assert_eq!(
+            program_and_args_of("tool.exe\t\t a  b"),
+            ("tool.exe".into(), "a  b".into())
+        );
+    }
+
+    #[test]
+    fn program_and_args_keep_unicode() {
+        assert_eq!(
+            program_and_args_of("прог.exe  ünï \"日本\""),
+            ("прог.exe".into(), "ünï \"日本\"".into())
+        );
+    }
+
+    #[test]
+    fn quoted_arguments_keep_their_backslashes() {
+        assert_eq!(quote_argument(OsStr::new("trailing\\")), "trailing\\");
+        assert_eq!(quote_argument(OsStr::new("a b\\")), "\"a b\\\\\"");
+        assert_eq!(quote_argument(OsStr::new("a\\\"b")), "\"a\\\\\\\"b\"");
+        assert_eq!(quote_argument(OsStr::new("a b\\c")), "\"a b\\c\"");
+    }
+}
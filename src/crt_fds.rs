@@ -0,0 +1,79 @@
+// The file descriptors inherited by the children using the C runtime, given
+// in the `lpReserved2` member of the startup information.
+//
+// The C runtime reads the block written by its own `spawn` functions: the
+// number of file descriptors as a 32-bit integer, followed by a byte of flags
+// for each file descriptor, followed by a handle for each file descriptor.
+// The block is packed, the handles aren't aligned.
+
+use crate::{stdio::duplicate_inheritable, Result};
+use std::os::windows::io::{AsRawHandle, OwnedHandle, RawHandle};
+use windows::Win32::{
+    Foundation::HANDLE,
+    Storage::FileSystem::{GetFileType, FILE_TYPE_CHAR, FILE_TYPE_PIPE},
+};
+
+// The flags of the C runtime for an open file descriptor, one that is a pipe
+// and one that is a character device, like a console.
+const FOPEN: u8 = 0x01;
+const FPIPE: u8 = 0x08;
+const FDEV: u8 = 0x40;
+
+// `cbReserved2` is a 16-bit integer.
+const MAX_BLOCK_SIZE: usize = u16::MAX as usize;
+
+// The largest file descriptor that fits in the block.
+pub(crate) const MAX_FD: u32 =
+    ((MAX_BLOCK_SIZE - 4) / (1 + std::mem::size_of::<isize>()) - 1) as u32;
+
+// The block and the inheritable duplicates of the handles it contains, which
+// have to live until the child is created.
+pub(crate) struct CrtFds {
+    block: Vec<u8>,
+    handles: Vec<OwnedHandle>,
+}
+
+impl CrtFds {
+    // `fds` has been validated: the file descriptors are unique and at most
+    // `MAX_FD`.
+    pub(crate) fn new(fds: &[(u32, HANDLE)]) -> Result<Self> {
+        let count = fds.iter().map(|(fd, _)| fd + 1).max().unwrap_or(0) as usize;
+        let mut flags = vec![0; count];
+        let mut raw_handles = vec![-1_isize; count];
+        let mut handles = Vec::with_capacity(fds.len());
+
+        for &(fd, handle) in fds {
+            let duplicate = duplicate_inheritable(handle)?;
+            let raw_handle = duplicate.as_raw_handle() as isize;
+
+            flags[fd as usize] = FOPEN
+                | match unsafe { GetFileType(HANDLE(raw_handle)) } {
+                    FILE_TYPE_PIPE => FPIPE,
+                    FILE_TYPE_CHAR => FDEV,
+                    _ => 0,
+                };
+            raw_handles[fd as usize] = raw_handle;
+            handles.push(duplicate);
+        }
+
+        let mut block = Vec::with_capacity(4 + count * (1 + std::mem::size_of::<isize>()));
+
+        block.extend((count as u32).to_le_bytes());
+        block.extend(flags);
+
+        for raw_handle in raw_handles {
+            block.extend(raw_handle.to_le_bytes());
+        }
+
+        Ok(Self { block, handles })
+    }
+
+    pub(crate) fn block(&self) -> &[u8] {
+        &self.block
+    }
+
+    // The handles of the file descriptors, inherited by the child.
+    pub(crate) fn handles(&self) -> impl Iterator<Item = RawHandle> + '_ {
+        self.handles.iter().map(AsRawHandle::as_raw_handle)
+    }
+}
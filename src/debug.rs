@@ -0,0 +1,239 @@
+use crate::{Child, Error, Result};
+use std::{io, time::Duration};
+use windows::Win32::{
+    Foundation::{
+        CloseHandle, GetLastError, DBG_CONTINUE, DBG_EXCEPTION_NOT_HANDLED, ERROR_SEM_TIMEOUT,
+        EXCEPTION_BREAKPOINT, STATUS_WX86_BREAKPOINT,
+    },
+    System::{
+        Diagnostics::Debug::{
+            ContinueDebugEvent, DebugActiveProcessStop, DebugSetProcessKillOnExit,
+            WaitForDebugEvent, CREATE_PROCESS_DEBUG_EVENT, CREATE_THREAD_DEBUG_EVENT, DEBUG_EVENT,
+            EXCEPTION_DEBUG_EVENT, EXIT_PROCESS_DEBUG_EVENT, EXIT_THREAD_DEBUG_EVENT,
+            LOAD_DLL_DEBUG_EVENT, OUTPUT_DEBUG_STRING_EVENT, RIP_EVENT, UNLOAD_DLL_DEBUG_EVENT,
+        },
+        Threading::{DEBUG_ONLY_THIS_PROCESS, DEBUG_PROCESS, INFINITE, PROCESS_CREATION_FLAGS},
+    },
+};
+
+/// The processes debugged by the calling thread, used by
+/// [`Command::debug`][crate::Command::debug].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugMode {
+    /// Only the child process.
+    ThisProcess,
+    /// The child process and the processes it creates, recursively.
+    ProcessTree,
+}
+
+impl DebugMode {
+    pub(crate) fn to_flags(self) -> PROCESS_CREATION_FLAGS {
+        match self {
+            Self::ThisProcess => DEBUG_ONLY_THIS_PROCESS,
+            Self::ProcessTree => DEBUG_PROCESS,
+        }
+    }
+}
+
+/// A debug event of a debugged process, returned by
+/// [`Child::next_debug_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugEvent {
+    /// The identifier of the process of the event.
+    pub pid: u32,
+    /// The identifier of the thread of the event.
+    pub thread_id: u32,
+    /// What happened.
+    pub kind: DebugEventKind,
+}
+
+/// The kind of a [`DebugEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DebugEventKind {
+    /// An exception has been raised in the process.
+    Exception {
+        /// The code of the exception, like `0xC0000005` for an access
+        /// violation.
+        code: u32,
+        /// Whether the debugger gets the exception before the handlers of the
+        /// process. The second chance comes when no handler handled it.
+        first_chance: bool,
+    },
+    /// A thread has been created.
+    CreateThread,
+    /// The process has been created.
+    CreateProcess,
+    /// A thread has exited.
+    ExitThread {
+        /// The exit code of the thread.
+        exit_code: u32,
+    },
+    /// The process has exited.
+    ExitProcess {
+        /// The exit code of the process.
+        exit_code: u32,
+    },
+    /// A DLL has been loaded.
+    LoadDll,
+    /// A DLL has been unloaded.
+    UnloadDll,
+    /// The process called `OutputDebugString`.
+    OutputDebugString,
+    /// The system debugging error event.
+    Rip,
+    /// An event with an unknown code.
+    Unknown(u32),
+}
+
+impl Child {
+    /// Waits for the next debug event of the processes debugged by the
+    /// calling thread and lets the process continue.
+    ///
+    /// A child spawned with [`Command::debug`][crate::Command::debug] stops
+    /// at each debug event until the debugger continues it: the events have
+    /// to be pumped, or the debugger detached with
+    /// [`detach_debugger`][Child::detach_debugger], or the child hangs. This
+    /// must be called from the thread that spawned the child, which is its
+    /// debugger, and returns the events of all the processes debugged by this
+    /// thread.
+    ///
+    /// The exceptions are passed to the handlers of the process, like if no
+    /// debugger was attached, except the breakpoints which are handled by the
+    /// debugger, like the one raised when the process starts. `Ok(None)` is returned if `timeout` elapsed
+    /// before an event, `None` waits forever. If the wait fails, a
+    /// [`DebugFailed`][Error::DebugFailed] error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, DebugEventKind, DebugMode};
+    ///
+    /// let child = Command::new("crashy.exe")
+    ///     .debug(DebugMode::ThisProcess)
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// while let Some(event) = child.next_debug_event(None).unwrap() {
+    ///     match event.kind {
+    ///         DebugEventKind::Exception { code, first_chance: false } => {
+    ///             println!("unhandled exception {:#x}", code);
+    ///         }
+    ///         DebugEventKind::ExitProcess { .. } if event.pid == child.id() => break,
+    ///         _ => {}
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Equivalent to the [`WaitForDebugEvent`][wait-for-debug-event] and
+    /// [`ContinueDebugEvent`][continue-debug-event] functions.
+    ///
+    /// [wait-for-debug-event]: https://docs.microsoft.com/en-us/windows/win32/api/debugapi/nf-debugapi-waitfordebugevent
+    /// [continue-debug-event]: https://docs.microsoft.com/en-us/windows/win32/api/debugapi/nf-debugapi-continuedebugevent
+    pub fn next_debug_event(&self, timeout: Option<Duration>) -> Result<Option<DebugEvent>> {
+        let millis = timeout.map_or(INFINITE, |timeout| {
+            timeout.as_millis().min(u128::from(INFINITE - 1)) as u32
+        });
+        let mut event = DEBUG_EVENT::default();
+
+        unsafe {
+            if WaitForDebugEvent(&mut event, millis).is_err() {
+                return match GetLastError() {
+                    ERROR_SEM_TIMEOUT => Ok(None),
+                    err => Err(Error::DebugFailed(io::Error::from_raw_os_error(
+                        err.0 as i32,
+                    ))),
+                };
+            }
+
+            let kind = match event.dwDebugEventCode {
+                EXCEPTION_DEBUG_EVENT => DebugEventKind::Exception {
+                    code: event.u.Exception.ExceptionRecord.ExceptionCode.0 as u32,
+                    first_chance: event.u.Exception.dwFirstChance != 0,
+                },
+                CREATE_THREAD_DEBUG_EVENT => DebugEventKind::CreateThread,
+                CREATE_PROCESS_DEBUG_EVENT => {
+                    // The debugger owns the handle of the image file.
+                    let _ = CloseHandle(event.u.CreateProcessInfo.hFile);
+                    DebugEventKind::CreateProcess
+                }
+                EXIT_THREAD_DEBUG_EVENT => DebugEventKind::ExitThread {
+                    exit_code: event.u.ExitThread.dwExitCode,
+                },
+                EXIT_PROCESS_DEBUG_EVENT => DebugEventKind::ExitProcess {
+                    exit_code: event.u.ExitProcess.dwExitCode,
+                },
+                LOAD_DLL_DEBUG_EVENT => {
+                    let _ = CloseHandle(event.u.LoadDll.hFile);
+                    DebugEventKind::LoadDll
+                }
+                UNLOAD_DLL_DEBUG_EVENT => DebugEventKind::UnloadDll,
+                OUTPUT_DEBUG_STRING_EVENT => DebugEventKind::OutputDebugString,
+                RIP_EVENT => DebugEventKind::Rip,
+                code => DebugEventKind::Unknown(code.0),
+            };
+
+            let status = match kind {
+                // The breakpoints are meant for the debugger, like the one
+                // raised by the loader when the process starts.
+                DebugEventKind::Exception { code, .. }
+                    if code == EXCEPTION_BREAKPOINT.0 as u32
+                        || code == STATUS_WX86_BREAKPOINT.0 as u32 =>
+                {
+                    DBG_CONTINUE
+                }
+                DebugEventKind::Exception { .. } => DBG_EXCEPTION_NOT_HANDLED,
+                _ => DBG_CONTINUE,
+            };
+
+            ContinueDebugEvent(event.dwProcessId, event.dwThreadId, status)
+                .map_err(|_| Error::DebugFailed(io::Error::last_os_error()))?;
+
+            Ok(Some(DebugEvent {
+                pid: event.dwProcessId,
+                thread_id: event.dwThreadId,
+                kind,
+            }))
+        }
+    }
+
+    /// Stops debugging the child, which continues independently.
+    ///
+    /// The child isn't killed when the debugger exits, as it is by default.
+    /// This must be called from the thread that spawned the child with
+    /// [`Command::debug`][crate::Command::debug], and the pending debug event
+    /// must have been continued with
+    /// [`next_debug_event`][Child::next_debug_event]. If the debugger cannot
+    /// be detached, a [`DebugFailed`][Error::DebugFailed] error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, DebugMode};
+    ///
+    /// let child = Command::new("app.exe")
+    ///     .debug(DebugMode::ThisProcess)
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// // The process creation event.
+    /// child.next_debug_event(None).unwrap();
+    /// child.detach_debugger().unwrap();
+    ///
+    /// child.wait().unwrap();
+    /// ```
+    ///
+    /// Equivalent to the [`DebugSetProcessKillOnExit`][debug-set-process-kill-on-exit]
+    /// and [`DebugActiveProcessStop`][debug-active-process-stop] functions.
+    ///
+    /// [debug-set-process-kill-on-exit]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-debugsetprocesskillonexit
+    /// [debug-active-process-stop]: https://docs.microsoft.com/en-us/windows/win32/api/debugapi/nf-debugapi-debugactiveprocessstop
+    pub fn detach_debugger(&self) -> Result<()> {
+        unsafe {
+            DebugSetProcessKillOnExit(false)
+                .map_err(|_| Error::DebugFailed(io::Error::last_os_error()))?;
+            DebugActiveProcessStop(self.id())
+                .map_err(|_| Error::DebugFailed(io::Error::last_os_error()))
+        }
+    }
+}
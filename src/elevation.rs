@@ -0,0 +1,300 @@
+// Spawning a child as administrator with the `runas` verb of
+// `ShellExecuteExW`, when `CreateProcessW` refuses a program whose manifest
+// requires elevation.
+
+use crate::{
+    api::Win32ProcessApi, metrics, os_code, path, validation, wide, Child, Command, Error,
+    Operation, Result,
+};
+use std::{
+    io, mem,
+    sync::{atomic::AtomicU32, Arc, Mutex, OnceLock},
+};
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::{
+            CloseHandle, GetLastError, ERROR_CANCELLED, ERROR_ELEVATION_REQUIRED,
+            ERROR_INVALID_HANDLE, HANDLE,
+        },
+        System::Threading::{GetProcessId, PROCESS_INFORMATION},
+        UI::{
+            Shell::{
+                ShellExecuteExW, SEE_MASK_NOASYNC, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW,
+            },
+            WindowsAndMessaging::SW_SHOWNORMAL,
+        },
+    },
+};
+
+/// A child spawned by [`Command::spawn_elevated_if_needed`], telling whether
+/// it runs as administrator.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::Command;
+///
+/// let spawned = Command::new("setup.exe /quiet")
+///     .spawn_elevated_if_needed()
+///     .unwrap();
+///
+/// if spawned.is_elevated() {
+///     println!("the installer runs as administrator");
+/// }
+///
+/// let status = spawned.into_child().wait().unwrap();
+/// ```
+#[derive(Debug)]
+pub enum ElevatedOrNormal {
+    /// The child has been spawned normally, with the token of the calling
+    /// process.
+    Normal(Child),
+    /// The program requires elevation, the child has been spawned as
+    /// administrator once the user accepted the UAC prompt.
+    Elevated(Child),
+}
+
+impl ElevatedOrNormal {
+    /// Returns `true` if the child has been spawned as administrator.
+    pub fn is_elevated(&self) -> bool {
+        matches!(self, Self::Elevated(_))
+    }
+
+    /// Returns the child, however it has been spawned.
+    pub fn into_child(self) -> Child {
+        match self {
+            Self::Normal(child) | Self::Elevated(child) => child,
+        }
+    }
+}
+
+impl Command {
+    /// Executes the command as a child process like [`spawn`][Command::spawn],
+    /// spawning it as administrator if the program requires it.
+    ///
+    /// When `CreateProcessW` fails with `ERROR_ELEVATION_REQUIRED`, like for
+    /// an installer whose manifest requires to run as administrator, the
+    /// command is spawned again with the `runas` verb of `ShellExecuteExW`,
+    /// which shows the UAC prompt. The elevated child gets the same command
+    /// line and working directory, and the
+    /// [application name][Command::application_name] if it has been set. If
+    /// the user declines the prompt, an
+    /// [`ElevationCancelled`][Error::ElevationCancelled] error is returned.
+    ///
+    /// `ShellExecuteExW` doesn't support the other options, like the standard
+    /// streams or the environment: if one of them has been set, an
+    /// [`InvalidCommand`][Error::InvalidCommand] error is returned before
+    /// spawning, even if the program doesn't require elevation. The elevated
+    /// child has no thread handle, see
+    /// [`raw_process_information`][Child::raw_process_information].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, ElevatedOrNormal};
+    ///
+    /// match Command::new("setup.exe /quiet").spawn_elevated_if_needed() {
+    ///     Ok(ElevatedOrNormal::Elevated(child)) => println!("elevated as {}", child.id()),
+    ///     Ok(ElevatedOrNormal::Normal(child)) => println!("spawned as {}", child.id()),
+    ///     Err(err) if err.is_elevation_cancelled() => println!("the user declined"),
+    ///     Err(err) => println!("cannot spawn the installer: {}", err),
+    /// }
+    /// ```
+    ///
+    /// Equivalent to the [`ShellExecuteExW`][shell-execute-ex-w] function
+    /// with the `runas` verb.
+    ///
+    /// [shell-execute-ex-w]: https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-shellexecuteexw
+    pub fn spawn_elevated_if_needed(&mut self) -> Result<ElevatedOrNormal> {
+        self.spawn_elevated_if_needed_with(
+            |command| command.spawn_with_default_stdio(false),
+            Command::spawn_elevated,
+        )
+    }
+
+    // Spawn the child with `spawn`, then with `elevate` if the program
+    // requires elevation. The decision doesn't depend on the UAC prompt.
+    fn spawn_elevated_if_needed_with(
+        &mut self,
+        spawn: impl FnOnce(&mut Self) -> Result<Child>,
+        elevate: impl FnOnce(&Self) -> Result<Child>,
+    ) -> Result<ElevatedOrNormal> {
+        validation::validate_elevation(self).map_err(self.context(Operation::Spawn, None))?;
+
+        match spawn(self) {
+            Ok(child) => Ok(ElevatedOrNormal::Normal(child)),
+            Err(Error::CreationFailed(ref err)) if os_code(err) == ERROR_ELEVATION_REQUIRED.0 => {
+                elevate(self)
+                    .map(ElevatedOrNormal::Elevated)
+                    .map_err(self.context(Operation::Spawn, None))
+            }
+            Err(err) => Err(self.context(Operation::Spawn, None)(err)),
+        }
+    }
+
+    fn spawn_elevated(&self) -> Result<Child> {
+        let child = self.create_elevated_child();
+
+        match child {
+            Ok(child) => self.finish_spawn(child),
+            Err(err) => {
+                metrics::spawn_failed();
+                Err(err)
+            }
+        }
+    }
+
+    fn create_elevated_child(&self) -> Result<Child> {
+        let (program, args) = validation::program_and_args(&self.command);
+        let file = match &self.application_name {
+            Some(application_name) => wide::to_wide(application_name.as_os_str())?,
+            None => wide::to_wide(&program)?,
+        };
+        let args = wide::to_wide(&args)?;
+        // Many programs don't understand a verbatim working directory.
+        let current_directory = self
+            .current_directory
+            .as_deref()
+            .map(|dir| wide::to_wide(path::from_verbatim(dir).as_os_str()))
+            .transpose()?;
+
+        let mut info = SHELLEXECUTEINFOW {
+            cbSize: mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+            fMask: SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NOASYNC,
+            lpVerb: w!("runas"),
+            lpFile: PCWSTR(file.as_ptr()),
+            lpParameters: PCWSTR(args.as_ptr()),
+            lpDirectory: current_directory
+                .as_ref()
+                .map_or(PCWSTR::null(), |dir| PCWSTR(dir.as_ptr())),
+            nShow: SW_SHOWNORMAL.0,
+            ..Default::default()
+        };
+
+        let pid = unsafe {
+            ShellExecuteExW(&mut info).map_err(|_| match GetLastError() {
+                ERROR_CANCELLED => Error::ElevationCancelled,
+                code => Error::ElevationFailed(io::Error::from_raw_os_error(code.0 as i32)),
+            })?;
+
+            // The process is always created for the `runas` verb of an
+            // executable, a document could be given to a running one.
+            if info.hProcess.is_invalid() {
+                return Err(Error::ElevationFailed(io::Error::from_raw_os_error(
+                    ERROR_INVALID_HANDLE.0 as i32,
+                )));
+            }
+
+            match GetProcessId(info.hProcess) {
+                0 => {
+                    let err = Error::GetProcessIdFailed(io::Error::last_os_error());
+                    let _ = CloseHandle(info.hProcess);
+                    return Err(err);
+                }
+                pid => pid,
+            }
+        };
+
+        // There is no thread handle to close.
+        #[cfg(feature = "leak-check")]
+        crate::leak_check::opened(1);
+
+        Ok(Child {
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            process_information: PROCESS_INFORMATION {
+                hProcess: info.hProcess,
+                hThread: HANDLE::default(),
+                dwProcessId: pid,
+                dwThreadId: 0,
+            },
+            api: Arc::new(Win32ProcessApi),
+            command: self.command.clone(),
+            current_directory: self.current_directory.clone(),
+            exit_status: OnceLock::new(),
+            suspend_count: AtomicU32::new(0),
+            cpu_sample: Mutex::new(None),
+            warn_on_unreaped: false,
+            registered: false,
+            tag: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockProcessApi;
+    use std::cell::Cell;
+
+    // Spawn `command` with a fake, the elevation spawning another fake child
+    // instead of showing the UAC prompt. The number of elevations is counted
+    // in `elevations`.
+    fn spawn(
+        command: &mut Command,
+        api: &MockProcessApi,
+        elevations: &Cell<u32>,
+        elevation: Result<()>,
+    ) -> Result<ElevatedOrNormal> {
+        command.spawn_elevated_if_needed_with(
+            |command| command.spawn_with_api_and_default_stdio(Arc::new(api.clone()), false),
+            |command| {
+                elevations.set(elevations.get() + 1);
+                elevation?;
+                command
+                    .clone()
+                    .spawn_with_api_and_default_stdio(Arc::new(api.clone()), false)
+            },
+        )
+    }
+
+    #[test]
+    fn program_requiring_elevation_is_elevated() {
+        let api = MockProcessApi::new();
+        let elevations = Cell::new(0);
+        api.fail_create(ERROR_ELEVATION_REQUIRED.0);
+
+        let spawned = spawn(&mut Command::new("setup.exe"), &api, &elevations, Ok(())).unwrap();
+
+        assert!(spawned.is_elevated());
+        assert_eq!(elevations.get(), 1);
+    }
+
+    #[test]
+    fn other_programs_are_not_elevated() {
+        let api = MockProcessApi::new();
+        let elevations = Cell::new(0);
+
+        let spawned = spawn(&mut Command::new("setup.exe"), &api, &elevations, Ok(())).unwrap();
+        assert!(!spawned.is_elevated());
+
+        // `ERROR_ACCESS_DENIED`
+        api.fail_create(5);
+        let err = spawn(&mut Command::new("setup.exe"), &api, &elevations, Ok(())).unwrap_err();
+        assert_eq!(err.code(), 5);
+        assert!(err.is_spawn_failure());
+
+        assert_eq!(elevations.get(), 0);
+    }
+
+    #[test]
+    fn declined_elevation_is_cancelled() {
+        let api = MockProcessApi::new();
+        let elevations = Cell::new(0);
+        api.fail_create(ERROR_ELEVATION_REQUIRED.0);
+
+        let err = spawn(
+            &mut Command::new("setup.exe"),
+            &api,
+            &elevations,
+            Err(Error::ElevationCancelled),
+        )
+        .unwrap_err();
+
+        assert!(err.is_elevation_cancelled());
+        assert!(err.is_spawn_failure());
+        assert_eq!(elevations.get(), 1);
+    }
+}
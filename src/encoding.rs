@@ -0,0 +1,206 @@
+// Decoding of the output of console programs, which isn't always UTF-8.
+
+use crate::Output;
+use windows::Win32::Globalization::{
+    GetACP, GetOEMCP, MultiByteToWideChar, MULTI_BYTE_TO_WIDE_CHAR_FLAGS,
+};
+
+/// The encoding of the output of a process, used by
+/// [`Output::stdout_decoded`].
+///
+/// Console programs often write text in the OEM code page (like 437 or 850)
+/// or in the ANSI code page (like 1252) instead of UTF-8, depending on how
+/// they have been built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputEncoding {
+    /// UTF-8.
+    Utf8,
+    /// The OEM code page of the system, used by the console by default. See
+    /// [`GetOEMCP`](https://docs.microsoft.com/en-us/windows/win32/api/winnls/nf-winnls-getoemcp).
+    OemCodepage,
+    /// The ANSI code page of the system. See
+    /// [`GetACP`](https://docs.microsoft.com/en-us/windows/win32/api/winnls/nf-winnls-getacp).
+    AnsiCodepage,
+    /// UTF-16 little-endian, written by some programs like `cmd.exe /u`.
+    Utf16Le,
+    /// Guess the encoding: UTF-16 if the output starts with a UTF-16 byte
+    /// order mark, UTF-8 if the output is valid UTF-8 and the OEM code page
+    /// otherwise.
+    #[default]
+    Auto,
+}
+
+impl OutputEncoding {
+    /// Decodes `bytes` with this encoding.
+    ///
+    /// The invalid sequences are replaced with `U+FFFD REPLACEMENT
+    /// CHARACTER`, or with the default character of the code page. A byte
+    /// order mark at the start of the bytes is removed.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 => {
+                let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+            Self::OemCodepage => decode_codepage(unsafe { GetOEMCP() }, bytes),
+            Self::AnsiCodepage => decode_codepage(unsafe { GetACP() }, bytes),
+            Self::Utf16Le => {
+                let bytes = bytes.strip_prefix(b"\xFF\xFE").unwrap_or(bytes);
+                let mut text = String::from_utf16_lossy(
+                    &bytes
+                        .chunks_exact(2)
+                        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                        .collect::<Vec<_>>(),
+                );
+
+                // A truncated code unit.
+                if bytes.len() % 2 != 0 {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                }
+
+                text
+            }
+            Self::Auto => {
+                if bytes.starts_with(b"\xFF\xFE") {
+                    Self::Utf16Le.decode(bytes)
+                } else if std::str::from_utf8(bytes).is_ok() {
+                    Self::Utf8.decode(bytes)
+                } else {
+                    Self::OemCodepage.decode(bytes)
+                }
+            }
+        }
+    }
+}
+
+impl Output {
+    /// Returns the standard output decoded with `encoding`.
+    ///
+    /// See [`OutputEncoding::decode`] for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, OutputEncoding};
+    ///
+    /// let output = Command::new("tree.com C:\\Users").output().unwrap();
+    ///
+    /// println!("{}", output.stdout_decoded(OutputEncoding::OemCodepage));
+    /// ```
+    pub fn stdout_decoded(&self, encoding: OutputEncoding) -> String {
+        encoding.decode(&self.stdout)
+    }
+
+    /// Returns the standard error decoded with `encoding`.
+    ///
+    /// See [`OutputEncoding::decode`] for more information.
+    pub fn stderr_decoded(&self, encoding: OutputEncoding) -> String {
+        encoding.decode(&self.stderr)
+    }
+}
+
+// Decode bytes in a code page with `MultiByteToWideChar`. If the code page
+// cannot be used, the bytes are decoded as UTF-8.
+fn decode_codepage(codepage: u32, bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return String::new();
+    }
+
+    // The length of the input is an `i32`.
+    if i32::try_from(bytes.len()).is_ok() {
+        let flags = MULTI_BYTE_TO_WIDE_CHAR_FLAGS(0);
+        let wide_len = unsafe { MultiByteToWideChar(codepage, flags, bytes, None) };
+
+        if wide_len > 0 {
+            let mut wide = vec![0; wide_len as usize];
+            let written = unsafe { MultiByteToWideChar(codepage, flags, bytes, Some(&mut wide)) };
+
+            if written > 0 {
+                wide.truncate(written as usize);
+
+                return String::from_utf16_lossy(&wide);
+            }
+        }
+    }
+
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn utf8_byte_order_mark_is_removed() {
+        assert_eq!(
+            OutputEncoding::Utf8.decode(b"\xEF\xBB\xBFcaf\xC3\xA9"),
+            "café"
+        );
+        assert_eq!(OutputEncoding::Utf8.decode(b"caf\xE9"), "caf\u{fffd}");
+        assert_eq!(OutputEncoding::Utf8.decode(b""), "");
+    }
+
+    #[test]
+    fn utf16_byte_order_mark_is_removed() {
+        let mut bytes = b"\xFF\xFE".to_vec();
+        bytes.extend(utf16le("café 😀"));
+
+        assert_eq!(OutputEncoding::Utf16Le.decode(&bytes), "café 😀");
+        assert_eq!(OutputEncoding::Utf16Le.decode(&utf16le("no bom")), "no bom");
+    }
+
+    #[test]
+    fn odd_utf16_length_ends_with_a_replacement() {
+        let mut bytes = utf16le("ab");
+        bytes.push(b'c');
+
+        assert_eq!(OutputEncoding::Utf16Le.decode(&bytes), "ab\u{fffd}");
+        // An unpaired surrogate.
+        assert_eq!(
+            OutputEncoding::Utf16Le.decode(&[0x3D, 0xD8, b'a', 0]),
+            "\u{fffd}a"
+        );
+    }
+
+    #[test]
+    fn code_pages_are_decoded() {
+        // `é` in the code pages 437 and 1252.
+        assert_eq!(decode_codepage(437, b"caf\x82"), "café");
+        assert_eq!(decode_codepage(1252, b"caf\xE9"), "café");
+        assert_eq!(decode_codepage(437, b""), "");
+        // The code pages of the system are supersets of ASCII.
+        assert_eq!(
+            OutputEncoding::OemCodepage.decode(b"plain text\r\n"),
+            "plain text\r\n"
+        );
+        assert_eq!(
+            OutputEncoding::AnsiCodepage.decode(b"plain text\r\n"),
+            "plain text\r\n"
+        );
+    }
+
+    #[test]
+    fn auto_detects_the_encoding() {
+        let mut utf16 = b"\xFF\xFE".to_vec();
+        utf16.extend(utf16le("wide"));
+        assert_eq!(OutputEncoding::Auto.decode(&utf16), "wide");
+
+        assert_eq!(
+            OutputEncoding::Auto.decode(b"\xEF\xBB\xBFcaf\xC3\xA9"),
+            "café"
+        );
+        assert_eq!(OutputEncoding::Auto.decode("café".as_bytes()), "café");
+
+        // Not UTF-8, so in the OEM code page.
+        assert_eq!(
+            OutputEncoding::Auto.decode(b"caf\x82"),
+            OutputEncoding::OemCodepage.decode(b"caf\x82")
+        );
+        assert_eq!(OutputEncoding::default(), OutputEncoding::Auto);
+    }
+}
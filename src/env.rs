@@ -0,0 +1,238 @@
+// The environment of a child process, given to `CreateProcessW` as a block of
+// `name=value` strings.
+
+use crate::wide;
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::OsStrExt,
+};
+use windows::{
+    core::PCWSTR,
+    Win32::System::Environment::{FreeEnvironmentStringsW, GetEnvironmentStringsW},
+};
+
+// The variables kept by `Command::clean_env`.
+pub(crate) const ESSENTIAL_VARIABLES: &[&str] = &[
+    "SystemRoot",
+    "SystemDrive",
+    "windir",
+    "ComSpec",
+    "PATHEXT",
+    "TEMP",
+    "TMP",
+    "NUMBER_OF_PROCESSORS",
+];
+
+// What the environment of the child is built from, before the variables set
+// with `Command::env`.
+#[derive(Debug, Clone, Default)]
+pub(crate) enum Base {
+    // The environment of the calling process.
+    #[default]
+    Inherit,
+    // The variables of the calling process with these names.
+    Clean(Vec<OsString>),
+    // The variables of a snapshot.
+    Snapshot(Vec<(OsString, OsString)>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Environment {
+    pub(crate) base: Base,
+    pub(crate) vars: Vec<(OsString, OsString)>,
+}
+
+impl Environment {
+    // Whether the child inherits the environment of the calling process
+    // unchanged.
+    pub(crate) fn is_inherited(&self) -> bool {
+        matches!(self.base, Base::Inherit) && self.vars.is_empty()
+    }
+
+    // The value of the variable `name` in the environment of the child.
+    pub(crate) fn var(&self, name: &OsStr) -> Option<OsString> {
+        let name_key = key(name);
+        let find = |vars: &[(OsString, OsString)]| {
+            vars.iter()
+                .rev()
+                .find(|(other, _)| key(other) == name_key)
+                .map(|(_, value)| value.clone())
+        };
+
+        find(&self.vars).or_else(|| match &self.base {
+            Base::Inherit => std::env::var_os(name),
+            Base::Clean(names) => names
+                .iter()
+                .any(|other| key(other) == name_key)
+                .then(|| std::env::var_os(name))
+                .flatten(),
+            Base::Snapshot(vars) => find(vars),
+        })
+    }
+
+    // Build the environment block, `None` when the child inherits the
+    // environment of the calling process unchanged.
+    pub(crate) fn to_block(&self) -> Option<Vec<u16>> {
+        let mut vars = match &self.base {
+            Base::Inherit if self.vars.is_empty() => return None,
+            Base::Inherit => std::env::vars_os().collect::<Vec<_>>(),
+            Base::Clean(names) => names
+                .iter()
+                .filter_map(|name| Some((name.clone(), std::env::var_os(name)?)))
+                .collect(),
+            Base::Snapshot(vars) => vars.clone(),
+        };
+
+        for (name, value) in &self.vars {
+            set(&mut vars, name, value);
+        }
+
+        // The variables are sorted by name, regardless of the case.
+        vars.sort_by_cached_key(|(name, _)| key(name));
+
+        let mut block = Vec::new();
+
+        for (name, value) in vars {
+            block.extend(name.encode_wide());
+            block.push(b'=' as u16);
+            block.extend(value.encode_wide());
+            block.push(0);
+        }
+
+        // An empty block still needs its terminator.
+        if block.is_empty() {
+            block.push(0);
+        }
+
+        block.push(0);
+
+        Some(block)
+    }
+}
+
+/// The environment of the calling process at a point in time.
+///
+/// The children spawned with a snapshot as their
+/// [base environment][crate::Command::base_env] don't see the changes made to
+/// the environment of the calling process after the snapshot, like the ones
+/// of [`std::env::set_var`]. The snapshot is read at once, it is consistent
+/// even if other threads change the environment.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{Command, EnvironmentSnapshot};
+///
+/// let snapshot = EnvironmentSnapshot::capture();
+///
+/// std::env::set_var("RUST_LOG", "trace");
+///
+/// // The child doesn't see `RUST_LOG=trace`.
+/// let status = Command::new("worker.exe")
+///     .base_env(&snapshot)
+///     .status()
+///     .expect("worker failed to start");
+///
+/// println!("PATH was {:?}", snapshot.get("PATH"));
+/// ```
+///
+/// Equivalent to the [`GetEnvironmentStringsW`][get-environment-strings-w]
+/// function.
+///
+/// [get-environment-strings-w]: https://docs.microsoft.com/en-us/windows/win32/api/processenv/nf-processenv-getenvironmentstringsw
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EnvironmentSnapshot {
+    vars: Vec<(OsString, OsString)>,
+}
+
+impl EnvironmentSnapshot {
+    /// Captures the environment of the calling process.
+    ///
+    /// An environment that cannot be read gives an empty snapshot.
+    pub fn capture() -> Self {
+        unsafe {
+            let block = GetEnvironmentStringsW();
+
+            if block.is_null() {
+                return Self::default();
+            }
+
+            let vars = parse_block(block.0);
+
+            let _ = FreeEnvironmentStringsW(PCWSTR(block.0));
+
+            Self { vars }
+        }
+    }
+
+    /// Returns the value of the variable named `name`, regardless of the
+    /// case.
+    pub fn get(&self, name: impl AsRef<OsStr>) -> Option<&OsStr> {
+        let name_key = key(name.as_ref());
+
+        self.vars
+            .iter()
+            .find(|(other, _)| key(other) == name_key)
+            .map(|(_, value)| value.as_os_str())
+    }
+
+    /// Returns an iterator over the names and the values of the variables, in
+    /// the order of the environment block.
+    pub fn iter(&self) -> impl Iterator<Item = (&OsStr, &OsStr)> {
+        self.vars
+            .iter()
+            .map(|(name, value)| (name.as_os_str(), value.as_os_str()))
+    }
+
+    pub(crate) fn vars(&self) -> &[(OsString, OsString)] {
+        &self.vars
+    }
+}
+
+// Parse an environment block, a sequence of `name=value` strings terminated by
+// an empty string.
+//
+// Safety: `block` must point to a valid environment block.
+pub(crate) unsafe fn parse_block(block: *const u16) -> Vec<(OsString, OsString)> {
+    let mut vars = Vec::new();
+    let mut ptr = block;
+
+    while *ptr != 0 {
+        let entry = wide::from_wide_until_nul(ptr);
+
+        ptr = ptr.add(entry.encode_wide().count() + 1);
+
+        if let Some(var) = split_entry(&entry) {
+            vars.push(var);
+        }
+    }
+
+    vars
+}
+
+// Split a `name=value` entry. The name can start with `=`, like the hidden
+// variables holding the current directory of each drive.
+fn split_entry(entry: &OsStr) -> Option<(OsString, OsString)> {
+    let entry = entry.encode_wide().collect::<Vec<_>>();
+    let separator = entry.iter().skip(1).position(|&unit| unit == b'=' as u16)? + 1;
+
+    Some((
+        wide::from_wide(&entry[..separator]),
+        wide::from_wide(&entry[separator + 1..]),
+    ))
+}
+
+// Set a variable, replacing the one with the same name regardless of the case.
+fn set(vars: &mut Vec<(OsString, OsString)>, name: &OsStr, value: &OsStr) {
+    let name_key = key(name);
+
+    match vars.iter_mut().find(|(other, _)| key(other) == name_key) {
+        Some((_, other_value)) => *other_value = value.to_owned(),
+        None => vars.push((name.to_owned(), value.to_owned())),
+    }
+}
+
+// The names of the variables are case-insensitive.
+fn key(name: &OsStr) -> String {
+    name.to_string_lossy().to_uppercase()
+}
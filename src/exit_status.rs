@@ -0,0 +1,350 @@
+use crate::{Command, Error, ExitStatus, Operation, Result};
+use std::{fmt, process::ExitCode};
+use windows::Win32::System::Threading::ExitProcess;
+
+/// The error of an unsuccessful [`ExitStatus`].
+///
+/// Returned by [`ExitStatus::exit_ok`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatusError(ExitStatus);
+
+impl ExitStatus {
+    /// The exception code of an access violation, like dereferencing a null
+    /// pointer.
+    pub const ACCESS_VIOLATION: u32 = 0xC000_0005;
+    /// The exception code of an illegal instruction.
+    pub const ILLEGAL_INSTRUCTION: u32 = 0xC000_001D;
+    /// The exception code of a stack overflow.
+    pub const STACK_OVERFLOW: u32 = 0xC000_00FD;
+    /// The code of a process that cannot start because a DLL it depends on
+    /// cannot be found.
+    pub const DLL_NOT_FOUND: u32 = 0xC000_0135;
+    /// The exception code of a corrupted heap.
+    pub const HEAP_CORRUPTION: u32 = 0xC000_0374;
+    /// The exception code of a fast fail, used by `abort` and by the stack
+    /// buffer overrun checks.
+    pub const STACK_BUFFER_OVERRUN: u32 = 0xC000_0409;
+    /// The code of a console process ended by `Ctrl+C` or `Ctrl+Break`,
+    /// `STATUS_CONTROL_C_EXIT`. See [`is_interrupted`][ExitStatus::is_interrupted].
+    pub const CONTROL_C_EXIT: u32 = 0xC000_013A;
+
+    /// Creates an exit status from a raw exit code, like
+    /// [`ExitStatusExt::from_raw`][std::os::windows::process::ExitStatusExt::from_raw]
+    /// does for the standard library.
+    ///
+    /// A constructed status behaves exactly like the status of a process that
+    /// exited with this code, which is useful to test the code handling exit
+    /// statuses.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use CreateProcessW::ExitStatus;
+    ///
+    /// let status = ExitStatus::from_raw(ExitStatus::ACCESS_VIOLATION);
+    ///
+    /// assert!(status.crashed());
+    /// assert_eq!(status.to_string(), "0xC0000005");
+    /// ```
+    pub const fn from_raw(code: u32) -> Self {
+        Self(code)
+    }
+
+    /// Converts the exit status into a `Result`, an unsuccessful status being
+    /// an [`ExitStatusError`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let status = Command::new("cargo.exe check").status().unwrap();
+    ///
+    /// if let Err(err) = status.exit_ok() {
+    ///     eprintln!("cargo check failed: {}", err);
+    /// }
+    /// ```
+    pub fn exit_ok(self) -> std::result::Result<(), ExitStatusError> {
+        if self.success() {
+            Ok(())
+        } else {
+            Err(ExitStatusError(self))
+        }
+    }
+
+    /// Returns the exit code of the process as a signed integer, like the
+    /// exit codes of the standard library.
+    ///
+    /// [`code`][ExitStatus::code] and this function are two views of the same
+    /// 32 bits. The `NTSTATUS` codes are usually written in hexadecimal and
+    /// are negative as signed integers: an access violation is `0xC0000005`,
+    /// which is `3221225477` as returned by `code` and `-1073741819` as
+    /// returned by this function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let status = Command::new("cargo.exe check").status().unwrap();
+    ///
+    /// std::process::exit(status.code_i32());
+    /// ```
+    pub fn code_i32(&self) -> i32 {
+        self.code() as i32
+    }
+
+    /// Ends the calling process with the exit code of this status.
+    ///
+    /// The full 32-bit code is preserved, including the `NTSTATUS` values,
+    /// so a wrapper program can exit exactly like the child it ran. Like
+    /// [`std::process::exit`], the destructors of the current thread and the
+    /// other threads aren't run.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let status = Command::new("tool.exe --from-shim").status().unwrap();
+    ///
+    /// status.exit_process();
+    /// ```
+    ///
+    /// Equivalent to the [`ExitProcess`][exit-process] function.
+    ///
+    /// [exit-process]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-exitprocess
+    pub fn exit_process(self) -> ! {
+        unsafe { ExitProcess(self.code()) }
+    }
+
+    /// Converts the exit status into an [`ExitCode`], to be returned from
+    /// `main`.
+    ///
+    /// This is a best-effort conversion: an `ExitCode` can only be created
+    /// from a `u8` with the standard library, so the codes above `255` become
+    /// [`ExitCode::FAILURE`]. Use [`exit_process`][ExitStatus::exit_process]
+    /// to preserve the full code.
+    pub fn to_exit_code(&self) -> ExitCode {
+        match u8::try_from(self.code()) {
+            Ok(code) => ExitCode::from(code),
+            Err(_) => ExitCode::FAILURE,
+        }
+    }
+
+    /// Returns the exit code formatted in hexadecimal, like `0xC0000005`.
+    ///
+    /// This is how the `NTSTATUS` codes are usually written, the
+    /// [`Display`][fmt::Display] implementation uses it for them.
+    pub fn to_hex_string(&self) -> String {
+        format!("0x{:08X}", self.code())
+    }
+
+    /// Returns `true` if the process looks like it crashed, which is when
+    /// the exit code is an `NTSTATUS` error (the severity bits `0xC0000000`
+    /// are set), like an access violation.
+    ///
+    /// A process [interrupted][ExitStatus::is_interrupted] by `Ctrl+C` isn't
+    /// considered as crashed, even if its exit code is an `NTSTATUS` error.
+    ///
+    /// This is a guess: a program can exit with such a code on purpose, and
+    /// a program that crashed can be ended with any code by its exception
+    /// handler. In practice, programs rarely use these codes for a deliberate
+    /// exit.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, ExitStatus};
+    ///
+    /// let status = Command::new("worker.exe").status().unwrap();
+    ///
+    /// if status.crashed() {
+    ///     println!("worker crashed with {}", status);
+    ///
+    ///     if status.exception_code() == Some(ExitStatus::ACCESS_VIOLATION) {
+    ///         println!("access violation");
+    ///     }
+    /// }
+    /// ```
+    pub fn crashed(&self) -> bool {
+        self.code() & 0xC000_0000 == 0xC000_0000 && !self.is_interrupted()
+    }
+
+    /// Returns `true` if the process has been ended by `Ctrl+C` or
+    /// `Ctrl+Break`, which is when the exit code is
+    /// [`CONTROL_C_EXIT`][ExitStatus::CONTROL_C_EXIT].
+    ///
+    /// This is the exit code of a console process that doesn't handle these
+    /// events.
+    pub fn is_interrupted(&self) -> bool {
+        self.code() == Self::CONTROL_C_EXIT
+    }
+
+    /// Returns the exit code if the process [crashed][ExitStatus::crashed],
+    /// which is the code of the exception that ended it.
+    ///
+    /// The codes of the common exceptions are available as constants, like
+    /// [`ACCESS_VIOLATION`][ExitStatus::ACCESS_VIOLATION].
+    pub fn exception_code(&self) -> Option<u32> {
+        self.crashed().then(|| self.code())
+    }
+
+    // Whether one of the severity bits of an `NTSTATUS` value is set:
+    // `0x40000000` for informational, `0x80000000` for warning and
+    // `0xC0000000` for error values.
+    pub(crate) fn has_severity(&self) -> bool {
+        self.code() & 0xC000_0000 != 0
+    }
+}
+
+impl PartialEq<u32> for ExitStatus {
+    fn eq(&self, other: &u32) -> bool {
+        self.code() == *other
+    }
+}
+
+impl PartialEq<i32> for ExitStatus {
+    fn eq(&self, other: &i32) -> bool {
+        self.code_i32() == *other
+    }
+}
+
+impl ExitStatusError {
+    /// Returns the exit code of the process, which is never zero.
+    pub fn code(&self) -> u32 {
+        self.0.code()
+    }
+
+    /// Returns the exit status of the process.
+    pub fn status(&self) -> ExitStatus {
+        self.0
+    }
+}
+
+impl fmt::Display for ExitStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Like `0xC0000005` for a crash, see the `Display` of `ExitStatus`.
+        write!(f, "process exited unsuccessfully: exit code {}", self.0)
+    }
+}
+
+impl std::error::Error for ExitStatusError {}
+
+impl Command {
+    /// Executes a command as a child process, waiting for it to finish and
+    /// checking that it exited successfully.
+    ///
+    /// This is a shortcut for [`status`][Command::status] followed by
+    /// [`exit_ok`][ExitStatus::exit_ok]. An unsuccessful exit status is
+    /// returned as an [`Unsuccessful`][Error::Unsuccessful] error, wrapped in
+    /// a [`CommandFailed`][Error::CommandFailed] error like the other errors.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// Command::new("cargo.exe build --release")
+    ///     .run()
+    ///     .expect("cargo build failed");
+    /// ```
+    pub fn run(&mut self) -> Result<()> {
+        let child = self.spawn()?;
+        let pid = Some(child.id());
+
+        let status = child.wait().map_err(self.context(Operation::Wait, pid))?;
+
+        status
+            .exit_ok()
+            .map_err(|err| self.context(Operation::Run, pid)(Error::Unsuccessful(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn successful_status_is_ok() {
+        assert_eq!(ExitStatus::from_raw(0).exit_ok(), Ok(()));
+    }
+
+    #[test]
+    fn failure_code_is_formatted_in_decimal() {
+        let err = ExitStatus::from_raw(1).exit_ok().unwrap_err();
+
+        assert_eq!(err.code(), 1);
+        assert_eq!(
+            err.to_string(),
+            "process exited unsuccessfully: exit code 1"
+        );
+    }
+
+    #[test]
+    fn crash_is_formatted_in_hexadecimal() {
+        let err = ExitStatus::from_raw(ExitStatus::ACCESS_VIOLATION)
+            .exit_ok()
+            .unwrap_err();
+
+        assert_eq!(err.status(), ExitStatus::from_raw(0xC000_0005));
+        assert_eq!(
+            err.to_string(),
+            "process exited unsuccessfully: exit code 0xC0000005"
+        );
+    }
+
+    #[test]
+    fn access_violation_is_negative_as_a_signed_code() {
+        let status = ExitStatus::from_raw(0xC000_0005);
+
+        assert_eq!(status.code(), 3_221_225_477);
+        assert_eq!(status.code_i32(), -1_073_741_819);
+        assert_eq!(status, 0xC000_0005u32);
+        assert_eq!(status, -1_073_741_819i32);
+        assert_ne!(status, 5u32);
+        assert_ne!(status, 5i32);
+    }
+
+    #[test]
+    fn codes_with_a_severity_are_displayed_in_hexadecimal() {
+        let display = |code| ExitStatus::from_raw(code).to_string();
+
+        assert_eq!(display(0x3FFF_FFFF), "1073741823");
+        // Informational, warning and error severities.
+        assert_eq!(display(0x4000_0000), "0x40000000");
+        assert_eq!(display(0x8000_0000), "0x80000000");
+        assert_eq!(display(0xC000_0000), "0xC0000000");
+        assert_eq!(display(0xFFFF_FFFF), "0xFFFFFFFF");
+        assert_eq!(
+            display(ExitStatus::CONTROL_C_EXIT),
+            "0xC000013A: interrupted (Ctrl+C)"
+        );
+    }
+
+    #[test]
+    fn control_c_exit_is_an_interruption() {
+        let status = ExitStatus::from_raw(ExitStatus::CONTROL_C_EXIT);
+
+        assert!(status.is_interrupted());
+        assert!(!status.crashed());
+        assert_eq!(status.exception_code(), None);
+        assert!(!ExitStatus::from_raw(ExitStatus::ACCESS_VIOLATION).is_interrupted());
+    }
+
+    #[test]
+    fn ntstatus_errors_are_crashes() {
+        let status = ExitStatus::from_raw(ExitStatus::STACK_OVERFLOW);
+        assert!(status.crashed());
+        assert_eq!(status.exception_code(), Some(ExitStatus::STACK_OVERFLOW));
+
+        // A warning isn't an error.
+        let status = ExitStatus::from_raw(0x8000_0003);
+        assert!(!status.crashed());
+        assert_eq!(status.exception_code(), None);
+
+        let status = ExitStatus::from_raw(1);
+        assert!(!status.crashed());
+        assert_eq!(status.exception_code(), None);
+    }
+}
@@ -0,0 +1,345 @@
+// Run a callback on the system thread pool when a process exits, without
+// blocking a thread per process.
+//
+// The registration is shared between the thread pool and its owner through an
+// `Arc`: the thread pool holds one reference for as long as the callback can
+// run. Whoever comes last between the callback and the cancellation gets to
+// unregister the wait.
+
+use crate::{Child, Error, ExitStatus, Result};
+use std::{
+    ffi::c_void,
+    fmt, io,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+};
+use windows::Win32::{
+    Foundation::{
+        CloseHandle, DuplicateHandle, BOOLEAN, DUPLICATE_SAME_ACCESS, HANDLE, INVALID_HANDLE_VALUE,
+    },
+    System::Threading::{
+        GetCurrentProcess, GetExitCodeProcess, RegisterWaitForSingleObject, UnregisterWaitEx,
+        INFINITE, WT_EXECUTEONLYONCE,
+    },
+};
+
+// The callback receives the (duplicated) process handle.
+type Callback = Box<dyn FnOnce(HANDLE) + Send>;
+
+/// A registration of a callback called when a child process exits.
+///
+/// Returned by [`Child::on_exit`]. Dropping the `ExitWatch` cancels the
+/// registration: it must be kept alive for the callback to be called. If the
+/// callback is running when the `ExitWatch` is dropped, the drop blocks until
+/// the callback has completed.
+pub struct ExitWatch {
+    exit_wait: Arc<ExitWait>,
+}
+
+impl fmt::Debug for ExitWatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExitWatch")
+            .field("fired", &self.exit_wait.fired.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+impl ExitWatch {
+    /// Cancels the registration, returning `true` if the callback has already
+    /// been called.
+    ///
+    /// This is the same as dropping the `ExitWatch`, except that it tells if
+    /// the callback has been called. This must not be called from the callback
+    /// itself.
+    pub fn cancel(self) -> bool {
+        self.exit_wait.cancel()
+    }
+}
+
+impl Drop for ExitWatch {
+    fn drop(&mut self) {
+        self.exit_wait.cancel();
+    }
+}
+
+impl Child {
+    /// Registers a callback called with the exit status of the child process
+    /// once it exits.
+    ///
+    /// The callback runs on a thread of the system thread pool instead of
+    /// blocking a thread per child, it should return quickly. If the child has
+    /// already exited, the callback is called right away. The registration is
+    /// cancelled when the returned [`ExitWatch`] is dropped. The callback
+    /// isn't called if the exit status of the process cannot be retrieved. If
+    /// the wait cannot be registered, a
+    /// [`RegisterWaitFailed`][Error::RegisterWaitFailed] error is returned.
+    ///
+    /// Equivalent to the [`RegisterWaitForSingleObject`][register-wait-for-single-object]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// let watch = child
+    ///     .on_exit(|status| println!("notepad exited with {}", status))
+    ///     .unwrap();
+    ///
+    /// // The callback is only called as long as `watch` is alive.
+    /// child.wait().unwrap();
+    /// drop(watch);
+    /// ```
+    ///
+    /// [register-wait-for-single-object]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-registerwaitforsingleobject
+    pub fn on_exit(&self, callback: impl FnOnce(ExitStatus) + Send + 'static) -> Result<ExitWatch> {
+        let exit_wait = ExitWait::register(self.process_information.hProcess, move |process| {
+            let mut exit_code: u32 = 0;
+
+            if unsafe { GetExitCodeProcess(process, &mut exit_code as *mut u32) }.is_ok() {
+                callback(ExitStatus(exit_code));
+            }
+        })?;
+
+        Ok(ExitWatch { exit_wait })
+    }
+
+    /// Returns a channel receiving the exit status of the child process once
+    /// it exits.
+    ///
+    /// Exactly one message is sent, then the channel is closed. This relies on
+    /// the same registration as [`on_exit`][Child::on_exit], but the
+    /// registration isn't tied to a guard: it is released once the process
+    /// has exited, even if the receiver has been dropped. The channel is
+    /// closed without a message if the exit status of the process cannot be
+    /// retrieved.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::time::Duration;
+    ///
+    /// let child = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    /// let exit = child.exit_channel().unwrap();
+    ///
+    /// match exit.recv_timeout(Duration::from_secs(5)) {
+    ///     Ok(status) => println!("exited with {}", status),
+    ///     Err(_) => println!("still running"),
+    /// }
+    /// ```
+    pub fn exit_channel(&self) -> Result<Receiver<ExitStatus>> {
+        let (sender, receiver) = mpsc::channel();
+
+        // The registration releases itself once the callback has run.
+        ExitWait::register(self.process_information.hProcess, move |process| {
+            let mut exit_code: u32 = 0;
+
+            if unsafe { GetExitCodeProcess(process, &mut exit_code as *mut u32) }.is_ok() {
+                let _ = sender.send(ExitStatus(exit_code));
+            }
+        })?;
+
+        Ok(receiver)
+    }
+
+    /// Waits asynchronously for the child to exit completely, returning the
+    /// status that it exited with.
+    ///
+    /// The wait is registered on the system thread pool, so no thread of the
+    /// runtime is blocked. Dropping the future before completion cancels the
+    /// registration without closing the handles of the child, so it
+    /// composes with `tokio::time::timeout`. See [`wait`][Child::wait] for
+    /// more information.
+    ///
+    /// Requires the `tokio` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() {
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// match tokio::time::timeout(Duration::from_secs(5), child.wait_async()).await {
+    ///     Ok(status) => println!("exited with {}", status.unwrap()),
+    ///     Err(_) => child.kill().unwrap(),
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn wait_async(&self) -> Result<ExitStatus> {
+        if let Some(status) = self.exit_status.get() {
+            return Ok(*status);
+        }
+
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+
+        let watch = ExitWatch {
+            exit_wait: ExitWait::register(self.process_information.hProcess, move |_| {
+                let _ = sender.send(());
+            })?,
+        };
+
+        let _ = receiver.await;
+        drop(watch);
+
+        // The process has exited, this doesn't block.
+        self.wait()
+    }
+}
+
+pub(crate) struct ExitWait {
+    // Duplicated process handle, kept open until the registration is dropped.
+    process: HANDLE,
+    wait: Mutex<Option<HANDLE>>,
+    callback: Mutex<Option<Callback>>,
+    fired: AtomicBool,
+}
+
+impl ExitWait {
+    // Register `callback` to be called once the process behind `process`
+    // exits. The callback is called right away (on the thread pool) if the
+    // process has already exited.
+    pub(crate) fn register(
+        process: HANDLE,
+        callback: impl FnOnce(HANDLE) + Send + 'static,
+    ) -> Result<Arc<Self>> {
+        let mut duplicate = HANDLE::default();
+
+        unsafe {
+            DuplicateHandle(
+                GetCurrentProcess(),
+                process,
+                GetCurrentProcess(),
+                &mut duplicate,
+                0,
+                false,
+                DUPLICATE_SAME_ACCESS,
+            )
+            .map_err(|_| Error::DuplicateHandleFailed(io::Error::last_os_error()))?;
+        }
+
+        #[cfg(feature = "leak-check")]
+        crate::leak_check::opened(1);
+
+        let exit_wait = Arc::new(Self {
+            process: duplicate,
+            wait: Mutex::new(None),
+            callback: Mutex::new(Some(Box::new(callback))),
+            fired: AtomicBool::new(false),
+        });
+
+        // The callback locks `wait` last, once the user callback has run, to
+        // unregister the wait. Keeping the lock while registering ensures the
+        // wait handle is stored before the callback takes it, even if the
+        // process has already exited.
+        let mut wait = exit_wait.wait.lock().unwrap_or_else(|err| err.into_inner());
+        let context = Arc::into_raw(Arc::clone(&exit_wait));
+        let mut wait_handle = HANDLE::default();
+
+        let res = unsafe {
+            RegisterWaitForSingleObject(
+                &mut wait_handle,
+                exit_wait.process,
+                Some(exit_callback),
+                Some(context as *const c_void),
+                INFINITE,
+                WT_EXECUTEONLYONCE,
+            )
+        };
+
+        match res {
+            Ok(()) => {
+                *wait = Some(wait_handle);
+                drop(wait);
+                Ok(exit_wait)
+            }
+            Err(_) => {
+                let err = io::Error::last_os_error();
+                drop(wait);
+                // The thread pool never got the reference.
+                unsafe { drop(Arc::from_raw(context)) };
+                Err(Error::RegisterWaitFailed(err))
+            }
+        }
+    }
+
+    // Cancel the registration, waiting for the callback to complete if it is
+    // running. Returns `true` if the callback has been (or is being) called.
+    //
+    // This must not be called from the callback itself.
+    pub(crate) fn cancel(self: &Arc<Self>) -> bool {
+        let wait = self
+            .wait
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+
+        if let Some(wait) = wait {
+            // `INVALID_HANDLE_VALUE` makes the call block until the callback
+            // (if running) has completed.
+            unsafe {
+                let _ = UnregisterWaitEx(wait, INVALID_HANDLE_VALUE);
+            }
+
+            if !self.fired.load(Ordering::SeqCst) {
+                // The callback will never run, release its reference.
+                unsafe { drop(Arc::from_raw(Arc::as_ptr(self))) };
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl Drop for ExitWait {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.process);
+        }
+
+        #[cfg(feature = "leak-check")]
+        crate::leak_check::closed(1);
+    }
+}
+
+unsafe extern "system" fn exit_callback(context: *mut c_void, _timed_out: BOOLEAN) {
+    let exit_wait = Arc::from_raw(context as *const ExitWait);
+
+    exit_wait.fired.store(true, Ordering::SeqCst);
+
+    let callback = exit_wait
+        .callback
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take();
+
+    if let Some(callback) = callback {
+        // Unwinding out of the thread pool would abort the process.
+        let process = exit_wait.process;
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(move || callback(process)));
+    }
+
+    // Unregister the wait unless it is being cancelled. A wait registered
+    // with `WT_EXECUTEONLYONCE` still needs to be unregistered to release its
+    // resources, this can be done from the callback without blocking.
+    let wait = exit_wait
+        .wait
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take();
+
+    if let Some(wait) = wait {
+        let _ = UnregisterWaitEx(wait, HANDLE::default());
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{Child, Command, Result};
+use std::{ops::Deref, time::Duration};
+
+// How long the guard waits for a killed child to exit.
+const KILL_WAIT: Duration = Duration::from_secs(5);
+
+/// A child process terminated when the guard goes out of scope.
+///
+/// Returned by [`Command::spawn_guarded`]. When the guard is dropped,
+/// including while unwinding from a panic, the child is terminated if it is
+/// still running and waited on for up to 5 seconds, then its handles are
+/// closed. A child that has already exited isn't terminated again. Use
+/// [`release`][ProcessGuard::release] to keep the child running.
+///
+/// The guard dereferences to the [`Child`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::Command;
+///
+/// let server = Command::new("test-server.exe --port 8080")
+///     .spawn_guarded()
+///     .unwrap();
+///
+/// println!("server running as {}", server.id());
+///
+/// // The server is terminated here, even if the test panics.
+/// ```
+#[derive(Debug)]
+pub struct ProcessGuard {
+    // `None` once released.
+    child: Option<Child>,
+}
+
+impl ProcessGuard {
+    /// Returns the child without terminating it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let guard = Command::new("notepad.exe").spawn_guarded().unwrap();
+    ///
+    /// let child = guard.release();
+    /// child.wait().unwrap();
+    /// ```
+    pub fn release(mut self) -> Child {
+        self.child.take().expect("the child is only taken once")
+    }
+}
+
+impl Deref for ProcessGuard {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        self.child.as_ref().expect("the child is only taken once")
+    }
+}
+
+impl Drop for ProcessGuard {
+    fn drop(&mut self) {
+        let child = match self.child.take() {
+            Some(child) => child,
+            None => return,
+        };
+
+        if !child.has_exited() {
+            let _ = child.kill();
+        }
+
+        // Collect the exit status, so the child isn't reported as unreaped.
+        if let Ok(true) = child.api.wait(child.raw_process_handle(), Some(KILL_WAIT)) {
+            let _ = child.reap_after_external_wait();
+        }
+    }
+}
+
+impl Command {
+    /// Executes the command as a child process like [`spawn`][Command::spawn],
+    /// returning a [`ProcessGuard`] that terminates the child when it goes out
+    /// of scope.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// {
+    ///     let _guard = Command::new("notepad.exe").spawn_guarded().unwrap();
+    ///
+    ///     std::thread::sleep(std::time::Duration::from_secs(1));
+    /// }
+    ///
+    /// // Notepad has been terminated.
+    /// ```
+    pub fn spawn_guarded(&mut self) -> Result<ProcessGuard> {
+        Ok(ProcessGuard {
+            child: Some(self.spawn()?),
+        })
+    }
+}
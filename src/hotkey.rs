@@ -0,0 +1,98 @@
+use std::{fmt, ops};
+
+/// The modifier keys of a hotkey, used by
+/// [`Command::hotkey`][crate::Command::hotkey].
+///
+/// The modifiers are combined with `|`.
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::HotkeyModifiers;
+///
+/// let modifiers = HotkeyModifiers::CONTROL | HotkeyModifiers::ALT;
+///
+/// assert!(modifiers.contains(HotkeyModifiers::ALT));
+/// assert!(!modifiers.contains(HotkeyModifiers::SHIFT));
+/// assert_eq!(modifiers.bits(), 0x6);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct HotkeyModifiers(u8);
+
+impl HotkeyModifiers {
+    /// No modifier key.
+    pub const NONE: Self = Self(0x0);
+    /// The SHIFT key (`HOTKEYF_SHIFT`).
+    pub const SHIFT: Self = Self(0x1);
+    /// The CTRL key (`HOTKEYF_CONTROL`).
+    pub const CONTROL: Self = Self(0x2);
+    /// The ALT key (`HOTKEYF_ALT`).
+    pub const ALT: Self = Self(0x4);
+    /// The key is an extended key (`HOTKEYF_EXT`).
+    pub const EXTENDED: Self = Self(0x8);
+
+    /// Returns the `HOTKEYF_*` flags of the modifiers.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if all the modifiers of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if no modifier is set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl ops::BitOr for HotkeyModifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for HotkeyModifiers {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl fmt::Debug for HotkeyModifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (Self::SHIFT, "SHIFT"),
+            (Self::CONTROL, "CONTROL"),
+            (Self::ALT, "ALT"),
+            (Self::EXTENDED, "EXTENDED"),
+        ];
+        let mut first = true;
+
+        for (modifier, name) in names {
+            if self.contains(modifier) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+
+        if first {
+            f.write_str("NONE")?;
+        }
+
+        Ok(())
+    }
+}
+
+// The value of a hotkey given to the child in `hStdInput`: the virtual-key
+// code in the low byte and the modifiers in the high byte, like the value of
+// `WM_SETHOTKEY`. `virtual_key` has been validated to fit in a byte.
+pub(crate) fn encode(virtual_key: u16, modifiers: HotkeyModifiers) -> u16 {
+    (virtual_key & 0xFF) | (u16::from(modifiers.bits()) << 8)
+}
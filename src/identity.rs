@@ -0,0 +1,158 @@
+// Identifying a child across restarts of the calling process, with its
+// identifier and its creation time, since an identifier is reused once the
+// process has exited and every handle to it has been closed.
+
+use crate::{api::Win32ProcessApi, filetime_to_system_time, metrics, Child, Error, Result};
+use std::{
+    ffi::OsString,
+    io,
+    sync::{atomic::AtomicU32, Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+use windows::Win32::{
+    Foundation::{CloseHandle, GetLastError, ERROR_INVALID_PARAMETER, FILETIME, HANDLE},
+    System::Threading::{
+        GetProcessTimes, OpenProcess, PROCESS_INFORMATION, PROCESS_QUERY_INFORMATION,
+        PROCESS_SET_INFORMATION, PROCESS_SUSPEND_RESUME, PROCESS_SYNCHRONIZE, PROCESS_TERMINATE,
+        PROCESS_VM_READ,
+    },
+};
+
+/// The identity of a child process, which can be stored to find it again with
+/// [`Child::reattach`], even from another process.
+///
+/// Returned by [`Child::identity`]. With the `serde` feature, the identity is
+/// serialized as a struct, the creation time like serde does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChildIdentity {
+    /// The identifier of the process.
+    pub pid: u32,
+    /// The time at which the process was created.
+    pub creation_time: SystemTime,
+}
+
+impl Child {
+    /// Returns the identity of the child process, its identifier and its
+    /// creation time.
+    ///
+    /// Unlike the identifier alone, the identity cannot be confused with a
+    /// process created later with the same identifier. If the creation time
+    /// cannot be queried, a
+    /// [`GetProcessTimesFailed`][Error::GetProcessTimesFailed] error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("worker.exe").spawn().unwrap();
+    /// let identity = child.identity().unwrap();
+    ///
+    /// println!("worker {} created at {:?}", identity.pid, identity.creation_time);
+    /// ```
+    pub fn identity(&self) -> Result<ChildIdentity> {
+        Ok(ChildIdentity {
+            pid: self.id(),
+            creation_time: self.process_times()?.creation_time,
+        })
+    }
+
+    /// Opens the child process with this identity again, returning `None` if
+    /// it is gone or if its identifier is used by another process.
+    ///
+    /// The returned child can be waited, killed and queried, but it has no
+    /// pipes nor main thread, and its command line is empty. It isn't killed
+    /// when dropped. If the process cannot be opened, like a process of
+    /// another user, an [`OpenProcessFailed`][Error::OpenProcessFailed] error
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Child, Command};
+    ///
+    /// let identity = Command::new("worker.exe").spawn().unwrap().identity().unwrap();
+    ///
+    /// // Later, after a restart of the calling process.
+    /// match Child::reattach(identity).unwrap() {
+    ///     Some(child) => println!("the worker exited with {}", child.wait().unwrap().code()),
+    ///     None => println!("the worker is gone"),
+    /// }
+    /// ```
+    ///
+    /// Equivalent to the [`OpenProcess`][open-process] function.
+    ///
+    /// [open-process]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-openprocess
+    pub fn reattach(identity: ChildIdentity) -> Result<Option<Child>> {
+        let access = PROCESS_QUERY_INFORMATION
+            | PROCESS_SET_INFORMATION
+            | PROCESS_SUSPEND_RESUME
+            | PROCESS_TERMINATE
+            | PROCESS_VM_READ
+            | PROCESS_SYNCHRONIZE;
+
+        let process = match unsafe { OpenProcess(access, false, identity.pid) } {
+            Ok(process) => process,
+            Err(_) => {
+                return match unsafe { GetLastError() } {
+                    // No process has this identifier.
+                    ERROR_INVALID_PARAMETER => Ok(None),
+                    code => Err(Error::OpenProcessFailed(io::Error::from_raw_os_error(
+                        code.0 as i32,
+                    ))),
+                };
+            }
+        };
+
+        let mut creation_time = FILETIME::default();
+        let mut unused = [FILETIME::default(); 3];
+
+        unsafe {
+            let res = GetProcessTimes(
+                process,
+                &mut creation_time,
+                &mut unused[0],
+                &mut unused[1],
+                &mut unused[2],
+            )
+            .map_err(|_| Error::GetProcessTimesFailed(io::Error::last_os_error()));
+
+            // The identifier is used by another process.
+            if res.is_err() || filetime_to_system_time(creation_time) != identity.creation_time {
+                let _ = CloseHandle(process);
+                return res.map(|()| None);
+            }
+        }
+
+        // There is no thread handle to close.
+        #[cfg(feature = "leak-check")]
+        crate::leak_check::opened(1);
+
+        let child = Child {
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            process_information: PROCESS_INFORMATION {
+                hProcess: process,
+                hThread: HANDLE::default(),
+                dwProcessId: identity.pid,
+                dwThreadId: 0,
+            },
+            api: Arc::new(Win32ProcessApi),
+            command: OsString::new(),
+            current_directory: None,
+            exit_status: OnceLock::new(),
+            suspend_count: AtomicU32::new(0),
+            cpu_sample: Mutex::new(None),
+            warn_on_unreaped: false,
+            registered: false,
+            tag: None,
+        };
+
+        metrics::reattached(&child);
+
+        Ok(Some(child))
+    }
+}
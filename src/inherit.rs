@@ -0,0 +1,137 @@
+// The handles given to a child by value, with `Command::inherit_handle`.
+//
+// The duplicate of the handle isn't inheritable, except while the child it is
+// given to is created: it has to be inheritable to be listed in the handles
+// inherited by the child, in its attributes. The children spawned meanwhile by
+// other threads with the crate only inherit the handles in their own list. An
+// inherited handle has the same value in the child, which is known as soon as
+// the handle is duplicated.
+
+use crate::{Command, Error, Result};
+use std::{
+    io,
+    os::windows::io::{AsRawHandle, BorrowedHandle, OwnedHandle},
+    sync::Arc,
+};
+use windows::Win32::Foundation::{SetHandleInformation, HANDLE, HANDLE_FLAGS, HANDLE_FLAG_INHERIT};
+
+/// The value of a handle inherited by a child process, returned by
+/// [`Command::inherit_handle`].
+///
+/// The value is the same in the child, it is given to it on its command
+/// line, usually as a decimal number with
+/// [`to_arg_string`][InheritedHandleToken::to_arg_string].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InheritedHandleToken {
+    value: isize,
+}
+
+impl InheritedHandleToken {
+    /// Returns the value of the handle as a decimal number, like `1234`.
+    pub fn to_arg_string(&self) -> String {
+        self.value.to_string()
+    }
+
+    /// Returns the value of the handle.
+    pub fn value(&self) -> isize {
+        self.value
+    }
+}
+
+impl Command {
+    /// Gives a handle to the child process, returning its value in the child.
+    ///
+    /// The handle is duplicated, so `handle` can be closed afterwards. The
+    /// duplicate is only inheritable while the child is created, and the
+    /// child is given the list of the handles it inherits, so the children
+    /// spawned meanwhile by other threads with this crate don't get it. The
+    /// processes created meanwhile by other means inheriting every
+    /// inheritable handle, like with [`std::process::Command`] or
+    /// [`inherit_handles(true)`][Command::inherit_handles], can get it. The
+    /// inheritance of handles is enabled unless it has been disabled with
+    /// [`inherit_handles`][Command::inherit_handles], in which case spawning
+    /// the command returns an [`InvalidCommand`][Error::InvalidCommand] error.
+    /// The duplicate is closed in the calling process once the child has been
+    /// spawned, the next children spawned with the command don't get it.
+    ///
+    /// The child receives the value of the handle on its command line, with
+    /// [`InheritedHandleToken::to_arg_string`]. If the handle cannot be
+    /// duplicated, a [`DuplicateHandleFailed`][Error::DuplicateHandleFailed]
+    /// error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::os::windows::io::{AsHandle, FromRawHandle, OwnedHandle};
+    /// use windows::{core::PCWSTR, Win32::System::Threading::CreateEventW};
+    ///
+    /// let ready = unsafe {
+    ///     let event = CreateEventW(None, true, false, PCWSTR::null()).unwrap();
+    ///     OwnedHandle::from_raw_handle(event.0 as _)
+    /// };
+    ///
+    /// let mut command = Command::new("server.exe");
+    /// let token = command.inherit_handle(ready.as_handle()).unwrap();
+    ///
+    /// command.args_from_str(format!("--ready-event {}", token.to_arg_string()).as_ref()).unwrap();
+    /// command.spawn().unwrap();
+    /// ```
+    pub fn inherit_handle(&mut self, handle: BorrowedHandle<'_>) -> Result<InheritedHandleToken> {
+        let duplicate = handle
+            .try_clone_to_owned()
+            .map_err(Error::DuplicateHandleFailed)?;
+        let token = InheritedHandleToken {
+            value: duplicate.as_raw_handle() as isize,
+        };
+
+        self.inherited_handles.push(Arc::new(duplicate));
+
+        Ok(token)
+    }
+}
+
+// The handles made inheritable until the guard is dropped, once the child has
+// been created.
+pub(crate) struct Inheritable {
+    handles: Vec<Arc<OwnedHandle>>,
+}
+
+impl Inheritable {
+    pub(crate) fn new(handles: &[Arc<OwnedHandle>]) -> Result<Self> {
+        let mut inheritable = Self {
+            handles: Vec::with_capacity(handles.len()),
+        };
+
+        for handle in handles {
+            unsafe {
+                SetHandleInformation(
+                    handle_of(handle),
+                    HANDLE_FLAG_INHERIT.0,
+                    HANDLE_FLAG_INHERIT,
+                )
+                .map_err(|_| Error::DuplicateHandleFailed(io::Error::last_os_error()))?;
+            }
+
+            // Reverted when dropped, even if a later handle fails.
+            inheritable.handles.push(Arc::clone(handle));
+        }
+
+        Ok(inheritable)
+    }
+}
+
+impl Drop for Inheritable {
+    fn drop(&mut self) {
+        for handle in &self.handles {
+            unsafe {
+                let _ =
+                    SetHandleInformation(handle_of(handle), HANDLE_FLAG_INHERIT.0, HANDLE_FLAGS(0));
+            }
+        }
+    }
+}
+
+fn handle_of(handle: &OwnedHandle) -> HANDLE {
+    HANDLE(handle.as_raw_handle() as isize)
+}
@@ -0,0 +1,108 @@
+// Counting the handles of the children opened by the crate, to find the ones
+// that are never closed (see the `leak-check` feature).
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use windows::Win32::System::Threading::{GetCurrentProcess, GetProcessHandleCount};
+
+// The process and thread handles opened minus the ones closed.
+static OPEN_HANDLES: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn opened(count: usize) {
+    OPEN_HANDLES.fetch_add(count, Ordering::Relaxed);
+}
+
+pub(crate) fn closed(count: usize) {
+    OPEN_HANDLES.fetch_sub(count, Ordering::Relaxed);
+}
+
+/// Returns the number of process and thread handles of children opened by
+/// the crate that haven't been closed yet.
+///
+/// Each [`Child`][crate::Child] holds two handles, a child that has been
+/// reattached or spawned elevated only one. The registry of
+/// [`Command::register`][crate::Command::register] and the callbacks of
+/// [`Child::on_exit`][crate::Child::on_exit] hold one more each. Once all of them
+/// are dropped, the count is back to zero.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{open_handle_count, Command};
+///
+/// let child = Command::new("notepad.exe").spawn().unwrap();
+/// assert_eq!(open_handle_count(), 2);
+///
+/// drop(child);
+/// assert_eq!(open_handle_count(), 0);
+/// ```
+pub fn open_handle_count() -> usize {
+    OPEN_HANDLES.load(Ordering::Relaxed)
+}
+
+/// Returns the number of handles opened by the calling process, by the crate
+/// or not.
+///
+/// Equivalent to the [`GetProcessHandleCount`][get-process-handle-count]
+/// function.
+///
+/// [get-process-handle-count]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getprocesshandlecount
+pub fn process_handle_count() -> u32 {
+    let mut count = 0;
+
+    unsafe {
+        let _ = GetProcessHandleCount(GetCurrentProcess(), &mut count);
+    }
+
+    count
+}
+
+/// Panics if handles of children opened by the crate haven't been closed, see
+/// [`open_handle_count`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{assert_no_leaks, Command};
+///
+/// Command::new("cmd.exe /c exit").status().unwrap();
+///
+/// assert_no_leaks();
+/// ```
+#[track_caller]
+pub fn assert_no_leaks() {
+    let count = open_handle_count();
+
+    assert!(count == 0, "{} handles of children are still open", count);
+}
+
+/// Panics like [`assert_no_leaks`], or if the calling process has more
+/// handles than `baseline`, a count returned by [`process_handle_count`]
+/// before spawning the children.
+///
+/// This also finds the handles the crate doesn't count, like the ones of the
+/// pipes, but the handles opened by other threads meanwhile are counted too.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{assert_no_leaks_since, process_handle_count, Command};
+///
+/// let baseline = process_handle_count();
+///
+/// Command::new("cmd.exe /c echo hello").output().unwrap();
+///
+/// assert_no_leaks_since(baseline);
+/// ```
+#[track_caller]
+pub fn assert_no_leaks_since(baseline: u32) {
+    assert_no_leaks();
+
+    let count = process_handle_count();
+
+    assert!(
+        count <= baseline,
+        "the process has {} handles, {} more than before",
+        count,
+        count - baseline
+    );
+}
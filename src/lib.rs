@@ -97,35 +97,210 @@
 //! }
 //! ```
 //!
+//! # Bindings
+//!
+//! The Win32 declarations come from the [windows][windows-rs] crate, which
+//! generates them from the metadata published by Microsoft, so the crate has
+//! no hand-written declaration to get wrong. There is no backend on
+//! `windows-sys`: it is generated from the same metadata, so it wouldn't fix
+//! anything, while every call would have to be written and tested twice. The
+//! declarations used by the crate are public with the `raw` feature.
+//!
 //! [windows-rs]: https://github.com/microsoft/windows-rs
 //! [create-processes-example]: https://docs.microsoft.com/en-us/windows/win32/procthread/creating-processes
 
+mod api;
+mod app_paths;
+#[cfg(feature = "tokio")]
+mod async_stdio;
+mod attributes;
+#[cfg(feature = "serde")]
+mod base64_bytes;
+mod child_set;
+pub mod cmdline;
+mod crt_fds;
+mod debug;
+mod elevation;
+mod encoding;
+mod env;
+mod exit_status;
+mod exit_wait;
+mod guard;
+mod hotkey;
+mod identity;
+mod inherit;
+#[cfg(feature = "leak-check")]
+mod leak_check;
+mod metrics;
+mod minidump;
+#[cfg(any(test, feature = "test-util"))]
+#[cfg_attr(not(feature = "test-util"), allow(dead_code))]
+mod mock;
+mod output;
+pub mod path;
+mod pipe;
+mod pipeline;
+mod pseudoconsole;
+#[cfg(feature = "raw")]
+pub mod raw;
+mod registry;
+mod retry;
+mod session;
+mod signature;
+mod stdio;
+mod supervisor;
+mod suspended;
+mod token;
+mod tree;
+mod user_session;
+mod validation;
+mod wait;
+mod watchdog;
+pub mod wide;
+mod window;
+mod wow64;
+
+#[cfg(feature = "test-util")]
+pub use api::{ProcessApi, ProcessSpec, RawProcess};
+#[cfg(feature = "tokio")]
+pub use async_stdio::{AsyncChildStderr, AsyncChildStdout};
+pub use child_set::{ChildSet, Token};
+pub use cmdline::{build_command_line, quote_argument};
+pub use debug::{DebugEvent, DebugEventKind, DebugMode};
+pub use elevation::ElevatedOrNormal;
+pub use encoding::OutputEncoding;
+pub use env::EnvironmentSnapshot;
+pub use exit_status::ExitStatusError;
+pub use exit_wait::ExitWatch;
+pub use guard::ProcessGuard;
+pub use hotkey::HotkeyModifiers;
+pub use identity::ChildIdentity;
+pub use inherit::InheritedHandleToken;
+#[cfg(feature = "leak-check")]
+pub use leak_check::{
+    assert_no_leaks, assert_no_leaks_since, open_handle_count, process_handle_count,
+};
+pub use metrics::{clear_metrics_hook, set_metrics_hook, Metrics, MetricsEvent};
+pub use minidump::MinidumpKind;
+#[cfg(feature = "test-util")]
+pub use mock::{MockProcessApi, RecordedSpec};
+pub use output::{Output, OutputError, ResourceUsage};
+pub use pipeline::{Pipeline, PipelineChildren};
+pub use registry::{kill_all_registered, kill_registered_on_panic};
+pub use retry::RetryPolicy;
+pub use session::{ExpectPattern, InteractiveSession};
+pub use signature::SignaturePolicy;
+pub use stdio::{ChildStderr, ChildStdin, ChildStdout, Stdio};
+pub use supervisor::{RestartPolicy, Supervisor, SupervisorEvent, SupervisorStatus};
+pub use token::RestrictedTokenOptions;
+pub use tree::{ProcessEntry, TreeStatus};
+pub use user_session::session_of_pid;
+pub use wait::{wait_all, wait_any, WaitOutcome};
+pub use watchdog::WatchdogGuard;
+pub use window::WindowInfo;
+
+use api::{ProcessApi, ProcessSpec, Win32ProcessApi};
+use crt_fds::CrtFds;
 use std::{
-    ffi::{OsStr, OsString},
-    fmt,
+    ffi::{c_void, OsStr, OsString},
+    fmt, io,
     mem::size_of,
-    os::windows::ffi::OsStrExt,
+    os::windows::{
+        ffi::OsStringExt,
+        io::{AsRawHandle, BorrowedHandle, OwnedHandle, RawHandle},
+    },
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use stdio::ParentPipes;
 use thiserror::Error;
 use windows::{
-    core::{PCWSTR, PWSTR},
+    core::{s, w, PCSTR, PCWSTR, PWSTR},
     Win32::{
-        Foundation::{CloseHandle, GetLastError, STATUS_PENDING, WAIT_OBJECT_0},
-        System::Threading::{
-            CreateProcessW, GetExitCodeProcess, TerminateProcess, WaitForSingleObject, INFINITE,
-            PROCESS_CREATION_FLAGS, PROCESS_INFORMATION, STARTUPINFOW,
+        Foundation::{
+            DuplicateHandle, GetLastError, RtlNtStatusToDosError, SetLastError, BOOL,
+            DUPLICATE_HANDLE_OPTIONS, DUPLICATE_SAME_ACCESS, ERROR_ABANDONED_WAIT_0,
+            ERROR_ACCESS_DENIED, ERROR_BAD_EXE_FORMAT, ERROR_CANCELLED, ERROR_DIRECTORY,
+            ERROR_ELEVATION_REQUIRED, ERROR_FILE_NOT_FOUND, ERROR_INSUFFICIENT_BUFFER,
+            ERROR_INVALID_IMAGE_HASH, ERROR_INVALID_OPERATION, ERROR_INVALID_PARAMETER,
+            ERROR_INVALID_WINDOW_HANDLE, ERROR_NOT_SUPPORTED, ERROR_NO_TOKEN, ERROR_PATH_NOT_FOUND,
+            ERROR_PRIVILEGE_NOT_HELD, ERROR_PROCESS_ABORTED, ERROR_SHARING_VIOLATION,
+            ERROR_TIMEOUT, FILETIME, HANDLE, NTSTATUS, STATUS_PENDING, WAIT_TIMEOUT, WIN32_ERROR,
+        },
+        System::{
+            Diagnostics::Debug::{
+                GetErrorMode, SetErrorMode, SEM_FAILCRITICALERRORS, SEM_NOGPFAULTERRORBOX,
+                THREAD_ERROR_MODE,
+            },
+            LibraryLoader::{GetModuleHandleW, GetProcAddress},
+            Memory::{
+                GetProcessWorkingSetSizeEx, SetProcessWorkingSetSizeEx,
+                QUOTA_LIMITS_HARDWS_MAX_DISABLE, QUOTA_LIMITS_HARDWS_MAX_ENABLE,
+                QUOTA_LIMITS_HARDWS_MIN_DISABLE, QUOTA_LIMITS_HARDWS_MIN_ENABLE,
+            },
+            ProcessStatus::{
+                K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS, PROCESS_MEMORY_COUNTERS_EX,
+            },
+            Threading::{
+                GetActiveProcessorCount, GetCurrentProcess, GetGuiResources, GetPriorityClass,
+                GetProcessAffinityMask, GetProcessHandleCount, GetProcessIoCounters,
+                GetProcessPriorityBoost, GetProcessTimes, IsWow64Process,
+                QueryFullProcessImageNameW, SetPriorityClass, SetProcessAffinityMask,
+                SetProcessPriorityBoost, ABOVE_NORMAL_PRIORITY_CLASS, ALL_PROCESSOR_GROUPS,
+                BELOW_NORMAL_PRIORITY_CLASS, CREATE_DEFAULT_ERROR_MODE, CREATE_PROTECTED_PROCESS,
+                CREATE_SUSPENDED, GR_GDIOBJECTS, GR_GDIOBJECTS_PEAK, GR_USEROBJECTS,
+                GR_USEROBJECTS_PEAK, HIGH_PRIORITY_CLASS, IDLE_PRIORITY_CLASS,
+                INHERIT_PARENT_AFFINITY, IO_COUNTERS, NORMAL_PRIORITY_CLASS,
+                PROCESS_CREATION_FLAGS, PROCESS_INFORMATION, PROCESS_NAME_FORMAT,
+                PROCESS_NAME_NATIVE, PROCESS_NAME_WIN32, REALTIME_PRIORITY_CLASS,
+                STARTF_FORCEOFFFEEDBACK, STARTF_FORCEONFEEDBACK, STARTF_PREVENTPINNING,
+                STARTF_TITLEISAPPID, STARTF_USEHOTKEY, STARTUPINFOW_FLAGS,
+            },
         },
     },
 };
 
 /// A process builder, providing control over how a new process should be
 /// spawned.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Command {
     command: OsString,
-    inherit_handles: bool,
+    application_name: Option<PathBuf>,
+    inherit_handles: Option<bool>,
     current_directory: Option<PathBuf>,
+    environment: env::Environment,
+    priority_class: Option<PriorityClass>,
+    startup_feedback: Option<bool>,
+    app_user_model_id: Option<OsString>,
+    hotkey: Option<(u16, HotkeyModifiers)>,
+    prevent_pinning: bool,
+    crt_fds: Vec<(u32, HANDLE)>,
+    inherited_handles: Vec<Arc<OwnedHandle>>,
+    suppress_error_dialogs: Option<bool>,
+    inherit_parent_affinity: bool,
+    disable_wow64_redirection: bool,
+    disable_priority_boost: bool,
+    working_set: Option<(usize, usize)>,
+    efficiency_mode: bool,
+    resolve_app_paths: bool,
+    signature: Option<SignaturePolicy>,
+    debug: Option<DebugMode>,
+    protected: Option<ProtectedMode>,
+    restricted: Option<RestrictedTokenOptions>,
+    user_session: bool,
+    warn_on_unreaped: bool,
+    register: bool,
+    tag: Option<Arc<str>>,
+    stdin: Option<Stdio>,
+    stdout: Option<Stdio>,
+    stderr: Option<Stdio>,
+    pipe_buffer_size: u32,
+    // Set by `InteractiveSession::spawn` for the spawn only.
+    pseudoconsole: Option<HANDLE>,
 }
 
 impl Command {
@@ -154,11 +329,169 @@ impl Command {
     pub fn new(command: impl Into<OsString>) -> Self {
         Self {
             command: command.into(),
-            inherit_handles: false,
+            application_name: None,
+            inherit_handles: None,
             current_directory: None,
+            environment: env::Environment::default(),
+            priority_class: None,
+            startup_feedback: None,
+            app_user_model_id: None,
+            hotkey: None,
+            prevent_pinning: false,
+            crt_fds: Vec::new(),
+            inherited_handles: Vec::new(),
+            suppress_error_dialogs: None,
+            inherit_parent_affinity: false,
+            disable_wow64_redirection: false,
+            disable_priority_boost: false,
+            working_set: None,
+            efficiency_mode: false,
+            resolve_app_paths: false,
+            signature: None,
+            debug: None,
+            protected: None,
+            restricted: None,
+            user_session: false,
+            warn_on_unreaped: false,
+            register: false,
+            tag: None,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            pipe_buffer_size: pipe::DEFAULT_BUFFER_SIZE,
+            pseudoconsole: None,
         }
     }
 
+    /// Create a new [`Command`] from a program and its arguments, with the
+    /// default configuration of [`new`][Command::new].
+    ///
+    /// The command line is built with [`build_command_line`]: the program is
+    /// quoted if it contains spaces, so `C:\Program Files\tool.exe` isn't
+    /// mistaken for `C:\Program.exe`, and the arguments are quoted so the
+    /// program receives them unchanged. To remove any ambiguity about the
+    /// program, give its path to [`application_name`][Command::application_name]
+    /// too.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let status = Command::from_program(
+    ///     r"C:\Program Files\Git\bin\git.exe",
+    ///     ["commit", "-m", "fix the \"quoting\""],
+    /// )
+    /// .status()
+    /// .expect("git failed to start");
+    /// ```
+    pub fn from_program(
+        program: impl AsRef<Path>,
+        args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> Self {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let args = args.iter().map(AsRef::as_ref).collect::<Vec<&OsStr>>();
+
+        Self::new(build_command_line(program.as_ref().as_os_str(), &args))
+    }
+
+    /// Sets the program to execute, instead of finding it from the command
+    /// line.
+    ///
+    /// The program isn't searched in the `PATH`: it's the full path to the
+    /// program, or a path relative to the current directory of the calling
+    /// process. The command line is still given to the child, whose first
+    /// argument is usually the program.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let program = r"C:\Program Files\tool.exe";
+    ///
+    /// let status = Command::from_program(program, ["--check"])
+    ///     .application_name(program)
+    ///     .status()
+    ///     .expect("the tool failed to start");
+    /// ```
+    ///
+    /// Equivalent to the `lpApplicationName` parameter of the
+    /// [`CreateProcessW`][create-process-w-parameters] function.
+    ///
+    /// [create-process-w-parameters]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw#parameters
+    pub fn application_name(&mut self, program: impl Into<PathBuf>) -> &mut Self {
+        self.application_name = Some(program.into());
+        self
+    }
+
+    /// Enable/disable the resolution of the program with the `App Paths`
+    /// registry key, like the Run dialog.
+    ///
+    /// `winword` works in the Run dialog because the shell looks it up in the
+    /// `SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths\winword.exe`
+    /// key, which `CreateProcessW` doesn't. When enabled and the program isn't
+    /// found, it is looked up in the key of the current user, then in the one
+    /// of the machine. If it is registered, the child is spawned with the
+    /// registered program as [application name][Command::application_name],
+    /// and the directory of the `Path` value appended to its `PATH`.
+    /// Otherwise, or if the registry cannot be read, the error of the program
+    /// not being found is returned.
+    ///
+    /// Only the programs given by their name are looked up, `.exe` is added if
+    /// it has no extension. This is disabled by default, and ignored when an
+    /// application name is set.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// Command::new("winword report.docx")
+    ///     .resolve_app_paths(true)
+    ///     .spawn()
+    ///     .expect("Word failed to start");
+    /// ```
+    pub fn resolve_app_paths(&mut self, resolve: bool) -> &mut Self {
+        self.resolve_app_paths = resolve;
+        self
+    }
+
+    /// Sets the signature the program must have to be spawned.
+    ///
+    /// Before creating the child, the program is resolved like
+    /// `CreateProcessW` does, adding `.exe` if it has no extension, and its
+    /// Authenticode signature is verified, without any user interface nor
+    /// revocation check. A program without an embedded signature, like most
+    /// of the programs of Windows, is verified with the signed catalog of the
+    /// system containing its hash. The verified path is given as
+    /// [application name][Command::application_name], so another program
+    /// found first in the search path cannot be spawned instead. If the
+    /// program isn't found, the error of `CreateProcessW` is returned, and if
+    /// its signature isn't accepted, an
+    /// [`UntrustedProgram`][Error::UntrustedProgram] error with the status of
+    /// the verification.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, SignaturePolicy};
+    ///
+    /// Command::new("updater.exe --check")
+    ///     .verify_signature(SignaturePolicy::Subject("Contoso Ltd".to_string()))
+    ///     .spawn()
+    ///     .expect("the updater failed to start");
+    /// ```
+    ///
+    /// Equivalent to calling [`WinVerifyTrust`][win-verify-trust] with the
+    /// `WINTRUST_ACTION_GENERIC_VERIFY_V2` action.
+    ///
+    /// [win-verify-trust]: https://docs.microsoft.com/en-us/windows/win32/api/wintrust/nf-wintrust-winverifytrust
+    pub fn verify_signature(&mut self, policy: SignaturePolicy) -> &mut Self {
+        self.signature = Some(policy);
+        self
+    }
+
     /// Enable/disable handles inherance.
     ///
     /// If this parameter is `true`, each inheritable handle in the calling
@@ -166,12 +499,21 @@ impl Command {
     /// the handles are not inherited. Note that inherited handles have the
     /// same value and access rights as the original handles.
     ///
+    /// Handles are inherited by default when a standard stream of the child is
+    /// configured with [`stdin`][Command::stdin], [`stdout`][Command::stdout]
+    /// or [`stderr`][Command::stderr], since the child needs to inherit them.
+    /// Disabling the inheritance in that case is invalid, spawning the command
+    /// returns an [`InvalidCommand`][Error::InvalidCommand] error. Unless the
+    /// inheritance is enabled with this method, the child only inherits the
+    /// handles it is given, listed in the `PROC_THREAD_ATTRIBUTE_HANDLE_LIST`
+    /// attribute.
+    ///
     /// Equivalent to the `bInheritHandles` parameter of the
     /// [`CreateProcessW`][create-process-w-parameters] function.
     ///
     /// [create-process-w-parameters]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw#parameters
     pub fn inherit_handles(&mut self, inherit: bool) -> &mut Self {
-        self.inherit_handles = inherit;
+        self.inherit_handles = Some(inherit);
         self
     }
 
@@ -180,6 +522,13 @@ impl Command {
     /// It's the full path to the current directory for the process. Note that
     /// you can use a raw string to avoid error when copy-pasting the path.
     ///
+    /// The path can be on a network share, like `\\server\share\repo`, or
+    /// verbatim, like `\\?\UNC\server\share\repo`, which is given to the
+    /// child in its usual form, see [`path::from_verbatim`]. `cmd.exe` refuses
+    /// a UNC working directory: spawning it with one returns an
+    /// [`InvalidCommand`][Error::InvalidCommand] error, `pushd` maps the share
+    /// to a drive from the command instead.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -200,306 +549,3256 @@ impl Command {
         self
     }
 
-    /// Executes the command as a child process, returning a handle to it.
+    /// Appends the arguments of a string to the command line, quoted again.
+    ///
+    /// The string is split into arguments like a program splits its command
+    /// line, then each argument is quoted with [`quote_argument`]. The
+    /// resulting command line can differ from the string, but the program
+    /// receives the same arguments: a string given by a user cannot inject
+    /// arguments in the ones appended after it, for example with a quote that
+    /// isn't closed. A string containing a null character returns an
+    /// [`InvalidInput`][Error::InvalidInput] error and the command isn't
+    /// changed.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use CreateProcessW::Command;
     ///
-    /// Command::new("notepad.exe")
-    ///     .spawn()
-    ///     .expect("notepad failed to start");
+    /// let user_args = r#"--name "a b" --path C:\dir\"#;
+    ///
+    /// let status = Command::new("tool.exe")
+    ///     .args_from_str(user_args.as_ref())
+    ///     .unwrap()
+    ///     .status()
+    ///     .expect("the tool failed to start");
     /// ```
-    pub fn spawn(&mut self) -> Result<Child> {
-        Child::new(
-            &self.command,
-            self.inherit_handles,
-            self.current_directory.as_deref(),
-        )
+    ///
+    /// Equivalent to the [`CommandLineToArgvW`][command-line-to-argv-w]
+    /// function.
+    ///
+    /// [command-line-to-argv-w]: https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-commandlinetoargvw
+    pub fn args_from_str(&mut self, s: &OsStr) -> Result<&mut Self> {
+        // The first token is parsed with the rules of the program, the
+        // arguments follow a placeholder one.
+        let mut command_line = OsString::from("_ ");
+        command_line.push(s);
+
+        for arg in cmdline::split(&command_line)?.iter().skip(1) {
+            self.command.push(" ");
+            self.command.push(quote_argument(arg));
+        }
+
+        Ok(self)
     }
 
-    /// Executes a command as a child process, waiting for it to finish and
-    /// collecting its status.
+    /// Sets an environment variable of the child process.
+    ///
+    /// The child inherits the environment of the calling process by default,
+    /// with the variables set by this function. The names of the variables
+    /// are case-insensitive, a variable replaces the one with the same name.
+    /// A name that is empty or contains `=` returns an
+    /// [`InvalidCommand`][Error::InvalidCommand] error when spawning.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use CreateProcessW::Command;
     ///
-    /// let status = Command::new("notepad.exe")
+    /// let status = Command::new("cargo.exe build")
+    ///     .env("CARGO_TARGET_DIR", r"C:\build\target")
     ///     .status()
-    ///     .expect("failed to execute process");
+    ///     .expect("cargo build failed to start");
+    /// ```
     ///
-    /// println!("process finished with: {}", status.code());
+    /// Equivalent to the `lpEnvironment` parameter of the
+    /// [`CreateProcessW`][create-process-w-parameters] function.
     ///
-    /// assert!(status.success());
-    /// ```
-    pub fn status(&mut self) -> Result<ExitStatus> {
-        self.spawn()?.wait()
+    /// [create-process-w-parameters]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw#parameters
+    pub fn env(&mut self, name: impl Into<OsString>, value: impl Into<OsString>) -> &mut Self {
+        self.environment.vars.push((name.into(), value.into()));
+        self
     }
-}
-
-/// Representation of a running or exited child process.
-///
-/// This structure is used to represent and manage child processes. A child
-/// process is created via the [`Command`] struct, which configures the spawning
-/// process and can itself be constructed using a builder-style interface.
-///
-/// # Warnings
-///
-/// Calling [`wait`][Child::wait] is necessary for the OS to release resources.
-/// A process that terminated but has not been waited on is still around as a
-/// "zombie". Leaving too many zombies around may exhaust global resources.
-///
-/// This library does *not* automatically wait on child processes (not even if
-/// the `Child` is dropped), it is up to the application developer to do so. As
-/// a consequence, dropping `Child` handles without waiting on them first is not
-/// recommended in long-running applications.
-///
-/// # Examples
-///
-/// ```no_run
-/// use CreateProcessW::Command;
-///
-/// let mut child = Command::new("notepad.exe")
-///     .spawn()
-///     .expect("failed to execute child");
-///
-/// let status = child.wait().expect("failed to wait on child");
-///
-/// assert!(status.success());
-/// ```
-#[derive(Debug)]
-pub struct Child {
-    process_information: PROCESS_INFORMATION,
-}
-
-impl Child {
-    // Create a new process and initialize it's memory. If it cannot be
-    // created, an [`CreateFailed`][Error::CreateFailed] error is returned.
-    //
-    // Equivalent to [`CreateProcessW`][create-process-w]
-    //
-    // [create-process-w]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw
-    fn new(
-        command: &OsStr,
-        inherit_handles: bool,
-        current_directory: Option<&Path>,
-    ) -> Result<Self> {
-        let mut startup_information = STARTUPINFOW::default();
-        let mut process_information = PROCESS_INFORMATION::default();
-
-        startup_information.cb = size_of::<STARTUPINFOW>() as u32;
-
-        let process_creation_flags = PROCESS_CREATION_FLAGS(0);
-
-        // Convert command to a wide string with a null terminator.
-        let mut command_wide = command.encode_wide().chain(Some(0)).collect::<Vec<_>>();
-
-        let current_directory_ptr = current_directory
-            .map(|path| {
-                path.as_os_str()
-                    .encode_wide()
-                    .chain(Some(0))
-                    .collect::<Vec<_>>()
-            })
-            .map(|wide_path| wide_path.as_ptr())
-            .unwrap_or(std::ptr::null_mut());
-
-        let res = unsafe {
-            CreateProcessW(
-                PCWSTR::null(),
-                PWSTR(command_wide.as_mut_ptr()),
-                None,
-                None,
-                inherit_handles,
-                process_creation_flags,
-                None,
-                PCWSTR(current_directory_ptr),
-                &startup_information,
-                &mut process_information,
-            )
-        };
 
-        match res {
-            Ok(()) => Ok(Self {
-                process_information,
-            }),
-            Err(_) => Err(Error::CreationFailed(unsafe { GetLastError().0 })),
+    /// Sets several environment variables of the child process, like
+    /// [`env`][Command::env].
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        for (name, value) in vars {
+            self.env(name, value);
         }
+        self
     }
 
-    /// Forces the child process to exit. If the child has already exited, a
-    /// [`KillFailed`][Error::KillFailed] error is returned.
+    /// Reduces the environment of the child process to the variables that
+    /// Windows programs need.
     ///
-    /// This function is used to unconditionally cause a process to exit and
-    /// stops execution of all threads within the process and requests
-    /// cancellation of all pending I/O. The terminated process cannot exit
-    /// until all pending I/O has been completed and canceled. When a
-    /// process terminates, its kernel object is not destroyed until all
-    /// processes that have open handles to the process have released those
-    /// handles.
+    /// The child only gets the values of `SystemRoot`, `SystemDrive`,
+    /// `windir`, `ComSpec`, `PATHEXT`, `TEMP`, `TMP` and
+    /// `NUMBER_OF_PROCESSORS` in the calling process, plus the variables set
+    /// with [`env`][Command::env] before or after this call. This makes the
+    /// child independent from the variables accumulated by the shell of the
+    /// user, like for reproducible builds. Note that `PATH` isn't kept, so
+    /// the child cannot find the programs it spawns by name.
     ///
-    /// Equivalent to the [`TerminateProcess`][terminate-process] function.
-    /// Note that the value passed as the `uExitCode` is always `0` at the
-    /// moment.
+    /// A fully empty environment is worse: without `SystemRoot`, the system
+    /// libraries cannot find their files and things like sockets and
+    /// cryptography fail in the child, and without `TEMP` the programs write
+    /// their temporary files in the Windows directory.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use CreateProcessW::Command;
     ///
-    /// let mut command = Command::new("notepad.exe");
-    ///
-    /// if let Ok(mut child) = command.spawn() {
-    ///     child.kill().expect("notepad wasn't running");
-    /// } else {
-    ///     println!("notepad didn't start");
-    /// }
+    /// let status = Command::new(r"C:\tools\build.exe")
+    ///     .clean_env()
+    ///     .env("SOURCE_DATE_EPOCH", "0")
+    ///     .status()
+    ///     .expect("build failed to start");
     /// ```
-    ///
-    /// [terminate-process]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-terminateprocess
-    pub fn kill(&self) -> Result<()> {
-        unsafe {
-            if TerminateProcess(self.process_information.hProcess, 0).is_err() {
-                Err(Error::KillFailed(GetLastError().0))
-            } else {
-                Ok(())
-            }
-        }
+    pub fn clean_env(&mut self) -> &mut Self {
+        self.clean_env_with(std::iter::empty::<OsString>())
     }
 
-    /// Waits for the child to exit completely, returning the status that it
-    /// exited with and closing handles. This function will continue to have the
-    /// same return value after it has been called at least once.
+    /// Reduces the environment of the child process like
+    /// [`clean_env`][Command::clean_env], also keeping the variables named
+    /// `extra_names`.
     ///
-    /// If the function fail, it return a
-    /// [`GetExitCodeFailed`][Error::GetExitCodeFailed] error.
+    /// # Examples
     ///
-    /// This is equivalent to calling the
-    /// [`WaitForSingleObject][wait-for-single-object] and the
-    /// [`CloseHandle`][close-handle] functions.
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let status = Command::new("cargo.exe build")
+    ///     .clean_env_with(["PATH", "USERPROFILE"])
+    ///     .status()
+    ///     .expect("cargo build failed to start");
+    /// ```
+    pub fn clean_env_with<I, K>(&mut self, extra_names: I) -> &mut Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<OsString>,
+    {
+        let names = env::ESSENTIAL_VARIABLES
+            .iter()
+            .map(OsString::from)
+            .chain(extra_names.into_iter().map(Into::into))
+            .collect();
+
+        self.environment.base = env::Base::Clean(names);
+        self
+    }
+
+    /// Builds the environment of the child process from a snapshot, instead
+    /// of the current environment of the calling process.
+    ///
+    /// The variables set with [`env`][Command::env] are added to the ones of
+    /// the snapshot. This replaces [`clean_env`][Command::clean_env], the
+    /// last one called is used.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use CreateProcessW::Command;
+    /// use CreateProcessW::{Command, EnvironmentSnapshot};
     ///
-    /// let mut command = Command::new("notepad.exe");
+    /// // Captured at startup, before the environment is changed.
+    /// let snapshot = EnvironmentSnapshot::capture();
     ///
-    /// if let Ok(mut child) = command.spawn() {
-    ///     child.wait().expect("command wasn't running");
-    ///     println!("Child has finished its execution!");
-    /// } else {
-    ///     println!("notepad didn't start");
-    /// }
+    /// let status = Command::new("worker.exe")
+    ///     .base_env(&snapshot)
+    ///     .env("WORKER_ID", "1")
+    ///     .status()
+    ///     .expect("worker failed to start");
     /// ```
-    ///
-    /// [wait-for-single-object]: https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-waitforsingleobject
-    /// [close-handle]: https://docs.microsoft.com/en-us/windows/win32/api/handleapi/nf-handleapi-closehandle
-    pub fn wait(&self) -> Result<ExitStatus> {
-        unsafe {
-            let mut exit_code: u32 = 0;
-            let res = WaitForSingleObject(self.process_information.hProcess, INFINITE);
-
-            if res == WAIT_OBJECT_0 {
-                if GetExitCodeProcess(
-                    self.process_information.hProcess,
-                    &mut exit_code as *mut u32,
-                )
-                .is_ok()
-                {
-                    close_handles(&self.process_information);
-                    Ok(ExitStatus(exit_code))
-                } else {
-                    Err(Error::GetExitCodeFailed(GetLastError().0))
-                }
-            } else {
-                Err(Error::WaitFailed(GetLastError().0))
-            }
-        }
+    pub fn base_env(&mut self, snapshot: &EnvironmentSnapshot) -> &mut Self {
+        self.environment.base = env::Base::Snapshot(snapshot.vars().to_vec());
+        self
     }
 
-    /// Attempts to collect the exit status of the child if it has already
-    /// exited.
+    /// Sets the priority class of the child process.
     ///
-    /// This function will not block the calling thread and will only check to
-    /// see if the child process has exited or not.
+    /// By default, the child process gets the
+    /// [`Normal`][PriorityClass::Normal] priority class, unless the priority
+    /// class of the calling process is [`Idle`][PriorityClass::Idle] or
+    /// [`BelowNormal`][PriorityClass::BelowNormal], in which case the child
+    /// process inherits it.
     ///
-    /// If the child has exited, then `Ok(Some(status))` is returned. If the
-    /// exit status is not available at this time then `Ok(None)` is returned.
-    /// If an error occurs, then that error is returned.
+    /// # Examples
     ///
-    /// Equivalent to the [`GetExitCodeProcess`][get-exit-code-process]
+    /// ```no_run
+    /// use CreateProcessW::{Command, PriorityClass};
+    ///
+    /// let check = Command::new("cargo.exe check")
+    ///     .priority_class(PriorityClass::BelowNormal)
+    ///     .status()
+    ///     .expect("cargo check command failed");
+    /// ```
+    ///
+    /// Equivalent to the priority class flags of the `dwCreationFlags`
+    /// parameter of the [`CreateProcessW`][create-process-w-parameters]
     /// function.
     ///
-    /// Note that this function will call [`CloseHandle`][close-handle] if the
-    /// child has exited. If the function fail, a
-    /// [`GetExitCodeProcess`][Error::GetExitCodeFailed] error is returned.
+    /// [create-process-w-parameters]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw#parameters
+    pub fn priority_class(&mut self, priority_class: PriorityClass) -> &mut Self {
+        self.priority_class = Some(priority_class);
+        self
+    }
+
+    /// Enable/disable the inheritance of the affinity of the calling process.
+    ///
+    /// By default, a child gets the affinity of the calling process only if
+    /// the calling process has threads in a single processor group. When
+    /// enabled, the child inherits the affinity of the calling process even
+    /// if it spans several groups. The affinity of the child can be changed
+    /// once it has been spawned with [`Child::set_affinity`].
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use CreateProcessW::Command;
     ///
-    /// let mut child = Command::new("notepad.exe").spawn().unwrap();
-    ///
-    /// match child.try_wait() {
-    ///     Ok(Some(status)) => println!("exited with: {}", status.code()),
-    ///     Ok(None) => {
-    ///         println!("status not ready yet, let's really wait");
-    ///         let status = child.wait().expect("cannot wait process");
-    ///         println!("waited: {}", status.code());
-    ///     }
-    ///     Err(e) => println!("error attempting to wait: {}", e),
-    /// }
+    /// let worker = Command::new("worker.exe")
+    ///     .inherit_parent_affinity(true)
+    ///     .spawn()
+    ///     .expect("worker failed to start");
     /// ```
     ///
-    /// [close-handle]: https://docs.microsoft.com/en-us/windows/win32/api/handleapi/nf-handleapi-closehandle
-    /// [get-exit-code]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getexitcodeprocess
+    /// Equivalent to the `INHERIT_PARENT_AFFINITY` flag of the
+    /// `dwCreationFlags` parameter of the
+    /// [`CreateProcessW`][create-process-w-parameters] function.
     ///
-    pub fn try_wait(&self) -> Result<Option<ExitStatus>> {
-        let mut exit_code: u32 = 0;
-
-        unsafe {
-            match GetExitCodeProcess(
-                self.process_information.hProcess,
-                &mut exit_code as *mut u32,
-            ) {
-                Ok(()) if exit_code as i32 == STATUS_PENDING.0 => Ok(None),
-                Ok(()) => {
-                    close_handles(&self.process_information);
-                    Ok(Some(ExitStatus(exit_code)))
-                }
-                Err(_) => Err(Error::GetExitCodeFailed(GetLastError().0)),
-            }
-        }
+    /// [create-process-w-parameters]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw#parameters
+    pub fn inherit_parent_affinity(&mut self, inherit: bool) -> &mut Self {
+        self.inherit_parent_affinity = inherit;
+        self
     }
 
-    /// Returns the process identifier associated with this child.
+    /// Disables the file system redirection of the calling thread while the
+    /// child is created.
+    ///
+    /// The accesses of a 32-bit process to `C:\Windows\System32` are
+    /// redirected to `C:\Windows\SysWOW64`, so a 32-bit parent silently
+    /// spawns the 32-bit version of a system program, or doesn't find a
+    /// program that only ships in 64-bit. When enabled, the redirection is
+    /// disabled around the creation of the child and restored afterwards, even
+    /// if it fails. [`path::sysnative_hint`] is an alternative that rewrites
+    /// the path of the program instead.
+    ///
+    /// This does nothing in a 64-bit process, which isn't redirected.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use CreateProcessW::Command;
     ///
-    /// let mut command = Command::new("notepad.exe");
-    ///
-    /// if let Ok(child) = command.spawn() {
-    ///     println!("Child's ID is {}", child.id());
-    /// } else {
-    ///     println!("notepad didn't start");
-    /// }
+    /// Command::new(r"C:\Windows\System32\bcdedit.exe /enum")
+    ///     .disable_wow64_redirection(true)
+    ///     .status()
+    ///     .expect("bcdedit failed to start");
     /// ```
-    pub fn id(&self) -> u32 {
-        self.process_information.dwProcessId
+    ///
+    /// Equivalent to the [`Wow64DisableWow64FsRedirection`][wow64-disable]
+    /// and [`Wow64RevertWow64FsRedirection`][wow64-revert] functions.
+    ///
+    /// [wow64-disable]: https://docs.microsoft.com/en-us/windows/win32/api/wow64apiset/nf-wow64apiset-wow64disablewow64fsredirection
+    /// [wow64-revert]: https://docs.microsoft.com/en-us/windows/win32/api/wow64apiset/nf-wow64apiset-wow64revertwow64fsredirection
+    pub fn disable_wow64_redirection(&mut self, disable: bool) -> &mut Self {
+        self.disable_wow64_redirection = disable;
+        self
     }
+
+    /// Disables the dynamic priority boost of the threads of the child
+    /// process.
+    ///
+    /// The system temporarily raises the priority of a thread when it wakes
+    /// up, like when its I/O completes or its window gets input, which makes
+    /// the scheduling of a benchmark less deterministic. When enabled, the
+    /// child is created suspended and the boost is disabled before its first
+    /// thread runs. If it cannot be disabled, the child is terminated and a
+    /// [`SetPriorityBoostFailed`][Error::SetPriorityBoostFailed] error is
+    /// returned. The boost can be changed once the child has been spawned with
+    /// [`Child::set_priority_boost`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// Command::new("bench.exe --iterations 1000")
+    ///     .disable_priority_boost(true)
+    ///     .status()
+    ///     .expect("bench failed to start");
+    /// ```
+    ///
+    /// Equivalent to the [`SetProcessPriorityBoost`][set-process-priority-boost]
+    /// function called on the child created with the `CREATE_SUSPENDED` flag.
+    ///
+    /// [set-process-priority-boost]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-setprocesspriorityboost
+    pub fn disable_priority_boost(&mut self, disable: bool) -> &mut Self {
+        self.disable_priority_boost = disable;
+        self
+    }
+
+    /// Sets the minimum and maximum working set sizes of the child process,
+    /// in bytes.
+    ///
+    /// The child is created suspended and the limits are set before its first
+    /// thread runs, like with [`Child::set_working_set`] without hard limits:
+    /// the system keeps the working set between them when memory is scarce,
+    /// so a memory-hungry child pages its own memory out before the one of
+    /// the other processes. The sizes are checked when spawning, an
+    /// [`InvalidCommand`][Error::InvalidCommand] error is returned if they are
+    /// invalid. If they cannot be set, the child is terminated and a
+    /// [`SetWorkingSetFailed`][Error::SetWorkingSetFailed] error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// Command::new("indexer.exe")
+    ///     .working_set(1 << 20, 256 << 20)
+    ///     .spawn()
+    ///     .expect("indexer failed to start");
+    /// ```
+    ///
+    /// Equivalent to the
+    /// [`SetProcessWorkingSetSizeEx`][set-process-working-set-size-ex]
+    /// function called on the child created with the `CREATE_SUSPENDED` flag.
+    ///
+    /// [set-process-working-set-size-ex]: https://docs.microsoft.com/en-us/windows/win32/api/memoryapi/nf-memoryapi-setprocessworkingsetsizeex
+    pub fn working_set(&mut self, min_bytes: usize, max_bytes: usize) -> &mut Self {
+        self.working_set = Some((min_bytes, max_bytes));
+        self
+    }
+
+    /// Enable/disable the efficiency mode of the child process, for
+    /// background work.
+    ///
+    /// The child is created suspended and the mode is enabled before its
+    /// first thread runs, like with [`Child::set_efficiency_mode`]. Task
+    /// Manager shows the efficiency mode of the processes that also have the
+    /// [idle priority class][PriorityClass::Idle], which can be set with
+    /// [`priority_class`][Command::priority_class]. If the mode cannot be
+    /// enabled, the child is terminated and the error of
+    /// [`Child::set_efficiency_mode`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, PriorityClass};
+    ///
+    /// Command::new("indexer.exe")
+    ///     .efficiency_mode(true)
+    ///     .priority_class(PriorityClass::Idle)
+    ///     .spawn()
+    ///     .expect("indexer failed to start");
+    /// ```
+    pub fn efficiency_mode(&mut self, enable: bool) -> &mut Self {
+        self.efficiency_mode = enable;
+        self
+    }
+
+    /// Spawns the child process under the debugger of the calling thread.
+    ///
+    /// The calling thread receives the debug events of the child, like its
+    /// exceptions before its own handlers, with
+    /// [`Child::next_debug_event`]. The child stops at each event until it is
+    /// continued, so the events must be pumped by the thread that spawned the
+    /// child, or the debugger detached with [`Child::detach_debugger`].
+    /// Otherwise the child hangs. The child is killed when the calling
+    /// thread exits, unless the debugger has been detached.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, DebugEventKind, DebugMode};
+    ///
+    /// let child = Command::new("crashy.exe")
+    ///     .debug(DebugMode::ProcessTree)
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// while let Some(event) = child.next_debug_event(None).unwrap() {
+    ///     if let DebugEventKind::Exception { code, .. } = event.kind {
+    ///         println!("process {} raised {:#x}", event.pid, code);
+    ///     }
+    ///
+    ///     if let DebugEventKind::ExitProcess { .. } = event.kind {
+    ///         if event.pid == child.id() {
+    ///             break;
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Equivalent to the `DEBUG_ONLY_THIS_PROCESS` and `DEBUG_PROCESS` flags
+    /// of the `dwCreationFlags` parameter of the
+    /// [`CreateProcessW`][create-process-w-parameters] function.
+    ///
+    /// [create-process-w-parameters]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw#parameters
+    pub fn debug(&mut self, mode: DebugMode) -> &mut Self {
+        self.debug = Some(mode);
+        self
+    }
+
+    /// Spawns the child as a protected process.
+    ///
+    /// The other processes, even the ones running as administrator, cannot
+    /// access the memory of a protected process or inject code into it. Only
+    /// the programs signed with a certificate allowed for protected processes,
+    /// like the ones of Windows and of antimalware services, can run
+    /// protected. Spawning another program returns a
+    /// [`ProtectedProcessFailed`][Error::ProtectedProcessFailed] error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, ProtectedMode};
+    ///
+    /// let service = Command::new(r"C:\Program Files\Contoso\scanner.exe")
+    ///     .protected_process(ProtectedMode::Light)
+    ///     .spawn()
+    ///     .expect("scanner failed to start");
+    /// ```
+    ///
+    /// Equivalent to the `CREATE_PROTECTED_PROCESS` flag of the
+    /// `dwCreationFlags` parameter of the
+    /// [`CreateProcessW`][create-process-w-parameters] function, with the
+    /// `PROC_THREAD_ATTRIBUTE_PROTECTION_LEVEL` attribute for
+    /// [`Light`][ProtectedMode::Light].
+    ///
+    /// [create-process-w-parameters]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw#parameters
+    pub fn protected_process(&mut self, mode: ProtectedMode) -> &mut Self {
+        self.protected = Some(mode);
+        self
+    }
+
+    /// Spawns the child with a restricted version of the token of the calling
+    /// process.
+    ///
+    /// This limits what a program that isn't trusted can do, like the tools
+    /// downloaded by the calling process, without the isolation of an
+    /// AppContainer: the child runs as the same user, minus the privileges
+    /// and groups removed by `options`. If the token cannot be created, a
+    /// [`TokenFailed`][Error::TokenFailed] error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, RestrictedTokenOptions};
+    ///
+    /// let mut options = RestrictedTokenOptions::new();
+    /// options.disable_max_privilege(true).disable_administrators();
+    ///
+    /// let status = Command::new("downloaded-tool.exe")
+    ///     .restricted(options)
+    ///     .status()
+    ///     .expect("the tool failed to start");
+    /// ```
+    ///
+    /// Equivalent to the [`CreateRestrictedToken`][create-restricted-token]
+    /// function, the child being created with the
+    /// [`CreateProcessAsUserW`][create-process-as-user-w] function.
+    ///
+    /// [create-restricted-token]: https://docs.microsoft.com/en-us/windows/win32/api/securitybaseapi/nf-securitybaseapi-createrestrictedtoken
+    /// [create-process-as-user-w]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessasuserw
+    pub fn restricted(&mut self, options: RestrictedTokenOptions) -> &mut Self {
+        self.restricted = Some(options);
+        self
+    }
+
+    /// Spawns the child on the desktop of the user logged on the console,
+    /// with the token and the environment of this user.
+    ///
+    /// This is how a service, which runs in session 0 without a desktop,
+    /// starts a program the user can see, like a tray helper. The calling
+    /// process must run as `LocalSystem`, or hold the `SeTcbPrivilege`
+    /// privilege, otherwise a [`MissingTcbPrivilege`][Error::MissingTcbPrivilege]
+    /// error is returned. If nobody is logged on the console, a
+    /// [`NoActiveSession`][Error::NoActiveSession] error is returned.
+    ///
+    /// The environment of the user replaces the one of the calling process,
+    /// the variables set with [`env`][Command::env] are added to it. It
+    /// cannot be combined with [`restricted`][Command::restricted].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Error};
+    ///
+    /// match Command::new("tray-helper.exe").in_active_user_session().spawn() {
+    ///     Ok(child) => println!("helper started with pid {}", child.id()),
+    ///     Err(Error::NoActiveSession) => println!("nobody is logged on"),
+    ///     Err(err) => panic!("the helper failed to start: {}", err),
+    /// }
+    /// ```
+    ///
+    /// Equivalent to the [`WTSGetActiveConsoleSessionId`][wts-get-active-console-session-id],
+    /// [`WTSQueryUserToken`][wts-query-user-token] and
+    /// [`CreateEnvironmentBlock`][create-environment-block] functions, the
+    /// child being created with the
+    /// [`CreateProcessAsUserW`][create-process-as-user-w] function on the
+    /// `winsta0\default` desktop.
+    ///
+    /// [wts-get-active-console-session-id]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-wtsgetactiveconsolesessionid
+    /// [wts-query-user-token]: https://docs.microsoft.com/en-us/windows/win32/api/wtsapi32/nf-wtsapi32-wtsqueryusertoken
+    /// [create-environment-block]: https://docs.microsoft.com/en-us/windows/win32/api/userenv/nf-userenv-createenvironmentblock
+    /// [create-process-as-user-w]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessasuserw
+    pub fn in_active_user_session(&mut self) -> &mut Self {
+        self.user_session = true;
+        self
+    }
+
+    /// Enable/disable the feedback cursor while the child process starts.
+    ///
+    /// By default, the system shows the "working in background" cursor for
+    /// up to a few seconds after the child starts, until it shows a window.
+    /// Disabling the feedback is useful for background processes, which
+    /// never show a window. Enabling it forces the feedback cursor.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let helper = Command::new("indexer.exe --background")
+    ///     .startup_feedback(false)
+    ///     .spawn()
+    ///     .expect("indexer failed to start");
+    /// ```
+    ///
+    /// Equivalent to the `STARTF_FORCEONFEEDBACK` and
+    /// `STARTF_FORCEOFFFEEDBACK` flags of the `dwFlags` member of the
+    /// [`STARTUPINFOW`][startupinfow] structure.
+    ///
+    /// [startupinfow]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-startupinfow
+    pub fn startup_feedback(&mut self, feedback: bool) -> &mut Self {
+        self.startup_feedback = Some(feedback);
+        self
+    }
+
+    /// Sets the AppUserModelID of the child process.
+    ///
+    /// The taskbar groups the windows by AppUserModelID, setting the one of
+    /// the calling application groups the windows of the child with it. The
+    /// ID is at most 128 characters long and cannot contain spaces, spawning
+    /// the command returns an [`InvalidCommand`][Error::InvalidCommand] error
+    /// otherwise.
+    ///
+    /// An ID set explicitly by the child with
+    /// `SetCurrentProcessExplicitAppUserModelID` takes precedence. The ID of
+    /// a shortcut only applies when the child is started from the shortcut,
+    /// not when it is spawned with this command.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let viewer = Command::new("viewer.exe report.pdf")
+    ///     .app_user_model_id("Contoso.Launcher")
+    ///     .spawn()
+    ///     .expect("viewer failed to start");
+    /// ```
+    ///
+    /// Equivalent to the `lpTitle` member of the
+    /// [`STARTUPINFOW`][startupinfow] structure with the `STARTF_TITLEISAPPID`
+    /// flag.
+    ///
+    /// [startupinfow]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-startupinfow
+    pub fn app_user_model_id(&mut self, id: impl Into<OsString>) -> &mut Self {
+        self.app_user_model_id = Some(id.into());
+        self
+    }
+
+    /// Enable/disable the pinning of the windows of the child process to the
+    /// taskbar, the windows can be pinned by default.
+    ///
+    /// This is useful for transient windows, like the windows of helpers,
+    /// that shouldn't be started again from the taskbar. The system requires
+    /// an [AppUserModelID][Command::app_user_model_id] to prevent the
+    /// pinning, spawning the command returns an
+    /// [`InvalidCommand`][Error::InvalidCommand] error without one.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let picker = Command::new("color-picker.exe")
+    ///     .app_user_model_id("Contoso.Editor.ColorPicker")
+    ///     .prevent_pinning(true)
+    ///     .spawn()
+    ///     .expect("color picker failed to start");
+    /// ```
+    ///
+    /// Equivalent to the `STARTF_PREVENTPINNING` flag of the `dwFlags` member
+    /// of the [`STARTUPINFOW`][startupinfow] structure.
+    ///
+    /// [startupinfow]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-startupinfow
+    pub fn prevent_pinning(&mut self, prevent: bool) -> &mut Self {
+        self.prevent_pinning = prevent;
+        self
+    }
+
+    /// Gives file descriptors of the C runtime to the child process.
+    ///
+    /// Each `(fd, handle)` pair opens the file descriptor `fd` of the child on
+    /// an inheritable duplicate of `handle`, the child reading or writing it
+    /// with `_read` and `_write` like a file descriptor opened by itself.
+    /// The file descriptors `0`, `1` and `2` replace the standard streams in
+    /// the C runtime of the child.
+    ///
+    /// This only works with children using the Microsoft C runtime (or
+    /// another runtime reading the same block, like Python), which read the
+    /// undocumented `lpReserved2` block written by the `spawn` functions of
+    /// the C runtime. The other children ignore it. The handles are
+    /// inherited, so the inheritance of handles is enabled by default and
+    /// disabling it returns an [`InvalidCommand`][Error::InvalidCommand]
+    /// error when spawning, like a file descriptor given twice or above
+    /// 7280. If a handle cannot be duplicated, a
+    /// [`StdioFailed`][Error::StdioFailed] error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::{fs::File, os::windows::io::AsRawHandle};
+    ///
+    /// let config = File::open("config.toml").unwrap();
+    ///
+    /// // The helper reads its configuration from the file descriptor 3.
+    /// let status = Command::new("helper.exe --config-fd 3")
+    ///     .crt_inherit_fds(&[(3, config.as_raw_handle())])
+    ///     .status()
+    ///     .expect("helper failed to start");
+    /// ```
+    ///
+    /// Equivalent to the `cbReserved2` and `lpReserved2` members of the
+    /// [`STARTUPINFOW`][startupinfow] structure.
+    ///
+    /// [startupinfow]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-startupinfow
+    pub fn crt_inherit_fds(&mut self, fds: &[(u32, RawHandle)]) -> &mut Self {
+        self.crt_fds = fds
+            .iter()
+            .map(|&(fd, handle)| (fd, HANDLE(handle as isize)))
+            .collect();
+        self
+    }
+
+    /// Sets the hotkey of the child process.
+    ///
+    /// The child receives the hotkey as if it had been registered by the
+    /// user, the system sends a `WM_HOTKEY` message to its first window when
+    /// the hotkey is pressed. `virtual_key` is a virtual-key code, like
+    /// `0x4C` for the L key.
+    ///
+    /// The hotkey is given to the child in place of its standard input, so
+    /// it cannot be combined with a configured standard stream or with
+    /// [`output`][Command::output]. Spawning the command returns an
+    /// [`InvalidCommand`][Error::InvalidCommand] error in that case, or if
+    /// `virtual_key` isn't a valid virtual-key code.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, HotkeyModifiers};
+    ///
+    /// let kiosk = Command::new("kiosk.exe")
+    ///     .hotkey(0x4C, HotkeyModifiers::CONTROL | HotkeyModifiers::ALT)
+    ///     .spawn()
+    ///     .expect("kiosk failed to start");
+    /// ```
+    ///
+    /// Equivalent to the `hStdInput` member of the
+    /// [`STARTUPINFOW`][startupinfow] structure with the `STARTF_USEHOTKEY`
+    /// flag.
+    ///
+    /// [startupinfow]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-startupinfow
+    pub fn hotkey(&mut self, virtual_key: u16, modifiers: HotkeyModifiers) -> &mut Self {
+        self.hotkey = Some((virtual_key, modifiers));
+        self
+    }
+
+    /// Enable/disable the error dialogs of the child process.
+    ///
+    /// By default, the child inherits the error mode of the calling process,
+    /// which usually shows a dialog when the child crashes or cannot find a
+    /// disk. The dialogs wait for a user, which hangs the unattended
+    /// machines like CI runners.
+    ///
+    /// When `true`, the dialogs of critical errors and the Windows Error
+    /// Reporting dialog are suppressed: the calling process sets the
+    /// `SEM_FAILCRITICALERRORS` and `SEM_NOGPFAULTERRORBOX` error modes while
+    /// spawning the child, which inherits them, and restores its error mode
+    /// afterwards. The error mode is shared by the threads of the calling
+    /// process, the spawns using this are serialized but other code changing
+    /// the error mode at the same time may be affected. When `false`, the
+    /// child gets the default error mode showing the dialogs, even if the
+    /// calling process suppressed them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let status = Command::new("flaky-test.exe")
+    ///     .suppress_error_dialogs(true)
+    ///     .status()
+    ///     .expect("test failed to start");
+    /// ```
+    ///
+    /// Equivalent to calling the [`SetErrorMode`][set-error-mode] function
+    /// when spawning the child when `true`, to the `CREATE_DEFAULT_ERROR_MODE`
+    /// flag of the `dwCreationFlags` parameter of the
+    /// [`CreateProcessW`][create-process-w-parameters] function when `false`.
+    ///
+    /// [set-error-mode]: https://docs.microsoft.com/en-us/windows/win32/api/errhandlingapi/nf-errhandlingapi-seterrormode
+    /// [create-process-w-parameters]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw#parameters
+    pub fn suppress_error_dialogs(&mut self, suppress: bool) -> &mut Self {
+        self.suppress_error_dialogs = Some(suppress);
+        self
+    }
+
+    /// Enable/disable a warning when the [`Child`] is dropped without having
+    /// been waited on.
+    ///
+    /// The warning is printed on the standard error and includes the process
+    /// identifier and the command line, which helps finding the call sites
+    /// that leave "zombie" processes around (see [`Child`]). It is also given
+    /// to the [metrics hook][set_metrics_hook] as a
+    /// [`MetricsEvent::Unreaped`], to be logged elsewhere. A child is
+    /// considered waited on once [`wait`][Child::wait] returned or
+    /// [`try_wait`][Child::try_wait] observed its exit. Use
+    /// [`forget`][Child::forget] to drop a child on purpose without the
+    /// warning. This is disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe")
+    ///     .warn_on_unreaped(cfg!(debug_assertions))
+    ///     .spawn()
+    ///     .expect("notepad failed to start");
+    /// ```
+    pub fn warn_on_unreaped(&mut self, warn: bool) -> &mut Self {
+        self.warn_on_unreaped = warn;
+        self
+    }
+
+    /// Enable/disable the registration of the child process in the global
+    /// registry of the crate.
+    ///
+    /// Registered children that are still running can be terminated all at
+    /// once with [`kill_all_registered`], for example before exiting or from a
+    /// panic hook installed with [`kill_registered_on_panic`]. This ensures
+    /// that no registered child outlives the calling process. A child is
+    /// removed from the registry once it has been waited on or
+    /// [forgotten][Child::forget]. This is disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{kill_all_registered, Command};
+    ///
+    /// let child = Command::new("notepad.exe")
+    ///     .register(true)
+    ///     .spawn()
+    ///     .expect("notepad failed to start");
+    ///
+    /// kill_all_registered();
+    /// ```
+    pub fn register(&mut self, register: bool) -> &mut Self {
+        self.register = register;
+        self
+    }
+
+    /// Sets a label of the child process, telling which task it runs.
+    ///
+    /// The tag is kept by the [`Child`], see [`Child::tag`], and shown in its
+    /// `Debug` output, in the errors of the command (see [`Error::tag`]) and
+    /// in the [events of the metrics][MetricsEvent]. It is shared by the
+    /// clones of the command, so a command used as a template can be cloned
+    /// cheaply.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let err = Command::new("missing.exe")
+    ///     .tag("build-docs")
+    ///     .spawn()
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(err.tag(), Some("build-docs"));
+    /// ```
+    pub fn tag(&mut self, tag: impl Into<String>) -> &mut Self {
+        self.tag = Some(Arc::from(tag.into()));
+        self
+    }
+
+    /// Configuration for the standard input of the child process.
+    ///
+    /// Defaults to [`inherit`][Stdio::inherit] when used with
+    /// [`spawn`][Command::spawn] or [`status`][Command::status], and defaults
+    /// to [`null`][Stdio::null] when used with [`output`][Command::output].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Stdio};
+    /// use std::io::Write;
+    ///
+    /// let mut child = Command::new("sort.exe")
+    ///     .stdin(Stdio::piped())
+    ///     .spawn()
+    ///     .expect("sort failed to start");
+    ///
+    /// let mut stdin = child.stdin.take().unwrap();
+    /// stdin.write_all(b"b\r\na\r\n").unwrap();
+    /// drop(stdin);
+    ///
+    /// child.wait().unwrap();
+    /// ```
+    ///
+    /// Equivalent to the `hStdInput` member of the
+    /// [`STARTUPINFOW`][startupinfow] structure.
+    ///
+    /// [startupinfow]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-startupinfow
+    pub fn stdin(&mut self, cfg: Stdio) -> &mut Self {
+        self.stdin = Some(cfg);
+        self
+    }
+
+    /// Configuration for the standard output of the child process.
+    ///
+    /// Defaults to [`inherit`][Stdio::inherit] when used with
+    /// [`spawn`][Command::spawn] or [`status`][Command::status], and defaults
+    /// to [`piped`][Stdio::piped] when used with [`output`][Command::output].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Stdio};
+    /// use std::io::Read;
+    ///
+    /// let mut child = Command::new("cmd.exe /c echo hello")
+    ///     .stdout(Stdio::piped())
+    ///     .spawn()
+    ///     .expect("cmd failed to start");
+    ///
+    /// let mut output = String::new();
+    /// child.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+    ///
+    /// assert_eq!(output.trim(), "hello");
+    /// ```
+    ///
+    /// Equivalent to the `hStdOutput` member of the
+    /// [`STARTUPINFOW`][startupinfow] structure.
+    ///
+    /// [startupinfow]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-startupinfow
+    pub fn stdout(&mut self, cfg: Stdio) -> &mut Self {
+        self.stdout = Some(cfg);
+        self
+    }
+
+    /// Configuration for the standard error of the child process.
+    ///
+    /// Defaults to [`inherit`][Stdio::inherit] when used with
+    /// [`spawn`][Command::spawn] or [`status`][Command::status], and defaults
+    /// to [`piped`][Stdio::piped] when used with [`output`][Command::output].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Stdio};
+    ///
+    /// let status = Command::new("cargo.exe check")
+    ///     .stderr(Stdio::null())
+    ///     .status()
+    ///     .expect("cargo check command failed");
+    /// ```
+    ///
+    /// Equivalent to the `hStdError` member of the
+    /// [`STARTUPINFOW`][startupinfow] structure.
+    ///
+    /// [startupinfow]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-startupinfow
+    pub fn stderr(&mut self, cfg: Stdio) -> &mut Self {
+        self.stderr = Some(cfg);
+        self
+    }
+
+    /// Sets the size of the buffers of the pipes created for the standard
+    /// streams of the child, 64 KiB by default.
+    ///
+    /// A larger buffer lets a child producing a lot of output keep running
+    /// while the calling process is busy, instead of blocking on its writes.
+    /// The size is clamped between 1 byte and 16 MiB. The system treats it as
+    /// a hint and may use a different size.
+    ///
+    /// This applies to the streams that are [piped][Stdio::piped], and to the
+    /// pipes between the stages of a [`Pipeline`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let output = Command::new("cargo.exe build -vv")
+    ///     .pipe_buffer_size(1024 * 1024)
+    ///     .output()
+    ///     .expect("cargo build command failed");
+    /// ```
+    ///
+    /// Equivalent to the `nInBufferSize` and `nOutBufferSize` parameters of
+    /// the [`CreateNamedPipeW`][create-named-pipe-w] function.
+    ///
+    /// [create-named-pipe-w]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-createnamedpipew
+    pub fn pipe_buffer_size(&mut self, bytes: usize) -> &mut Self {
+        self.pipe_buffer_size = bytes.clamp(1, pipe::MAX_BUFFER_SIZE as usize) as u32;
+        self
+    }
+
+    /// Executes the command as a child process, returning a handle to it.
+    ///
+    /// If the child cannot be spawned, a
+    /// [`CommandFailed`][Error::CommandFailed] error is returned, its message
+    /// including the command line. The error of the failed operation is its
+    /// [source][std::error::Error::source].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// Command::new("notepad.exe")
+    ///     .spawn()
+    ///     .expect("notepad failed to start");
+    /// ```
+    pub fn spawn(&mut self) -> Result<Child> {
+        self.spawn_with_default_stdio(false)
+            .map_err(self.context(Operation::Spawn, None))
+    }
+
+    /// Executes the command as a child process like [`spawn`][Command::spawn],
+    /// terminating it if it is still running once `timeout` has elapsed.
+    ///
+    /// The timeout is enforced by a thread, even if the calling code hangs,
+    /// until the child exits or the returned [`WatchdogGuard`] is
+    /// [disarmed][WatchdogGuard::disarm]. Only the child is terminated, with
+    /// the exit code `1`, not the processes it created: terminating the
+    /// whole tree reliably needs a job object, which the crate doesn't
+    /// manage. The descendants can be listed with
+    /// [`descendants`][Child::descendants] once the watchdog has expired. If
+    /// the watchdog cannot be armed, the child is terminated and the error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::time::Duration;
+    ///
+    /// let (child, watchdog) = Command::new("cargo.exe test")
+    ///     .spawn_with_timeout(Duration::from_secs(600))
+    ///     .unwrap();
+    ///
+    /// let status = child.wait().unwrap();
+    /// watchdog.disarm();
+    /// ```
+    pub fn spawn_with_timeout(&mut self, timeout: Duration) -> Result<(Child, WatchdogGuard)> {
+        let child = self.spawn()?;
+
+        match WatchdogGuard::arm(&child, timeout) {
+            Ok(watchdog) => Ok((child, watchdog)),
+            Err(err) => {
+                let _ = child.kill();
+                Err(self.context(Operation::Spawn, Some(child.id()))(err))
+            }
+        }
+    }
+
+    /// Executes the command as a child process like [`spawn`][Command::spawn],
+    /// creating and managing the child with `api` instead of the Win32 API.
+    ///
+    /// The waits, the termination and the exit status of the child go through
+    /// `api`, so a fake like [`MockProcessApi`] can script them. The other
+    /// methods of [`Child`] use the handles returned by `api` with the Win32
+    /// API.
+    ///
+    /// Requires the `test-util` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, MockProcessApi};
+    /// use std::sync::Arc;
+    ///
+    /// let api = MockProcessApi::new();
+    /// let child = Command::new("worker.exe --once")
+    ///     .spawn_with_api(Arc::new(api.clone()))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(api.command_lines(), ["worker.exe --once"]);
+    ///
+    /// child.kill().unwrap();
+    /// assert_eq!(child.wait().unwrap().code(), 0);
+    /// ```
+    #[cfg(feature = "test-util")]
+    pub fn spawn_with_api(&mut self, api: Arc<dyn ProcessApi>) -> Result<Child> {
+        self.spawn_with_api_and_default_stdio(api, false)
+            .map_err(self.context(Operation::Spawn, None))
+    }
+
+    // Wrap an error with the operation that failed and the configuration of
+    // the command, so the message tells which command was responsible. `pid`
+    // is the identifier of the child once it has been spawned.
+    pub(crate) fn context(
+        &self,
+        operation: Operation,
+        pid: Option<u32>,
+    ) -> impl FnOnce(Error) -> Error + '_ {
+        move |err| Error::CommandFailed {
+            operation,
+            command: truncate(&self.command.to_string_lossy(), 128),
+            pid,
+            current_directory: self.current_directory.clone(),
+            tag: self.tag.clone(),
+            source: Box::new(err),
+        }
+    }
+
+    // Spawn the child, the standard streams that aren't configured default to
+    // the ones used by `output` when `capture` is `true`.
+    fn spawn_with_default_stdio(&mut self, capture: bool) -> Result<Child> {
+        self.spawn_with_api_and_default_stdio(Arc::new(Win32ProcessApi), capture)
+    }
+
+    fn spawn_with_api_and_default_stdio(
+        &mut self,
+        api: Arc<dyn ProcessApi>,
+        capture: bool,
+    ) -> Result<Child> {
+        let child = self.create_child_resolving_app_paths(api, capture);
+
+        if child.is_err() {
+            metrics::spawn_failed();
+        }
+
+        child
+    }
+
+    fn create_child(&mut self, api: Arc<dyn ProcessApi>, capture: bool) -> Result<Child> {
+        validation::validate(self, capture)?;
+
+        // Verified before anything is set up, then pinned as application name.
+        let verified_program = match &self.signature {
+            Some(policy) => {
+                let _redirection = self
+                    .disable_wow64_redirection
+                    .then(wow64::RedirectionDisabled::new)
+                    .flatten();
+
+                Some(signature::verify_program(self, policy)?)
+            }
+            None => None,
+        };
+
+        let default_stdio = |stdio: &Option<Stdio>, captured: fn() -> Stdio| match stdio {
+            Some(stdio) => Some(stdio.clone()),
+            None if capture => Some(captured()),
+            None => None,
+        };
+
+        let stdin = default_stdio(&self.stdin, Stdio::null);
+        let stdout = default_stdio(&self.stdout, Stdio::piped);
+        let stderr = default_stdio(&self.stderr, Stdio::piped);
+
+        // The standard streams of a child attached to a pseudoconsole are the
+        // ones of the console.
+        let stdio = if self.pseudoconsole.is_some() {
+            None
+        } else if stdin.is_some() || stdout.is_some() || stderr.is_some() {
+            Some(stdio::setup(
+                &stdin.unwrap_or_else(Stdio::inherit),
+                &stdout.unwrap_or_else(Stdio::inherit),
+                &stderr.unwrap_or_else(Stdio::inherit),
+                self.pipe_buffer_size,
+            )?)
+        } else {
+            None
+        };
+
+        let (stdio_handles, pipes) = match stdio {
+            Some((handles, pipes)) => (Some(handles), pipes),
+            None => (None, ParentPipes::default()),
+        };
+
+        // The handles have to live until the child is created.
+        let mut token = self
+            .restricted
+            .as_ref()
+            .map(token::restricted_token)
+            .transpose()?;
+        let mut environment = self.environment.to_block();
+
+        if self.user_session {
+            let session = user_session::active_user_session(&user_session::Win32SessionTokens)?;
+
+            token = Some(session.token);
+
+            // The child starts from the environment of the user.
+            if matches!(self.environment.base, env::Base::Inherit) {
+                environment = env::Environment {
+                    base: env::Base::Snapshot(session.environment),
+                    vars: self.environment.vars.clone(),
+                }
+                .to_block();
+            }
+        }
+
+        let crt_fds = if self.crt_fds.is_empty() {
+            None
+        } else {
+            Some(CrtFds::new(&self.crt_fds)?)
+        };
+
+        // Many programs don't understand a verbatim working directory.
+        let current_directory = self.current_directory.as_deref().map(path::from_verbatim);
+
+        let mut creation_flags = self
+            .priority_class
+            .map(PriorityClass::to_flags)
+            .unwrap_or(PROCESS_CREATION_FLAGS(0));
+
+        if self.suppress_error_dialogs == Some(false) {
+            creation_flags |= CREATE_DEFAULT_ERROR_MODE;
+        }
+
+        if self.inherit_parent_affinity {
+            creation_flags |= INHERIT_PARENT_AFFINITY;
+        }
+
+        if let Some(debug) = self.debug {
+            creation_flags |= debug.to_flags();
+        }
+
+        if self.protected.is_some() {
+            creation_flags |= CREATE_PROTECTED_PROCESS;
+        }
+
+        if self.starts_suspended() {
+            creation_flags |= CREATE_SUSPENDED;
+        }
+
+        let mut startup_flags = match self.startup_feedback {
+            Some(true) => STARTF_FORCEONFEEDBACK,
+            Some(false) => STARTF_FORCEOFFFEEDBACK,
+            None => STARTUPINFOW_FLAGS(0),
+        };
+
+        if self.app_user_model_id.is_some() {
+            startup_flags |= STARTF_TITLEISAPPID;
+        }
+
+        if self.hotkey.is_some() {
+            startup_flags |= STARTF_USEHOTKEY;
+        }
+
+        if self.prevent_pinning {
+            startup_flags |= STARTF_PREVENTPINNING;
+        }
+
+        // The child only inherits its own handles, listed in its attributes,
+        // and not the ones made inheritable meanwhile for the children spawned
+        // by other threads. `inherit_handles(true)` inherits all of them.
+        let mut handle_list = match self.inherit_handles {
+            Some(_) => Vec::new(),
+            None => stdio_handles
+                .iter()
+                .flat_map(|stdio| [stdio.stdin(), stdio.stdout(), stdio.stderr()])
+                .filter(|handle| !handle.is_invalid())
+                .map(|handle| handle.0 as RawHandle)
+                .chain(crt_fds.iter().flat_map(CrtFds::handles))
+                .chain(
+                    self.inherited_handles
+                        .iter()
+                        .map(|handle| handle.as_raw_handle()),
+                )
+                .collect::<Vec<_>>(),
+        };
+
+        // A handle cannot be listed twice.
+        handle_list.sort_unstable();
+        handle_list.dedup();
+
+        let spec = ProcessSpec {
+            command_line: &self.command,
+            application_name: verified_program
+                .as_deref()
+                .or(self.application_name.as_deref()),
+            current_directory: current_directory.as_deref(),
+            inherit_handles: self.inherit_handles.unwrap_or(!handle_list.is_empty()),
+            handle_list: (!handle_list.is_empty()).then_some(handle_list.as_slice()),
+            creation_flags: creation_flags.0,
+            startup_flags: startup_flags.0,
+            title: self.app_user_model_id.as_deref(),
+            desktop: self.user_session.then(|| OsStr::new(user_session::DESKTOP)),
+            hotkey: self
+                .hotkey
+                .map(|(virtual_key, modifiers)| hotkey::encode(virtual_key, modifiers)),
+            reserved2: crt_fds.as_ref().map(CrtFds::block),
+            protection_level: self.protected.and_then(ProtectedMode::protection_level),
+            token: token.as_ref().map(AsRawHandle::as_raw_handle),
+            environment: environment.as_deref(),
+            stdio: stdio_handles.as_ref().map(|stdio| {
+                [stdio.stdin(), stdio.stdout(), stdio.stderr()].map(|handle| handle.0 as RawHandle)
+            }),
+            pseudoconsole: self.pseudoconsole.map(|console| console.0 as RawHandle),
+        };
+
+        let child = {
+            let _inheritable = inherit::Inheritable::new(&self.inherited_handles)?;
+            // Restored once the child is created, even on panic.
+            let _redirection = self
+                .disable_wow64_redirection
+                .then(wow64::RedirectionDisabled::new)
+                .flatten();
+
+            if self.suppress_error_dialogs == Some(true) {
+                with_error_dialogs_suppressed(|| Child::new(api, &spec))
+            } else {
+                Child::new(api, &spec)
+            }
+        };
+
+        let mut child = child.map_err(|err| match err {
+            // The program isn't signed to run protected.
+            Error::CreationFailed(err)
+                if self.protected.is_some()
+                    && matches!(
+                        WIN32_ERROR(os_code(&err)),
+                        ERROR_ACCESS_DENIED | ERROR_INVALID_IMAGE_HASH
+                    ) =>
+            {
+                Error::ProtectedProcessFailed(err)
+            }
+            err => err,
+        })?;
+
+        // Close the handles of the child in the calling process, so the pipes
+        // are closed once the child exits.
+        drop(stdio_handles);
+
+        child.stdin = pipes.stdin;
+        child.stdout = pipes.stdout;
+        child.stderr = pipes.stderr;
+
+        // The child has its own handles now.
+        self.inherited_handles.clear();
+
+        let child = self.finish_spawn(child)?;
+
+        if self.starts_suspended() {
+            self.start_suspended(&child)?;
+        }
+
+        Ok(child)
+    }
+
+    // Apply the options of the command that are handled once the child has
+    // been spawned, however it has been spawned.
+    fn finish_spawn(&self, mut child: Child) -> Result<Child> {
+        child.tag = self.tag.clone();
+        child.warn_on_unreaped = self.warn_on_unreaped;
+
+        metrics::spawned(&child);
+
+        if self.register {
+            // Don't let an unregistered child outlive the calling process.
+            if let Err(err) = registry::register(&child) {
+                let _ = child.kill();
+                return Err(err);
+            }
+
+            child.registered = true;
+        }
+
+        Ok(child)
+    }
+
+    /// Executes a command as a child process, waiting for it to finish and
+    /// collecting its status.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let status = Command::new("notepad.exe")
+    ///     .status()
+    ///     .expect("failed to execute process");
+    ///
+    /// println!("process finished with: {}", status.code());
+    ///
+    /// assert!(status.success());
+    /// ```
+    pub fn status(&mut self) -> Result<ExitStatus> {
+        let child = self.spawn()?;
+
+        child
+            .wait()
+            .map_err(self.context(Operation::Wait, Some(child.id())))
+    }
+
+    /// Executes a command as a child process, waiting asynchronously for it
+    /// to finish and collecting its status.
+    ///
+    /// See [`Child::wait_async`] for more information.
+    ///
+    /// Requires the `tokio` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// # async fn run() {
+    /// let status = Command::new("cargo.exe check")
+    ///     .status_async()
+    ///     .await
+    ///     .expect("failed to execute process");
+    ///
+    /// println!("process finished with: {}", status.code());
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn status_async(&mut self) -> Result<ExitStatus> {
+        let child = self.spawn()?;
+
+        child.wait_async().await
+    }
+}
+
+/// The priority class of a process.
+///
+/// The priority class is used with the priority of each thread of the process
+/// to determine the scheduling priority of the threads. See
+/// [`Scheduling Priorities`][scheduling-priorities] for more information.
+///
+/// [scheduling-priorities]: https://docs.microsoft.com/en-us/windows/win32/procthread/scheduling-priorities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriorityClass {
+    /// Threads run only when the system is idle, like a screen saver.
+    Idle,
+    /// Priority above `Idle` but below `Normal`.
+    BelowNormal,
+    /// Process with no special scheduling needs.
+    Normal,
+    /// Priority above `Normal` but below `High`.
+    AboveNormal,
+    /// Process that performs time-critical tasks that must be executed
+    /// immediately.
+    High,
+    /// Highest possible priority, preempting all other threads including
+    /// operating system processes. Requires the
+    /// `SeIncreaseBasePriorityPrivilege` privilege, otherwise `High` is used.
+    Realtime,
+    /// A priority class value that isn't modeled by this enum, only returned
+    /// by [`Child::priority`].
+    Other(UnknownPriorityClass),
+}
+
+/// A priority class returned by the system that isn't modeled by
+/// [`PriorityClass`].
+///
+/// It cannot be created outside of the crate, so a [`PriorityClass`] given to
+/// [`Command::priority_class`] or [`Child::set_priority`] is always one the
+/// system returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownPriorityClass(u32);
+
+impl UnknownPriorityClass {
+    /// Returns the raw value returned by `GetPriorityClass`.
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl PriorityClass {
+    fn to_flags(self) -> PROCESS_CREATION_FLAGS {
+        match self {
+            Self::Idle => IDLE_PRIORITY_CLASS,
+            Self::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+            Self::Normal => NORMAL_PRIORITY_CLASS,
+            Self::AboveNormal => ABOVE_NORMAL_PRIORITY_CLASS,
+            Self::High => HIGH_PRIORITY_CLASS,
+            Self::Realtime => REALTIME_PRIORITY_CLASS,
+            Self::Other(UnknownPriorityClass(value)) => PROCESS_CREATION_FLAGS(value),
+        }
+    }
+
+    fn from_flags(flags: PROCESS_CREATION_FLAGS) -> Self {
+        match flags {
+            IDLE_PRIORITY_CLASS => Self::Idle,
+            BELOW_NORMAL_PRIORITY_CLASS => Self::BelowNormal,
+            NORMAL_PRIORITY_CLASS => Self::Normal,
+            ABOVE_NORMAL_PRIORITY_CLASS => Self::AboveNormal,
+            HIGH_PRIORITY_CLASS => Self::High,
+            REALTIME_PRIORITY_CLASS => Self::Realtime,
+            PROCESS_CREATION_FLAGS(value) => Self::Other(UnknownPriorityClass(value)),
+        }
+    }
+}
+
+/// The protection of a protected process, used by
+/// [`Command::protected_process`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectedMode {
+    /// A protected process, for the programs of Windows protecting media
+    /// content.
+    Full,
+    /// A protected process light (PPL), with the protection level of the
+    /// calling process, which must be a protected process light itself.
+    Light,
+}
+
+impl ProtectedMode {
+    // The `PROTECTION_LEVEL_*` value of the attribute list.
+    fn protection_level(self) -> Option<u32> {
+        // `PROTECTION_LEVEL_SAME`
+        const PROTECTION_LEVEL_SAME: u32 = 0xFFFF_FFFF;
+
+        match self {
+            Self::Full => None,
+            Self::Light => Some(PROTECTION_LEVEL_SAME),
+        }
+    }
+}
+
+/// Representation of a running or exited child process.
+///
+/// This structure is used to represent and manage child processes. A child
+/// process is created via the [`Command`] struct, which configures the spawning
+/// process and can itself be constructed using a builder-style interface.
+///
+/// # Warnings
+///
+/// The handles of the process are kept open for as long as the `Child` is
+/// alive and closed when it is dropped. A process that terminated but whose
+/// `Child` is still around remains a "zombie". Leaving too many zombies around
+/// may exhaust global resources.
+///
+/// This library does *not* automatically wait on child processes (not even if
+/// the `Child` is dropped), it is up to the application developer to do so.
+///
+/// # Thread safety
+///
+/// `Child` is `Send` and `Sync`: process handles can be used from any thread
+/// and the state kept by the `Child` is synchronized. A child can be waited on
+/// a thread while another thread kills it or queries it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::Command;
+///
+/// let mut child = Command::new("notepad.exe")
+///     .spawn()
+///     .expect("failed to execute child");
+///
+/// let status = child.wait().expect("failed to wait on child");
+///
+/// assert!(status.success());
+/// ```
+///
+/// Waiting on another thread:
+///
+/// ```no_run
+/// use CreateProcessW::Command;
+/// use std::sync::Arc;
+///
+/// let child = Arc::new(Command::new("notepad.exe").spawn().unwrap());
+///
+/// let waiter = {
+///     let child = Arc::clone(&child);
+///     std::thread::spawn(move || child.wait())
+/// };
+///
+/// println!("waiting on {}", child.id());
+/// child.kill().unwrap();
+///
+/// let status = waiter.join().unwrap().unwrap();
+/// ```
+pub struct Child {
+    /// The handle for writing to the standard input of the child, if it has
+    /// been [piped][Stdio::piped].
+    pub stdin: Option<ChildStdin>,
+    /// The handle for reading from the standard output of the child, if it
+    /// has been [piped][Stdio::piped].
+    pub stdout: Option<ChildStdout>,
+    /// The handle for reading from the standard error of the child, if it has
+    /// been [piped][Stdio::piped].
+    pub stderr: Option<ChildStderr>,
+    process_information: PROCESS_INFORMATION,
+    api: Arc<dyn ProcessApi>,
+    command: OsString,
+    current_directory: Option<PathBuf>,
+    exit_status: OnceLock<ExitStatus>,
+    suspend_count: AtomicU32,
+    cpu_sample: Mutex<Option<(Duration, Instant)>>,
+    warn_on_unreaped: bool,
+    registered: bool,
+    tag: Option<Arc<str>>,
+}
+
+impl Child {
+    // Create a new process and initialize it's memory. If it cannot be
+    // created, a [`CreationFailed`][Error::CreationFailed] error is returned.
+    //
+    // Equivalent to [`CreateProcessW`][create-process-w]
+    //
+    // [create-process-w]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw
+    fn new(api: Arc<dyn ProcessApi>, spec: &ProcessSpec<'_>) -> Result<Self> {
+        let process = api.create_process(spec)?;
+
+        let child = Self {
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            process_information: PROCESS_INFORMATION {
+                hProcess: HANDLE(process.process as isize),
+                hThread: HANDLE(process.thread as isize),
+                dwProcessId: process.pid,
+                dwThreadId: process.thread_id,
+            },
+            api,
+            command: spec.command_line.to_owned(),
+            current_directory: spec.current_directory.map(Path::to_path_buf),
+            exit_status: OnceLock::new(),
+            suspend_count: AtomicU32::new(0),
+            cpu_sample: Mutex::new(None),
+            warn_on_unreaped: false,
+            registered: false,
+            tag: None,
+        };
+
+        Ok(child)
+    }
+
+    /// Forces the child process to exit. If the exit status of the child has
+    /// already been collected by [`wait`][Child::wait] or
+    /// [`try_wait`][Child::try_wait], an [`AlreadyReaped`][Error::AlreadyReaped]
+    /// error is returned without calling the system. If the child has exited
+    /// otherwise, a [`KillFailed`][Error::KillFailed] error is returned.
+    ///
+    /// This function is used to unconditionally cause a process to exit and
+    /// stops execution of all threads within the process and requests
+    /// cancellation of all pending I/O. The terminated process cannot exit
+    /// until all pending I/O has been completed and canceled. When a
+    /// process terminates, its kernel object is not destroyed until all
+    /// processes that have open handles to the process have released those
+    /// handles.
+    ///
+    /// Equivalent to the [`TerminateProcess`][terminate-process] function.
+    /// Note that the value passed as the `uExitCode` is always `0` at the
+    /// moment.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let mut command = Command::new("notepad.exe");
+    ///
+    /// if let Ok(mut child) = command.spawn() {
+    ///     child.kill().expect("notepad wasn't running");
+    /// } else {
+    ///     println!("notepad didn't start");
+    /// }
+    /// ```
+    ///
+    /// [terminate-process]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-terminateprocess
+    pub fn kill(&self) -> Result<()> {
+        if self.exit_status.get().is_some() {
+            return Err(Error::AlreadyReaped);
+        }
+
+        self.api.terminate(self.raw_process_handle(), 0)
+    }
+
+    /// Waits for the child to exit completely, returning the status that it
+    /// exited with. This function will continue to have the same return value
+    /// after it has been called at least once.
+    ///
+    /// If the wait fails, a [`WaitFailed`][Error::WaitFailed] error is
+    /// returned. If the exit code cannot be retrieved, a
+    /// [`GetExitCodeFailed`][Error::GetExitCodeFailed] error is returned.
+    ///
+    /// This is equivalent to calling the
+    /// [`WaitForSingleObject`][wait-for-single-object] and the
+    /// [`GetExitCodeProcess`][get-exit-code-process] functions.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let mut command = Command::new("notepad.exe");
+    ///
+    /// if let Ok(mut child) = command.spawn() {
+    ///     child.wait().expect("command wasn't running");
+    ///     println!("Child has finished its execution!");
+    /// } else {
+    ///     println!("notepad didn't start");
+    /// }
+    /// ```
+    ///
+    /// [wait-for-single-object]: https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-waitforsingleobject
+    /// [get-exit-code-process]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getexitcodeprocess
+    pub fn wait(&self) -> Result<ExitStatus> {
+        if let Some(status) = self.exit_status.get() {
+            return Ok(*status);
+        }
+
+        // The wait doesn't time out without a timeout.
+        if !self.api.wait(self.raw_process_handle(), None)? {
+            return Err(Error::Timeout);
+        }
+
+        let exit_code = self.api.get_exit_code(self.raw_process_handle())?;
+
+        Ok(self.set_exit_status(ExitStatus(exit_code)))
+    }
+
+    /// Attempts to collect the exit status of the child if it has already
+    /// exited.
+    ///
+    /// This function will not block the calling thread and will only check to
+    /// see if the child process has exited or not.
+    ///
+    /// If the child has exited, then `Ok(Some(status))` is returned. If the
+    /// exit status is not available at this time then `Ok(None)` is returned.
+    /// If an error occurs, then that error is returned.
+    ///
+    /// Equivalent to the [`GetExitCodeProcess`][get-exit-code-process]
+    /// function.
+    ///
+    /// Once the child has exited, the status is kept and returned by the
+    /// following calls. If the function fail, a
+    /// [`GetExitCodeProcess`][Error::GetExitCodeFailed] error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let mut child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// match child.try_wait() {
+    ///     Ok(Some(status)) => println!("exited with: {}", status.code()),
+    ///     Ok(None) => {
+    ///         println!("status not ready yet, let's really wait");
+    ///         let status = child.wait().expect("cannot wait process");
+    ///         println!("waited: {}", status.code());
+    ///     }
+    ///     Err(e) => println!("error attempting to wait: {}", e),
+    /// }
+    /// ```
+    ///
+    /// [get-exit-code-process]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getexitcodeprocess
+    ///
+    pub fn try_wait(&self) -> Result<Option<ExitStatus>> {
+        if let Some(status) = self.exit_status.get() {
+            return Ok(Some(*status));
+        }
+
+        match self.api.get_exit_code(self.raw_process_handle())? {
+            exit_code if exit_code as i32 == STATUS_PENDING.0 => Ok(None),
+            exit_code => Ok(Some(self.set_exit_status(ExitStatus(exit_code)))),
+        }
+    }
+
+    /// Returns the exit status of the child if it has already been collected,
+    /// without calling the system.
+    ///
+    /// The status is collected by [`wait`][Child::wait], by
+    /// [`try_wait`][Child::try_wait] once the child has exited, by
+    /// [`wait_any`] for the child it returns, or by
+    /// [`reap_after_external_wait`][Child::reap_after_external_wait]. `None`
+    /// is returned while the child is running, and after it has exited until
+    /// its status is collected: the callbacks of [`on_exit`][Child::on_exit]
+    /// don't collect it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    /// assert_eq!(child.exit_status(), None);
+    ///
+    /// child.wait().unwrap();
+    /// assert_eq!(child.exit_status().map(|status| status.code()), Some(3));
+    /// ```
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.exit_status.get().copied()
+    }
+
+    /// Returns the handle of the child process, to wait on it with another
+    /// wait function.
+    ///
+    /// The handle is signaled once the process has exited. This allows to
+    /// include the child in an external event loop, like a call to
+    /// `MsgWaitForMultipleObjectsEx`, instead of using [`wait`][Child::wait].
+    /// Once the handle has been signaled, use
+    /// [`reap_after_external_wait`][Child::reap_after_external_wait] to
+    /// collect the exit status. The handle must not be closed, it is closed
+    /// when the `Child` is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::os::windows::io::AsRawHandle;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    /// let handle = child.wait_handle().as_raw_handle();
+    ///
+    /// // Wait on `handle` with the event loop, then:
+    /// let status = child.reap_after_external_wait().unwrap();
+    /// ```
+    pub fn wait_handle(&self) -> BorrowedHandle<'_> {
+        unsafe { BorrowedHandle::borrow_raw(self.process_information.hProcess.0 as RawHandle) }
+    }
+
+    /// Collects the exit status of the child after its
+    /// [handle][Child::wait_handle] has been signaled, without waiting.
+    ///
+    /// This is meant to be called once the caller observed the exit of the
+    /// child with its own wait function. If the process hasn't exited, a
+    /// [`StillRunning`][Error::StillRunning] error is returned instead of
+    /// blocking. Unlike [`try_wait`][Child::try_wait], the state of the
+    /// handle is checked, so a process that exited with the `STILL_ACTIVE`
+    /// (259) exit code is reaped too. Like the other wait functions, the
+    /// status is kept and the handles are closed when the `Child` is dropped.
+    ///
+    /// Equivalent to the [`GetExitCodeProcess`][get-exit-code-process]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Error};
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// match child.reap_after_external_wait() {
+    ///     Ok(status) => println!("exited with: {}", status.code()),
+    ///     Err(Error::StillRunning) => println!("notepad is still running"),
+    ///     Err(e) => println!("error attempting to reap: {}", e),
+    /// }
+    /// ```
+    ///
+    /// [get-exit-code-process]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getexitcodeprocess
+    pub fn reap_after_external_wait(&self) -> Result<ExitStatus> {
+        if let Some(status) = self.exit_status.get() {
+            return Ok(*status);
+        }
+
+        if !self.has_exited() {
+            return Err(Error::StillRunning);
+        }
+
+        let exit_code = self.api.get_exit_code(self.raw_process_handle())?;
+
+        Ok(self.set_exit_status(ExitStatus(exit_code)))
+    }
+
+    /// Returns the process identifier associated with this child.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let mut command = Command::new("notepad.exe");
+    ///
+    /// if let Ok(child) = command.spawn() {
+    ///     println!("Child's ID is {}", child.id());
+    /// } else {
+    ///     println!("notepad didn't start");
+    /// }
+    /// ```
+    pub fn id(&self) -> u32 {
+        self.process_information.dwProcessId
+    }
+
+    /// Returns the command line the child process has been spawned with.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// assert_eq!(child.command_line(), "notepad.exe");
+    /// ```
+    pub fn command_line(&self) -> &OsStr {
+        &self.command
+    }
+
+    /// Returns the working directory the child process has been spawned in,
+    /// if it has been set with [`current_dir`][Command::current_dir].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("cargo.exe check")
+    ///     .current_dir(r"C:\Users\<user>\repos\<repo_name>")
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// println!("spawned in {:?}", child.spawn_dir());
+    /// ```
+    pub fn spawn_dir(&self) -> Option<&Path> {
+        self.current_directory.as_deref()
+    }
+
+    /// Drops the child without waiting on it, on purpose.
+    ///
+    /// The handles of the process are closed but the process itself keeps
+    /// running. Unlike a plain drop, this never prints the warning enabled by
+    /// [`warn_on_unreaped`][Command::warn_on_unreaped] and the child is
+    /// removed from the [registry][Command::register].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// Command::new("notepad.exe")
+    ///     .warn_on_unreaped(true)
+    ///     .spawn()
+    ///     .expect("notepad failed to start")
+    ///     .forget();
+    /// ```
+    pub fn forget(mut self) {
+        self.warn_on_unreaped = false;
+
+        if self.registered {
+            registry::unregister(self.id());
+        }
+    }
+
+    /// Suspends all the threads of the child process.
+    ///
+    /// Suspensions are counted: every call to `suspend` must be balanced by a
+    /// call to [`resume`][Child::resume] before the process runs again. If the
+    /// child has already exited, an [`Exited`][Error::Exited] error is
+    /// returned. If the function fail, a
+    /// [`SuspendFailed`][Error::SuspendFailed] error is returned.
+    ///
+    /// Equivalent to the undocumented `NtSuspendProcess` function, which is
+    /// loaded at runtime from `ntdll.dll`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// child.suspend().expect("cannot suspend process");
+    /// std::thread::sleep(std::time::Duration::from_secs(2));
+    /// child.resume().expect("cannot resume process");
+    /// ```
+    pub fn suspend(&self) -> Result<()> {
+        if self.has_exited() {
+            return Err(Error::Exited);
+        }
+
+        unsafe {
+            call_process_function(s!("NtSuspendProcess"), self.process_information.hProcess)
+                .map_err(|code| Error::SuspendFailed(io::Error::from_raw_os_error(code as i32)))?;
+        }
+
+        self.suspend_count.fetch_add(1, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Resumes the threads of a child process suspended with
+    /// [`suspend`][Child::suspend].
+    ///
+    /// If the child isn't suspended, nothing is done and a
+    /// [`NotSuspended`][Error::NotSuspended] error is returned. If the function
+    /// fail, a [`ResumeFailed`][Error::ResumeFailed] error is returned.
+    ///
+    /// Equivalent to the undocumented `NtResumeProcess` function, which is
+    /// loaded at runtime from `ntdll.dll`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// child.suspend().expect("cannot suspend process");
+    /// child.resume().expect("cannot resume process");
+    /// ```
+    pub fn resume(&self) -> Result<()> {
+        if self
+            .suspend_count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                count.checked_sub(1)
+            })
+            .is_err()
+        {
+            return Err(Error::NotSuspended);
+        }
+
+        unsafe {
+            call_process_function(s!("NtResumeProcess"), self.process_information.hProcess).map_err(
+                |code| {
+                    self.suspend_count.fetch_add(1, Ordering::SeqCst);
+                    Error::ResumeFailed(io::Error::from_raw_os_error(code as i32))
+                },
+            )
+        }
+    }
+
+    /// Sets the priority class of the child process.
+    ///
+    /// If the child has already exited, an [`Exited`][Error::Exited] error is
+    /// returned. If the function fail, a
+    /// [`SetPriorityFailed`][Error::SetPriorityFailed] error is returned. The
+    /// process handle of a spawned child has all access rights, so it can't
+    /// lack `PROCESS_SET_INFORMATION`.
+    ///
+    /// Equivalent to the [`SetPriorityClass`][set-priority-class] function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, PriorityClass};
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// child
+    ///     .set_priority(PriorityClass::Idle)
+    ///     .expect("cannot set priority class");
+    /// ```
+    ///
+    /// [set-priority-class]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-setpriorityclass
+    pub fn set_priority(&self, priority_class: PriorityClass) -> Result<()> {
+        if self.has_exited() {
+            return Err(Error::Exited);
+        }
+
+        unsafe {
+            SetPriorityClass(self.process_information.hProcess, priority_class.to_flags())
+                .map_err(|_| Error::SetPriorityFailed(io::Error::last_os_error()))
+        }
+    }
+
+    /// Returns the current priority class of the child process.
+    ///
+    /// The priority class can be changed by other processes or by the child
+    /// itself, so it may differ from the one set when spawning the process. If
+    /// the function fail, a [`GetPriorityFailed`][Error::GetPriorityFailed]
+    /// error is returned.
+    ///
+    /// Equivalent to the [`GetPriorityClass`][get-priority-class] function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// println!("priority class: {:?}", child.priority().unwrap());
+    /// ```
+    ///
+    /// [get-priority-class]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getpriorityclass
+    pub fn priority(&self) -> Result<PriorityClass> {
+        unsafe {
+            match GetPriorityClass(self.process_information.hProcess) {
+                0 => Err(Error::GetPriorityFailed(io::Error::last_os_error())),
+                value => Ok(PriorityClass::from_flags(PROCESS_CREATION_FLAGS(value))),
+            }
+        }
+    }
+
+    /// Enables or disables the dynamic priority boost of the threads of the
+    /// child process, see [`Command::disable_priority_boost`].
+    ///
+    /// If the child has already exited, an [`Exited`][Error::Exited] error is
+    /// returned. If the function fail, a
+    /// [`SetPriorityBoostFailed`][Error::SetPriorityBoostFailed] error is
+    /// returned.
+    ///
+    /// Equivalent to the [`SetProcessPriorityBoost`][set-process-priority-boost]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("bench.exe").spawn().unwrap();
+    ///
+    /// child
+    ///     .set_priority_boost(false)
+    ///     .expect("cannot disable priority boost");
+    /// assert!(!child.priority_boost().unwrap());
+    /// ```
+    ///
+    /// [set-process-priority-boost]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-setprocesspriorityboost
+    pub fn set_priority_boost(&self, enabled: bool) -> Result<()> {
+        if self.has_exited() {
+            return Err(Error::Exited);
+        }
+
+        unsafe {
+            SetProcessPriorityBoost(self.process_information.hProcess, !enabled)
+                .map_err(|_| Error::SetPriorityBoostFailed(io::Error::last_os_error()))
+        }
+    }
+
+    /// Returns `true` if the dynamic priority boost of the threads of the
+    /// child process is enabled, which is the default.
+    ///
+    /// If the function fail, a
+    /// [`GetPriorityBoostFailed`][Error::GetPriorityBoostFailed] error is
+    /// returned.
+    ///
+    /// Equivalent to the [`GetProcessPriorityBoost`][get-process-priority-boost]
+    /// function.
+    ///
+    /// [get-process-priority-boost]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getprocesspriorityboost
+    pub fn priority_boost(&self) -> Result<bool> {
+        let mut disabled = BOOL(0);
+
+        unsafe {
+            GetProcessPriorityBoost(self.process_information.hProcess, &mut disabled)
+                .map_err(|_| Error::GetPriorityBoostFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok(!disabled.as_bool())
+    }
+
+    /// Enables or disables the efficiency mode of the child process.
+    ///
+    /// In efficiency mode, also called EcoQoS, the system runs the threads of
+    /// the child at a lower clock speed, or on the efficiency cores of the
+    /// processor, to save power. Disabling it gives the choice back to the
+    /// system, like for any other process, instead of forcing the high
+    /// quality of service. This is available since Windows 10 version
+    /// 1709, an [`Unsupported`][Error::Unsupported] error is returned on older
+    /// versions. If the child has already exited, an
+    /// [`Exited`][Error::Exited] error is returned. If the function fail, a
+    /// [`SetEfficiencyModeFailed`][Error::SetEfficiencyModeFailed] error is
+    /// returned.
+    ///
+    /// Equivalent to the [`SetProcessInformation`][set-process-information]
+    /// function with the `ProcessPowerThrottling` class and the
+    /// `PROCESS_POWER_THROTTLING_EXECUTION_SPEED` flag, which is loaded at
+    /// runtime from `kernel32.dll`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Error};
+    ///
+    /// let child = Command::new("indexer.exe").spawn().unwrap();
+    ///
+    /// match child.set_efficiency_mode(true) {
+    ///     Ok(()) | Err(Error::Unsupported(_)) => {}
+    ///     Err(err) => panic!("cannot enable efficiency mode: {}", err),
+    /// }
+    /// ```
+    ///
+    /// [set-process-information]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-setprocessinformation
+    pub fn set_efficiency_mode(&self, enabled: bool) -> Result<()> {
+        // `ProcessPowerThrottling`.
+        const PROCESS_POWER_THROTTLING: i32 = 4;
+
+        if self.has_exited() {
+            return Err(Error::Exited);
+        }
+
+        let state = power_throttling_state(enabled);
+
+        unsafe {
+            let function = load_function(w!("kernel32.dll"), s!("SetProcessInformation"))
+                .map_err(|_| Error::Unsupported("SetProcessInformation"))?;
+            let function: unsafe extern "system" fn(HANDLE, i32, *const c_void, u32) -> BOOL =
+                std::mem::transmute(function);
+
+            if function(
+                self.process_information.hProcess,
+                PROCESS_POWER_THROTTLING,
+                state.as_ptr() as *const c_void,
+                size_of::<[u32; 3]>() as u32,
+            )
+            .as_bool()
+            {
+                return Ok(());
+            }
+
+            Err(efficiency_mode_error(GetLastError()))
+        }
+    }
+
+    /// Sets the processors on which the threads of the child process are
+    /// allowed to run.
+    ///
+    /// Each bit of the mask represents a logical processor. The mask must be a
+    /// subset of the system affinity mask returned by
+    /// [`affinity`][Child::affinity], otherwise an
+    /// [`InvalidInput`][Error::InvalidInput] error is returned without calling
+    /// the system, as it is for a zero mask. If the child has already exited,
+    /// an [`Exited`][Error::Exited] error is returned. If the function fail, a
+    /// [`SetAffinityFailed`][Error::SetAffinityFailed] error is returned.
+    ///
+    /// Equivalent to the [`SetProcessAffinityMask`][set-process-affinity-mask]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// // Only run on the first logical processor.
+    /// child.set_affinity(0b1).expect("cannot set affinity");
+    /// ```
+    ///
+    /// [set-process-affinity-mask]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-setprocessaffinitymask
+    pub fn set_affinity(&self, mask: usize) -> Result<()> {
+        if mask == 0 {
+            return Err(Error::InvalidInput("affinity mask cannot be zero"));
+        }
+
+        if self.has_exited() {
+            return Err(Error::Exited);
+        }
+
+        let (_, system_mask) = self.affinity()?;
+
+        if mask & !system_mask != 0 {
+            return Err(Error::InvalidInput(
+                "affinity mask contains processors that aren't available",
+            ));
+        }
+
+        unsafe {
+            SetProcessAffinityMask(self.process_information.hProcess, mask)
+                .map_err(|_| Error::SetAffinityFailed(io::Error::last_os_error()))
+        }
+    }
+
+    /// Returns the affinity mask of the child process and the affinity mask of
+    /// the system, in that order.
+    ///
+    /// If the function fail, a [`GetAffinityFailed`][Error::GetAffinityFailed]
+    /// error is returned.
+    ///
+    /// Equivalent to the [`GetProcessAffinityMask`][get-process-affinity-mask]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// let (process_mask, system_mask) = child.affinity().unwrap();
+    /// println!("process: {:#b}, system: {:#b}", process_mask, system_mask);
+    /// ```
+    ///
+    /// [get-process-affinity-mask]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-getprocessaffinitymask
+    pub fn affinity(&self) -> Result<(usize, usize)> {
+        let mut process_mask: usize = 0;
+        let mut system_mask: usize = 0;
+
+        unsafe {
+            GetProcessAffinityMask(
+                self.process_information.hProcess,
+                &mut process_mask as *mut usize,
+                &mut system_mask as *mut usize,
+            )
+            .map_err(|_| Error::GetAffinityFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok((process_mask, system_mask))
+    }
+
+    /// Returns timing information about the child process.
+    ///
+    /// The exit time is only available once the process has exited. This
+    /// function can still be called after [`wait`][Child::wait] since the
+    /// handles of the process are kept open until the `Child` is dropped. If
+    /// the function fail, a
+    /// [`GetProcessTimesFailed`][Error::GetProcessTimesFailed] error is
+    /// returned.
+    ///
+    /// Equivalent to the [`GetProcessTimes`][get-process-times] function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("cargo.exe build").spawn().unwrap();
+    /// child.wait().unwrap();
+    ///
+    /// let times = child.process_times().unwrap();
+    /// println!("user time: {:?}", times.user_time);
+    /// ```
+    ///
+    /// [get-process-times]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getprocesstimes
+    pub fn process_times(&self) -> Result<ProcessTimes> {
+        let mut creation_time = FILETIME::default();
+        let mut exit_time = FILETIME::default();
+        let mut kernel_time = FILETIME::default();
+        let mut user_time = FILETIME::default();
+
+        unsafe {
+            GetProcessTimes(
+                self.process_information.hProcess,
+                &mut creation_time,
+                &mut exit_time,
+                &mut kernel_time,
+                &mut user_time,
+            )
+            .map_err(|_| Error::GetProcessTimesFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok(ProcessTimes {
+            creation_time: filetime_to_system_time(creation_time),
+            exit_time: self
+                .has_exited()
+                .then(|| filetime_to_system_time(exit_time)),
+            kernel_time: filetime_to_duration(kernel_time),
+            user_time: filetime_to_duration(user_time),
+        })
+    }
+
+    /// Returns memory usage information about the child process.
+    ///
+    /// If the function fail, a
+    /// [`GetMemoryInfoFailed`][Error::GetMemoryInfoFailed] error is returned.
+    ///
+    /// Equivalent to the [`GetProcessMemoryInfo`][get-process-memory-info]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// let memory_info = child.memory_info().unwrap();
+    ///
+    /// if memory_info.working_set_size > 512 * 1024 * 1024 {
+    ///     child.kill().unwrap();
+    /// }
+    /// ```
+    ///
+    /// [get-process-memory-info]: https://docs.microsoft.com/en-us/windows/win32/api/psapi/nf-psapi-getprocessmemoryinfo
+    pub fn memory_info(&self) -> Result<MemoryInfo> {
+        let mut counters = PROCESS_MEMORY_COUNTERS_EX {
+            cb: size_of::<PROCESS_MEMORY_COUNTERS_EX>() as u32,
+            ..Default::default()
+        };
+
+        let res = unsafe {
+            K32GetProcessMemoryInfo(
+                self.process_information.hProcess,
+                &mut counters as *mut PROCESS_MEMORY_COUNTERS_EX as *mut PROCESS_MEMORY_COUNTERS,
+                counters.cb,
+            )
+        };
+
+        if res.as_bool() {
+            Ok(MemoryInfo {
+                working_set_size: counters.WorkingSetSize as u64,
+                peak_working_set_size: counters.PeakWorkingSetSize as u64,
+                private_usage: counters.PrivateUsage as u64,
+                page_fault_count: u64::from(counters.PageFaultCount),
+            })
+        } else {
+            Err(Error::GetMemoryInfoFailed(io::Error::last_os_error()))
+        }
+    }
+
+    /// Sets the minimum and maximum working set sizes of the child process,
+    /// in bytes.
+    ///
+    /// The working set is the memory of the child that is resident in
+    /// physical memory. By default, the limits are soft: the system keeps the
+    /// working set between them when memory is scarce, but lets it grow above
+    /// the maximum otherwise. If `hard` is `true`, the working set never grows
+    /// above the maximum, the child pages its own memory out instead.
+    ///
+    /// The minimum must be non-zero and at most the maximum, which must be at
+    /// least 13 pages, otherwise an [`InvalidInput`][Error::InvalidInput]
+    /// error is returned without calling the system. If the child has already
+    /// exited, an [`Exited`][Error::Exited] error is returned. If the function
+    /// fail, a [`SetWorkingSetFailed`][Error::SetWorkingSetFailed] error is
+    /// returned, like when the minimum is above the available memory.
+    ///
+    /// Equivalent to the
+    /// [`SetProcessWorkingSetSizeEx`][set-process-working-set-size-ex]
+    /// function, with the `QUOTA_LIMITS_HARDWS_MAX_ENABLE` flag if `hard` is
+    /// `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("indexer.exe").spawn().unwrap();
+    ///
+    /// child.set_working_set(1 << 20, 256 << 20, true).unwrap();
+    /// assert!(child.working_set().unwrap().hard_max);
+    /// ```
+    ///
+    /// [set-process-working-set-size-ex]: https://docs.microsoft.com/en-us/windows/win32/api/memoryapi/nf-memoryapi-setprocessworkingsetsizeex
+    pub fn set_working_set(&self, min_bytes: usize, max_bytes: usize, hard: bool) -> Result<()> {
+        if let Some(problem) = validation::check_working_set(min_bytes, max_bytes) {
+            return Err(Error::InvalidInput(problem));
+        }
+
+        if self.has_exited() {
+            return Err(Error::Exited);
+        }
+
+        let flags = QUOTA_LIMITS_HARDWS_MIN_DISABLE
+            | if hard {
+                QUOTA_LIMITS_HARDWS_MAX_ENABLE
+            } else {
+                QUOTA_LIMITS_HARDWS_MAX_DISABLE
+            };
+
+        unsafe {
+            SetProcessWorkingSetSizeEx(
+                self.process_information.hProcess,
+                min_bytes,
+                max_bytes,
+                flags,
+            )
+            .map_err(|_| Error::SetWorkingSetFailed(io::Error::last_os_error()))
+        }
+    }
+
+    /// Returns the working set limits of the child process.
+    ///
+    /// If the function fail, a
+    /// [`GetWorkingSetFailed`][Error::GetWorkingSetFailed] error is returned.
+    ///
+    /// Equivalent to the
+    /// [`GetProcessWorkingSetSizeEx`][get-process-working-set-size-ex]
+    /// function.
+    ///
+    /// [get-process-working-set-size-ex]: https://docs.microsoft.com/en-us/windows/win32/api/memoryapi/nf-memoryapi-getprocessworkingsetsizeex
+    pub fn working_set(&self) -> Result<WorkingSetLimits> {
+        let mut min_bytes = 0;
+        let mut max_bytes = 0;
+        let mut flags = 0;
+
+        unsafe {
+            GetProcessWorkingSetSizeEx(
+                self.process_information.hProcess,
+                &mut min_bytes,
+                &mut max_bytes,
+                &mut flags,
+            )
+            .map_err(|_| Error::GetWorkingSetFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok(WorkingSetLimits {
+            min_bytes,
+            max_bytes,
+            hard_min: flags & QUOTA_LIMITS_HARDWS_MIN_ENABLE.0 != 0,
+            hard_max: flags & QUOTA_LIMITS_HARDWS_MAX_ENABLE.0 != 0,
+        })
+    }
+
+    /// Returns I/O statistics of the child process.
+    ///
+    /// This can be called on a running child as well as on an exited one. If
+    /// the function fail, a [`GetIoCountersFailed`][Error::GetIoCountersFailed]
+    /// error is returned.
+    ///
+    /// Equivalent to the [`GetProcessIoCounters`][get-process-io-counters]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("cargo.exe build").spawn().unwrap();
+    /// child.wait().unwrap();
+    ///
+    /// let io_counters = child.io_counters().unwrap();
+    /// println!("{} bytes written", io_counters.write_bytes);
+    /// ```
+    ///
+    /// [get-process-io-counters]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-getprocessiocounters
+    pub fn io_counters(&self) -> Result<IoCounters> {
+        let mut counters = IO_COUNTERS::default();
+
+        unsafe {
+            GetProcessIoCounters(self.process_information.hProcess, &mut counters)
+                .map_err(|_| Error::GetIoCountersFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok(IoCounters {
+            read_operations: counters.ReadOperationCount,
+            write_operations: counters.WriteOperationCount,
+            other_operations: counters.OtherOperationCount,
+            read_bytes: counters.ReadTransferCount,
+            write_bytes: counters.WriteTransferCount,
+            other_bytes: counters.OtherTransferCount,
+        })
+    }
+
+    /// Returns the number of handles opened by the child process.
+    ///
+    /// A count that keeps growing while the child runs is the sign of a
+    /// handle leak. If the function fails, a
+    /// [`GetHandleCountFailed`][Error::GetHandleCountFailed] error is returned.
+    ///
+    /// Equivalent to the [`GetProcessHandleCount`][get-process-handle-count]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("server.exe").spawn().unwrap();
+    ///
+    /// println!("{} handles", child.handle_count().unwrap());
+    /// ```
+    ///
+    /// [get-process-handle-count]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getprocesshandlecount
+    pub fn handle_count(&self) -> Result<u32> {
+        let mut count = 0;
+
+        unsafe {
+            GetProcessHandleCount(self.process_information.hProcess, &mut count)
+                .map_err(|_| Error::GetHandleCountFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok(count)
+    }
+
+    /// Returns the identifier of the Remote Desktop Services session the
+    /// child process runs in, see [`session_of_pid`].
+    ///
+    /// The session of an exited child can be returned as long as its handle
+    /// is open, since its identifier cannot be reused meanwhile.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{session_of_pid, Command};
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// assert_eq!(
+    ///     child.session_id().unwrap(),
+    ///     session_of_pid(std::process::id()).unwrap()
+    /// );
+    /// ```
+    pub fn session_id(&self) -> Result<u32> {
+        session_of_pid(self.id())
+    }
+
+    /// Returns the handle count, the memory usage and the CPU usage of the
+    /// child process at once, for a dashboard sampling it periodically.
+    ///
+    /// The CPU usage is the one of [`cpu_usage`][Child::cpu_usage] since the
+    /// previous sample, it is `0.0` for the first one. The first error of the
+    /// underlying functions is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::time::Duration;
+    ///
+    /// let child = Command::new("server.exe").spawn().unwrap();
+    ///
+    /// while child.try_wait().unwrap().is_none() {
+    ///     let health = child.sample_health().unwrap();
+    ///     println!(
+    ///         "{} handles, {} bytes, {:.1}% CPU",
+    ///         health.handle_count, health.memory.private_usage, health.cpu_usage
+    ///     );
+    ///
+    ///     std::thread::sleep(Duration::from_secs(10));
+    /// }
+    /// ```
+    pub fn sample_health(&self) -> Result<HealthSample> {
+        Ok(HealthSample {
+            handle_count: self.handle_count()?,
+            memory: self.memory_info()?,
+            cpu_usage: self.cpu_usage(false)?,
+        })
+    }
+
+    /// Returns the number of GDI and USER objects used by the child process,
+    /// and their peak.
+    ///
+    /// A process is limited to 10,000 objects of each kind by default, a child
+    /// leaking them eventually fails to paint its windows. The counts of a
+    /// console process, which has no GUI objects, are zero. This can be called
+    /// on a running child, the counts of an exited child are zero. If the
+    /// function fails, a [`GetGuiResourcesFailed`][Error::GetGuiResourcesFailed]
+    /// error is returned.
+    ///
+    /// Equivalent to the [`GetGuiResources`][get-gui-resources] function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("mspaint.exe").spawn().unwrap();
+    ///
+    /// let resources = child.gui_resources().unwrap();
+    /// println!(
+    ///     "{} GDI objects (peak {})",
+    ///     resources.gdi_objects, resources.gdi_peak
+    /// );
+    /// ```
+    ///
+    /// [get-gui-resources]: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getguiresources
+    pub fn gui_resources(&self) -> Result<GuiResources> {
+        // Zero is also the count of a process without GUI objects, the error
+        // is told apart by the last error code.
+        let count = |flags| unsafe {
+            SetLastError(WIN32_ERROR(0));
+
+            match GetGuiResources(self.process_information.hProcess, flags) {
+                0 => match GetLastError().0 {
+                    0 => Ok(0),
+                    code => Err(Error::GetGuiResourcesFailed(io::Error::from_raw_os_error(
+                        code as i32,
+                    ))),
+                },
+                count => Ok(count),
+            }
+        };
+
+        Ok(GuiResources {
+            gdi_objects: count(GR_GDIOBJECTS)?,
+            user_objects: count(GR_USEROBJECTS)?,
+            gdi_peak: count(GR_GDIOBJECTS_PEAK)?,
+            user_peak: count(GR_USEROBJECTS_PEAK)?,
+        })
+    }
+
+    /// Returns the CPU usage of the child process since the previous call, in
+    /// percent.
+    ///
+    /// The usage is computed from the kernel and user times returned by
+    /// [`process_times`][Child::process_times]. The previous sample is kept in
+    /// the `Child`, the first call returns `0.0` since there is nothing to
+    /// compare with. By default, `100.0` means that one logical processor has
+    /// been fully used. If `all_processors` is `true`, the usage is normalized
+    /// over all the logical processors of the system, like Task Manager does.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("cargo.exe build").spawn().unwrap();
+    ///
+    /// child.cpu_usage(false).unwrap();
+    ///
+    /// while child.try_wait().unwrap().is_none() {
+    ///     std::thread::sleep(std::time::Duration::from_secs(1));
+    ///     println!("CPU usage: {:.1}%", child.cpu_usage(false).unwrap());
+    /// }
+    /// ```
+    pub fn cpu_usage(&self, all_processors: bool) -> Result<f32> {
+        let times = self.process_times()?;
+        let cpu_time = times.kernel_time + times.user_time;
+        let now = Instant::now();
+
+        let previous = self
+            .cpu_sample
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .replace((cpu_time, now));
+
+        let (previous_cpu_time, previous_instant) = match previous {
+            Some(sample) => sample,
+            None => return Ok(0.0),
+        };
+
+        let elapsed = now.duration_since(previous_instant).as_secs_f32();
+
+        if elapsed == 0.0 {
+            return Ok(0.0);
+        }
+
+        let mut usage = cpu_time.saturating_sub(previous_cpu_time).as_secs_f32() / elapsed * 100.0;
+
+        if all_processors {
+            let processors = unsafe { GetActiveProcessorCount(ALL_PROCESSOR_GROUPS) }.max(1);
+            usage /= processors as f32;
+        }
+
+        Ok(usage)
+    }
+
+    /// Returns the architecture the child process is running as.
+    ///
+    /// This makes it possible to detect a 32-bit process running under WOW64
+    /// on a 64-bit system. It works right after spawning, including for
+    /// suspended processes. If the function fail, a
+    /// [`GetArchitectureFailed`][Error::GetArchitectureFailed] error is
+    /// returned.
+    ///
+    /// Equivalent to the [`IsWow64Process2`][is-wow64-process-2] function. On
+    /// versions of Windows where this function isn't available, the
+    /// [`IsWow64Process`][is-wow64-process] function is used instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, ProcessArch};
+    ///
+    /// let child = Command::new(r"C:\Windows\SysWOW64\cmd.exe /c pause")
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(child.architecture().unwrap(), ProcessArch::X86);
+    /// ```
+    ///
+    /// [is-wow64-process-2]: https://docs.microsoft.com/en-us/windows/win32/api/wow64apiset/nf-wow64apiset-iswow64process2
+    /// [is-wow64-process]: https://docs.microsoft.com/en-us/windows/win32/api/wow64apiset/nf-wow64apiset-iswow64process
+    pub fn architecture(&self) -> Result<ProcessArch> {
+        unsafe {
+            if let Ok(function) = load_function(w!("kernel32.dll"), s!("IsWow64Process2")) {
+                let function: unsafe extern "system" fn(HANDLE, *mut u16, *mut u16) -> BOOL =
+                    std::mem::transmute(function);
+
+                let mut process_machine: u16 = 0;
+                let mut native_machine: u16 = 0;
+
+                if !function(
+                    self.process_information.hProcess,
+                    &mut process_machine,
+                    &mut native_machine,
+                )
+                .as_bool()
+                {
+                    return Err(Error::GetArchitectureFailed(io::Error::last_os_error()));
+                }
+
+                // The process machine is unknown when the process doesn't run
+                // under WOW64, the process runs as the native machine.
+                if process_machine == IMAGE_FILE_MACHINE_UNKNOWN {
+                    Ok(ProcessArch::from_machine(native_machine))
+                } else {
+                    Ok(ProcessArch::from_machine(process_machine))
+                }
+            } else {
+                // Without `IsWow64Process2`, the system is either x86 or x64.
+                if is_wow64(self.process_information.hProcess)? {
+                    Ok(ProcessArch::X86)
+                } else if cfg!(target_pointer_width = "64") || is_wow64(GetCurrentProcess())? {
+                    Ok(ProcessArch::X64)
+                } else {
+                    Ok(ProcessArch::X86)
+                }
+            }
+        }
+    }
+
+    /// Returns the full path of the executable of the child process.
+    ///
+    /// This is the binary that actually ended up running after the command has
+    /// been resolved, in the Win32 path format (like
+    /// `C:\Windows\System32\notepad.exe`). If the function fail, a
+    /// [`GetProgramPathFailed`][Error::GetProgramPathFailed] error is
+    /// returned.
+    ///
+    /// Equivalent to the
+    /// [`QueryFullProcessImageNameW`][query-full-process-image-name-w]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad").spawn().unwrap();
+    ///
+    /// println!("running {}", child.program_path().unwrap().display());
+    /// ```
+    ///
+    /// [query-full-process-image-name-w]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-queryfullprocessimagenamew
+    pub fn program_path(&self) -> Result<PathBuf> {
+        self.query_program_path(PROCESS_NAME_WIN32)
+    }
+
+    /// Returns the full path of the executable of the child process, in the
+    /// native path format (like
+    /// `\Device\HarddiskVolume1\Windows\System32\notepad.exe`).
+    ///
+    /// See [`program_path`][Child::program_path] for more information.
+    pub fn native_program_path(&self) -> Result<PathBuf> {
+        self.query_program_path(PROCESS_NAME_NATIVE)
+    }
+
+    fn query_program_path(&self, format: PROCESS_NAME_FORMAT) -> Result<PathBuf> {
+        // Start with `MAX_PATH` and grow the buffer until the path fits, up to
+        // the maximum length of an extended-length path.
+        let mut buffer = vec![0u16; 260];
+
+        loop {
+            let mut size = buffer.len() as u32;
+
+            let res = unsafe {
+                QueryFullProcessImageNameW(
+                    self.process_information.hProcess,
+                    format,
+                    PWSTR(buffer.as_mut_ptr()),
+                    &mut size,
+                )
+            };
+
+            match res {
+                Ok(()) => return Ok(PathBuf::from(OsString::from_wide(&buffer[..size as usize]))),
+                Err(_) => {
+                    let code = unsafe { GetLastError() };
+
+                    if code == ERROR_INSUFFICIENT_BUFFER && buffer.len() < 32768 {
+                        buffer.resize(buffer.len() * 2, 0);
+                    } else {
+                        return Err(Error::GetProgramPathFailed(io::Error::from_raw_os_error(
+                            code.0 as i32,
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Duplicates the process handle of the child into another process,
+    /// returning the value of the new handle in the handle table of the
+    /// target.
+    ///
+    /// `target` is a handle to the target process, which needs the
+    /// `PROCESS_DUP_HANDLE` access right. By default the new handle has the
+    /// same access rights as the handle of the child, `access` can be used to
+    /// request specific rights instead. If the function fail, a
+    /// [`DuplicateHandleFailed`][Error::DuplicateHandleFailed] error is
+    /// returned.
+    ///
+    /// The returned value is only meaningful in the target process: it must
+    /// not be used nor closed in the calling process. The target process owns
+    /// the new handle and is responsible for closing it, it is otherwise
+    /// closed when the target process exits. The value has to be sent to the
+    /// target process by other means, like its command line or a pipe.
+    ///
+    /// Equivalent to the [`DuplicateHandle`][duplicate-handle] function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::os::windows::io::AsHandle;
+    ///
+    /// let worker = Command::new("worker.exe").spawn().unwrap();
+    /// let watchdog = std::process::Command::new("watchdog.exe").spawn().unwrap();
+    ///
+    /// let handle = worker
+    ///     .duplicate_handle_to(watchdog.as_handle(), None, false)
+    ///     .unwrap();
+    ///
+    /// println!("the watchdog can wait on {:?}", handle);
+    /// ```
+    ///
+    /// [duplicate-handle]: https://docs.microsoft.com/en-us/windows/win32/api/handleapi/nf-handleapi-duplicatehandle
+    pub fn duplicate_handle_to(
+        &self,
+        target: BorrowedHandle<'_>,
+        access: Option<u32>,
+        inheritable: bool,
+    ) -> Result<RawHandle> {
+        let mut target_handle = HANDLE::default();
+
+        let options = if access.is_some() {
+            DUPLICATE_HANDLE_OPTIONS(0)
+        } else {
+            DUPLICATE_SAME_ACCESS
+        };
+
+        unsafe {
+            DuplicateHandle(
+                GetCurrentProcess(),
+                self.process_information.hProcess,
+                HANDLE(target.as_raw_handle() as isize),
+                &mut target_handle,
+                access.unwrap_or(0),
+                inheritable,
+                options,
+            )
+            .map_err(|_| Error::DuplicateHandleFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok(target_handle.0 as RawHandle)
+    }
+
+    /// Hands the child to the system thread pool, which closes its handles as
+    /// soon as the process exits.
+    ///
+    /// This is useful for short-lived children whose exit status doesn't
+    /// matter: the handles are released promptly without blocking a thread
+    /// per child. The thread pool is created lazily by the system and handles
+    /// any number of children. If the wait cannot be registered, a
+    /// [`RegisterWaitFailed`][Error::RegisterWaitFailed] error is returned
+    /// and the handles are closed right away.
+    ///
+    /// Requires the `reaper` feature.
+    ///
+    /// Equivalent to the [`RegisterWaitForSingleObject`][register-wait-for-single-object]
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// for _ in 0..100 {
+    ///     Command::new("cmd.exe /c exit")
+    ///         .spawn()
+    ///         .unwrap()
+    ///         .reap_in_background()
+    ///         .unwrap();
+    /// }
+    /// ```
+    ///
+    /// [register-wait-for-single-object]: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-registerwaitforsingleobject
+    #[cfg(feature = "reaper")]
+    pub fn reap_in_background(self) -> Result<()> {
+        self.reap_in_background_with(|_| ())
+    }
+
+    /// Hands the child to the system thread pool like
+    /// [`reap_in_background`][Child::reap_in_background], calling `callback`
+    /// with the exit status once the process has exited.
+    ///
+    /// The callback runs on a thread of the thread pool, it should return
+    /// quickly.
+    ///
+    /// Requires the `reaper` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// Command::new("cargo.exe check")
+    ///     .spawn()
+    ///     .unwrap()
+    ///     .reap_in_background_with(|status| println!("cargo check exited: {:?}", status))
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "reaper")]
+    pub fn reap_in_background_with(
+        self,
+        callback: impl FnOnce(Result<ExitStatus>) + Send + 'static,
+    ) -> Result<()> {
+        let process = self.process_information.hProcess;
+
+        exit_wait::ExitWait::register(process, move |_| {
+            let status = self.wait();
+            // Close the handles before calling back.
+            drop(self);
+            callback(status);
+        })?;
+
+        Ok(())
+    }
+
+    // Keep the exit status of the process once it has been collected,
+    // returning the first status that has been kept.
+    fn set_exit_status(&self, status: ExitStatus) -> ExitStatus {
+        let mut first = false;
+        let status = *self.exit_status.get_or_init(|| {
+            first = true;
+            status
+        });
+
+        if first {
+            metrics::exited(self);
+
+            if self.registered {
+                registry::unregister(self.id());
+            }
+        }
+
+        status
+    }
+
+    // Check if the process has exited without collecting its exit status.
+    fn has_exited(&self) -> bool {
+        self.exit_status.get().is_some()
+            || matches!(
+                self.api
+                    .wait(self.raw_process_handle(), Some(Duration::ZERO)),
+                Ok(true)
+            )
+    }
+
+    /// Returns the tag of the child, set with [`Command::tag`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("cargo.exe doc").tag("build-docs").spawn().unwrap();
+    ///
+    /// assert_eq!(child.tag(), Some("build-docs"));
+    /// ```
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    fn raw_process_handle(&self) -> RawHandle {
+        self.process_information.hProcess.0 as RawHandle
+    }
+}
+
+impl fmt::Debug for Child {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Child")
+            .field("pid", &self.process_information.dwProcessId)
+            .field("thread_id", &self.process_information.dwThreadId)
+            .field("reaped", &self.exit_status.get().is_some())
+            .field("exit_status", &self.exit_status.get())
+            .field("command", &truncate(&self.command.to_string_lossy(), 64))
+            .field("spawn_dir", &self.current_directory)
+            .field("tag", &self.tag)
+            .finish()
+    }
+}
+
+// Truncate a string to `max_chars` characters for display purposes.
+fn truncate(s: &str, max_chars: usize) -> String {
+    match s.char_indices().nth(max_chars) {
+        Some((index, _)) => format!("{}...", &s[..index]),
+        None => s.to_string(),
+    }
+}
+
+// `Child` is meant to be shared between threads, make sure it stays `Send` and
+// `Sync` when its fields change.
+fn _assert_child_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Child>();
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        if self.warn_on_unreaped && self.exit_status.get().is_none() {
+            eprintln!(
+                "warning: child process {} ({}) dropped without being waited on",
+                self.id(),
+                truncate(&self.command.to_string_lossy(), 64),
+            );
+            metrics::unreaped(self);
+        }
+
+        if self.exit_status.get().is_none() {
+            metrics::dropped(self);
+        }
+
+        self.api.close_handle(self.raw_process_handle());
+
+        // A reattached or elevated child has no thread handle.
+        if !self.process_information.hThread.is_invalid() {
+            self.api
+                .close_handle(self.process_information.hThread.0 as RawHandle);
+        }
+    }
+}
+
+// Run `f` with the error dialogs suppressed in the error mode of the calling
+// process, so a child created by `f` inherits it. The error mode is restored
+// afterwards, and the changes are serialized since the error mode is shared by
+// the threads of the process.
+fn with_error_dialogs_suppressed<T>(f: impl FnOnce() -> T) -> T {
+    static ERROR_MODE: Mutex<()> = Mutex::new(());
+
+    let _guard = ERROR_MODE.lock().unwrap_or_else(|err| err.into_inner());
+
+    unsafe {
+        let previous = GetErrorMode();
+
+        SetErrorMode(THREAD_ERROR_MODE(previous) | SEM_FAILCRITICALERRORS | SEM_NOGPFAULTERRORBOX);
+
+        let res = f();
+
+        SetErrorMode(THREAD_ERROR_MODE(previous));
+
+        res
+    }
+}
+
+// Load a function of a module that is already loaded in the process, like
+// `ntdll.dll` or `kernel32.dll`. This is used for functions that are
+// undocumented or not available on every version of Windows, so they are
+// loaded at runtime instead of being linked.
+unsafe fn load_function(
+    module: PCWSTR,
+    name: PCSTR,
+) -> std::result::Result<unsafe extern "system" fn() -> isize, u32> {
+    let module = GetModuleHandleW(module).map_err(|_| GetLastError().0)?;
+    GetProcAddress(module, name).ok_or_else(|| GetLastError().0)
+}
+
+// Call a function of `ntdll.dll` that takes a process handle and returns a
+// `NTSTATUS`, like `NtSuspendProcess`. On failure, the `NTSTATUS` is converted
+// to a system error code.
+unsafe fn call_process_function(name: PCSTR, process: HANDLE) -> std::result::Result<(), u32> {
+    let function = load_function(w!("ntdll.dll"), name)?;
+    let function: unsafe extern "system" fn(HANDLE) -> NTSTATUS = std::mem::transmute(function);
+
+    let status = function(process);
+
+    if status.0 >= 0 {
+        Ok(())
+    } else {
+        Err(RtlNtStatusToDosError(status))
+    }
+}
+
+unsafe fn is_wow64(process: HANDLE) -> Result<bool> {
+    let mut wow64 = BOOL::default();
+
+    IsWow64Process(process, &mut wow64)
+        .map_err(|_| Error::GetArchitectureFailed(io::Error::last_os_error()))?;
+
+    Ok(wow64.as_bool())
+}
+
+const IMAGE_FILE_MACHINE_UNKNOWN: u16 = 0;
+const IMAGE_FILE_MACHINE_I386: u16 = 0x014c;
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+
+/// The architecture a process is running as.
+///
+/// Returned by [`Child::architecture`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessArch {
+    /// 32-bit x86, natively or under WOW64.
+    X86,
+    /// 64-bit x86.
+    X64,
+    /// 64-bit ARM.
+    Arm64,
+    /// Any other architecture.
+    Unknown,
+}
+
+impl ProcessArch {
+    fn from_machine(machine: u16) -> Self {
+        match machine {
+            IMAGE_FILE_MACHINE_I386 => Self::X86,
+            IMAGE_FILE_MACHINE_AMD64 => Self::X64,
+            IMAGE_FILE_MACHINE_ARM64 => Self::Arm64,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+// The `PROCESS_POWER_THROTTLING_STATE` (version, control mask and state mask)
+// of `Child::set_efficiency_mode`. When disabling, the execution speed is left
+// out of the control mask so the system manages it again, a state mask
+// without it would force the high quality of service.
+fn power_throttling_state(enabled: bool) -> [u32; 3] {
+    const PROCESS_POWER_THROTTLING_CURRENT_VERSION: u32 = 1;
+    const PROCESS_POWER_THROTTLING_EXECUTION_SPEED: u32 = 0x1;
+
+    if enabled {
+        [
+            PROCESS_POWER_THROTTLING_CURRENT_VERSION,
+            PROCESS_POWER_THROTTLING_EXECUTION_SPEED,
+            PROCESS_POWER_THROTTLING_EXECUTION_SPEED,
+        ]
+    } else {
+        [PROCESS_POWER_THROTTLING_CURRENT_VERSION, 0, 0]
+    }
+}
+
+// The error of a failed `SetProcessInformation` call of
+// `Child::set_efficiency_mode`.
+fn efficiency_mode_error(code: WIN32_ERROR) -> Error {
+    match code {
+        // The information class is unknown to the older versions.
+        ERROR_INVALID_PARAMETER => Error::Unsupported("ProcessPowerThrottling"),
+        code => Error::SetEfficiencyModeFailed(io::Error::from_raw_os_error(code.0 as i32)),
+    }
+}
+
+// Number of 100-nanosecond intervals between the Windows epoch (1601-01-01)
+// and the Unix epoch (1970-01-01).
+const WINDOWS_TO_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+fn filetime_to_intervals(filetime: FILETIME) -> u64 {
+    (u64::from(filetime.dwHighDateTime) << 32) | u64::from(filetime.dwLowDateTime)
 }
 
-unsafe fn close_handles(process_info: &PROCESS_INFORMATION) {
-    let _ = CloseHandle(process_info.hProcess);
-    let _ = CloseHandle(process_info.hThread);
+// Convert a number of 100-nanosecond intervals, split in seconds and
+// nanoseconds so the conversion cannot overflow.
+fn intervals_to_duration(intervals: u64) -> Duration {
+    Duration::new(
+        intervals / 10_000_000,
+        (intervals % 10_000_000) as u32 * 100,
+    )
+}
+
+// Convert a `FILETIME` holding a duration (in 100-nanosecond intervals).
+fn filetime_to_duration(filetime: FILETIME) -> Duration {
+    intervals_to_duration(filetime_to_intervals(filetime))
+}
+
+// Convert a `FILETIME` holding a point in time (since the Windows epoch).
+pub(crate) fn filetime_to_system_time(filetime: FILETIME) -> SystemTime {
+    let intervals = filetime_to_intervals(filetime);
+
+    if intervals >= WINDOWS_TO_UNIX_EPOCH {
+        UNIX_EPOCH + intervals_to_duration(intervals - WINDOWS_TO_UNIX_EPOCH)
+    } else {
+        UNIX_EPOCH - intervals_to_duration(WINDOWS_TO_UNIX_EPOCH - intervals)
+    }
+}
+
+/// Timing information of a process.
+///
+/// Returned by [`Child::process_times`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessTimes {
+    /// The time at which the process was created.
+    pub creation_time: SystemTime,
+    /// The time at which the process exited, `None` if it's still running.
+    pub exit_time: Option<SystemTime>,
+    /// The amount of time that the process has executed in kernel mode.
+    pub kernel_time: Duration,
+    /// The amount of time that the process has executed in user mode.
+    pub user_time: Duration,
+}
+
+/// Memory usage information of a process, in bytes.
+///
+/// Returned by [`Child::memory_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryInfo {
+    /// The current working set size.
+    pub working_set_size: u64,
+    /// The peak working set size.
+    pub peak_working_set_size: u64,
+    /// The amount of memory that cannot be shared with other processes.
+    pub private_usage: u64,
+    /// The number of page faults (this one isn't in bytes).
+    pub page_fault_count: u64,
+}
+
+/// The working set limits of a process, in bytes.
+///
+/// Returned by [`Child::working_set`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkingSetLimits {
+    /// The minimum working set size.
+    pub min_bytes: usize,
+    /// The maximum working set size.
+    pub max_bytes: usize,
+    /// The working set never shrinks below the minimum.
+    pub hard_min: bool,
+    /// The working set never grows above the maximum.
+    pub hard_max: bool,
+}
+
+/// I/O statistics of a process.
+///
+/// Returned by [`Child::io_counters`]. Operations that are neither reads nor
+/// writes (like control functions) are counted as "other".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoCounters {
+    /// The number of read operations performed.
+    pub read_operations: u64,
+    /// The number of write operations performed.
+    pub write_operations: u64,
+    /// The number of other operations performed.
+    pub other_operations: u64,
+    /// The number of bytes read.
+    pub read_bytes: u64,
+    /// The number of bytes written.
+    pub write_bytes: u64,
+    /// The number of bytes transferred during other operations.
+    pub other_bytes: u64,
+}
+
+/// The health of a process at a point in time.
+///
+/// Returned by [`Child::sample_health`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthSample {
+    /// The number of handles opened by the process.
+    pub handle_count: u32,
+    /// The memory usage of the process.
+    pub memory: MemoryInfo,
+    /// The CPU usage of the process since the previous sample, in percent of
+    /// one logical processor.
+    pub cpu_usage: f32,
+}
+
+/// The GUI objects used by a process.
+///
+/// Returned by [`Child::gui_resources`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GuiResources {
+    /// The number of GDI objects, like bitmaps, brushes and fonts.
+    pub gdi_objects: u32,
+    /// The number of USER objects, like windows, menus and cursors.
+    pub user_objects: u32,
+    /// The peak number of GDI objects.
+    pub gdi_peak: u32,
+    /// The peak number of USER objects.
+    pub user_peak: u32,
 }
 
 /// Describes the result of a process after it has terminated.
@@ -508,7 +3807,15 @@ unsafe fn close_handles(process_info: &PROCESS_INFORMATION) {
 /// child process. Child processes are created via the [`Command`] struct and
 /// their exit status is exposed through the [`status`][Command::status]
 /// method, or the [`wait`][Child::wait] method of a [`Child`] process.
+///
+/// With the `serde` feature, the status is serialized as its exit code, a
+/// `u32`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct ExitStatus(u32);
 
 impl ExitStatus {
@@ -528,8 +3835,20 @@ impl ExitStatus {
 
 impl fmt::Display for ExitStatus {
     /// Formats the value using the given formatter.
+    ///
+    /// The exit codes that look like an `NTSTATUS` value (one of the severity
+    /// bits `0xC0000000` is set) are formatted in hexadecimal like
+    /// `0xC0000005`, the other ones in decimal. An
+    /// [interrupted][ExitStatus::is_interrupted] process is formatted as
+    /// `0xC000013A: interrupted (Ctrl+C)`.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        if self.is_interrupted() {
+            write!(f, "{}: interrupted (Ctrl+C)", self.to_hex_string())
+        } else if self.has_severity() {
+            f.write_str(&self.to_hex_string())
+        } else {
+            self.0.fmt(f)
+        }
     }
 }
 
@@ -543,47 +3862,899 @@ type Result<T> = std::result::Result<T, Error>;
 /// to the user but if you want more information about an error code, you can
 /// look at the [`System Error Codes`][system-error-codes].
 ///
+/// The variants of the errors reported by the system hold the [`io::Error`]
+/// with the code, which is their [source][std::error::Error::source]. The
+/// message of a variant doesn't repeat the message of its source, so the
+/// error reporters walking the chain of sources, like `anyhow`, print each
+/// message once.
+///
 /// [get-last-error]: https://docs.microsoft.com/en-us/windows/win32/api/errhandlingapi/nf-errhandlingapi-getlasterror
 /// [system-error-codes]: https://docs.microsoft.com/en-us/windows/win32/debug/system-error-codes
 #[derive(Error, Debug)]
 pub enum Error {
     /// An error occurred when calling [`CreateProcessW`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw)
-    #[error("cannot create process (code {:#x})", 0)]
-    CreationFailed(u32),
+    #[error("cannot create process (code {:#x}){}", os_code(.0), creation_hint(.0))]
+    CreationFailed(#[source] io::Error),
 
     /// An error occurred when calling [`WaitForSingleObject`](https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-waitforsingleobject).
-    #[error("cannot wait process (code {:#x})", 0)]
-    WaitFailed(u32),
+    #[error("cannot wait process (code {:#x})", os_code(.0))]
+    WaitFailed(#[source] io::Error),
 
     /// An error occurred when calling [`TerminateProcess`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-terminateprocess)
-    #[error("cannot kill process (code {:#x})", 0)]
-    KillFailed(u32),
+    #[error("cannot kill process (code {:#x})", os_code(.0))]
+    KillFailed(#[source] io::Error),
 
     /// An error occurred when calling [`GetExitCodeProcess`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getexitcodeprocess)
-    #[error("cannot get exit status (code {:#x})", 0)]
-    GetExitCodeFailed(u32),
+    #[error("cannot get exit status (code {:#x})", os_code(.0))]
+    GetExitCodeFailed(#[source] io::Error),
 
     /// An error occurred when calling
     /// [`GetProcessId`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getprocessid).
-    #[error("cannot get process id (code {:#x})", 0)]
-    GetProcessIdFailed(u32),
+    #[error("cannot get process id (code {:#x})", os_code(.0))]
+    GetProcessIdFailed(#[source] io::Error),
+
+    /// An error occurred when opening a process with
+    /// [`OpenProcess`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-openprocess),
+    /// for [`Child::reattach`].
+    #[error("cannot open the process (code {:#x})", os_code(.0))]
+    OpenProcessFailed(#[source] io::Error),
+
+    /// An error occurred when calling `NtSuspendProcess`.
+    #[error("cannot suspend process (code {:#x})", os_code(.0))]
+    SuspendFailed(#[source] io::Error),
+
+    /// An error occurred when calling `NtResumeProcess`.
+    #[error("cannot resume process (code {:#x})", os_code(.0))]
+    ResumeFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`SetPriorityClass`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-setpriorityclass).
+    #[error("cannot set priority class (code {:#x})", os_code(.0))]
+    SetPriorityFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetPriorityClass`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getpriorityclass).
+    #[error("cannot get priority class (code {:#x})", os_code(.0))]
+    GetPriorityFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`SetProcessPriorityBoost`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-setprocesspriorityboost).
+    #[error("cannot set priority boost (code {:#x})", os_code(.0))]
+    SetPriorityBoostFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetProcessPriorityBoost`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getprocesspriorityboost).
+    #[error("cannot get priority boost (code {:#x})", os_code(.0))]
+    GetPriorityBoostFailed(#[source] io::Error),
+
+    /// An error occurred when enabling or disabling the efficiency mode with
+    /// [`SetProcessInformation`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-setprocessinformation).
+    #[error("cannot set efficiency mode (code {:#x})", os_code(.0))]
+    SetEfficiencyModeFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`SetProcessAffinityMask`](https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-setprocessaffinitymask).
+    #[error("cannot set affinity mask (code {:#x})", os_code(.0))]
+    SetAffinityFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetProcessAffinityMask`](https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-getprocessaffinitymask).
+    #[error("cannot get affinity mask (code {:#x})", os_code(.0))]
+    GetAffinityFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetProcessTimes`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getprocesstimes).
+    #[error("cannot get process times (code {:#x})", os_code(.0))]
+    GetProcessTimesFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetProcessMemoryInfo`](https://docs.microsoft.com/en-us/windows/win32/api/psapi/nf-psapi-getprocessmemoryinfo).
+    #[error("cannot get memory info (code {:#x})", os_code(.0))]
+    GetMemoryInfoFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`SetProcessWorkingSetSizeEx`](https://docs.microsoft.com/en-us/windows/win32/api/memoryapi/nf-memoryapi-setprocessworkingsetsizeex).
+    #[error("cannot set working set size (code {:#x})", os_code(.0))]
+    SetWorkingSetFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetProcessWorkingSetSizeEx`](https://docs.microsoft.com/en-us/windows/win32/api/memoryapi/nf-memoryapi-getprocessworkingsetsizeex).
+    #[error("cannot get working set size (code {:#x})", os_code(.0))]
+    GetWorkingSetFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetProcessIoCounters`](https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-getprocessiocounters).
+    #[error("cannot get io counters (code {:#x})", os_code(.0))]
+    GetIoCountersFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetProcessHandleCount`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getprocesshandlecount).
+    #[error("cannot get handle count (code {:#x})", os_code(.0))]
+    GetHandleCountFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`ProcessIdToSessionId`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-processidtosessionid).
+    #[error("cannot get session id (code {:#x})", os_code(.0))]
+    GetSessionIdFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`GetGuiResources`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getguiresources).
+    #[error("cannot get GUI resources (code {:#x})", os_code(.0))]
+    GetGuiResourcesFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`IsWow64Process2`](https://docs.microsoft.com/en-us/windows/win32/api/wow64apiset/nf-wow64apiset-iswow64process2)
+    /// or [`IsWow64Process`](https://docs.microsoft.com/en-us/windows/win32/api/wow64apiset/nf-wow64apiset-iswow64process).
+    #[error("cannot get process architecture (code {:#x})", os_code(.0))]
+    GetArchitectureFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`QueryFullProcessImageNameW`](https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-queryfullprocessimagenamew).
+    #[error("cannot get program path (code {:#x})", os_code(.0))]
+    GetProgramPathFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`EnumWindows`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-enumwindows).
+    #[error("cannot enumerate windows (code {:#x})", os_code(.0))]
+    EnumWindowsFailed(#[source] io::Error),
+
+    /// The child process doesn't have a matching visible top-level window.
+    #[error("window not found")]
+    WindowNotFound,
+
+    /// The system refused to bring the window of the child process to the
+    /// foreground. See the conditions of [`SetForegroundWindow`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setforegroundwindow).
+    #[error("cannot bring window to the foreground")]
+    ForegroundRefused,
+
+    /// An error occurred when calling [`CreateToolhelp32Snapshot`](https://docs.microsoft.com/en-us/windows/win32/api/tlhelp32/nf-tlhelp32-createtoolhelp32snapshot)
+    /// or [`Process32FirstW`](https://docs.microsoft.com/en-us/windows/win32/api/tlhelp32/nf-tlhelp32-process32firstw).
+    #[error("cannot take a snapshot of the processes (code {:#x})", os_code(.0))]
+    SnapshotFailed(#[source] io::Error),
+
+    /// A protected process cannot be created with
+    /// [`Command::protected_process`], the program must be signed with a
+    /// certificate allowed for protected processes.
+    #[error("cannot create a protected process, the program must be signed for protected processes (code {:#x})", os_code(.0))]
+    ProtectedProcessFailed(#[source] io::Error),
+
+    /// The signature of the program isn't accepted by
+    /// [`Command::verify_signature`], with the status returned by
+    /// [`WinVerifyTrust`](https://docs.microsoft.com/en-us/windows/win32/api/wintrust/nf-wintrust-winverifytrust),
+    /// like `TRUST_E_NOSIGNATURE`, or `TRUST_E_SUBJECT_NOT_TRUSTED` if the
+    /// signer doesn't match.
+    #[error("the signature of the program isn't trusted (status {:#x})", os_code(.0))]
+    UntrustedProgram(#[source] io::Error),
+
+    /// An error occurred when creating the token of a child with
+    /// [`OpenProcessToken`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-openprocesstoken)
+    /// or [`CreateRestrictedToken`](https://docs.microsoft.com/en-us/windows/win32/api/securitybaseapi/nf-securitybaseapi-createrestrictedtoken).
+    #[error("cannot create the token of the process (code {:#x})", os_code(.0))]
+    TokenFailed(#[source] io::Error),
+
+    /// Nobody is logged on the console, for
+    /// [`Command::in_active_user_session`].
+    #[error("cannot find an active user session, nobody is logged on the console")]
+    NoActiveSession,
+
+    /// The token of the user logged on the console cannot be queried, the
+    /// calling process must run as `LocalSystem` or hold the `SeTcbPrivilege`
+    /// privilege, like a service.
+    #[error("cannot query the token of the user, the calling process must run as LocalSystem or hold SeTcbPrivilege")]
+    MissingTcbPrivilege,
+
+    /// An error occurred when getting the token or the environment of the user
+    /// logged on the console with [`WTSQueryUserToken`](https://docs.microsoft.com/en-us/windows/win32/api/wtsapi32/nf-wtsapi32-wtsqueryusertoken),
+    /// [`DuplicateTokenEx`](https://docs.microsoft.com/en-us/windows/win32/api/securitybaseapi/nf-securitybaseapi-duplicatetokenex)
+    /// or [`CreateEnvironmentBlock`](https://docs.microsoft.com/en-us/windows/win32/api/userenv/nf-userenv-createenvironmentblock).
+    #[error("cannot get the token of the user (code {:#x})", os_code(.0))]
+    UserTokenFailed(#[source] io::Error),
+
+    /// An error occurred when setting the attributes of a process with
+    /// [`UpdateProcThreadAttribute`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-updateprocthreadattribute).
+    #[error("cannot set the attributes of the process (code {:#x})", os_code(.0))]
+    AttributeFailed(#[source] io::Error),
+
+    /// An error occurred when arming the watchdog of
+    /// [`Command::spawn_with_timeout`], when calling [`CreateEventW`](https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-createeventw)
+    /// or creating its thread.
+    #[error("cannot arm the watchdog of the process (code {:#x})", os_code(.0))]
+    WatchdogFailed(#[source] io::Error),
+
+    /// An error occurred when spawning a process as administrator with
+    /// [`ShellExecuteExW`](https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-shellexecuteexw).
+    #[error("cannot spawn the process as administrator (code {:#x})", os_code(.0))]
+    ElevationFailed(#[source] io::Error),
+
+    /// The user declined the UAC prompt of
+    /// [`Command::spawn_elevated_if_needed`].
+    #[error("the elevation has been cancelled by the user")]
+    ElevationCancelled,
+
+    /// An error occurred when debugging a child with [`WaitForDebugEvent`](https://docs.microsoft.com/en-us/windows/win32/api/debugapi/nf-debugapi-waitfordebugevent),
+    /// [`ContinueDebugEvent`](https://docs.microsoft.com/en-us/windows/win32/api/debugapi/nf-debugapi-continuedebugevent)
+    /// or [`DebugActiveProcessStop`](https://docs.microsoft.com/en-us/windows/win32/api/debugapi/nf-debugapi-debugactiveprocessstop).
+    #[error("cannot debug the child process (code {:#x})", os_code(.0))]
+    DebugFailed(#[source] io::Error),
+
+    /// An error occurred when writing a minidump with [`MiniDumpWriteDump`](https://docs.microsoft.com/en-us/windows/win32/api/minidumpapiset/nf-minidumpapiset-minidumpwritedump).
+    #[error("cannot write minidump (code {:#x})", os_code(.0))]
+    MinidumpFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`DuplicateHandle`](https://docs.microsoft.com/en-us/windows/win32/api/handleapi/nf-handleapi-duplicatehandle).
+    #[error("cannot duplicate handle (code {:#x})", os_code(.0))]
+    DuplicateHandleFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`CommandLineToArgvW`](https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-commandlinetoargvw).
+    #[error("cannot split command line (code {:#x})", os_code(.0))]
+    SplitCommandLineFailed(#[source] io::Error),
+
+    /// An error occurred when calling [`RegisterWaitForSingleObject`](https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-registerwaitforsingleobject).
+    #[error("cannot register wait (code {:#x})", os_code(.0))]
+    RegisterWaitFailed(#[source] io::Error),
+
+    /// An error occurred when creating the standard streams of the child
+    /// with [`CreatePipe`](https://docs.microsoft.com/en-us/windows/win32/api/namedpipeapi/nf-namedpipeapi-createpipe)
+    /// or when reading its output.
+    #[error("cannot use standard streams (code {:#x})", os_code(.0))]
+    StdioFailed(#[source] io::Error),
+
+    /// An error occurred when creating the console of an
+    /// [`InteractiveSession`] with [`CreatePseudoConsole`](https://docs.microsoft.com/en-us/windows/console/createpseudoconsole).
+    #[error("cannot create a pseudoconsole (code {:#x})", os_code(.0))]
+    PseudoConsoleFailed(#[source] io::Error),
+
+    /// A wait returned `WAIT_ABANDONED`, the object waited on is a mutex
+    /// whose owner exited without releasing it.
+    #[error("wait abandoned")]
+    WaitAbandoned,
+
+    /// The operation requires a running process but the child has already
+    /// exited.
+    #[error("process has already exited")]
+    Exited,
+
+    /// The operation requires a running process but the exit status of the
+    /// child has already been collected, by [`wait`][Child::wait] or
+    /// [`try_wait`][Child::try_wait].
+    #[error("process has already been reaped")]
+    AlreadyReaped,
+
+    /// [`resume`][Child::resume] has been called on a child that isn't
+    /// suspended.
+    #[error("process is not suspended")]
+    NotSuspended,
+
+    /// The child process hasn't exited yet.
+    #[error("process is still running")]
+    StillRunning,
+
+    /// The operation didn't complete before the timeout elapsed.
+    #[error("operation timed out")]
+    Timeout,
+
+    /// The pattern expected by an [`InteractiveSession`] didn't appear before
+    /// the timeout elapsed.
+    #[error("timed out waiting for {pattern:?}")]
+    ExpectTimeout {
+        /// The pattern that was expected.
+        pattern: String,
+        /// The output received while waiting.
+        output: String,
+    },
+
+    /// The child of an [`InteractiveSession`] closed its output (usually by
+    /// exiting) before the expected pattern appeared.
+    #[error("process exited while waiting for {pattern:?}")]
+    ExpectExited {
+        /// The pattern that was expected.
+        pattern: String,
+        /// The output received while waiting.
+        output: String,
+    },
+
+    /// An error occurred when running a command with [`Command::spawn`],
+    /// [`Command::status`], [`Command::output`] or [`Command::run`].
+    ///
+    /// The message includes the command line (truncated if it's too long),
+    /// the identifier of the child if it has been spawned, the working
+    /// directory and the tag if they have been set. The error of the
+    /// operation is the source of this one.
+    #[error(
+        "cannot {operation} `{command}`{}",
+        command_details(.pid, .current_directory, .tag)
+    )]
+    CommandFailed {
+        /// The operation that failed.
+        operation: Operation,
+        /// The command line, truncated if it's too long.
+        command: String,
+        /// The identifier of the child, if it has been spawned.
+        pid: Option<u32>,
+        /// The working directory of the command, if it has been set.
+        current_directory: Option<PathBuf>,
+        /// The tag of the command, if it has been set with
+        /// [`Command::tag`].
+        tag: Option<Arc<str>>,
+        /// The error of the operation.
+        source: Box<Error>,
+    },
+
+    /// The command failed to spawn after the attempts of
+    /// [`Command::spawn_retry`].
+    #[error("failed after {attempts} attempts")]
+    Retried {
+        /// The number of attempts.
+        attempts: u32,
+        /// The error of the last attempt.
+        source: Box<Error>,
+    },
+
+    /// The child process exited unsuccessfully. Returned by [`Command::run`].
+    #[error(transparent)]
+    Unsuccessful(ExitStatusError),
+
+    /// A string contains a null character, which cannot be given to the
+    /// system.
+    #[error("string contains a null character")]
+    InteriorNul,
+
+    /// The configuration of the [`Command`] is invalid, the process hasn't
+    /// been spawned. Every problem found is listed, so they can be fixed at
+    /// once.
+    #[error("invalid command: {}", .0.join("; "))]
+    InvalidCommand(Vec<String>),
+
+    /// The operation isn't supported by this version of Windows, which lacks
+    /// the given function or feature.
+    #[error("not supported by this version of Windows: {0}")]
+    Unsupported(&'static str),
+
+    /// An invalid value has been given, the operation hasn't been attempted.
+    #[error("invalid input: {0}")]
+    InvalidInput(&'static str),
 }
 
 impl Error {
+    /// Returns the kind of the error, using the categories of [`io::Error`].
+    ///
+    /// The system error codes that matter when spawning a process are mapped
+    /// explicitly:
+    ///
+    /// * `ERROR_FILE_NOT_FOUND` and `ERROR_PATH_NOT_FOUND`:
+    ///   [`NotFound`][io::ErrorKind::NotFound].
+    /// * `ERROR_ACCESS_DENIED` and `ERROR_ELEVATION_REQUIRED` (the program
+    ///   requires to run as administrator):
+    ///   [`PermissionDenied`][io::ErrorKind::PermissionDenied].
+    /// * `ERROR_BAD_EXE_FORMAT` (the program is built for another architecture
+    ///   or is corrupt): [`InvalidData`][io::ErrorKind::InvalidData].
+    /// * `ERROR_SHARING_VIOLATION` (the program is being written by another
+    ///   process): [`ResourceBusy`][io::ErrorKind::ResourceBusy].
+    /// * `ERROR_DIRECTORY` (the working directory isn't a directory):
+    ///   [`NotADirectory`][io::ErrorKind::NotADirectory].
+    ///
+    /// The other codes are classified like the standard library does. The
+    /// errors that aren't reported by the system have the closest kind.
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Self::CommandFailed { source, .. } | Self::Retried { source, .. } => source.kind(),
+            Self::Exited | Self::ExpectExited { .. } => io::ErrorKind::UnexpectedEof,
+            Self::NotSuspended
+            | Self::AlreadyReaped
+            | Self::InteriorNul
+            | Self::InvalidCommand(_)
+            | Self::InvalidInput(_) => io::ErrorKind::InvalidInput,
+            Self::StillRunning => io::ErrorKind::WouldBlock,
+            Self::Unsupported(_) => io::ErrorKind::Unsupported,
+            Self::Unsuccessful(_) => io::ErrorKind::Other,
+            Self::Timeout | Self::ExpectTimeout { .. } => io::ErrorKind::TimedOut,
+            Self::WindowNotFound => io::ErrorKind::NotFound,
+            Self::ForegroundRefused
+            | Self::MissingTcbPrivilege
+            | Self::ElevationCancelled
+            | Self::UntrustedProgram(_) => io::ErrorKind::PermissionDenied,
+            Self::NoActiveSession => io::ErrorKind::NotFound,
+            _ => error_kind(WIN32_ERROR(self.code())),
+        }
+    }
+
+    /// Returns the operation that failed if the error happened when running
+    /// a command, see [`CommandFailed`][Error::CommandFailed].
+    pub fn operation(&self) -> Option<Operation> {
+        match self {
+            Self::CommandFailed { operation, .. } => Some(*operation),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the error happened when spawning a command, before
+    /// the child existed.
+    ///
+    /// The other errors of a command happen once the child has been spawned,
+    /// like a failed wait of [`Command::status`]: the child may still be
+    /// running, its identifier is available with [`pid`][Error::pid]. This
+    /// tells whether retrying the command can start a second child.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// match Command::new("missing.exe").status() {
+    ///     Err(err) if err.is_spawn_failure() => println!("not started: {}", err),
+    ///     Err(err) => println!("started as {:?} but failed: {}", err.pid(), err),
+    ///     Ok(status) => println!("exited with {}", status.code()),
+    /// }
+    /// ```
+    pub fn is_spawn_failure(&self) -> bool {
+        self.operation() == Some(Operation::Spawn)
+    }
+
+    /// Returns `true` if the user declined the elevation of
+    /// [`Command::spawn_elevated_if_needed`].
+    pub fn is_elevation_cancelled(&self) -> bool {
+        match self {
+            Self::CommandFailed { source, .. } => source.is_elevation_cancelled(),
+            Self::ElevationCancelled => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the identifier of the child if the error happened when running
+    /// a command that has been spawned.
+    pub fn pid(&self) -> Option<u32> {
+        match self {
+            Self::CommandFailed { pid, .. } => *pid,
+            _ => None,
+        }
+    }
+
+    /// Returns the working directory of the command if the error happened
+    /// when running a command with a working directory.
+    pub fn current_directory(&self) -> Option<&Path> {
+        match self {
+            Self::CommandFailed {
+                current_directory, ..
+            } => current_directory.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the tag of the command if the error happened when running a
+    /// command with a tag, see [`Command::tag`].
+    pub fn tag(&self) -> Option<&str> {
+        match self {
+            Self::CommandFailed { tag, .. } => tag.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of attempts if the error happened when spawning a
+    /// command with [`Command::spawn_retry`].
+    pub fn attempts(&self) -> Option<u32> {
+        match self {
+            Self::CommandFailed { source, .. } => source.attempts(),
+            Self::Retried { attempts, .. } => Some(*attempts),
+            _ => None,
+        }
+    }
+
+    /// Returns the error reported by the system, with the same code.
+    ///
+    /// Returns `None` for the errors that aren't reported by the system, like
+    /// [`Timeout`][Error::Timeout]. The outer layers of context, like
+    /// [`CommandFailed`][Error::CommandFailed], are looked through.
+    pub fn os_error(&self) -> Option<io::Error> {
+        match self {
+            Self::CommandFailed { source, .. } | Self::Retried { source, .. } => source.os_error(),
+            _ if self.is_system() => Some(io::Error::from_raw_os_error(self.code() as i32)),
+            _ => None,
+        }
+    }
+
+    // Whether the error code has been reported by the system.
+    fn is_system(&self) -> bool {
+        !matches!(
+            self,
+            Self::CommandFailed { .. }
+                | Self::Retried { .. }
+                | Self::Exited
+                | Self::AlreadyReaped
+                | Self::ExpectExited { .. }
+                | Self::NotSuspended
+                | Self::Unsuccessful(_)
+                | Self::InteriorNul
+                | Self::InvalidCommand(_)
+                | Self::InvalidInput(_)
+                | Self::Unsupported(_)
+                | Self::StillRunning
+                | Self::Timeout
+                | Self::ExpectTimeout { .. }
+                | Self::WindowNotFound
+                | Self::ForegroundRefused
+                | Self::NoActiveSession
+        )
+    }
+
     /// Return the system error code of the Error
     ///
-    /// This error code isn't formatted like the code in the error string.
+    /// This error code isn't formatted like the code in the error string. For
+    /// errors that aren't reported by the system, the closest system error
+    /// code is returned.
     ///
     /// To more information about this [codes][system-error-codes.]
     ///
     /// [system-error-codes]: https://docs.microsoft.com/en-us/windows/win32/debug/system-error-codes
     pub fn code(&self) -> u32 {
         match *self {
-            Self::CreationFailed(code) => code,
-            Self::WaitFailed(code) => code,
-            Self::KillFailed(code) => code,
-            Self::GetExitCodeFailed(code) => code,
-            Self::GetProcessIdFailed(code) => code,
+            Self::CommandFailed { ref source, .. } | Self::Retried { ref source, .. } => {
+                source.code()
+            }
+            Self::CreationFailed(ref err) => os_code(err),
+            Self::WaitFailed(ref err) => os_code(err),
+            Self::KillFailed(ref err) => os_code(err),
+            Self::GetExitCodeFailed(ref err) => os_code(err),
+            Self::GetProcessIdFailed(ref err) => os_code(err),
+            Self::OpenProcessFailed(ref err) => os_code(err),
+            Self::SuspendFailed(ref err) => os_code(err),
+            Self::ResumeFailed(ref err) => os_code(err),
+            Self::SetPriorityFailed(ref err) => os_code(err),
+            Self::GetPriorityFailed(ref err) => os_code(err),
+            Self::SetPriorityBoostFailed(ref err) => os_code(err),
+            Self::GetPriorityBoostFailed(ref err) => os_code(err),
+            Self::SetEfficiencyModeFailed(ref err) => os_code(err),
+            Self::SetAffinityFailed(ref err) => os_code(err),
+            Self::GetAffinityFailed(ref err) => os_code(err),
+            Self::GetProcessTimesFailed(ref err) => os_code(err),
+            Self::GetMemoryInfoFailed(ref err) => os_code(err),
+            Self::SetWorkingSetFailed(ref err) => os_code(err),
+            Self::GetWorkingSetFailed(ref err) => os_code(err),
+            Self::GetIoCountersFailed(ref err) => os_code(err),
+            Self::GetHandleCountFailed(ref err) => os_code(err),
+            Self::GetSessionIdFailed(ref err) => os_code(err),
+            Self::GetGuiResourcesFailed(ref err) => os_code(err),
+            Self::GetArchitectureFailed(ref err) => os_code(err),
+            Self::GetProgramPathFailed(ref err) => os_code(err),
+            Self::EnumWindowsFailed(ref err) => os_code(err),
+            Self::SnapshotFailed(ref err) => os_code(err),
+            Self::ProtectedProcessFailed(ref err) => os_code(err),
+            Self::UntrustedProgram(ref err) => os_code(err),
+            Self::TokenFailed(ref err) => os_code(err),
+            Self::UserTokenFailed(ref err) => os_code(err),
+            Self::AttributeFailed(ref err) => os_code(err),
+            Self::WatchdogFailed(ref err) => os_code(err),
+            Self::ElevationFailed(ref err) => os_code(err),
+            Self::ElevationCancelled => ERROR_CANCELLED.0,
+            Self::DebugFailed(ref err) => os_code(err),
+            Self::MinidumpFailed(ref err) => os_code(err),
+            Self::DuplicateHandleFailed(ref err) => os_code(err),
+            Self::RegisterWaitFailed(ref err) => os_code(err),
+            Self::SplitCommandLineFailed(ref err) => os_code(err),
+            Self::StdioFailed(ref err) => os_code(err),
+            Self::PseudoConsoleFailed(ref err) => os_code(err),
+            Self::WaitAbandoned => ERROR_ABANDONED_WAIT_0.0,
+            Self::Exited => ERROR_PROCESS_ABORTED.0,
+            Self::AlreadyReaped => ERROR_PROCESS_ABORTED.0,
+            Self::NotSuspended => ERROR_INVALID_OPERATION.0,
+            Self::StillRunning => WAIT_TIMEOUT.0,
+            Self::Timeout => ERROR_TIMEOUT.0,
+            Self::ExpectTimeout { .. } => ERROR_TIMEOUT.0,
+            Self::ExpectExited { .. } => ERROR_PROCESS_ABORTED.0,
+            Self::Unsuccessful(_) => ERROR_PROCESS_ABORTED.0,
+            Self::InteriorNul => ERROR_INVALID_PARAMETER.0,
+            Self::InvalidCommand(_) => ERROR_INVALID_PARAMETER.0,
+            Self::InvalidInput(_) => ERROR_INVALID_PARAMETER.0,
+            Self::Unsupported(_) => ERROR_NOT_SUPPORTED.0,
+            Self::WindowNotFound => ERROR_INVALID_WINDOW_HANDLE.0,
+            Self::ForegroundRefused => ERROR_ACCESS_DENIED.0,
+            Self::NoActiveSession => ERROR_NO_TOKEN.0,
+            Self::MissingTcbPrivilege => ERROR_PRIVILEGE_NOT_HELD.0,
+        }
+    }
+}
+
+/// An operation of a [`Command`], used by [`Error::CommandFailed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Spawning the child process.
+    Spawn,
+    /// Waiting for the child process to exit.
+    Wait,
+    /// Reading the output of the child process.
+    ReadOutput,
+    /// Running the command to completion with [`Command::run`].
+    Run,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Spawn => "spawn",
+            Self::Wait => "wait for",
+            Self::ReadOutput => "read the output of",
+            Self::Run => "run",
+        })
+    }
+}
+
+// Classify a system error code. See `Error::kind`.
+fn error_kind(code: WIN32_ERROR) -> io::ErrorKind {
+    match code {
+        ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND => io::ErrorKind::NotFound,
+        ERROR_ACCESS_DENIED | ERROR_ELEVATION_REQUIRED => io::ErrorKind::PermissionDenied,
+        ERROR_BAD_EXE_FORMAT => io::ErrorKind::InvalidData,
+        ERROR_SHARING_VIOLATION => io::ErrorKind::ResourceBusy,
+        ERROR_DIRECTORY => io::ErrorKind::NotADirectory,
+        _ => io::Error::from_raw_os_error(code.0 as i32).kind(),
+    }
+}
+
+// The code of an error reported by the system.
+pub(crate) fn os_code(err: &io::Error) -> u32 {
+    err.raw_os_error().unwrap_or_default() as u32
+}
+
+// Explain the error codes of `CreateProcessW` that are hard to understand.
+fn creation_hint(err: &io::Error) -> &'static str {
+    match WIN32_ERROR(os_code(err)) {
+        ERROR_BAD_EXE_FORMAT => {
+            ": the program is built for another architecture or the file is corrupt"
+        }
+        ERROR_ELEVATION_REQUIRED => ": the program requires to run as administrator",
+        _ => "",
+    }
+}
+
+// Format the tag, the identifier and the working directory of a failed
+// command.
+fn command_details(
+    pid: &Option<u32>,
+    current_directory: &Option<PathBuf>,
+    tag: &Option<Arc<str>>,
+) -> String {
+    let mut details = String::new();
+
+    if let Some(tag) = tag {
+        details.push_str(&format!(" [{}]", tag));
+    }
+
+    if let Some(pid) = pid {
+        details.push_str(&format!(" (pid {})", pid));
+    }
+
+    if let Some(dir) = current_directory {
+        details.push_str(&format!(" in {:?}", dir));
+    }
+
+    details
+}
+
+impl From<Error> for io::Error {
+    /// Converts the error into an [`io::Error`], for code working with the
+    /// standard library.
+    ///
+    /// The errors reported by the system become an OS error with the same
+    /// code, available with [`io::Error::raw_os_error`], unless the standard
+    /// library classifies the code differently than [`Error::kind`]. The other
+    /// errors are wrapped with their [`kind`][Error::kind], the original error
+    /// being available through [`io::Error::get_ref`].
+    fn from(err: Error) -> Self {
+        let kind = err.kind();
+
+        if err.is_system() {
+            let os_error = io::Error::from_raw_os_error(err.code() as i32);
+
+            if os_error.kind() == kind {
+                return os_error;
+            }
         }
+
+        io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::MockProcessApi;
+
+    fn spawn_mock(api: &MockProcessApi) -> Result<Child> {
+        Command::new("worker.exe").spawn_with_api_and_default_stdio(Arc::new(api.clone()), false)
+    }
+
+    #[test]
+    fn failed_wait_of_a_command_isnt_a_spawn_failure() {
+        let api = MockProcessApi::new();
+        let mut command = Command::new("worker.exe");
+
+        // `ERROR_ACCESS_DENIED`
+        api.fail_create(5);
+        let err = command.spawn_with_api(Arc::new(api.clone())).unwrap_err();
+        assert!(err.is_spawn_failure());
+        assert_eq!(err.pid(), None);
+
+        // Like `status`.
+        let child = command.spawn_with_api(Arc::new(api.clone())).unwrap();
+        // `ERROR_INVALID_HANDLE`
+        api.fail_wait(6);
+        let err = child
+            .wait()
+            .map_err(command.context(Operation::Wait, Some(child.id())))
+            .unwrap_err();
+        assert!(!err.is_spawn_failure());
+        assert_eq!(err.pid(), Some(child.id()));
+
+        api.exit(child.id(), 0);
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn failed_creation_opens_no_handle() {
+        let api = MockProcessApi::new();
+        // `ERROR_ACCESS_DENIED`
+        api.fail_create(5);
+
+        let err = spawn_mock(&api).unwrap_err();
+
+        assert!(matches!(err, Error::CreationFailed(ref err) if os_code(err) == 5));
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(api.command_lines().is_empty());
+        assert_eq!(api.open_handles(), 0);
+    }
+
+    fn creation_kind(code: i32) -> io::ErrorKind {
+        Error::CreationFailed(io::Error::from_raw_os_error(code)).kind()
+    }
+
+    #[test]
+    fn missing_files_are_not_found() {
+        // `ERROR_FILE_NOT_FOUND` and `ERROR_PATH_NOT_FOUND`
+        assert_eq!(creation_kind(2), io::ErrorKind::NotFound);
+        assert_eq!(creation_kind(3), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn denied_and_elevated_programs_are_permission_denied() {
+        // `ERROR_ACCESS_DENIED` and `ERROR_ELEVATION_REQUIRED`
+        assert_eq!(creation_kind(5), io::ErrorKind::PermissionDenied);
+        assert_eq!(creation_kind(740), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn bad_executables_are_invalid_data() {
+        // `ERROR_BAD_EXE_FORMAT`
+        assert_eq!(creation_kind(193), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn programs_being_written_are_busy() {
+        // `ERROR_SHARING_VIOLATION`
+        assert_eq!(creation_kind(32), io::ErrorKind::ResourceBusy);
+    }
+
+    #[test]
+    fn bad_directories_are_not_directories() {
+        // `ERROR_DIRECTORY`
+        assert_eq!(creation_kind(267), io::ErrorKind::NotADirectory);
+    }
+
+    #[test]
+    fn other_codes_have_the_kind_of_the_standard_library() {
+        // `ERROR_NOT_ENOUGH_MEMORY`
+        assert_eq!(creation_kind(8), io::Error::from_raw_os_error(8).kind());
+        // The context of a command is looked through.
+        let err = Command::new("worker.exe").context(Operation::Spawn, None)(
+            Error::CreationFailed(io::Error::from_raw_os_error(193)),
+        );
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn failed_wait_does_not_collect_the_status() {
+        let api = MockProcessApi::new();
+        let child = spawn_mock(&api).unwrap();
+        // `ERROR_INVALID_HANDLE`
+        api.fail_wait(6);
+
+        assert!(matches!(child.wait(), Err(Error::WaitFailed(ref err)) if os_code(err) == 6));
+        assert!(child.exit_status.get().is_none());
+
+        api.exit(child.id(), 3);
+        assert_eq!(child.wait().unwrap().code(), 3);
+
+        drop(child);
+        assert_eq!(api.open_handles(), 0);
+    }
+
+    #[test]
+    fn kill_with_a_bogus_handle_fails() {
+        let child = Child {
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            process_information: PROCESS_INFORMATION::default(),
+            api: Arc::new(Win32ProcessApi),
+            command: OsString::new(),
+            current_directory: None,
+            exit_status: OnceLock::new(),
+            suspend_count: AtomicU32::new(0),
+            cpu_sample: Mutex::new(None),
+            warn_on_unreaped: false,
+            registered: false,
+            tag: None,
+        };
+
+        let err = child.kill().unwrap_err();
+
+        // `ERROR_INVALID_HANDLE`
+        assert!(matches!(err, Error::KillFailed(ref err) if os_code(err) == 6));
+        assert_eq!(err.code(), 6);
+        assert!(err.os_error().is_some());
+        assert_eq!(err.kind(), io::Error::from_raw_os_error(6).kind());
+
+        // The null handles have never been opened.
+        std::mem::forget(child);
+    }
+
+    #[test]
+    fn debug_shows_the_pid_and_no_handle() {
+        let api = MockProcessApi::new();
+        let child = Command::new(format!("worker.exe {}", "x".repeat(100)))
+            .tag("build")
+            .spawn_with_api_and_default_stdio(Arc::new(api.clone()), false)
+            .unwrap();
+
+        let debug = format!("{:?}", child);
+        assert!(debug.contains(&format!("pid: {}", child.id())));
+        assert!(debug.contains(r#"tag: Some("build")"#));
+        assert!(debug.contains("reaped: false"));
+        // The command line is truncated to 64 characters.
+        assert!(debug.contains(&format!("worker.exe {}...", "x".repeat(53))));
+        // The handles are meaningless outside of the calling process.
+        assert!(!debug.contains("0x"));
+
+        api.exit(child.id(), 0);
+        child.wait().unwrap();
+        assert!(format!("{:?}", child).contains("reaped: true"));
+    }
+
+    #[test]
+    fn filetime_conversions_do_not_overflow() {
+        let filetime = |intervals: u64| FILETIME {
+            dwLowDateTime: intervals as u32,
+            dwHighDateTime: (intervals >> 32) as u32,
+        };
+
+        assert_eq!(
+            filetime_to_system_time(filetime(WINDOWS_TO_UNIX_EPOCH + 15_000_000)),
+            UNIX_EPOCH + Duration::from_millis(1500)
+        );
+        assert_eq!(
+            filetime_to_system_time(filetime(WINDOWS_TO_UNIX_EPOCH - 10_000_000)),
+            UNIX_EPOCH - Duration::from_secs(1)
+        );
+        // Multiplying by 100 would overflow a `u64` of nanoseconds.
+        assert_eq!(
+            filetime_to_duration(filetime(u64::MAX)),
+            Duration::new(u64::MAX / 10_000_000, (u64::MAX % 10_000_000) as u32 * 100)
+        );
+        assert!(filetime_to_system_time(filetime(1 << 62)) > UNIX_EPOCH);
+    }
+
+    #[test]
+    fn disabled_efficiency_mode_is_managed_by_the_system() {
+        assert_eq!(power_throttling_state(true), [1, 1, 1]);
+        assert_eq!(power_throttling_state(false), [1, 0, 0]);
+    }
+
+    #[test]
+    fn unknown_power_throttling_class_is_unsupported() {
+        assert!(matches!(
+            efficiency_mode_error(ERROR_INVALID_PARAMETER),
+            Error::Unsupported("ProcessPowerThrottling")
+        ));
+        // `ERROR_ACCESS_DENIED`
+        assert!(matches!(
+            efficiency_mode_error(WIN32_ERROR(5)),
+            Error::SetEfficiencyModeFailed(err) if err.raw_os_error() == Some(5)
+        ));
+    }
+
+    #[test]
+    fn unknown_priority_class_keeps_its_value() {
+        let priority_class = PriorityClass::from_flags(PROCESS_CREATION_FLAGS(0x1234));
+
+        assert!(matches!(
+            priority_class,
+            PriorityClass::Other(value) if value.value() == 0x1234
+        ));
+        assert_eq!(priority_class.to_flags(), PROCESS_CREATION_FLAGS(0x1234));
+        assert_eq!(
+            PriorityClass::from_flags(IDLE_PRIORITY_CLASS),
+            PriorityClass::Idle
+        );
     }
 }
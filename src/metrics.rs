@@ -0,0 +1,225 @@
+use crate::Child;
+use std::{
+    ffi::OsString,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+static SPAWNED: AtomicU64 = AtomicU64::new(0);
+static SPAWN_FAILURES: AtomicU64 = AtomicU64::new(0);
+static RUNNING: AtomicU64 = AtomicU64::new(0);
+// In units of 100 nanoseconds, like the times of `GetProcessTimes`.
+static CPU_TIME: AtomicU64 = AtomicU64::new(0);
+
+type Hook = Arc<dyn Fn(&MetricsEvent) + Send + Sync>;
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+
+/// The counters of the children spawned by the calling process, returned by
+/// [`Metrics::snapshot`].
+///
+/// The counters are updated by all the children, regardless of the
+/// [`Command`][crate::Command] that spawned them. They are cheap to update and
+/// to read, to be exported by a monitoring system with
+/// [`set_metrics_hook`] or by reading a snapshot periodically.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{Command, Metrics};
+///
+/// Command::new("cmd.exe /c exit").status().unwrap();
+///
+/// let metrics = Metrics::snapshot();
+/// println!(
+///     "{} spawned, {} failed, {} running, {:?} of CPU time",
+///     metrics.spawned, metrics.spawn_failures, metrics.running, metrics.cpu_time,
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Metrics {
+    /// The children spawned successfully.
+    pub spawned: u64,
+    /// The children that failed to spawn, including the ones with an
+    /// [invalid configuration][crate::Error::InvalidCommand].
+    pub spawn_failures: u64,
+    /// The children whose exit status hasn't been collected yet, by
+    /// [`wait`][Child::wait], [`try_wait`][Child::try_wait] or the reaper
+    /// thread, and that haven't been dropped, including the ones
+    /// [reattached][Child::reattach]. A child dropped while running isn't
+    /// counted anymore, since it cannot be followed.
+    pub running: u64,
+    /// The CPU time used by the children, in user and kernel mode, added when
+    /// their exit status is collected.
+    pub cpu_time: Duration,
+}
+
+impl Metrics {
+    /// Returns the current values of the counters.
+    ///
+    /// The counters are read one by one, a child spawned meanwhile can be
+    /// counted in some of them only.
+    pub fn snapshot() -> Self {
+        Self {
+            spawned: SPAWNED.load(Ordering::Relaxed),
+            spawn_failures: SPAWN_FAILURES.load(Ordering::Relaxed),
+            running: RUNNING.load(Ordering::Relaxed),
+            cpu_time: hundred_nanos_to_duration(CPU_TIME.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// A change of the [`Metrics`], given to the hook set with
+/// [`set_metrics_hook`].
+///
+/// The events of a child include its [tag][crate::Command::tag], if it has
+/// one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MetricsEvent {
+    /// A child has been spawned.
+    Spawned {
+        /// The identifier of the child.
+        pid: u32,
+        /// The tag of the child.
+        tag: Option<Arc<str>>,
+    },
+    /// A child failed to spawn.
+    SpawnFailed,
+    /// The exit status of a child has been collected.
+    Exited {
+        /// The identifier of the child.
+        pid: u32,
+        /// The CPU time used by the child, `None` if it cannot be read.
+        cpu_time: Option<Duration>,
+        /// The tag of the child.
+        tag: Option<Arc<str>>,
+    },
+    /// A child has been dropped while running.
+    Dropped {
+        /// The identifier of the child.
+        pid: u32,
+        /// The tag of the child.
+        tag: Option<Arc<str>>,
+    },
+    /// A child has been dropped without being waited on while
+    /// [`warn_on_unreaped`][crate::Command::warn_on_unreaped] is enabled,
+    /// right before the [`Dropped`][MetricsEvent::Dropped] event. The counters
+    /// don't change.
+    Unreaped {
+        /// The identifier of the child.
+        pid: u32,
+        /// The command line of the child.
+        command: OsString,
+        /// The tag of the child.
+        tag: Option<Arc<str>>,
+    },
+}
+
+/// Sets the hook called on each change of the [`Metrics`], after the
+/// counters have been updated.
+///
+/// This bridges the counters to a monitoring system, like a Prometheus
+/// exporter, without this crate depending on it. The hook is called on the
+/// thread that spawned or reaped the child, it should return quickly. It
+/// replaces the previous hook.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{set_metrics_hook, MetricsEvent};
+///
+/// set_metrics_hook(|event| match event {
+///     MetricsEvent::Spawned { pid, tag } => println!("spawned {} {:?}", pid, tag),
+///     MetricsEvent::SpawnFailed => println!("spawn failed"),
+///     _ => {}
+/// });
+/// ```
+pub fn set_metrics_hook(hook: impl Fn(&MetricsEvent) + Send + Sync + 'static) {
+    *HOOK.write().unwrap_or_else(|err| err.into_inner()) = Some(Arc::new(hook));
+}
+
+/// Removes the hook set with [`set_metrics_hook`].
+pub fn clear_metrics_hook() {
+    *HOOK.write().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+pub(crate) fn spawned(child: &Child) {
+    SPAWNED.fetch_add(1, Ordering::Relaxed);
+    RUNNING.fetch_add(1, Ordering::Relaxed);
+
+    notify(&MetricsEvent::Spawned {
+        pid: child.id(),
+        tag: child.tag.clone(),
+    });
+}
+
+// A child found again with `Child::reattach`, running until its exit status is
+// collected, but not spawned by this process.
+pub(crate) fn reattached(_child: &Child) {
+    RUNNING.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn spawn_failed() {
+    SPAWN_FAILURES.fetch_add(1, Ordering::Relaxed);
+
+    notify(&MetricsEvent::SpawnFailed);
+}
+
+// Called once per child, when its exit status is collected.
+pub(crate) fn exited(child: &Child) {
+    RUNNING.fetch_sub(1, Ordering::Relaxed);
+
+    let cpu_time = child
+        .process_times()
+        .ok()
+        .map(|times| times.kernel_time + times.user_time);
+
+    if let Some(cpu_time) = cpu_time {
+        CPU_TIME.fetch_add((cpu_time.as_nanos() / 100) as u64, Ordering::Relaxed);
+    }
+
+    notify(&MetricsEvent::Exited {
+        pid: child.id(),
+        cpu_time,
+        tag: child.tag.clone(),
+    });
+}
+
+// Called once per child dropped before its exit status is collected.
+pub(crate) fn dropped(child: &Child) {
+    RUNNING.fetch_sub(1, Ordering::Relaxed);
+
+    notify(&MetricsEvent::Dropped {
+        pid: child.id(),
+        tag: child.tag.clone(),
+    });
+}
+
+// Called when a child that should have been waited on is dropped.
+pub(crate) fn unreaped(child: &Child) {
+    notify(&MetricsEvent::Unreaped {
+        pid: child.id(),
+        command: child.command.clone(),
+        tag: child.tag.clone(),
+    });
+}
+
+fn notify(event: &MetricsEvent) {
+    // The hook is cloned so it can set another hook.
+    let hook = HOOK.read().unwrap_or_else(|err| err.into_inner()).clone();
+
+    if let Some(hook) = hook {
+        hook(event);
+    }
+}
+
+fn hundred_nanos_to_duration(hundred_nanos: u64) -> Duration {
+    Duration::new(
+        hundred_nanos / 10_000_000,
+        (hundred_nanos % 10_000_000) as u32 * 100,
+    )
+}
@@ -0,0 +1,99 @@
+use crate::{Child, Error, Result};
+use std::{ffi::c_void, fs::File, io, os::windows::io::AsRawHandle, path::Path};
+use windows::{
+    core::{s, w},
+    Win32::{
+        Foundation::{BOOL, HANDLE},
+        System::LibraryLoader::{GetProcAddress, LoadLibraryW},
+    },
+};
+
+/// The kind of information written to a minidump.
+///
+/// Used by [`Child::write_minidump`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinidumpKind {
+    /// Only the information needed to capture the stack traces of all the
+    /// threads of the process.
+    Normal,
+    /// All the accessible memory of the process. The dump can be very large.
+    FullMemory,
+}
+
+impl MinidumpKind {
+    // Values of the `MINIDUMP_TYPE` enum.
+    fn to_raw(self) -> u32 {
+        match self {
+            Self::Normal => 0x0,
+            Self::FullMemory => 0x2,
+        }
+    }
+}
+
+type MiniDumpWriteDump = unsafe extern "system" fn(
+    HANDLE,
+    u32,
+    HANDLE,
+    u32,
+    *const c_void,
+    *const c_void,
+    *const c_void,
+) -> BOOL;
+
+impl Child {
+    /// Writes a minidump of the child process to the file at `path`.
+    ///
+    /// This is useful to capture the state of a hung process before killing
+    /// it. The file is created or truncated. If the dump cannot be written, a
+    /// [`MinidumpFailed`][Error::MinidumpFailed] error is returned. When the
+    /// error comes from `dbghelp.dll`, its code is an `HRESULT`.
+    ///
+    /// Equivalent to the [`MiniDumpWriteDump`][mini-dump-write-dump] function,
+    /// which is loaded at runtime from `dbghelp.dll`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, MinidumpKind};
+    /// use std::path::Path;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// child
+    ///     .write_minidump(Path::new("notepad.dmp"), MinidumpKind::Normal)
+    ///     .expect("cannot write minidump");
+    /// child.kill().unwrap();
+    /// ```
+    ///
+    /// [mini-dump-write-dump]: https://docs.microsoft.com/en-us/windows/win32/api/minidumpapiset/nf-minidumpapiset-minidumpwritedump
+    pub fn write_minidump(&self, path: &Path, kind: MinidumpKind) -> Result<()> {
+        let file = File::create(path).map_err(Error::MinidumpFailed)?;
+
+        unsafe {
+            let module = LoadLibraryW(w!("dbghelp.dll"))
+                .map_err(|_| Error::MinidumpFailed(io::Error::last_os_error()))?;
+            let function = GetProcAddress(module, s!("MiniDumpWriteDump"))
+                .ok_or_else(|| Error::MinidumpFailed(io::Error::last_os_error()))?;
+            let function: MiniDumpWriteDump = std::mem::transmute(function);
+
+            let res = function(
+                self.process_information.hProcess,
+                self.id(),
+                HANDLE(file.as_raw_handle() as isize),
+                kind.to_raw(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+            );
+
+            if res.as_bool() {
+                Ok(())
+            } else {
+                let err = io::Error::last_os_error();
+                drop(file);
+                let _ = std::fs::remove_file(path);
+                Err(Error::MinidumpFailed(err))
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+// A scriptable fake of the Win32 calls, to test the code handling children
+// without spawning processes.
+
+use crate::{
+    api::{ProcessApi, ProcessSpec, RawProcess},
+    Error, Result,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::OsString,
+    io,
+    os::windows::{ffi::OsStringExt, io::RawHandle},
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::Duration,
+};
+
+// The exit code of a process that is still running.
+const STILL_ACTIVE: u32 = 259;
+
+/// A fake [`ProcessApi`] creating fake processes.
+///
+/// The fake processes run until they are ended with
+/// [`exit`][MockProcessApi::exit] or killed. The failures of the calls can be
+/// scripted, which is otherwise hard to trigger deterministically. Clones
+/// share the same fake processes.
+///
+/// Requires the `test-util` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{Command, Error, MockProcessApi};
+/// use std::sync::Arc;
+///
+/// let api = MockProcessApi::new();
+///
+/// let child = Command::new("worker.exe")
+///     .spawn_with_api(Arc::new(api.clone()))
+///     .unwrap();
+///
+/// api.exit(child.id(), 3);
+/// assert_eq!(child.wait().unwrap().code(), 3);
+///
+/// // `ERROR_FILE_NOT_FOUND`
+/// api.fail_create(2);
+/// let err = Command::new("missing.exe")
+///     .spawn_with_api(Arc::new(api.clone()))
+///     .unwrap_err();
+/// assert_eq!(err.code(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockProcessApi {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    exited: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next_pid: u32,
+    // The exit code of the processes, by identifier.
+    processes: HashMap<u32, Option<u32>>,
+    command_lines: Vec<OsString>,
+    specs: Vec<RecordedSpec>,
+    open_handles: usize,
+    // The codes of the next creations failing, in order.
+    create_errors: VecDeque<u32>,
+    wait_error: Option<u32>,
+    terminate_error: Option<u32>,
+    exit_code_error: Option<u32>,
+}
+
+impl MockProcessApi {
+    /// Creates a fake without processes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the next creation fail with a
+    /// [`CreationFailed`][Error::CreationFailed] error with `code`.
+    ///
+    /// Each call makes one more creation fail, so the creations that follow
+    /// a failure can be scripted too, like a failure that is retried.
+    pub fn fail_create(&self, code: u32) {
+        self.state().create_errors.push_back(code);
+    }
+
+    /// Makes the next wait fail with a [`WaitFailed`][Error::WaitFailed]
+    /// error with `code`, like a wait returning `WAIT_FAILED`.
+    pub fn fail_wait(&self, code: u32) {
+        self.state().wait_error = Some(code);
+    }
+
+    /// Makes the next termination fail with a
+    /// [`KillFailed`][Error::KillFailed] error with `code`.
+    pub fn fail_terminate(&self, code: u32) {
+        self.state().terminate_error = Some(code);
+    }
+
+    /// Makes the next query of an exit code fail with a
+    /// [`GetExitCodeFailed`][Error::GetExitCodeFailed] error with `code`.
+    pub fn fail_get_exit_code(&self, code: u32) {
+        self.state().exit_code_error = Some(code);
+    }
+
+    /// Ends the fake process with the identifier `pid`, which exits with
+    /// `exit_code`. Nothing happens if it has already exited.
+    pub fn exit(&self, pid: u32, exit_code: u32) {
+        let mut state = self.state();
+
+        if let Some(code @ None) = state.processes.get_mut(&pid) {
+            *code = Some(exit_code);
+            self.shared.exited.notify_all();
+        }
+    }
+
+    /// Returns the command lines of the fake processes that have been
+    /// created, in creation order.
+    pub fn command_lines(&self) -> Vec<OsString> {
+        self.state().command_lines.clone()
+    }
+
+    /// Returns what has been given to the creations of the fake processes,
+    /// in creation order. The failed creations are included.
+    pub fn specs(&self) -> Vec<RecordedSpec> {
+        self.state().specs.clone()
+    }
+
+    /// Returns the number of handles that haven't been closed.
+    pub fn open_handles(&self) -> usize {
+        self.state().open_handles
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// What has been given to the creation of a fake process, see
+/// [`MockProcessApi::specs`].
+///
+/// This is an owned copy of the [`ProcessSpec`], the handles being only
+/// recorded as present or not since they aren't valid after the creation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RecordedSpec {
+    /// The command line of the process.
+    pub command_line: OsString,
+    /// The program to execute.
+    pub application_name: Option<PathBuf>,
+    /// The working directory of the process.
+    pub current_directory: Option<PathBuf>,
+    /// Whether the process inherits the inheritable handles.
+    pub inherit_handles: bool,
+    /// The number of handles in the handle list, if there is one.
+    pub handle_list_len: Option<usize>,
+    /// The process creation flags.
+    pub creation_flags: u32,
+    /// The flags of the startup information.
+    pub startup_flags: u32,
+    /// The title of the startup information.
+    pub title: Option<OsString>,
+    /// The window station and desktop of the process.
+    pub desktop: Option<OsString>,
+    /// The hotkey of the process.
+    pub hotkey: Option<u16>,
+    /// The `lpReserved2` block of the startup information.
+    pub reserved2: Option<Vec<u8>>,
+    /// The protection level of the process.
+    pub protection_level: Option<u32>,
+    /// Whether the process runs with a token.
+    pub has_token: bool,
+    /// The `name=value` variables of the environment block, if there is one.
+    pub environment: Option<Vec<OsString>>,
+    /// Whether the standard handles are configured.
+    pub has_stdio: bool,
+    /// Whether the process is attached to a pseudoconsole.
+    pub has_pseudoconsole: bool,
+}
+
+impl RecordedSpec {
+    fn new(spec: &ProcessSpec<'_>) -> Self {
+        Self {
+            command_line: spec.command_line.to_owned(),
+            application_name: spec.application_name.map(PathBuf::from),
+            current_directory: spec.current_directory.map(PathBuf::from),
+            inherit_handles: spec.inherit_handles,
+            handle_list_len: spec.handle_list.map(<[RawHandle]>::len),
+            creation_flags: spec.creation_flags,
+            startup_flags: spec.startup_flags,
+            title: spec.title.map(OsString::from),
+            desktop: spec.desktop.map(OsString::from),
+            hotkey: spec.hotkey,
+            reserved2: spec.reserved2.map(<[u8]>::to_vec),
+            protection_level: spec.protection_level,
+            has_token: spec.token.is_some(),
+            // The block ends with an empty string.
+            environment: spec.environment.map(|block| {
+                block
+                    .split(|&unit| unit == 0)
+                    .take_while(|var| !var.is_empty())
+                    .map(OsString::from_wide)
+                    .collect()
+            }),
+            has_stdio: spec.stdio.is_some(),
+            has_pseudoconsole: spec.pseudoconsole.is_some(),
+        }
+    }
+}
+
+// The handles of a fake process are derived from its identifier.
+fn pid(handle: RawHandle) -> u32 {
+    (handle as usize / 8) as u32
+}
+
+impl ProcessApi for MockProcessApi {
+    fn create_process(&self, spec: &ProcessSpec<'_>) -> Result<RawProcess> {
+        let mut state = self.state();
+
+        state.specs.push(RecordedSpec::new(spec));
+
+        if let Some(code) = state.create_errors.pop_front() {
+            return Err(Error::CreationFailed(io::Error::from_raw_os_error(
+                code as i32,
+            )));
+        }
+
+        state.next_pid += 1;
+
+        let pid = state.next_pid;
+
+        state.processes.insert(pid, None);
+        state.command_lines.push(spec.command_line.to_owned());
+        state.open_handles += 2;
+
+        Ok(RawProcess {
+            process: (pid as usize * 8) as RawHandle,
+            thread: (pid as usize * 8 + 4) as RawHandle,
+            pid,
+            thread_id: pid,
+        })
+    }
+
+    fn terminate(&self, process: RawHandle, exit_code: u32) -> Result<()> {
+        if let Some(code) = self.state().terminate_error.take() {
+            return Err(Error::KillFailed(io::Error::from_raw_os_error(code as i32)));
+        }
+
+        self.exit(pid(process), exit_code);
+
+        Ok(())
+    }
+
+    fn wait(&self, process: RawHandle, timeout: Option<Duration>) -> Result<bool> {
+        let mut state = self.state();
+
+        if let Some(code) = state.wait_error.take() {
+            return Err(Error::WaitFailed(io::Error::from_raw_os_error(code as i32)));
+        }
+
+        let pid = pid(process);
+        let running = |state: &mut State| matches!(state.processes.get(&pid), Some(None));
+
+        let state = match timeout {
+            Some(timeout) => {
+                self.shared
+                    .exited
+                    .wait_timeout_while(state, timeout, running)
+                    .unwrap_or_else(|err| err.into_inner())
+                    .0
+            }
+            None => self
+                .shared
+                .exited
+                .wait_while(state, running)
+                .unwrap_or_else(|err| err.into_inner()),
+        };
+
+        Ok(!matches!(state.processes.get(&pid), Some(None)))
+    }
+
+    fn get_exit_code(&self, process: RawHandle) -> Result<u32> {
+        let mut state = self.state();
+
+        if let Some(code) = state.exit_code_error.take() {
+            return Err(Error::GetExitCodeFailed(io::Error::from_raw_os_error(
+                code as i32,
+            )));
+        }
+
+        match state.processes.get(&pid(process)) {
+            Some(code) => Ok(code.unwrap_or(STILL_ACTIVE)),
+            // `ERROR_INVALID_HANDLE`
+            None => Err(Error::GetExitCodeFailed(io::Error::from_raw_os_error(6))),
+        }
+    }
+
+    fn close_handle(&self, _handle: RawHandle) {
+        let mut state = self.state();
+
+        state.open_handles = state.open_handles.saturating_sub(1);
+    }
+}
@@ -0,0 +1,517 @@
+use crate::{
+    stdio::io_error, truncate, Child, Command, ExitStatus, ExitStatusError, Operation, Result,
+};
+use std::{
+    borrow::Cow,
+    fmt,
+    io::Read,
+    thread,
+    time::{Duration, Instant},
+};
+
+// How many bytes of each stream are shown by the `Debug` implementation.
+const DEBUG_LEN: usize = 256;
+
+// How many characters of the standard error are shown by `OutputError`.
+const ERROR_STDERR_LEN: usize = 512;
+
+/// The output of a finished process.
+///
+/// Returned by [`Command::output`].
+///
+/// With the `serde` feature, the output is serialized as a struct, the
+/// streams being base64 strings (the standard alphabet, with padding) since
+/// they can contain any bytes:
+///
+/// ```json
+/// { "status": 0, "stdout": "aGVsbG8NCg==", "stderr": "" }
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Output {
+    /// The exit status of the process.
+    pub status: ExitStatus,
+    /// The data that the process wrote to its standard output.
+    #[cfg_attr(feature = "serde", serde(with = "crate::base64_bytes"))]
+    pub stdout: Vec<u8>,
+    /// The data that the process wrote to its standard error.
+    #[cfg_attr(feature = "serde", serde(with = "crate::base64_bytes"))]
+    pub stderr: Vec<u8>,
+}
+
+/// The error of an [`Output`] with an unsuccessful exit status.
+///
+/// Returned by [`Output::exit_ok`]. The message includes the beginning of the
+/// standard error, which usually tells why the process failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputError {
+    error: ExitStatusError,
+    output: Output,
+}
+
+/// The resources used by a process.
+///
+/// Returned by [`Command::output_with_usage`]. The values that couldn't be
+/// queried are `None`.
+///
+/// With the `serde` feature, the durations are serialized like serde does,
+/// as their seconds and nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResourceUsage {
+    /// The amount of time that the process has executed in user mode.
+    pub user_time: Option<Duration>,
+    /// The amount of time that the process has executed in kernel mode.
+    pub kernel_time: Option<Duration>,
+    /// The time elapsed between spawning the process and the end of the wait.
+    pub wall_time: Duration,
+    /// The peak working set size, in bytes.
+    pub peak_working_set: Option<u64>,
+    /// The number of bytes read.
+    pub io_read_bytes: Option<u64>,
+    /// The number of bytes written.
+    pub io_write_bytes: Option<u64>,
+}
+
+impl Output {
+    /// Returns `true` if the process exited successfully.
+    ///
+    /// See [`ExitStatus::success`] for more information.
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    /// Converts the output into a `Result`, an unsuccessful status being an
+    /// [`OutputError`] that keeps the output.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let output = Command::new("cargo.exe metadata --format-version 1")
+    ///     .output()
+    ///     .unwrap()
+    ///     .exit_ok()
+    ///     .expect("cargo metadata failed");
+    ///
+    /// println!("{}", output.stdout_str());
+    /// ```
+    pub fn exit_ok(self) -> std::result::Result<Output, OutputError> {
+        match self.status.exit_ok() {
+            Ok(()) => Ok(self),
+            Err(error) => Err(OutputError {
+                error,
+                output: self,
+            }),
+        }
+    }
+
+    /// Returns the standard output converted to a string.
+    ///
+    /// The invalid UTF-8 sequences are replaced with `U+FFFD REPLACEMENT
+    /// CHARACTER`, like [`String::from_utf8_lossy`] does.
+    pub fn stdout_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    /// Returns the standard error converted to a string.
+    ///
+    /// See [`stdout_str`][Output::stdout_str] for more information.
+    pub fn stderr_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+
+    /// Returns an iterator over the lines of the standard output.
+    ///
+    /// The lines end with `\n` or `\r\n`, which isn't included. The invalid
+    /// UTF-8 sequences are replaced like [`stdout_str`][Output::stdout_str]
+    /// does.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let output = Command::new("cmd.exe /c dir /b").output().unwrap();
+    ///
+    /// for file in output.stdout_lines() {
+    ///     println!("{}", file);
+    /// }
+    /// ```
+    pub fn stdout_lines(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        lines(&self.stdout)
+    }
+
+    /// Returns an iterator over the lines of the standard error.
+    ///
+    /// See [`stdout_lines`][Output::stdout_lines] for more information.
+    pub fn stderr_lines(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        lines(&self.stderr)
+    }
+}
+
+impl fmt::Debug for Output {
+    /// Formats the status and the beginning of each stream as escaped text.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Output")
+            .field("status", &self.status)
+            .field("stdout", &Truncated(&self.stdout))
+            .field("stderr", &Truncated(&self.stderr))
+            .finish()
+    }
+}
+
+impl OutputError {
+    /// Returns the exit status of the process.
+    pub fn status(&self) -> ExitStatus {
+        self.output.status
+    }
+
+    /// Returns the output of the process.
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Converts into the output of the process.
+    pub fn into_output(self) -> Output {
+        self.output
+    }
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+
+        let stderr = self.output.stderr_str();
+        let stderr = stderr.trim();
+
+        if !stderr.is_empty() {
+            write!(f, ": {}", truncate(stderr, ERROR_STDERR_LEN))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+// Format the beginning of a stream as escaped text.
+struct Truncated<'a>(&'a [u8]);
+
+impl fmt::Debug for Truncated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.0.len().min(DEBUG_LEN);
+
+        write!(f, "\"{}\"", self.0[..len].escape_ascii())?;
+
+        if len < self.0.len() {
+            write!(f, "... ({} bytes)", self.0.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Command {
+    /// Executes the command as a child process, waiting for it to finish and
+    /// collecting all of its output.
+    ///
+    /// By default, the standard output and the standard error are captured
+    /// and the standard input is connected to the `NUL` device. Both streams
+    /// are read concurrently, so the child cannot get stuck writing to a full
+    /// pipe. If the output cannot be read, a
+    /// [`StdioFailed`][crate::Error::StdioFailed] error is returned, wrapped
+    /// in a [`CommandFailed`][crate::Error::CommandFailed] error like the
+    /// other errors.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let output = Command::new("cmd.exe /c echo hello")
+    ///     .output()
+    ///     .expect("failed to execute process");
+    ///
+    /// assert!(output.status.success());
+    /// assert_eq!(output.stdout, b"hello\r\n");
+    /// ```
+    pub fn output(&mut self) -> Result<Output> {
+        self.output_and_child().map(|(output, _)| output)
+    }
+
+    /// Executes the command as a child process like
+    /// [`output`][Command::output], also collecting the resources it used.
+    ///
+    /// The resources are queried once the child has exited, before its
+    /// handles are closed. The ones that cannot be queried, usually because
+    /// of the access rights, are `None` instead of failing the whole call.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let (output, usage) = Command::new("cargo.exe build --release")
+    ///     .output_with_usage()
+    ///     .unwrap();
+    ///
+    /// println!("exited with {} in {:?}", output.status, usage.wall_time);
+    /// println!("cpu time: {:?}", usage.user_time);
+    /// println!("peak memory: {:?} bytes", usage.peak_working_set);
+    /// ```
+    pub fn output_with_usage(&mut self) -> Result<(Output, ResourceUsage)> {
+        let start = Instant::now();
+        let (output, child) = self.output_and_child()?;
+        let wall_time = start.elapsed();
+
+        let times = child.process_times().ok();
+        let memory = child.memory_info().ok();
+        let io = child.io_counters().ok();
+
+        let usage = ResourceUsage {
+            user_time: times.map(|times| times.user_time),
+            kernel_time: times.map(|times| times.kernel_time),
+            wall_time,
+            peak_working_set: memory.map(|memory| memory.peak_working_set_size),
+            io_read_bytes: io.map(|io| io.read_bytes),
+            io_write_bytes: io.map(|io| io.write_bytes),
+        };
+
+        Ok((output, usage))
+    }
+
+    // Collect the output like `output`, also returning the child that has
+    // exited.
+    fn output_and_child(&mut self) -> Result<(Output, Child)> {
+        let mut child = self
+            .spawn_with_default_stdio(true)
+            .map_err(self.context(Operation::Spawn, None))?;
+
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        let (stdout, stderr) = match (stdout, stderr) {
+            (Some(stdout), Some(stderr)) => {
+                // Read the standard error on another thread, the child blocks
+                // as soon as one of the pipes is full.
+                let stderr = thread::spawn(move || read_to_end(Some(stderr)));
+                let stdout = read_to_end(Some(stdout));
+                let stderr = stderr
+                    .join()
+                    .unwrap_or_else(|err| std::panic::resume_unwind(err));
+
+                (stdout, stderr)
+            }
+            (stdout, stderr) => (read_to_end(stdout), read_to_end(stderr)),
+        };
+        let pid = Some(child.id());
+        let stdout = stdout.map_err(self.context(Operation::ReadOutput, pid))?;
+        let stderr = stderr.map_err(self.context(Operation::ReadOutput, pid))?;
+
+        let status = child.wait().map_err(self.context(Operation::Wait, pid))?;
+
+        let output = Output {
+            status,
+            stdout,
+            stderr,
+        };
+
+        Ok((output, child))
+    }
+
+    /// Executes the command as a child process, waiting asynchronously for it
+    /// to finish and collecting all of its output.
+    ///
+    /// Both streams are read concurrently with the wait. See
+    /// [`output`][Command::output] for more information.
+    ///
+    /// Requires the `tokio` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// # async fn run() {
+    /// let output = Command::new("cargo.exe check")
+    ///     .output_async()
+    ///     .await
+    ///     .expect("failed to execute process");
+    ///
+    /// println!("{}", String::from_utf8_lossy(&output.stderr));
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn output_async(&mut self) -> Result<Output> {
+        let mut child = self
+            .spawn_with_default_stdio(true)
+            .map_err(self.context(Operation::Spawn, None))?;
+
+        let stdout = child.stdout.take().map(crate::ChildStdout::into_async);
+        let stderr = child.stderr.take().map(crate::ChildStderr::into_async);
+
+        let (stdout, stderr, status) = tokio::join!(
+            read_to_end_async(stdout),
+            read_to_end_async(stderr),
+            child.wait_async(),
+        );
+
+        let pid = Some(child.id());
+
+        Ok(Output {
+            status: status.map_err(self.context(Operation::Wait, pid))?,
+            stdout: stdout.map_err(self.context(Operation::ReadOutput, pid))?,
+            stderr: stderr.map_err(self.context(Operation::ReadOutput, pid))?,
+        })
+    }
+}
+
+// Split the lines like `str::lines` does.
+fn lines(bytes: &[u8]) -> impl Iterator<Item = Cow<'_, str>> {
+    let bytes = match bytes {
+        [] => None,
+        bytes => Some(bytes.strip_suffix(b"\n").unwrap_or(bytes)),
+    };
+
+    bytes
+        .into_iter()
+        .flat_map(|bytes| bytes.split(|&byte| byte == b'\n'))
+        .map(|line| String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)))
+}
+
+fn read_to_end(reader: Option<impl Read>) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    if let Some(mut reader) = reader {
+        reader.read_to_end(&mut buffer).map_err(io_error)?;
+    }
+
+    Ok(buffer)
+}
+
+#[cfg(feature = "tokio")]
+async fn read_to_end_async(reader: Option<impl tokio::io::AsyncRead + Unpin>) -> Result<Vec<u8>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut buffer = Vec::new();
+
+    if let Some(mut reader) = reader {
+        reader.read_to_end(&mut buffer).await.map_err(io_error)?;
+    }
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(stdout: &[u8], stderr: &[u8]) -> Output {
+        Output {
+            status: ExitStatus::from_raw(0),
+            stdout: stdout.to_vec(),
+            stderr: stderr.to_vec(),
+        }
+    }
+
+    #[test]
+    fn lines_end_with_lf_or_crlf() {
+        let output = output(b"one\r\ntwo\nthree\r\n\r\nlast", b"");
+
+        assert_eq!(
+            output.stdout_lines().collect::<Vec<_>>(),
+            ["one", "two", "three", "", "last"]
+        );
+        assert_eq!(output.stderr_lines().count(), 0);
+        // A lone `\r` doesn't end a line, the final `\n` doesn't start one.
+        assert_eq!(lines(b"a\rb\n").collect::<Vec<_>>(), ["a\rb"]);
+        assert_eq!(lines(b"\n").collect::<Vec<_>>(), [""]);
+    }
+
+    #[test]
+    fn invalid_utf8_in_lines_is_replaced() {
+        let output = output(b"", b"caf\xe9\r\n\xff\xfe ok\r\n");
+
+        assert_eq!(
+            output.stderr_lines().collect::<Vec<_>>(),
+            ["caf\u{fffd}", "\u{fffd}\u{fffd} ok"]
+        );
+        assert_eq!(
+            output.stderr_str(),
+            "caf\u{fffd}\r\n\u{fffd}\u{fffd} ok\r\n"
+        );
+    }
+
+    #[test]
+    fn error_message_includes_the_standard_error() {
+        let mut failed = output(b"partial", b"  error: missing file\r\n");
+        failed.status = ExitStatus::from_raw(2);
+
+        let err = failed.clone().exit_ok().unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "process exited unsuccessfully: exit code 2: error: missing file"
+        );
+        assert_eq!(err.status(), ExitStatus::from_raw(2));
+        assert_eq!(err.output(), &failed);
+        assert_eq!(err.into_output(), failed);
+    }
+
+    #[test]
+    fn error_message_truncates_a_long_standard_error() {
+        let mut failed = output(b"", "é".repeat(ERROR_STDERR_LEN * 2).as_bytes());
+        failed.status = ExitStatus::from_raw(1);
+
+        let message = failed.exit_ok().unwrap_err().to_string();
+
+        assert_eq!(
+            message,
+            format!(
+                "process exited unsuccessfully: exit code 1: {}...",
+                "é".repeat(ERROR_STDERR_LEN)
+            )
+        );
+
+        // Nothing is appended without a standard error.
+        let mut failed = output(b"out", b" \r\n");
+        failed.status = ExitStatus::from_raw(1);
+        assert_eq!(
+            failed.exit_ok().unwrap_err().to_string(),
+            "process exited unsuccessfully: exit code 1"
+        );
+    }
+
+    #[test]
+    fn successful_output_passes_through() {
+        let succeeded = output(b"out", b"warning");
+
+        assert_eq!(succeeded.clone().exit_ok(), Ok(succeeded));
+    }
+
+    #[test]
+    fn debug_escapes_and_truncates_the_streams() {
+        let long = vec![b'x'; DEBUG_LEN + 10];
+        let output = output(b"hi\r\n\xff", &long);
+
+        let debug = format!("{:?}", output);
+
+        assert!(debug.starts_with("Output { status: "), "{debug}");
+        assert!(debug.contains(r#"stdout: "hi\r\n\xff""#), "{debug}");
+        assert!(
+            debug.contains(&format!(
+                "stderr: \"{}\"... ({} bytes)",
+                "x".repeat(DEBUG_LEN),
+                DEBUG_LEN + 10
+            )),
+            "{debug}"
+        );
+        assert_eq!(format!("{:?}", Truncated(b"")), "\"\"");
+        assert_eq!(
+            format!("{:?}", Truncated(&long[..DEBUG_LEN])),
+            format!("\"{}\"", "x".repeat(DEBUG_LEN))
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! Conversions between the UNC paths and their verbatim form.
+//!
+//! A file on a network share has a UNC path like `\\server\share\tool.exe`,
+//! and a verbatim form like `\\?\UNC\server\share\tool.exe`, which isn't
+//! limited to `MAX_PATH` characters and is returned by
+//! [`std::fs::canonicalize`]. Both can be given to [`Command::new`] and
+//! [`Command::current_dir`], but many programs don't understand a verbatim
+//! working directory: it is converted to the usual form when spawning.
+//!
+//! Note that `cmd.exe` refuses a UNC working directory, see
+//! [`Command::current_dir`].
+//!
+//! [`Command::new`]: crate::Command::new
+//! [`Command::current_dir`]: crate::Command::current_dir
+
+use crate::wow64;
+use std::{
+    ffi::OsString,
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::{Component, Path, PathBuf, Prefix},
+};
+
+// The verbatim paths are limited to 32,767 characters, the working directory
+// to `MAX_PATH` including the null terminator and the trailing backslash.
+const MAX_PATH: usize = 260;
+
+const BACKSLASH: u16 = b'\\' as u16;
+
+/// Converts a path to its verbatim form.
+///
+/// The UNC paths are prefixed with `\\?\UNC\` and the absolute paths of a
+/// drive with `\\?\`. The other paths, like the relative ones, are returned
+/// unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::path;
+/// use std::path::Path;
+///
+/// assert_eq!(
+///     path::to_verbatim(Path::new(r"\\server\tools\lint.exe")),
+///     Path::new(r"\\?\UNC\server\tools\lint.exe"),
+/// );
+/// assert_eq!(path::to_verbatim(Path::new(r"C:\tools")), Path::new(r"\\?\C:\tools"));
+/// assert_eq!(path::to_verbatim(Path::new(r"tools")), Path::new(r"tools"));
+/// ```
+pub fn to_verbatim(path: &Path) -> PathBuf {
+    let (prefix, rest) = match split_prefix(path) {
+        Some(split) => split,
+        None => return path.to_path_buf(),
+    };
+
+    let verbatim_prefix = match prefix {
+        Prefix::UNC(server, share) => {
+            let mut verbatim = OsString::from(r"\\?\UNC\");
+            verbatim.push(server);
+            verbatim.push(r"\");
+            verbatim.push(share);
+            verbatim
+        }
+        // Only the absolute paths of a drive, `C:tools` is relative to the
+        // working directory of the drive.
+        Prefix::Disk(drive) if rest.first() == Some(&BACKSLASH) => {
+            OsString::from(format!(r"\\?\{}:", drive as char))
+        }
+        _ => return path.to_path_buf(),
+    };
+
+    join(verbatim_prefix, &rest)
+}
+
+/// Converts a verbatim path to its usual form.
+///
+/// `\\?\UNC\server\share` becomes `\\server\share` and `\\?\C:\` becomes
+/// `C:\`. The path is returned unchanged if it isn't verbatim, or if it
+/// cannot be represented without the prefix: if it is too long, or contains
+/// components that would be interpreted differently, like `..` or a trailing
+/// dot.
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::path;
+/// use std::path::Path;
+///
+/// assert_eq!(
+///     path::from_verbatim(Path::new(r"\\?\UNC\server\tools")),
+///     Path::new(r"\\server\tools"),
+/// );
+/// assert_eq!(path::from_verbatim(Path::new(r"\\?\C:\tools")), Path::new(r"C:\tools"));
+/// assert_eq!(path::from_verbatim(Path::new(r"\\?\C:\tools.")), Path::new(r"\\?\C:\tools."));
+/// ```
+pub fn from_verbatim(path: &Path) -> PathBuf {
+    let (prefix, rest) = match split_prefix(path) {
+        Some(split) => split,
+        None => return path.to_path_buf(),
+    };
+
+    let usual_prefix = match prefix {
+        Prefix::VerbatimUNC(server, share) => {
+            let mut usual = OsString::from(r"\\");
+            usual.push(server);
+            usual.push(r"\");
+            usual.push(share);
+            usual
+        }
+        // `C:` alone would be relative to the working directory of the drive.
+        Prefix::VerbatimDisk(drive) if rest.first() == Some(&BACKSLASH) => {
+            OsString::from(format!("{}:", drive as char))
+        }
+        _ => return path.to_path_buf(),
+    };
+
+    let usual = join(usual_prefix, &rest);
+
+    if usual.as_os_str().encode_wide().count() < MAX_PATH && is_unambiguous(&rest) {
+        usual
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Returns `true` if the path is on a network share, in its usual or
+/// verbatim form.
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::path;
+/// use std::path::Path;
+///
+/// assert!(path::is_unc(Path::new(r"\\server\tools")));
+/// assert!(path::is_unc(Path::new(r"\\?\UNC\server\tools")));
+/// assert!(!path::is_unc(Path::new(r"C:\tools")));
+/// ```
+pub fn is_unc(path: &Path) -> bool {
+    matches!(
+        split_prefix(path),
+        Some((Prefix::UNC(..) | Prefix::VerbatimUNC(..), _))
+    )
+}
+
+/// Rewrites a path in the `System32` directory to use the `SysNative` alias,
+/// if the calling process is a 32-bit process on 64-bit Windows.
+///
+/// The accesses of a 32-bit process to `C:\Windows\System32` are redirected
+/// to `C:\Windows\SysWOW64`, which only contains 32-bit programs: a program
+/// that only ships in 64-bit isn't found. `SysNative` isn't redirected, the
+/// 64-bit program is spawned. Unlike
+/// [`Command::disable_wow64_redirection`][crate::Command::disable_wow64_redirection],
+/// this doesn't change the redirection of the calling thread.
+///
+/// The path is returned unchanged by a 64-bit process, or if it isn't in
+/// `System32`.
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::path;
+/// use std::path::Path;
+///
+/// let program = path::sysnative_hint(Path::new(r"C:\Windows\System32\bcdedit.exe"));
+///
+/// if cfg!(target_pointer_width = "64") {
+///     assert_eq!(program, Path::new(r"C:\Windows\System32\bcdedit.exe"));
+/// }
+/// ```
+pub fn sysnative_hint(path: &Path) -> PathBuf {
+    match wow64::is_wow64().then(wow64::windows_directory).flatten() {
+        Some(windows) => replace_system32(path, &windows),
+        None => path.to_path_buf(),
+    }
+}
+
+// Replace the `System32` directory of `windows` at the start of `path` with
+// `SysNative`, ignoring the case like the file system.
+fn replace_system32(path: &Path, windows: &Path) -> PathBuf {
+    let mut components = path.components();
+
+    for expected in windows.join("System32").components() {
+        match components.next() {
+            Some(component) if component.as_os_str().eq_ignore_ascii_case(expected) => {}
+            _ => return path.to_path_buf(),
+        }
+    }
+
+    windows.join("SysNative").join(components.as_path())
+}
+
+// Split a path into its prefix and the wide string that follows it.
+fn split_prefix(path: &Path) -> Option<(Prefix<'_>, Vec<u16>)> {
+    match path.components().next()? {
+        Component::Prefix(prefix) => {
+            let len = prefix.as_os_str().encode_wide().count();
+            let rest = path.as_os_str().encode_wide().skip(len).collect();
+
+            Some((prefix.kind(), rest))
+        }
+        _ => None,
+    }
+}
+
+fn join(prefix: OsString, rest: &[u16]) -> PathBuf {
+    let mut path = prefix.encode_wide().collect::<Vec<_>>();
+    path.extend_from_slice(rest);
+
+    PathBuf::from(OsString::from_wide(&path))
+}
+
+// The path is parsed the same way without the verbatim prefix: the separators
+// are backslashes and the components aren't `.`, `..` or names ending with a
+// dot or a space, which are normalized away.
+fn is_unambiguous(rest: &[u16]) -> bool {
+    !rest.contains(&(b'/' as u16))
+        && rest
+            .split(|&unit| unit == BACKSLASH)
+            .filter(|component| !component.is_empty())
+            .all(|component| {
+                !matches!(component.last(), Some(&unit) if unit == b'.' as u16 || unit == b' ' as u16)
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system32_is_replaced_by_sysnative() {
+        assert_eq!(
+            replace_system32(
+                Path::new(r"C:\Windows\System32\bcdedit.exe"),
+                Path::new(r"C:\Windows")
+            ),
+            Path::new(r"C:\Windows\SysNative\bcdedit.exe")
+        );
+        // The file system ignores the case.
+        assert_eq!(
+            replace_system32(
+                Path::new(r"c:\windows\system32\drivers\etc\hosts"),
+                Path::new(r"C:\Windows")
+            ),
+            Path::new(r"C:\Windows\SysNative\drivers\etc\hosts")
+        );
+    }
+
+    #[test]
+    fn other_paths_are_unchanged() {
+        let windows = Path::new(r"C:\Windows");
+
+        for path in [
+            r"C:\Windows\SysWOW64\bcdedit.exe",
+            r"C:\Windows\System32x\tool.exe",
+            r"D:\Windows\System32\bcdedit.exe",
+            r"C:\tools\System32\bcdedit.exe",
+            r"bcdedit.exe",
+        ] {
+            assert_eq!(replace_system32(Path::new(path), windows), Path::new(path));
+        }
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn native_process_gets_the_path_unchanged() {
+        let path = Path::new(r"C:\Windows\System32\bcdedit.exe");
+
+        assert_eq!(sysnative_hint(path), path);
+    }
+}
@@ -0,0 +1,431 @@
+// Pipes connecting the calling process and its children.
+//
+// Anonymous pipes don't support overlapped I/O, so the pipes are named pipes
+// with a unique name, like the anonymous pipes of libuv and of the standard
+// library. The end kept by the calling process is opened for overlapped I/O,
+// the end given to the child is a plain synchronous handle since most programs
+// don't expect an overlapped standard stream.
+//
+// The named pipe only accepts a single instance (`FILE_FLAG_FIRST_PIPE_INSTANCE`
+// with one instance at most) and is connected right after it has been created,
+// so no other process can connect to it. Remote clients are rejected too. The
+// DACL of the pipe only grants access to the user of the calling process,
+// instead of the default DACL which also grants it to LocalSystem and to the
+// administrators.
+
+use crate::{wide, Error, Result};
+use std::{
+    collections::hash_map::RandomState,
+    ffi::{c_void, OsStr},
+    fs::{File, OpenOptions},
+    hash::{BuildHasher, Hasher},
+    io::{self, Read, Write},
+    mem::{size_of, size_of_val},
+    os::windows::io::{
+        AsHandle, AsRawHandle, BorrowedHandle, FromRawHandle, IntoRawHandle, OwnedHandle, RawHandle,
+    },
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{
+            GetLastError, LocalFree, ERROR_ACCESS_DENIED, ERROR_BROKEN_PIPE, ERROR_IO_PENDING,
+            ERROR_PIPE_BUSY, HANDLE, HLOCAL,
+        },
+        Security::{
+            Authorization::{
+                ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+                SDDL_REVISION_1,
+            },
+            GetTokenInformation, TokenUser, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, TOKEN_QUERY,
+            TOKEN_USER,
+        },
+        Storage::FileSystem::{
+            ReadFile, WriteFile, FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED,
+            PIPE_ACCESS_INBOUND, PIPE_ACCESS_OUTBOUND,
+        },
+        System::{
+            Pipes::{
+                CreateNamedPipeW, CreatePipe, PeekNamedPipe, PIPE_READMODE_BYTE,
+                PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_WAIT,
+            },
+            Threading::{CreateEventW, GetCurrentProcess, GetCurrentProcessId, OpenProcessToken},
+            IO::{GetOverlappedResult, OVERLAPPED},
+        },
+    },
+};
+
+// The default size of the buffers of the pipes.
+pub(crate) const DEFAULT_BUFFER_SIZE: u32 = 64 * 1024;
+
+// The largest size of the buffers of the pipes. The buffers are taken from
+// the nonpaged pool of the system, which is limited.
+pub(crate) const MAX_BUFFER_SIZE: u32 = 16 * 1024 * 1024;
+
+// How many names are tried before giving up when a pipe with the same name
+// already exists.
+const MAX_ATTEMPTS: usize = 16;
+
+static PIPE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// The end of a pipe kept by the calling process. It is opened for overlapped
+// I/O, a blocking read or write waits for the overlapped operation to
+// complete.
+#[derive(Debug)]
+pub(crate) struct Pipe {
+    handle: OwnedHandle,
+    // Manual-reset event signaled when an operation completes.
+    event: OwnedHandle,
+}
+
+impl Pipe {
+    fn new(handle: OwnedHandle) -> Result<Self> {
+        let event = unsafe {
+            CreateEventW(None, true, false, PCWSTR::null())
+                .map_err(|_| Error::StdioFailed(io::Error::last_os_error()))?
+        };
+
+        Ok(Self {
+            handle,
+            event: unsafe { owned(event) },
+        })
+    }
+
+    pub(crate) fn raw(&self) -> HANDLE {
+        HANDLE(self.handle.as_raw_handle() as isize)
+    }
+
+    // Read without blocking, returning `None` when no data is available yet.
+    // The end of file is returned once the other end has been closed.
+    pub(crate) fn try_read(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        let mut available: u32 = 0;
+
+        let res = unsafe { PeekNamedPipe(self.raw(), None, 0, None, Some(&mut available), None) };
+
+        if res.is_err() {
+            let err = io::Error::last_os_error();
+
+            // The other end has been closed, this is the end of file.
+            if err.raw_os_error() == Some(ERROR_BROKEN_PIPE.0 as i32) {
+                return Ok(Some(0));
+            }
+
+            return Err(err);
+        }
+
+        if available == 0 {
+            return Ok(None);
+        }
+
+        // Only the available bytes are read, so the read completes right away.
+        let len = buf.len().min(available as usize);
+
+        self.read(&mut buf[..len]).map(Some)
+    }
+
+    // Start an overlapped operation and wait for its completion, returning
+    // the number of bytes transferred.
+    fn overlapped(
+        &self,
+        start: impl FnOnce(*mut OVERLAPPED) -> windows::core::Result<()>,
+    ) -> io::Result<usize> {
+        let mut overlapped = OVERLAPPED {
+            hEvent: HANDLE(self.event.as_raw_handle() as isize),
+            ..Default::default()
+        };
+
+        unsafe {
+            if start(&mut overlapped).is_err() && GetLastError() != ERROR_IO_PENDING {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut transferred: u32 = 0;
+
+            GetOverlappedResult(self.raw(), &overlapped, &mut transferred, true)
+                .map_err(|_| io::Error::last_os_error())?;
+
+            Ok(transferred as usize)
+        }
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(u32::MAX as usize);
+
+        let res = self.overlapped(|overlapped| unsafe {
+            ReadFile(self.raw(), Some(&mut buf[..len]), None, Some(overlapped))
+        });
+
+        match res {
+            // The other end has been closed, this is the end of file.
+            Err(err) if err.raw_os_error() == Some(ERROR_BROKEN_PIPE.0 as i32) => Ok(0),
+            res => res,
+        }
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(u32::MAX as usize);
+
+        self.overlapped(|overlapped| unsafe {
+            WriteFile(self.raw(), Some(&buf[..len]), None, Some(overlapped))
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsHandle for Pipe {
+    fn as_handle(&self) -> BorrowedHandle<'_> {
+        self.handle.as_handle()
+    }
+}
+
+impl AsRawHandle for Pipe {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.handle.as_raw_handle()
+    }
+}
+
+impl IntoRawHandle for Pipe {
+    fn into_raw_handle(self) -> RawHandle {
+        self.handle.into_raw_handle()
+    }
+}
+
+// Create a pipe, returning the end kept by the calling process (readable if
+// `ours_readable` is `true`) and the end given to the child. Both ends are not
+// inheritable. The system treats `buffer_size` as a hint.
+pub(crate) fn pipe(ours_readable: bool, buffer_size: u32) -> Result<(Pipe, OwnedHandle)> {
+    let descriptor = user_only_security_descriptor()?;
+    let security_attributes = SECURITY_ATTRIBUTES {
+        nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
+        lpSecurityDescriptor: descriptor.0 .0,
+        bInheritHandle: false.into(),
+    };
+    let mut attempts = 0;
+
+    loop {
+        let name = format!(
+            r"\\.\pipe\__create_process_w_anonymous_pipe__.{}.{}.{:x}",
+            unsafe { GetCurrentProcessId() },
+            PIPE_COUNTER.fetch_add(1, Ordering::Relaxed),
+            RandomState::new().build_hasher().finish(),
+        );
+        let wide_name = wide::to_wide(OsStr::new(&name))?;
+
+        let access = if ours_readable {
+            PIPE_ACCESS_INBOUND
+        } else {
+            PIPE_ACCESS_OUTBOUND
+        };
+
+        let handle = unsafe {
+            CreateNamedPipeW(
+                PCWSTR(wide_name.as_ptr()),
+                access | FILE_FLAG_FIRST_PIPE_INSTANCE | FILE_FLAG_OVERLAPPED,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                1,
+                buffer_size,
+                buffer_size,
+                0,
+                Some(&security_attributes),
+            )
+        };
+
+        if handle.is_invalid() {
+            let code = unsafe { GetLastError() };
+            attempts += 1;
+
+            // A pipe with the same name already exists, try another name.
+            if (code == ERROR_ACCESS_DENIED || code == ERROR_PIPE_BUSY) && attempts < MAX_ATTEMPTS {
+                continue;
+            }
+
+            return Err(Error::StdioFailed(io::Error::from_raw_os_error(
+                code.0 as i32,
+            )));
+        }
+
+        let ours = Pipe::new(unsafe { owned(handle) })?;
+
+        // Connect the end of the child, which is opened for synchronous I/O.
+        let theirs = OpenOptions::new()
+            .read(!ours_readable)
+            .write(ours_readable)
+            .open(&name)
+            .map_err(crate::stdio::io_error)?;
+
+        return Ok((ours, OwnedHandle::from(theirs)));
+    }
+}
+
+// A security descriptor allocated by
+// `ConvertStringSecurityDescriptorToSecurityDescriptorW`.
+struct LocalSecurityDescriptor(PSECURITY_DESCRIPTOR);
+
+impl Drop for LocalSecurityDescriptor {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = LocalFree(HLOCAL(self.0 .0 as isize));
+        }
+    }
+}
+
+// Create a security descriptor whose protected DACL grants all the access to
+// the user of the calling process only.
+fn user_only_security_descriptor() -> Result<LocalSecurityDescriptor> {
+    let error = |_| Error::StdioFailed(io::Error::last_os_error());
+
+    unsafe {
+        let mut token = HANDLE::default();
+
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).map_err(error)?;
+
+        // Closed on every path.
+        let token = owned(token);
+
+        // Large enough for a `TOKEN_USER` followed by the longest SID, and
+        // aligned like it.
+        let mut buffer = [0u64; 16];
+        let mut len = 0;
+
+        GetTokenInformation(
+            HANDLE(token.as_raw_handle() as isize),
+            TokenUser,
+            Some(buffer.as_mut_ptr() as *mut c_void),
+            size_of_val(&buffer) as u32,
+            &mut len,
+        )
+        .map_err(error)?;
+
+        let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+        let mut sid = PWSTR::null();
+
+        ConvertSidToStringSidW(user.User.Sid, &mut sid).map_err(error)?;
+
+        let sddl = "D:P(A;;GA;;;"
+            .encode_utf16()
+            .chain(sid.as_wide().iter().copied())
+            .chain(")\0".encode_utf16())
+            .collect::<Vec<_>>();
+
+        let _ = LocalFree(HLOCAL(sid.0 as isize));
+
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            PCWSTR(sddl.as_ptr()),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        )
+        .map_err(error)?;
+
+        Ok(LocalSecurityDescriptor(descriptor))
+    }
+}
+
+// Create an anonymous pipe for synchronous I/O, returning the read and write
+// ends. Both ends are not inheritable. The system treats `buffer_size` as a
+// hint.
+pub(crate) fn anonymous_pipe(buffer_size: u32) -> Result<(OwnedHandle, OwnedHandle)> {
+    let mut read = HANDLE::default();
+    let mut write = HANDLE::default();
+
+    unsafe {
+        CreatePipe(&mut read, &mut write, None, buffer_size)
+            .map_err(|_| Error::StdioFailed(io::Error::last_os_error()))?;
+
+        Ok((owned(read), owned(write)))
+    }
+}
+
+// Relay a pipe opened for overlapped I/O through a synchronous pipe, returning
+// the end of the synchronous pipe to give to a child. This is used when the
+// end of a pipe kept by the calling process is given to another child. The
+// data is copied by a thread until the end of file.
+pub(crate) fn relay(mut pipe: Pipe, child_readable: bool, buffer_size: u32) -> Result<OwnedHandle> {
+    let (read, write) = anonymous_pipe(buffer_size)?;
+
+    if child_readable {
+        thread::spawn(move || {
+            let _ = io::copy(&mut pipe, &mut File::from(write));
+        });
+
+        Ok(read)
+    } else {
+        thread::spawn(move || {
+            let _ = io::copy(&mut File::from(read), &mut pipe);
+        });
+
+        Ok(write)
+    }
+}
+
+pub(crate) unsafe fn owned(handle: HANDLE) -> OwnedHandle {
+    OwnedHandle::from_raw_handle(handle.0 as RawHandle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn end_of_the_calling_process_is_overlapped() {
+        let (ours, theirs) = pipe(true, DEFAULT_BUFFER_SIZE).unwrap();
+
+        // A synchronous read would block until this write.
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            File::from(theirs).write_all(b"x").unwrap();
+        });
+
+        let mut buf = [0u8; 1];
+        let mut overlapped = OVERLAPPED {
+            hEvent: HANDLE(ours.event.as_raw_handle() as isize),
+            ..Default::default()
+        };
+
+        unsafe {
+            let res = ReadFile(ours.raw(), Some(&mut buf), None, Some(&mut overlapped));
+
+            assert!(res.is_err());
+            assert_eq!(GetLastError(), ERROR_IO_PENDING);
+
+            let mut transferred: u32 = 0;
+
+            GetOverlappedResult(ours.raw(), &overlapped, &mut transferred, true).unwrap();
+
+            assert_eq!(transferred, 1);
+        }
+
+        writer.join().unwrap();
+        assert_eq!(buf, *b"x");
+    }
+
+    #[test]
+    fn try_read_returns_the_end_of_file_of_a_closed_pipe() {
+        let (mut ours, theirs) = pipe(true, DEFAULT_BUFFER_SIZE).unwrap();
+        let mut buf = [0u8; 16];
+
+        assert_eq!(ours.try_read(&mut buf).unwrap(), None);
+
+        let mut theirs = File::from(theirs);
+        theirs.write_all(b"last").unwrap();
+        drop(theirs);
+
+        // The data written before the close is read first.
+        assert_eq!(ours.try_read(&mut buf).unwrap(), Some(4));
+        assert_eq!(&buf[..4], b"last");
+        assert_eq!(ours.try_read(&mut buf).unwrap(), Some(0));
+        assert_eq!(ours.try_read(&mut buf).unwrap(), Some(0));
+    }
+}
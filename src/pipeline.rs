@@ -0,0 +1,149 @@
+use crate::{pipe::anonymous_pipe, Child, Command, Error, ExitStatus, Result, Stdio};
+use std::os::windows::io::OwnedHandle;
+
+/// A sequence of commands, the standard output of each command being
+/// connected to the standard input of the next one.
+///
+/// Created with [`Command::pipe_to`]. Unlike running the pipeline with
+/// `cmd.exe /c "a | b"`, the exit status of each stage is available.
+///
+/// The standard input of the first stage and the standard output of the last
+/// stage are configured on their command. The standard error of each stage is
+/// also configured on its command.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    commands: Vec<Command>,
+}
+
+/// The child processes of a [`Pipeline`], in stage order.
+///
+/// Returned by [`Pipeline::spawn`].
+#[derive(Debug)]
+pub struct PipelineChildren {
+    children: Vec<Child>,
+}
+
+impl Command {
+    /// Creates a [`Pipeline`] connecting the standard output of this command
+    /// to the standard input of `next`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let children = Command::new("cmd.exe /c dir /b")
+    ///     .pipe_to(Command::new("sort.exe /r"))
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// let statuses = children.wait().unwrap();
+    /// assert!(statuses.iter().all(|status| status.success()));
+    /// ```
+    pub fn pipe_to(self, next: Command) -> Pipeline {
+        Pipeline {
+            commands: vec![self, next],
+        }
+    }
+}
+
+impl Pipeline {
+    /// Adds a stage at the end of the pipeline.
+    pub fn pipe_to(mut self, next: Command) -> Self {
+        self.commands.push(next);
+        self
+    }
+
+    /// Spawns all the stages of the pipeline.
+    ///
+    /// An anonymous pipe is created between each pair of stages, with the
+    /// [buffer size][Command::pipe_buffer_size] of the stage writing to it. If
+    /// a stage cannot be spawned, the stages that have already been spawned
+    /// are killed and waited, then the error is returned.
+    pub fn spawn(&mut self) -> Result<PipelineChildren> {
+        let last = self.commands.len() - 1;
+        let mut children: Vec<Child> = Vec::with_capacity(self.commands.len());
+        let mut next_stdin: Option<OwnedHandle> = None;
+
+        for (index, command) in self.commands.iter_mut().enumerate() {
+            // The pipes between the stages are synchronous pipes, both ends
+            // are given to children.
+            let pipe = match (index != last)
+                .then(|| anonymous_pipe(command.pipe_buffer_size))
+                .transpose()
+            {
+                Ok(pipe) => pipe,
+                Err(err) => return Err(kill_stages(&children, err)),
+            };
+
+            let stdin = next_stdin
+                .take()
+                .map(|read| command.stdin.replace(Stdio::from(read)));
+            let stdout = pipe.map(|(read, write)| {
+                next_stdin = Some(read);
+                command.stdout.replace(Stdio::from(write))
+            });
+
+            let res = command.spawn();
+
+            // Restore the configuration of the command. This closes the ends
+            // of the pipes given to this stage in the calling process, only
+            // the child holds them now, otherwise the end of file would never
+            // be reached.
+            if let Some(stdin) = stdin {
+                command.stdin = stdin;
+            }
+
+            if let Some(stdout) = stdout {
+                command.stdout = stdout;
+            }
+
+            match res {
+                Ok(child) => children.push(child),
+                Err(err) => return Err(kill_stages(&children, err)),
+            }
+        }
+
+        Ok(PipelineChildren { children })
+    }
+}
+
+// Kill the stages that have already been spawned when the pipeline cannot be
+// spawned completely, and wait for them so they are gone once the error is
+// returned.
+fn kill_stages(children: &[Child], err: Error) -> Error {
+    for child in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    err
+}
+
+impl PipelineChildren {
+    /// Returns the child processes, in stage order.
+    ///
+    /// The standard output of the last stage is available in its
+    /// [`stdout`][Child::stdout] field if it has been [piped][Stdio::piped].
+    pub fn children(&self) -> &[Child] {
+        &self.children
+    }
+
+    /// Returns the child processes, in stage order.
+    pub fn children_mut(&mut self) -> &mut [Child] {
+        &mut self.children
+    }
+
+    /// Converts into the child processes, in stage order.
+    pub fn into_children(self) -> Vec<Child> {
+        self.children
+    }
+
+    /// Waits for all the stages to exit, returning their exit status in
+    /// stage order.
+    ///
+    /// See [`Child::wait`] for more information.
+    pub fn wait(&self) -> Result<Vec<ExitStatus>> {
+        self.children.iter().map(Child::wait).collect()
+    }
+}
@@ -0,0 +1,97 @@
+// A pseudoconsole (ConPTY) hosting a child, for the programs that only behave
+// interactively in a console.
+//
+// The child reads its input from and writes its output to the pseudoconsole,
+// which is connected to the calling process with two anonymous pipes. The
+// output is the rendering of the console, text mixed with VT sequences.
+// `CreatePseudoConsole` is only available since Windows 10 1809, so it is
+// loaded at runtime.
+
+use crate::{load_function, pipe, Error, Result};
+use std::{
+    fs::File,
+    io,
+    os::windows::io::{AsRawHandle, RawHandle},
+};
+use windows::{
+    core::{s, w, HRESULT},
+    Win32::{Foundation::HANDLE, System::Console::COORD},
+};
+
+// The size of the console, in characters. Wide enough for the lines of most
+// programs not to be wrapped.
+const SIZE: COORD = COORD { X: 200, Y: 50 };
+
+type CreatePseudoConsole =
+    unsafe extern "system" fn(COORD, HANDLE, HANDLE, u32, *mut isize) -> HRESULT;
+type ClosePseudoConsole = unsafe extern "system" fn(isize);
+
+#[derive(Debug)]
+pub(crate) struct PseudoConsole {
+    console: isize,
+    close: ClosePseudoConsole,
+}
+
+impl PseudoConsole {
+    // Create a pseudoconsole, returning it with the end of its input to write
+    // to and the end of its output to read from.
+    //
+    // The output has to be read until its end, closing the pseudoconsole may
+    // block until then.
+    pub(crate) fn new() -> Result<(Self, File, File)> {
+        let (create, close) = unsafe {
+            let create = load_function(w!("kernel32.dll"), s!("CreatePseudoConsole"))
+                .map_err(|_| Error::Unsupported("CreatePseudoConsole"))?;
+            let close = load_function(w!("kernel32.dll"), s!("ClosePseudoConsole"))
+                .map_err(|_| Error::Unsupported("ClosePseudoConsole"))?;
+
+            (
+                std::mem::transmute::<_, CreatePseudoConsole>(create),
+                std::mem::transmute::<_, ClosePseudoConsole>(close),
+            )
+        };
+
+        let (input_read, input_write) = pipe::anonymous_pipe(0)?;
+        let (output_read, output_write) = pipe::anonymous_pipe(0)?;
+        let mut console = 0;
+
+        let res = unsafe {
+            create(
+                SIZE,
+                HANDLE(input_read.as_raw_handle() as isize),
+                HANDLE(output_write.as_raw_handle() as isize),
+                0,
+                &mut console,
+            )
+        };
+
+        if res.is_err() {
+            return Err(Error::PseudoConsoleFailed(io::Error::from_raw_os_error(
+                res.0,
+            )));
+        }
+
+        // The pseudoconsole has its own handles of the pipes, its ends are
+        // only closed with it.
+        drop((input_read, output_write));
+
+        Ok((
+            Self { console, close },
+            File::from(input_write),
+            File::from(output_read),
+        ))
+    }
+
+    // The `HPCON` given to the child in its attributes.
+    pub(crate) fn as_raw(&self) -> RawHandle {
+        self.console as RawHandle
+    }
+}
+
+impl Drop for PseudoConsole {
+    // The children attached to the pseudoconsole are terminated and its
+    // output is closed.
+    fn drop(&mut self) {
+        unsafe { (self.close)(self.console) }
+    }
+}
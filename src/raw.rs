@@ -0,0 +1,75 @@
+//! The Win32 declarations used by this crate, for interop with unsafe code.
+//!
+//! These are re-exports of the [`windows`][windows-rs] crate, the exact types
+//! used by this crate, so they can be shared without conversion. The
+//! re-exports follow the needs of this crate and may change in any release:
+//! this module isn't covered by the semver guarantees of the safe API.
+//!
+//! Requires the `raw` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use CreateProcessW::{raw, Command};
+//!
+//! let child = Command::new("notepad.exe").spawn().unwrap();
+//! let process_information = child.raw_process_information();
+//!
+//! unsafe {
+//!     let res = raw::WaitForSingleObject(process_information.hProcess, raw::INFINITE);
+//!     assert_eq!(res, raw::WAIT_OBJECT_0);
+//!
+//!     let mut exit_code = 0;
+//!     raw::GetExitCodeProcess(process_information.hProcess, &mut exit_code).unwrap();
+//!     assert_ne!(exit_code as i32, raw::STATUS_PENDING.0);
+//! }
+//!
+//! // The handles are still owned by the child.
+//! let status = child.reap_after_external_wait().unwrap();
+//! ```
+//!
+//! [windows-rs]: https://github.com/microsoft/windows-rs
+
+use crate::Child;
+
+pub use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{
+            CloseHandle, DuplicateHandle, GetLastError, SetHandleInformation, BOOL,
+            DUPLICATE_SAME_ACCESS, HANDLE, HANDLE_FLAG_INHERIT, NTSTATUS, STATUS_PENDING,
+            WAIT_ABANDONED_0, WAIT_EVENT, WAIT_FAILED, WAIT_OBJECT_0, WAIT_TIMEOUT, WIN32_ERROR,
+        },
+        Storage::FileSystem::{ReadFile, WriteFile},
+        System::{
+            Diagnostics::Debug::{
+                GetErrorMode, SetErrorMode, SEM_FAILCRITICALERRORS, SEM_NOGPFAULTERRORBOX,
+                THREAD_ERROR_MODE,
+            },
+            Pipes::{CreateNamedPipeW, CreatePipe, PeekNamedPipe},
+            Threading::{
+                CreateProcessW, GetCurrentProcess, GetExitCodeProcess, GetPriorityClass,
+                GetProcessAffinityMask, GetProcessTimes, SetPriorityClass, SetProcessAffinityMask,
+                TerminateProcess, WaitForMultipleObjects, WaitForSingleObject,
+                CREATE_DEFAULT_ERROR_MODE, CREATE_PROTECTED_PROCESS, DEBUG_ONLY_THIS_PROCESS,
+                DEBUG_PROCESS, INFINITE, INHERIT_PARENT_AFFINITY, PROCESS_CREATION_FLAGS,
+                PROCESS_INFORMATION, STARTF_FORCEOFFFEEDBACK, STARTF_FORCEONFEEDBACK,
+                STARTF_PREVENTPINNING, STARTF_TITLEISAPPID, STARTF_USEHOTKEY, STARTF_USESTDHANDLES,
+                STARTUPINFOW, STARTUPINFOW_FLAGS,
+            },
+        },
+    },
+};
+
+impl Child {
+    /// Returns the [`PROCESS_INFORMATION`] of the child, as returned by
+    /// [`CreateProcessW`].
+    ///
+    /// The handles are owned by the child: they must not be closed and are
+    /// invalid once the child is dropped.
+    ///
+    /// Requires the `raw` feature.
+    pub fn raw_process_information(&self) -> PROCESS_INFORMATION {
+        self.process_information
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{Child, Error, Result};
+use std::{
+    io,
+    sync::{Mutex, MutexGuard},
+};
+use windows::Win32::{
+    Foundation::{CloseHandle, DuplicateHandle, DUPLICATE_SAME_ACCESS, HANDLE, WAIT_OBJECT_0},
+    System::Threading::{GetCurrentProcess, TerminateProcess, WaitForSingleObject},
+};
+
+// Children spawned with `Command::register`. Each entry holds a duplicate of
+// the process handle so it stays valid after the `Child` has been dropped.
+static REGISTRY: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+struct Entry {
+    pid: u32,
+    handle: HANDLE,
+}
+
+fn registry() -> MutexGuard<'static, Vec<Entry>> {
+    REGISTRY.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Add a child to the registry, pruning the children that have exited.
+pub(crate) fn register(child: &Child) -> Result<()> {
+    let mut handle = HANDLE::default();
+
+    unsafe {
+        DuplicateHandle(
+            GetCurrentProcess(),
+            child.process_information.hProcess,
+            GetCurrentProcess(),
+            &mut handle,
+            0,
+            false,
+            DUPLICATE_SAME_ACCESS,
+        )
+        .map_err(|_| Error::DuplicateHandleFailed(io::Error::last_os_error()))?;
+    }
+
+    #[cfg(feature = "leak-check")]
+    crate::leak_check::opened(1);
+
+    let mut registry = registry();
+
+    registry.retain(|entry| {
+        let exited = unsafe { WaitForSingleObject(entry.handle, 0) == WAIT_OBJECT_0 };
+
+        if exited {
+            unsafe {
+                let _ = CloseHandle(entry.handle);
+            }
+
+            #[cfg(feature = "leak-check")]
+            crate::leak_check::closed(1);
+        }
+
+        !exited
+    });
+
+    registry.push(Entry {
+        pid: child.id(),
+        handle,
+    });
+
+    Ok(())
+}
+
+// Remove a child from the registry, if it is registered.
+pub(crate) fn unregister(pid: u32) {
+    registry().retain(|entry| {
+        if entry.pid == pid {
+            unsafe {
+                let _ = CloseHandle(entry.handle);
+            }
+
+            #[cfg(feature = "leak-check")]
+            crate::leak_check::closed(1);
+        }
+
+        entry.pid != pid
+    });
+}
+
+/// Terminates all the registered child processes that are still running,
+/// returning how many have been terminated.
+///
+/// Children are registered with [`Command::register`][crate::Command::register].
+/// They are removed from the registry once they have exited and have been
+/// waited on, when they are [forgotten][Child::forget] or when they are
+/// terminated by this function.
+///
+/// Equivalent to the [`TerminateProcess`][terminate-process] function for each
+/// registered child.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{kill_all_registered, Command};
+///
+/// for _ in 0..3 {
+///     Command::new("notepad.exe").register(true).spawn().unwrap();
+/// }
+///
+/// assert_eq!(kill_all_registered(), 3);
+/// ```
+///
+/// [terminate-process]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-terminateprocess
+pub fn kill_all_registered() -> usize {
+    let entries = std::mem::take(&mut *registry());
+    let mut killed = 0;
+
+    for entry in entries {
+        unsafe {
+            if WaitForSingleObject(entry.handle, 0) != WAIT_OBJECT_0
+                && TerminateProcess(entry.handle, 1).is_ok()
+            {
+                killed += 1;
+            }
+
+            let _ = CloseHandle(entry.handle);
+        }
+
+        #[cfg(feature = "leak-check")]
+        crate::leak_check::closed(1);
+    }
+
+    killed
+}
+
+/// Installs a panic hook that terminates all the registered child processes
+/// (see [`kill_all_registered`]).
+///
+/// The previous panic hook is still called before terminating the children.
+/// Note that there is no equivalent hook when the process exits normally,
+/// [`kill_all_registered`] has to be called before returning from `main`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{kill_registered_on_panic, Command};
+///
+/// kill_registered_on_panic();
+///
+/// Command::new("notepad.exe").register(true).spawn().unwrap();
+///
+/// panic!("notepad is terminated");
+/// ```
+pub fn kill_registered_on_panic() {
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        kill_all_registered();
+    }));
+}
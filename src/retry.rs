@@ -0,0 +1,219 @@
+use crate::{
+    api::{ProcessApi, Win32ProcessApi},
+    Child, Command, Error, Operation, Result,
+};
+use std::{io, sync::Arc, thread, time::Duration};
+use windows::Win32::Foundation::{ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION};
+
+/// When [`Command::spawn_retry`] spawns the command again after a failure.
+///
+/// Only the system errors listed in [`retryable`][RetryPolicy::retryable]
+/// are retried, the other ones are returned at once. The errors of the
+/// [`NotFound`][io::ErrorKind::NotFound] kind are never retried, even if
+/// they are listed: a missing program doesn't appear by waiting.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy {
+///     attempts: 5,
+///     delay: Duration::from_millis(20),
+///     ..RetryPolicy::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one. `0` is
+    /// treated like `1`.
+    pub attempts: u32,
+    /// The delay before the first retry. It doubles after each retry.
+    pub delay: Duration,
+    /// The system error codes retried, like `ERROR_ACCESS_DENIED` (`5`) for
+    /// an antivirus scanning the program.
+    pub retryable: Vec<u32>,
+}
+
+impl Default for RetryPolicy {
+    /// Retries `ERROR_SHARING_VIOLATION` and `ERROR_LOCK_VIOLATION`, returned
+    /// while the program is still opened by another process, up to 3
+    /// attempts, starting with a delay of 50 milliseconds.
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            delay: Duration::from_millis(50),
+            retryable: vec![ERROR_SHARING_VIOLATION.0, ERROR_LOCK_VIOLATION.0],
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn should_retry(&self, err: &Error) -> bool {
+        err.is_system()
+            && err.kind() != io::ErrorKind::NotFound
+            && self.retryable.contains(&err.code())
+    }
+
+    fn delay(&self, retries: u32) -> Duration {
+        self.delay
+            .checked_mul(1 << retries.min(16))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+impl Command {
+    /// Executes the command as a child process like [`spawn`][Command::spawn],
+    /// spawning it again when it fails with a transient error according to
+    /// `policy`.
+    ///
+    /// This is useful to run a program that has just been written, while an
+    /// antivirus or an indexer still holds it open. The calling thread sleeps
+    /// between the attempts. The error of the last attempt is returned with
+    /// the number of attempts, see [`Error::attempts`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, RetryPolicy};
+    ///
+    /// let child = Command::new(r"target\debug\tool.exe")
+    ///     .spawn_retry(RetryPolicy::default())
+    ///     .unwrap();
+    /// ```
+    pub fn spawn_retry(&mut self, policy: RetryPolicy) -> Result<Child> {
+        self.retry_with_api(policy, Arc::new(Win32ProcessApi))
+    }
+
+    /// Executes the command as a child process like
+    /// [`spawn_retry`][Command::spawn_retry], with `api` instead of the Win32
+    /// calls, like [`spawn_with_api`][Command::spawn_with_api].
+    ///
+    /// Requires the `test-util` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, MockProcessApi, RetryPolicy};
+    /// use std::sync::Arc;
+    ///
+    /// let api = MockProcessApi::new();
+    /// // `ERROR_SHARING_VIOLATION`
+    /// api.fail_create(32);
+    ///
+    /// let child = Command::new("tool.exe")
+    ///     .spawn_retry_with_api(RetryPolicy::default(), Arc::new(api.clone()))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(api.command_lines(), ["tool.exe"]);
+    /// ```
+    #[cfg(feature = "test-util")]
+    pub fn spawn_retry_with_api(
+        &mut self,
+        policy: RetryPolicy,
+        api: Arc<dyn ProcessApi>,
+    ) -> Result<Child> {
+        self.retry_with_api(policy, api)
+    }
+
+    fn retry_with_api(&mut self, policy: RetryPolicy, api: Arc<dyn ProcessApi>) -> Result<Child> {
+        let mut attempts = 1;
+
+        loop {
+            match self.spawn_with_api_and_default_stdio(Arc::clone(&api), false) {
+                Ok(child) => return Ok(child),
+                Err(err) if attempts < policy.attempts && policy.should_retry(&err) => {
+                    thread::sleep(policy.delay(attempts - 1));
+                    attempts += 1;
+                }
+                Err(err) => {
+                    let err = Error::Retried {
+                        attempts,
+                        source: Box::new(err),
+                    };
+
+                    return Err(self.context(Operation::Spawn, None)(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockProcessApi;
+    use std::time::Instant;
+
+    // `ERROR_SHARING_VIOLATION`
+    const SHARING_VIOLATION: u32 = 32;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            delay: Duration::from_millis(50),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn delay_doubles_after_each_retry() {
+        let policy = policy();
+
+        assert_eq!(policy.delay(0), Duration::from_millis(50));
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn transient_failures_are_retried() {
+        let api = MockProcessApi::new();
+        api.fail_create(SHARING_VIOLATION);
+        api.fail_create(SHARING_VIOLATION);
+
+        let start = Instant::now();
+        let child = Command::new("tool.exe")
+            .retry_with_api(policy(), Arc::new(api.clone()))
+            .unwrap();
+
+        // 50 milliseconds before the second attempt, 100 before the third.
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(api.command_lines(), ["tool.exe"]);
+
+        drop(child);
+    }
+
+    #[test]
+    fn last_failure_is_returned_with_the_attempts() {
+        let api = MockProcessApi::new();
+
+        for _ in 0..3 {
+            api.fail_create(SHARING_VIOLATION);
+        }
+
+        let start = Instant::now();
+        let err = Command::new("tool.exe")
+            .retry_with_api(policy(), Arc::new(api.clone()))
+            .unwrap_err();
+
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(err.attempts(), Some(3));
+        assert_eq!(err.code(), SHARING_VIOLATION);
+        assert!(api.command_lines().is_empty());
+    }
+
+    #[test]
+    fn other_failures_are_not_retried() {
+        let api = MockProcessApi::new();
+        // `ERROR_ACCESS_DENIED`
+        api.fail_create(5);
+
+        let err = Command::new("tool.exe")
+            .retry_with_api(policy(), Arc::new(api.clone()))
+            .unwrap_err();
+
+        assert_eq!(err.attempts(), Some(1));
+        assert_eq!(err.code(), 5);
+    }
+}
@@ -0,0 +1,527 @@
+// Script interactive programs through a console or their standard input and
+// output.
+//
+// The output is read on a background thread so a read can be given a timeout.
+
+use crate::{
+    pseudoconsole::PseudoConsole, stdio::io_error, Child, ChildStdin, Command, Error, ExitStatus,
+    Result,
+};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+use windows::Win32::Foundation::HANDLE;
+
+const CHUNK_SIZE: usize = 4 * 1024;
+
+// How often the exit of a child attached to a pseudoconsole is checked while
+// waiting for its output, which is only closed with the pseudoconsole.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// An expect-style session driving an interactive child process.
+///
+/// Lines are sent to the input of the child and its output is searched for
+/// patterns, literal strings or regular expressions (see [`ExpectPattern`]).
+/// The output that precedes a pattern is returned or discarded, the output
+/// that follows it is kept for the next search.
+///
+/// A session created with [`spawn`][InteractiveSession::spawn] runs the
+/// child in a pseudoconsole, so it behaves like in a terminal: it doesn't
+/// buffer its output and prompts like to a user. The output is the rendering
+/// of the console, with VT sequences that can be removed with
+/// [`strip_escapes`][InteractiveSession::strip_escapes]. A session created
+/// with [`new`][InteractiveSession::new] uses the standard input and output of
+/// a child spawned with them [piped][crate::Stdio::piped] instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{Command, InteractiveSession};
+/// use std::time::Duration;
+///
+/// let mut session = InteractiveSession::spawn(&mut Command::new("cmd.exe")).unwrap();
+///
+/// session.strip_escapes(true);
+/// session.send_line("echo marker").unwrap();
+/// session.expect("marker").unwrap();
+/// session.send_line("exit").unwrap();
+///
+/// assert!(session.wait().unwrap().success());
+/// ```
+#[derive(Debug)]
+pub struct InteractiveSession {
+    child: Child,
+    input: Option<Input>,
+    // Closed before `output`, whose thread has to keep reading until the end
+    // of the output of the pseudoconsole.
+    console: Option<PseudoConsole>,
+    output: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    stripper: Option<EscapeStripper>,
+    timeout: Duration,
+}
+
+impl InteractiveSession {
+    /// Spawns the command attached to a new pseudoconsole and creates a
+    /// session for it.
+    ///
+    /// The standard streams configured on the command are ignored, the child
+    /// uses the ones of the console. The pseudoconsole is only available
+    /// since Windows 10 1809, an [`Unsupported`][Error::Unsupported] error
+    /// is returned by the older versions.
+    pub fn spawn(command: &mut Command) -> Result<Self> {
+        let (console, input, output) = PseudoConsole::new()?;
+
+        command.pseudoconsole = Some(HANDLE(console.as_raw() as isize));
+        let child = command.spawn();
+        command.pseudoconsole = None;
+
+        Ok(Self::with_io(
+            child?,
+            Input::Console(input),
+            Some(console),
+            output,
+        ))
+    }
+
+    /// Creates a session for a child spawned with its standard input and
+    /// output [piped][Stdio::piped].
+    ///
+    /// If they aren't, an [`InvalidInput`][Error::InvalidInput] error is
+    /// returned.
+    pub fn new(mut child: Child) -> Result<Self> {
+        match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => {
+                Ok(Self::with_io(child, Input::Pipe(stdin), None, stdout))
+            }
+            _ => Err(Error::InvalidInput(
+                "the standard input and output of the child must be piped",
+            )),
+        }
+    }
+
+    fn with_io(
+        child: Child,
+        input: Input,
+        console: Option<PseudoConsole>,
+        mut output: impl Read + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut chunk = [0; CHUNK_SIZE];
+
+            loop {
+                let len = match output.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => len,
+                };
+
+                // The session has been dropped.
+                if sender.send(chunk[..len].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            child,
+            input: Some(input),
+            console,
+            output: receiver,
+            buffer: Vec::new(),
+            stripper: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Enable/disable the removal of the VT escape sequences (colors, cursor
+    /// moves, window title...) from the output. This is disabled by default.
+    ///
+    /// Only the output received after this call is affected.
+    pub fn strip_escapes(&mut self, strip: bool) -> &mut Self {
+        self.stripper = strip.then(EscapeStripper::default);
+        self
+    }
+
+    /// Sets the timeout used by [`expect`][InteractiveSession::expect], 30
+    /// seconds by default.
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the child process.
+    pub fn child(&self) -> &Child {
+        &self.child
+    }
+
+    /// Writes a line to the input of the child, followed by `\r` (the
+    /// <kbd>Enter</kbd> key) in a pseudoconsole, by `\r\n` otherwise.
+    pub fn send_line(&mut self, line: &str) -> Result<()> {
+        let input = self
+            .input
+            .as_mut()
+            .ok_or(Error::InvalidInput("the standard input has been closed"))?;
+        let end_of_line: &[u8] = match input {
+            Input::Pipe(_) => b"\r\n",
+            Input::Console(_) => b"\r",
+        };
+
+        input
+            .write_all(line.as_bytes())
+            .and_then(|()| input.write_all(end_of_line))
+            .and_then(|()| input.flush())
+            .map_err(io_error)
+    }
+
+    /// Waits until `pattern` appears in the output, returning the output up
+    /// to and including the pattern.
+    ///
+    /// If `timeout` elapses first, an [`ExpectTimeout`][Error::ExpectTimeout]
+    /// error is returned. If the child exits or closes its output first, an
+    /// [`ExpectExited`][Error::ExpectExited] error is returned. Both errors
+    /// carry the output received so far, which is kept for the next search.
+    pub fn read_until(
+        &mut self,
+        pattern: impl Into<ExpectPattern>,
+        timeout: Duration,
+    ) -> Result<String> {
+        let pattern = pattern.into();
+        // A timeout too large to be represented waits forever.
+        let deadline = Instant::now().checked_add(timeout);
+        let mut searched = 0;
+
+        loop {
+            if let Some(end) = pattern.find_end(&self.buffer, searched) {
+                let output = self.buffer.drain(..end).collect::<Vec<_>>();
+
+                return Ok(String::from_utf8_lossy(&output).into_owned());
+            }
+
+            searched = self.buffer.len();
+
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let wait = match (remaining, &self.console) {
+                (Some(remaining), Some(_)) => Some(remaining.min(EXIT_POLL_INTERVAL)),
+                (None, Some(_)) => Some(EXIT_POLL_INTERVAL),
+                (remaining, None) => remaining,
+            };
+
+            let chunk = match wait {
+                Some(wait) => self.output.recv_timeout(wait),
+                None => self
+                    .output
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match chunk {
+                Ok(chunk) => match &mut self.stripper {
+                    Some(stripper) => stripper.strip(&chunk, &mut self.buffer),
+                    None => self.buffer.extend_from_slice(&chunk),
+                },
+                Err(RecvTimeoutError::Timeout)
+                    if !deadline.is_some_and(|deadline| Instant::now() >= deadline) =>
+                {
+                    // Closing the pseudoconsole of an exited child flushes
+                    // and closes its output.
+                    if matches!(self.child.try_wait(), Ok(Some(_))) {
+                        self.console = None;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(Error::ExpectTimeout {
+                        pattern: pattern.as_str().to_owned(),
+                        output: self.buffered(),
+                    })
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::ExpectExited {
+                        pattern: pattern.as_str().to_owned(),
+                        output: self.buffered(),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Waits until `pattern` appears in the output, discarding the output up
+    /// to and including the pattern.
+    ///
+    /// This uses the timeout of [`set_timeout`][InteractiveSession::set_timeout].
+    /// See [`read_until`][InteractiveSession::read_until] for more
+    /// information.
+    pub fn expect(&mut self, pattern: impl Into<ExpectPattern>) -> Result<()> {
+        self.read_until(pattern, self.timeout).map(drop)
+    }
+
+    /// Returns the output that has been received but not consumed by a search
+    /// yet.
+    pub fn buffered(&self) -> String {
+        String::from_utf8_lossy(&self.buffer).into_owned()
+    }
+
+    /// Closes the standard input of the child and waits for it to exit.
+    ///
+    /// The input of a pseudoconsole stays open, the child has to be asked to
+    /// exit, for example with [`send_line`][InteractiveSession::send_line].
+    /// See [`Child::wait`] for more information.
+    pub fn wait(mut self) -> Result<ExitStatus> {
+        if let Some(Input::Pipe(_)) = self.input {
+            drop(self.input.take());
+        }
+
+        self.child.wait()
+    }
+
+    /// Closes the standard input of the child and returns it.
+    ///
+    /// A child attached to a pseudoconsole is terminated with it if it
+    /// hasn't exited yet.
+    pub fn into_child(self) -> Child {
+        self.child
+    }
+}
+
+/// A pattern searched by an [`InteractiveSession`] in the output of its child.
+///
+/// Strings are converted to literal patterns. With the `regex` feature, a
+/// [`regex::bytes::Regex`] is converted to a regular expression matched
+/// against the raw bytes of the output.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "regex")]
+/// # {
+/// use CreateProcessW::{Command, InteractiveSession};
+///
+/// let mut session = InteractiveSession::spawn(&mut Command::new("cmd.exe")).unwrap();
+///
+/// session.expect(regex::bytes::Regex::new(r"[A-Z]:\\.*>").unwrap()).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub enum ExpectPattern {
+    /// A literal string.
+    Literal(String),
+    /// A regular expression.
+    #[cfg(feature = "regex")]
+    Regex(regex::bytes::Regex),
+}
+
+impl ExpectPattern {
+    /// Returns the pattern, as given to [`Error::ExpectTimeout`] and
+    /// [`Error::ExpectExited`].
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Literal(literal) => literal,
+            #[cfg(feature = "regex")]
+            Self::Regex(regex) => regex.as_str(),
+        }
+    }
+
+    // Returns the end of the first match in `buffer`, whose first `searched`
+    // bytes didn't contain a match.
+    fn find_end(&self, buffer: &[u8], searched: usize) -> Option<usize> {
+        match self {
+            Self::Literal(literal) => {
+                // Only the new output (and the end of the old one, in case
+                // the literal is split between two chunks) needs to be
+                // searched.
+                let start = searched.saturating_sub(literal.len());
+
+                find(&buffer[start..], literal.as_bytes())
+                    .map(|position| start + position + literal.len())
+            }
+            // A match can start anywhere, like `.*` at the start of the
+            // buffer.
+            #[cfg(feature = "regex")]
+            Self::Regex(regex) => regex.find(buffer).map(|found| found.end()),
+        }
+    }
+}
+
+impl From<&str> for ExpectPattern {
+    fn from(literal: &str) -> Self {
+        Self::Literal(literal.to_owned())
+    }
+}
+
+impl From<String> for ExpectPattern {
+    fn from(literal: String) -> Self {
+        Self::Literal(literal)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl From<regex::bytes::Regex> for ExpectPattern {
+    fn from(regex: regex::bytes::Regex) -> Self {
+        Self::Regex(regex)
+    }
+}
+
+// The input of the child, its standard input or the input of its
+// pseudoconsole.
+#[derive(Debug)]
+enum Input {
+    Pipe(ChildStdin),
+    Console(File),
+}
+
+impl Write for Input {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Pipe(stdin) => stdin.write(buf),
+            Self::Console(input) => input.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Pipe(stdin) => stdin.flush(),
+            Self::Console(input) => input.flush(),
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// Remove the VT escape sequences from a stream. The state is kept between
+// chunks since a sequence can be split.
+#[derive(Debug, Default)]
+struct EscapeStripper {
+    state: EscapeState,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    #[default]
+    Text,
+    // After `ESC`.
+    Escape,
+    // Control Sequence Introducer: `ESC [`, ends with a byte in `@..=~`.
+    Csi,
+    // Operating System Command: `ESC ]`, ends with `BEL` or `ESC \`.
+    Osc,
+    // `ESC` in an Operating System Command.
+    OscEscape,
+}
+
+impl EscapeStripper {
+    fn strip(&mut self, input: &[u8], output: &mut Vec<u8>) {
+        for &byte in input {
+            self.state = match (self.state, byte) {
+                (EscapeState::Text, 0x1b) => EscapeState::Escape,
+                (EscapeState::Text, _) => {
+                    output.push(byte);
+                    EscapeState::Text
+                }
+                (EscapeState::Escape, b'[') => EscapeState::Csi,
+                (EscapeState::Escape, b']') => EscapeState::Osc,
+                // Two-byte sequence.
+                (EscapeState::Escape, _) => EscapeState::Text,
+                (EscapeState::Csi, 0x40..=0x7e) => EscapeState::Text,
+                (EscapeState::Csi, _) => EscapeState::Csi,
+                (EscapeState::Osc, 0x07) => EscapeState::Text,
+                (EscapeState::Osc, 0x1b) => EscapeState::OscEscape,
+                (EscapeState::Osc, _) => EscapeState::Osc,
+                (EscapeState::OscEscape, b'\\') => EscapeState::Text,
+                (EscapeState::OscEscape, _) => EscapeState::Osc,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut stripper = EscapeStripper::default();
+        let mut output = Vec::new();
+
+        for chunk in chunks {
+            stripper.strip(chunk, &mut output);
+        }
+
+        output
+    }
+
+    #[test]
+    fn control_sequences_are_stripped() {
+        assert_eq!(strip(&[b"\x1b[1;31mred\x1b[0m text"]), b"red text");
+        assert_eq!(strip(&[b"\x1b[?25l\x1b[2J\x1b[Hhome"]), b"home");
+    }
+
+    #[test]
+    fn operating_system_commands_are_stripped() {
+        // Terminated by `BEL`.
+        assert_eq!(strip(&[b"\x1b]0;title\x07prompt>"]), b"prompt>");
+        // Terminated by `ESC \`, an `ESC` alone doesn't end it.
+        assert_eq!(strip(&[b"\x1b]0;a\x1bb\x1b\\prompt>"]), b"prompt>");
+    }
+
+    #[test]
+    fn two_byte_escapes_are_stripped() {
+        assert_eq!(strip(&[b"a\x1b=b\x1b>c"]), b"abc");
+    }
+
+    #[test]
+    fn sequences_split_between_chunks_are_stripped() {
+        assert_eq!(strip(&[b"one\x1b", b"[3", b"2mtwo"]), b"onetwo");
+        assert_eq!(strip(&[b"\x1b]0;ti", b"tle\x1b", b"\\three"]), b"three");
+        assert_eq!(strip(&[b"\x1b[0m", b"four", b"\x1b", b""]), b"four");
+    }
+
+    #[test]
+    fn literal_is_found_across_chunks() {
+        let pattern = ExpectPattern::from("marker");
+        let mut buffer = b"output: mar".to_vec();
+
+        assert_eq!(pattern.find_end(&buffer, 0), None);
+
+        // Only the end of the first chunk is searched again.
+        let searched = buffer.len();
+        buffer.extend_from_slice(b"ker and more");
+        assert_eq!(pattern.find_end(&buffer, searched), Some(14));
+        assert_eq!(&buffer[..14], b"output: marker");
+    }
+
+    #[test]
+    fn first_literal_is_found() {
+        let pattern = ExpectPattern::from(String::from("ab"));
+
+        assert_eq!(pattern.find_end(b"xxabab", 0), Some(4));
+        assert_eq!(pattern.find_end(b"a", 0), None);
+        assert_eq!(ExpectPattern::from("").find_end(b"abc", 0), Some(0));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_is_found_across_chunks() {
+        let pattern = ExpectPattern::from(regex::bytes::Regex::new(r"exit code \d+\r?\n").unwrap());
+        let mut buffer = b"exit code 4".to_vec();
+
+        assert_eq!(pattern.find_end(&buffer, 0), None);
+
+        let searched = buffer.len();
+        buffer.extend_from_slice(b"2\r\nnext");
+        assert_eq!(pattern.find_end(&buffer, searched), Some(14));
+        assert_eq!(pattern.as_str(), r"exit code \d+\r?\n");
+    }
+}
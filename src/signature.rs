@@ -0,0 +1,399 @@
+// Checking the Authenticode signature of the program before spawning it.
+//
+// The program is resolved like `CreateProcessW` does and given to it as the
+// application name, so the file that has been verified is the one executed,
+// unless it is replaced in between. Most of the programs of Windows don't
+// have an embedded signature, their hash is in a signed catalog of the system
+// instead.
+
+use crate::{cmdline, validation, wide, Command, Error, Result};
+use std::{
+    env,
+    ffi::{c_void, OsStr, OsString},
+    fs::File,
+    io,
+    mem::size_of,
+    os::windows::{
+        ffi::{OsStrExt, OsStringExt},
+        io::AsRawHandle,
+    },
+    path::{Path, PathBuf},
+};
+use windows::{
+    core::{w, GUID, PCWSTR},
+    Win32::{
+        Foundation::{
+            ERROR_FILE_NOT_FOUND, HANDLE, HWND, MAX_PATH, TRUST_E_NOSIGNATURE,
+            TRUST_E_SUBJECT_NOT_TRUSTED,
+        },
+        Security::{
+            Cryptography::{
+                Catalog::{
+                    CryptCATAdminAcquireContext2, CryptCATAdminCalcHashFromFileHandle2,
+                    CryptCATAdminEnumCatalogFromHash, CryptCATAdminReleaseCatalogContext,
+                    CryptCATAdminReleaseContext, CryptCATCatalogInfoFromContext, CATALOG_INFO,
+                },
+                CertGetCertificateContextProperty, CertGetNameStringW, CERT_CONTEXT,
+                CERT_NAME_SIMPLE_DISPLAY_TYPE, CERT_SHA1_HASH_PROP_ID,
+            },
+            WinTrust::{
+                WTHelperGetProvSignerFromChain, WTHelperProvDataFromStateData, WinVerifyTrust,
+                DRIVER_ACTION_VERIFY, WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_CATALOG_INFO,
+                WINTRUST_DATA, WINTRUST_DATA_0, WINTRUST_DATA_UNION_CHOICE, WINTRUST_FILE_INFO,
+                WTD_CHOICE_CATALOG, WTD_CHOICE_FILE, WTD_REVOKE_NONE, WTD_STATEACTION_CLOSE,
+                WTD_STATEACTION_VERIFY, WTD_UI_NONE,
+            },
+        },
+        System::SystemInformation::{GetSystemDirectoryW, GetWindowsDirectoryW},
+    },
+};
+
+/// The signatures accepted by [`Command::verify_signature`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Any valid signature, from a certificate trusted by the system.
+    AnyValid,
+    /// A valid signature from a certificate with this subject, like
+    /// `Contoso Ltd`, its simple display name.
+    Subject(String),
+    /// A valid signature from the certificate with this SHA-1 thumbprint.
+    Thumbprint([u8; 20]),
+}
+
+// Resolve the program of `command` like `CreateProcessW`, then verify it.
+// Returns the path to give as the application name.
+pub(crate) fn verify_program(command: &Command, policy: &SignaturePolicy) -> Result<PathBuf> {
+    let program = match &command.application_name {
+        Some(application_name) => application_name.clone(),
+        None => resolve(&command.command)?,
+    };
+
+    verify(&program, policy)?;
+
+    Ok(program)
+}
+
+// Find the program of `command_line` like `CreateProcessW`. An unquoted
+// program containing spaces is tried up to each space in turn, like
+// `C:\Program` then `C:\Program Files\tool`, since it may not be quoted.
+fn resolve(command_line: &OsStr) -> Result<PathBuf> {
+    let units = command_line
+        .encode_wide()
+        .skip_while(|&unit| cmdline::is_whitespace(unit))
+        .collect::<Vec<_>>();
+
+    let candidates = if units.first() == Some(&(b'"' as u16)) {
+        vec![validation::program_and_args(command_line).0]
+    } else {
+        units
+            .iter()
+            .enumerate()
+            .filter(|&(_, &unit)| unit == b' ' as u16 || unit == b'\t' as u16)
+            .map(|(index, _)| &units[..index])
+            .chain([units.as_slice()])
+            .map(OsString::from_wide)
+            .collect()
+    };
+
+    candidates
+        .iter()
+        .filter(|candidate| !candidate.is_empty())
+        .find_map(|candidate| search(candidate))
+        .ok_or_else(|| {
+            Error::CreationFailed(io::Error::from_raw_os_error(ERROR_FILE_NOT_FOUND.0 as i32))
+        })
+}
+
+// Search a program like `CreateProcessW`, adding `.exe` if it has no
+// extension. A program with a directory is only looked up from the working
+// directory, the other ones in the directory of the calling program, the
+// working directory, the 32-bit and 16-bit system directories, the Windows
+// directory, then the directories of the `PATH` of the calling process.
+fn search(program: &OsStr) -> Option<PathBuf> {
+    let mut program = PathBuf::from(program);
+
+    if program.extension().is_none() {
+        program.as_mut_os_string().push(".exe");
+    }
+
+    let has_directory = program
+        .as_os_str()
+        .encode_wide()
+        .any(|unit| unit == b'\\' as u16 || unit == b'/' as u16 || unit == b':' as u16);
+
+    if has_directory {
+        return program.is_file().then_some(program);
+    }
+
+    let windows_directory = system_directory(GetWindowsDirectoryW);
+    let directories = [
+        env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf)),
+        env::current_dir().ok(),
+        system_directory(GetSystemDirectoryW),
+        windows_directory.as_ref().map(|dir| dir.join("System")),
+        windows_directory,
+    ];
+
+    directories
+        .into_iter()
+        .flatten()
+        .chain(env::var_os("PATH").iter().flat_map(env::split_paths))
+        .map(|directory| directory.join(&program))
+        .find(|path| path.is_file())
+}
+
+// A directory returned by `GetSystemDirectoryW` or `GetWindowsDirectoryW`.
+fn system_directory(get: unsafe fn(Option<&mut [u16]>) -> u32) -> Option<PathBuf> {
+    let mut buffer = [0; MAX_PATH as usize];
+    let len = unsafe { get(Some(&mut buffer)) } as usize;
+
+    (len > 0 && len < buffer.len()).then(|| PathBuf::from(wide::from_wide(&buffer[..len])))
+}
+
+// Verify the embedded signature of the program, or the signature of the
+// catalog of the system containing its hash if it has none.
+fn verify(program: &Path, policy: &SignaturePolicy) -> Result<()> {
+    let path = wide::to_wide(program.as_os_str())?;
+    let mut file = WINTRUST_FILE_INFO {
+        cbStruct: size_of::<WINTRUST_FILE_INFO>() as u32,
+        pcwszFilePath: PCWSTR(path.as_ptr()),
+        ..Default::default()
+    };
+
+    let res = unsafe {
+        verify_trust(
+            WTD_CHOICE_FILE,
+            WINTRUST_DATA_0 { pFile: &mut file },
+            policy,
+        )
+    };
+
+    match res {
+        Err(Error::UntrustedProgram(err)) if err.raw_os_error() == Some(TRUST_E_NOSIGNATURE.0) => {
+            verify_catalog(program, &path, policy)
+        }
+        res => res,
+    }
+}
+
+// A catalog administrator context, released when dropped.
+struct CatalogAdmin(isize);
+
+impl Drop for CatalogAdmin {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CryptCATAdminReleaseContext(self.0, 0);
+        }
+    }
+}
+
+// A catalog found by `CryptCATAdminEnumCatalogFromHash`, released when
+// dropped.
+struct Catalog<'a> {
+    admin: &'a CatalogAdmin,
+    info: isize,
+}
+
+impl Drop for Catalog<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CryptCATAdminReleaseCatalogContext(self.admin.0, self.info, 0);
+        }
+    }
+}
+
+// Verify the signature of the catalog of the system containing the hash of
+// the program, `path` being the program as a wide string.
+fn verify_catalog(program: &Path, path: &[u16], policy: &SignaturePolicy) -> Result<()> {
+    let untrusted = |_| Error::UntrustedProgram(io::Error::last_os_error());
+    let file = File::open(program).map_err(Error::UntrustedProgram)?;
+
+    unsafe {
+        let mut admin = 0;
+
+        CryptCATAdminAcquireContext2(
+            &mut admin,
+            Some(&DRIVER_ACTION_VERIFY as *const GUID),
+            w!("SHA256"),
+            None,
+            0,
+        )
+        .map_err(untrusted)?;
+
+        let admin = CatalogAdmin(admin);
+        let mut hash = [0u8; 64];
+        let mut hash_len = hash.len() as u32;
+
+        CryptCATAdminCalcHashFromFileHandle2(
+            admin.0,
+            HANDLE(file.as_raw_handle() as isize),
+            &mut hash_len,
+            Some(hash.as_mut_ptr()),
+            0,
+        )
+        .map_err(untrusted)?;
+
+        let hash = &mut hash[..hash_len as usize];
+
+        let catalog = match CryptCATAdminEnumCatalogFromHash(admin.0, hash, 0, None) {
+            0 => {
+                return Err(Error::UntrustedProgram(io::Error::from_raw_os_error(
+                    TRUST_E_NOSIGNATURE.0,
+                )))
+            }
+            info => Catalog {
+                admin: &admin,
+                info,
+            },
+        };
+
+        let mut catalog_info = CATALOG_INFO {
+            cbStruct: size_of::<CATALOG_INFO>() as u32,
+            ..Default::default()
+        };
+
+        CryptCATCatalogInfoFromContext(catalog.info, &mut catalog_info, 0).map_err(untrusted)?;
+
+        // The member of the catalog is tagged with the hash in hexadecimal.
+        let tag = hash
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>()
+            .encode_utf16()
+            .chain([0])
+            .collect::<Vec<_>>();
+
+        let mut member = WINTRUST_CATALOG_INFO {
+            cbStruct: size_of::<WINTRUST_CATALOG_INFO>() as u32,
+            pcwszCatalogFilePath: PCWSTR(catalog_info.wszCatalogFile.as_ptr()),
+            pcwszMemberTag: PCWSTR(tag.as_ptr()),
+            pcwszMemberFilePath: PCWSTR(path.as_ptr()),
+            hMemberFile: HANDLE(file.as_raw_handle() as isize),
+            pbCalculatedFileHash: hash.as_mut_ptr(),
+            cbCalculatedFileHash: hash.len() as u32,
+            hCatAdmin: admin.0,
+            ..Default::default()
+        };
+
+        verify_trust(
+            WTD_CHOICE_CATALOG,
+            WINTRUST_DATA_0 {
+                pCatalog: &mut member,
+            },
+            policy,
+        )
+    }
+}
+
+// Verify the trust of `subject`, then check its signer against the policy.
+//
+// Safety: `subject` must point to the structure of `choice`, valid during
+// the call.
+unsafe fn verify_trust(
+    choice: WINTRUST_DATA_UNION_CHOICE,
+    subject: WINTRUST_DATA_0,
+    policy: &SignaturePolicy,
+) -> Result<()> {
+    let mut data = WINTRUST_DATA {
+        cbStruct: size_of::<WINTRUST_DATA>() as u32,
+        dwUIChoice: WTD_UI_NONE,
+        fdwRevocationChecks: WTD_REVOKE_NONE,
+        dwUnionChoice: choice,
+        Anonymous: subject,
+        dwStateAction: WTD_STATEACTION_VERIFY,
+        ..Default::default()
+    };
+    let mut action: GUID = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+
+    let status = WinVerifyTrust(
+        HWND::default(),
+        &mut action,
+        &mut data as *mut WINTRUST_DATA as *mut c_void,
+    );
+
+    let res = if status != 0 {
+        Err(Error::UntrustedProgram(io::Error::from_raw_os_error(
+            status,
+        )))
+    } else if signer_matches(data.hWVTStateData, policy) {
+        Ok(())
+    } else {
+        Err(Error::UntrustedProgram(io::Error::from_raw_os_error(
+            TRUST_E_SUBJECT_NOT_TRUSTED.0,
+        )))
+    };
+
+    // Release the state kept for the signer.
+    data.dwStateAction = WTD_STATEACTION_CLOSE;
+    WinVerifyTrust(
+        HWND::default(),
+        &mut action,
+        &mut data as *mut WINTRUST_DATA as *mut c_void,
+    );
+
+    res
+}
+
+// Whether the certificate of the signer matches the policy.
+//
+// Safety: `state` must be the state of a successful verification.
+unsafe fn signer_matches(state: HANDLE, policy: &SignaturePolicy) -> bool {
+    let certificate = match policy {
+        SignaturePolicy::AnyValid => return true,
+        _ => match signer_certificate(state) {
+            Some(certificate) => certificate,
+            None => return false,
+        },
+    };
+
+    match policy {
+        SignaturePolicy::AnyValid => true,
+        SignaturePolicy::Subject(subject) => {
+            let mut buffer = [0u16; 256];
+            let len = CertGetNameStringW(
+                certificate,
+                CERT_NAME_SIMPLE_DISPLAY_TYPE,
+                0,
+                None,
+                Some(&mut buffer),
+            ) as usize;
+
+            // The length includes the null terminator.
+            len > 1 && buffer[..len - 1] == *subject.encode_utf16().collect::<Vec<_>>()
+        }
+        SignaturePolicy::Thumbprint(thumbprint) => {
+            let mut hash = [0u8; 20];
+            let mut size = hash.len() as u32;
+
+            CertGetCertificateContextProperty(
+                certificate,
+                CERT_SHA1_HASH_PROP_ID,
+                Some(hash.as_mut_ptr() as *mut c_void),
+                &mut size,
+            )
+            .is_ok()
+                && hash == *thumbprint
+        }
+    }
+}
+
+// The certificate of the first signer, the leaf of its chain.
+unsafe fn signer_certificate(state: HANDLE) -> Option<*const CERT_CONTEXT> {
+    let provider = WTHelperProvDataFromStateData(state);
+
+    if provider.is_null() {
+        return None;
+    }
+
+    let signer = WTHelperGetProvSignerFromChain(provider, 0, false, 0);
+
+    if signer.is_null() || (*signer).csCertChain == 0 || (*signer).pasCertChain.is_null() {
+        return None;
+    }
+
+    let certificate = (*(*signer).pasCertChain).pCert;
+
+    (!certificate.is_null()).then_some(certificate as *const CERT_CONTEXT)
+}
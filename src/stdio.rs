@@ -0,0 +1,414 @@
+// Standard streams of child processes.
+//
+// When at least one stream is configured, the child gets all three handles
+// through `STARTF_USESTDHANDLES`. The handles given to the child are
+// inheritable and are closed in the calling process right after the child has
+// been created, otherwise reading from a pipe would never reach the end of
+// file. See the `pipe` module for how the pipes are created.
+
+use crate::{
+    pipe::{owned, pipe, relay, Pipe},
+    Error, Result,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::windows::io::{
+        AsHandle, AsRawHandle, BorrowedHandle, IntoRawHandle, OwnedHandle, RawHandle,
+    },
+    sync::{Arc, Mutex},
+};
+use windows::Win32::{
+    Foundation::{
+        DuplicateHandle, SetHandleInformation, DUPLICATE_SAME_ACCESS, HANDLE, HANDLE_FLAG_INHERIT,
+    },
+    System::{
+        Console::{
+            GetStdHandle, STD_ERROR_HANDLE, STD_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+        },
+        Threading::GetCurrentProcess,
+    },
+};
+
+/// Describes what to do with a standard stream of a child process.
+///
+/// Used by [`Command::stdin`][crate::Command::stdin],
+/// [`Command::stdout`][crate::Command::stdout] and
+/// [`Command::stderr`][crate::Command::stderr].
+#[derive(Debug, Clone)]
+pub struct Stdio(StdioKind);
+
+#[derive(Debug, Clone)]
+enum StdioKind {
+    Inherit,
+    Piped,
+    Null,
+    Handle(Arc<OwnedHandle>),
+    // The end of a pipe of another child, taken by the first child it is
+    // given to.
+    Relay(Arc<Mutex<Option<Pipe>>>),
+}
+
+impl Stdio {
+    /// The child inherits the corresponding standard stream of the calling
+    /// process.
+    pub fn inherit() -> Self {
+        Self(StdioKind::Inherit)
+    }
+
+    /// A new pipe connects the child and the calling process.
+    ///
+    /// The end of the pipe kept by the calling process is available in the
+    /// [`stdin`][crate::Child::stdin], [`stdout`][crate::Child::stdout] or
+    /// [`stderr`][crate::Child::stderr] field of the [`Child`][crate::Child].
+    pub fn piped() -> Self {
+        Self(StdioKind::Piped)
+    }
+
+    /// The stream is connected to the `NUL` device: the child reads nothing
+    /// and what it writes is discarded.
+    pub fn null() -> Self {
+        Self(StdioKind::Null)
+    }
+
+    // The pipes kept by the calling process are opened for overlapped I/O,
+    // they are relayed through a synchronous pipe when given to another child.
+    fn relay(pipe: Pipe) -> Self {
+        Self(StdioKind::Relay(Arc::new(Mutex::new(Some(pipe)))))
+    }
+}
+
+impl From<ChildStdin> for Stdio {
+    /// Connects the stream to the standard input of another child, like the
+    /// shell operator `|`.
+    fn from(stdin: ChildStdin) -> Self {
+        Self::relay(stdin.0)
+    }
+}
+
+impl From<ChildStdout> for Stdio {
+    /// Connects the stream to the standard output of another child, like the
+    /// shell operator `|`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Stdio};
+    ///
+    /// let mut dir = Command::new("cmd.exe /c dir /b")
+    ///     .stdout(Stdio::piped())
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// let output = Command::new("sort.exe")
+    ///     .stdin(Stdio::from(dir.stdout.take().unwrap()))
+    ///     .output()
+    ///     .unwrap();
+    /// ```
+    fn from(stdout: ChildStdout) -> Self {
+        Self::relay(stdout.0)
+    }
+}
+
+impl From<ChildStderr> for Stdio {
+    /// Connects the stream to the standard error of another child.
+    fn from(stderr: ChildStderr) -> Self {
+        Self::relay(stderr.0)
+    }
+}
+
+impl From<OwnedHandle> for Stdio {
+    /// Connects the stream to a handle, like a file or the end of a pipe. The
+    /// handle must not be opened for overlapped I/O.
+    fn from(handle: OwnedHandle) -> Self {
+        Self(StdioKind::Handle(Arc::new(handle)))
+    }
+}
+
+impl From<File> for Stdio {
+    /// Connects the stream to a file, opened for reading for the standard
+    /// input and for writing for the standard output and error.
+    fn from(file: File) -> Self {
+        Self(StdioKind::Handle(Arc::new(OwnedHandle::from(file))))
+    }
+}
+
+/// A handle to the standard input of a child process.
+///
+/// Available in [`Child::stdin`][crate::Child::stdin] when the standard input
+/// has been [piped][Stdio::piped]. Dropping it closes the pipe, so the child
+/// reads the end of file.
+#[derive(Debug)]
+pub struct ChildStdin(Pipe);
+
+impl Write for ChildStdin {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// A handle to the standard output of a child process.
+///
+/// Available in [`Child::stdout`][crate::Child::stdout] when the standard
+/// output has been [piped][Stdio::piped]. Reading returns the end of file
+/// once the child (and any process that inherited the pipe) has exited.
+#[derive(Debug)]
+pub struct ChildStdout(Pipe);
+
+impl ChildStdout {
+    /// Reads the available output without blocking.
+    ///
+    /// Returns `Ok(None)` when no output is available yet and the child still
+    /// has the pipe open, and `Ok(Some(0))` at the end of file. Otherwise, at
+    /// most the available output is read, so the call doesn't block.
+    ///
+    /// This is useful to poll the output from a timer, in a GUI event loop
+    /// for example.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, Stdio};
+    /// use std::{thread, time::Duration};
+    ///
+    /// let mut child = Command::new("cargo.exe build")
+    ///     .stdout(Stdio::piped())
+    ///     .spawn()
+    ///     .unwrap();
+    /// let stdout = child.stdout.as_mut().unwrap();
+    /// let mut buf = [0; 4096];
+    ///
+    /// loop {
+    ///     match stdout.try_read(&mut buf).unwrap() {
+    ///         Some(0) => break,
+    ///         Some(len) => print!("{}", String::from_utf8_lossy(&buf[..len])),
+    ///         None => thread::sleep(Duration::from_millis(100)),
+    ///     }
+    /// }
+    /// ```
+    pub fn try_read(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        self.0.try_read(buf)
+    }
+}
+
+impl Read for ChildStdout {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+/// A handle to the standard error of a child process.
+///
+/// Available in [`Child::stderr`][crate::Child::stderr] when the standard
+/// error has been [piped][Stdio::piped]. See [`ChildStdout`] for more
+/// information.
+#[derive(Debug)]
+pub struct ChildStderr(Pipe);
+
+impl ChildStderr {
+    /// Reads the available error output without blocking.
+    ///
+    /// See [`ChildStdout::try_read`] for more information.
+    pub fn try_read(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        self.0.try_read(buf)
+    }
+}
+
+impl Read for ChildStderr {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+macro_rules! impl_handle_traits {
+    ($($name:ident),*) => {
+        $(
+            impl AsHandle for $name {
+                fn as_handle(&self) -> BorrowedHandle<'_> {
+                    self.0.as_handle()
+                }
+            }
+
+            impl AsRawHandle for $name {
+                fn as_raw_handle(&self) -> RawHandle {
+                    self.0.as_raw_handle()
+                }
+            }
+
+            impl IntoRawHandle for $name {
+                fn into_raw_handle(self) -> RawHandle {
+                    self.0.into_raw_handle()
+                }
+            }
+        )*
+    };
+}
+
+impl_handle_traits!(ChildStdin, ChildStdout, ChildStderr);
+
+// The handles given to the child for its standard streams. A handle is `None`
+// when the calling process doesn't have the inherited stream.
+pub(crate) struct StdioHandles {
+    stdin: Option<OwnedHandle>,
+    stdout: Option<OwnedHandle>,
+    stderr: Option<OwnedHandle>,
+}
+
+impl StdioHandles {
+    pub(crate) fn stdin(&self) -> HANDLE {
+        raw(&self.stdin)
+    }
+
+    pub(crate) fn stdout(&self) -> HANDLE {
+        raw(&self.stdout)
+    }
+
+    pub(crate) fn stderr(&self) -> HANDLE {
+        raw(&self.stderr)
+    }
+}
+
+// The ends of the pipes kept by the calling process.
+#[derive(Default)]
+pub(crate) struct ParentPipes {
+    pub(crate) stdin: Option<ChildStdin>,
+    pub(crate) stdout: Option<ChildStdout>,
+    pub(crate) stderr: Option<ChildStderr>,
+}
+
+// Create the handles of the standard streams of a child process, the pipes
+// being created with buffers of `buffer_size` bytes.
+pub(crate) fn setup(
+    stdin: &Stdio,
+    stdout: &Stdio,
+    stderr: &Stdio,
+    buffer_size: u32,
+) -> Result<(StdioHandles, ParentPipes)> {
+    let (child_stdin, parent_stdin) = child_handle(stdin, STD_INPUT_HANDLE, true, buffer_size)?;
+    let (child_stdout, parent_stdout) =
+        child_handle(stdout, STD_OUTPUT_HANDLE, false, buffer_size)?;
+    let (child_stderr, parent_stderr) = child_handle(stderr, STD_ERROR_HANDLE, false, buffer_size)?;
+
+    Ok((
+        StdioHandles {
+            stdin: child_stdin,
+            stdout: child_stdout,
+            stderr: child_stderr,
+        },
+        ParentPipes {
+            stdin: parent_stdin.map(ChildStdin),
+            stdout: parent_stdout.map(ChildStdout),
+            stderr: parent_stderr.map(ChildStderr),
+        },
+    ))
+}
+
+// Create the handle of a standard stream for the child, `readable` being
+// `true` for the standard input. For a pipe, the end kept by the calling
+// process is returned too.
+fn child_handle(
+    stdio: &Stdio,
+    std_handle: STD_HANDLE,
+    readable: bool,
+    buffer_size: u32,
+) -> Result<(Option<OwnedHandle>, Option<Pipe>)> {
+    match &stdio.0 {
+        StdioKind::Inherit => Ok((duplicate_std_handle(std_handle)?, None)),
+        StdioKind::Handle(handle) => {
+            // The handle of the `Stdio` isn't made inheritable itself, so
+            // the children spawned later don't inherit it.
+            let handle = HANDLE(handle.as_raw_handle() as isize);
+
+            Ok((Some(duplicate_inheritable(handle)?), None))
+        }
+        StdioKind::Relay(pipe) => {
+            let pipe = pipe
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .take()
+                .ok_or(Error::InvalidInput(
+                    "the pipe of another child can only be given to one child",
+                ))?;
+            let handle = relay(pipe, readable, buffer_size)?;
+
+            set_inheritable(&handle)?;
+
+            Ok((Some(handle), None))
+        }
+        StdioKind::Null => {
+            let file = OpenOptions::new()
+                .read(readable)
+                .write(!readable)
+                .open("NUL")
+                .map_err(io_error)?;
+            let handle = OwnedHandle::from(file);
+
+            set_inheritable(&handle)?;
+
+            Ok((Some(handle), None))
+        }
+        StdioKind::Piped => {
+            // The calling process writes to the standard input of the child.
+            let (parent, child) = pipe(!readable, buffer_size)?;
+
+            set_inheritable(&child)?;
+
+            Ok((Some(child), Some(parent)))
+        }
+    }
+}
+
+// Duplicate a standard handle of the calling process as an inheritable handle.
+fn duplicate_std_handle(std_handle: STD_HANDLE) -> Result<Option<OwnedHandle>> {
+    match unsafe { GetStdHandle(std_handle) } {
+        Ok(handle) if !handle.is_invalid() => Ok(Some(duplicate_inheritable(handle)?)),
+        // The calling process doesn't have this stream, like a GUI
+        // application without a console.
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn duplicate_inheritable(handle: HANDLE) -> Result<OwnedHandle> {
+    unsafe {
+        let mut duplicate = HANDLE::default();
+
+        DuplicateHandle(
+            GetCurrentProcess(),
+            handle,
+            GetCurrentProcess(),
+            &mut duplicate,
+            0,
+            true,
+            DUPLICATE_SAME_ACCESS,
+        )
+        .map_err(|_| Error::StdioFailed(io::Error::last_os_error()))?;
+
+        Ok(owned(duplicate))
+    }
+}
+
+fn set_inheritable(handle: &OwnedHandle) -> Result<()> {
+    unsafe {
+        SetHandleInformation(
+            HANDLE(handle.as_raw_handle() as isize),
+            HANDLE_FLAG_INHERIT.0,
+            HANDLE_FLAG_INHERIT,
+        )
+        .map_err(|_| Error::StdioFailed(io::Error::last_os_error()))
+    }
+}
+
+fn raw(handle: &Option<OwnedHandle>) -> HANDLE {
+    handle
+        .as_ref()
+        .map(|handle| HANDLE(handle.as_raw_handle() as isize))
+        .unwrap_or_default()
+}
+
+pub(crate) fn io_error(err: io::Error) -> Error {
+    Error::StdioFailed(err)
+}
@@ -0,0 +1,370 @@
+// Keep a child process running in a background thread, restarting it
+// according to a policy.
+
+use crate::{
+    wait::{deadline, wait_any_handle},
+    Child, Command, ExitStatus, Result,
+};
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+// The longest delay between two restarts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// When a [`Supervisor`] restarts its child process.
+///
+/// The exit of the child is classified in three ways: a success (exit code
+/// `0`), a crash (see [`ExitStatus::crashed`]) or a failure (any other exit
+/// code, which usually means the program exited on purpose).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Restart the child when it exits successfully.
+    pub on_success: bool,
+    /// Restart the child when it exits with a failure exit code.
+    pub on_failure: bool,
+    /// Restart the child when it crashes.
+    pub on_crash: bool,
+    /// The maximum number of restarts before giving up.
+    pub max_restarts: u32,
+    /// The delay before the first restart. It doubles after each restart, up
+    /// to one minute, so a child that keeps crashing isn't restarted in a
+    /// loop.
+    pub backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    /// Restarts the child when it crashes, up to 5 times, starting with a
+    /// delay of one second.
+    fn default() -> Self {
+        Self {
+            on_success: false,
+            on_failure: false,
+            on_crash: true,
+            max_restarts: 5,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RestartPolicy {
+    fn should_restart(&self, status: ExitStatus) -> bool {
+        if status.success() {
+            self.on_success
+        } else if status.crashed() {
+            self.on_crash
+        } else {
+            self.on_failure
+        }
+    }
+
+    fn delay(&self, restarts: u32) -> Duration {
+        self.backoff
+            .checked_mul(1 << restarts.min(16))
+            .unwrap_or(MAX_BACKOFF)
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// An event sent by a [`Supervisor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisorEvent {
+    /// The child has been spawned, with the given process identifier.
+    Started(u32),
+    /// The child has exited.
+    Exited(ExitStatus),
+    /// The supervisor gave up restarting the child, either because the
+    /// maximum number of restarts has been reached or because the child
+    /// cannot be spawned or waited on.
+    GaveUp,
+}
+
+/// The state of a [`Supervisor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisorStatus {
+    /// The child is running, with the given process identifier.
+    Running(u32),
+    /// The child has exited and will be restarted after a delay.
+    BackingOff,
+    /// The child has exited and the policy doesn't restart it.
+    Exited(ExitStatus),
+    /// The supervisor gave up restarting the child.
+    GaveUp,
+    /// The supervisor has been stopped.
+    Stopped,
+}
+
+/// A child process kept running in a background thread.
+///
+/// The child is restarted when it exits, according to a [`RestartPolicy`].
+/// The [`Command`] is spawned again for each restart. Dropping the supervisor
+/// stops the restarts but leaves the current child running, use
+/// [`stop`][Supervisor::stop] to end it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{Command, RestartPolicy, Supervisor, SupervisorEvent};
+///
+/// let supervisor = Supervisor::spawn(Command::new("worker.exe"), RestartPolicy::default());
+///
+/// for event in supervisor.events() {
+///     match event {
+///         SupervisorEvent::Started(pid) => println!("worker started ({})", pid),
+///         SupervisorEvent::Exited(status) => println!("worker exited with {}", status),
+///         SupervisorEvent::GaveUp => break,
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Supervisor {
+    shared: Arc<Shared>,
+    events: Receiver<SupervisorEvent>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    stopping: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    status: SupervisorStatus,
+    child: Option<Arc<Child>>,
+    restarts: u32,
+    stopping: bool,
+}
+
+impl Supervisor {
+    /// Spawns the command in a background thread and keeps it running
+    /// according to `policy`.
+    pub fn spawn(command: Command, policy: RestartPolicy) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                status: SupervisorStatus::BackingOff,
+                child: None,
+                restarts: 0,
+                stopping: false,
+            }),
+            stopping: Condvar::new(),
+        });
+
+        let (sender, events) = mpsc::channel();
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || supervise(command, policy, &shared, &sender))
+        };
+
+        Self {
+            shared,
+            events,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the current state of the supervisor.
+    pub fn status(&self) -> SupervisorStatus {
+        self.shared.state().status
+    }
+
+    /// Returns how many times the child has been restarted.
+    pub fn restarts(&self) -> u32 {
+        self.shared.state().restarts
+    }
+
+    /// Returns the channel receiving the events of the supervisor.
+    ///
+    /// The channel is closed once the supervisor doesn't restart the child
+    /// anymore.
+    pub fn events(&self) -> &Receiver<SupervisorEvent> {
+        &self.events
+    }
+
+    /// Stops the supervisor, ending the child.
+    ///
+    /// The top-level windows of the child are asked to close first. If the
+    /// child is still running after `grace`, it is killed. This returns once
+    /// the background thread has exited, even if the child cannot be killed,
+    /// in which case the error is returned once the child has exited.
+    pub fn stop(mut self, grace: Duration) -> Result<()> {
+        let child = {
+            let mut state = self.shared.state();
+            state.stopping = true;
+            self.shared.stopping.notify_all();
+            state.child.clone()
+        };
+
+        let res = match child {
+            Some(child) => {
+                let _ = child.close_windows();
+
+                let handle = child.process_information.hProcess;
+
+                match wait_any_handle(&[handle], deadline(Some(grace))) {
+                    Ok(Some(_)) => Ok(()),
+                    Ok(None) => child.kill(),
+                    Err(err) => Err(err),
+                }
+            }
+            None => Ok(()),
+        };
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        res
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.shared.state().stopping = true;
+        self.shared.stopping.notify_all();
+    }
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn supervise(
+    mut command: Command,
+    policy: RestartPolicy,
+    shared: &Shared,
+    events: &Sender<SupervisorEvent>,
+) {
+    loop {
+        let child = {
+            let mut state = shared.state();
+
+            if state.stopping {
+                state.status = SupervisorStatus::Stopped;
+                return;
+            }
+
+            // Spawning with the lock held ensures `stop` either sees the
+            // child or prevents it from being spawned.
+            match command.spawn() {
+                Ok(child) => {
+                    let child = Arc::new(child);
+
+                    state.status = SupervisorStatus::Running(child.id());
+                    state.child = Some(Arc::clone(&child));
+                    child
+                }
+                Err(_) => {
+                    state.status = SupervisorStatus::GaveUp;
+                    let _ = events.send(SupervisorEvent::GaveUp);
+                    return;
+                }
+            }
+        };
+
+        let _ = events.send(SupervisorEvent::Started(child.id()));
+
+        let status = child.wait();
+        let mut state = shared.state();
+
+        state.child = None;
+
+        let status = match status {
+            Ok(status) => status,
+            Err(_) => {
+                state.status = SupervisorStatus::GaveUp;
+                let _ = events.send(SupervisorEvent::GaveUp);
+                return;
+            }
+        };
+
+        let _ = events.send(SupervisorEvent::Exited(status));
+
+        if state.stopping {
+            state.status = SupervisorStatus::Stopped;
+            return;
+        }
+
+        if !policy.should_restart(status) {
+            state.status = SupervisorStatus::Exited(status);
+            return;
+        }
+
+        if state.restarts >= policy.max_restarts {
+            state.status = SupervisorStatus::GaveUp;
+            let _ = events.send(SupervisorEvent::GaveUp);
+            return;
+        }
+
+        state.status = SupervisorStatus::BackingOff;
+
+        let delay = policy.delay(state.restarts);
+        let (mut state, _) = shared
+            .stopping
+            .wait_timeout_while(state, delay, |state| !state.stopping)
+            .unwrap_or_else(|err| err.into_inner());
+
+        if state.stopping {
+            state.status = SupervisorStatus::Stopped;
+            return;
+        }
+
+        state.restarts += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRASH: ExitStatus = ExitStatus(ExitStatus::ACCESS_VIOLATION);
+
+    #[test]
+    fn delay_doubles_up_to_a_minute() {
+        let policy = RestartPolicy {
+            backoff: Duration::from_millis(500),
+            ..RestartPolicy::default()
+        };
+
+        assert_eq!(policy.delay(0), Duration::from_millis(500));
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(7), MAX_BACKOFF);
+        assert_eq!(policy.delay(u32::MAX), MAX_BACKOFF);
+
+        let policy = RestartPolicy {
+            backoff: Duration::MAX,
+            ..RestartPolicy::default()
+        };
+        assert_eq!(policy.delay(1), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn exits_are_classified() {
+        let policy = RestartPolicy::default();
+
+        assert!(policy.should_restart(CRASH));
+        assert!(!policy.should_restart(ExitStatus(0)));
+        assert!(!policy.should_restart(ExitStatus(1)));
+
+        let policy = RestartPolicy {
+            on_success: true,
+            on_failure: true,
+            on_crash: false,
+            ..RestartPolicy::default()
+        };
+
+        assert!(!policy.should_restart(CRASH));
+        assert!(policy.should_restart(ExitStatus(0)));
+        assert!(policy.should_restart(ExitStatus(1)));
+    }
+}
@@ -0,0 +1,49 @@
+// The settings that cannot be given to `CreateProcessW`, applied to the child
+// before it runs: it is created suspended, configured, then its main thread is
+// resumed.
+
+use crate::{Child, Command, Error, Result};
+use std::io;
+use windows::Win32::System::Threading::ResumeThread;
+
+impl Command {
+    // Whether the child has to be created suspended.
+    pub(crate) fn starts_suspended(&self) -> bool {
+        self.disable_priority_boost || self.working_set.is_some() || self.efficiency_mode
+    }
+
+    // Apply the settings to `child`, created suspended, then resume its main
+    // thread. The child is terminated if one of them fails, so it never runs
+    // without them. It is called once the child is tracked, so the metrics
+    // count it as dropped.
+    pub(crate) fn start_suspended(&self, child: &Child) -> Result<()> {
+        let res = self.configure(child).and_then(|()| unsafe {
+            match ResumeThread(child.process_information.hThread) {
+                u32::MAX => Err(Error::ResumeFailed(io::Error::last_os_error())),
+                _ => Ok(()),
+            }
+        });
+
+        if res.is_err() {
+            let _ = child.kill();
+        }
+
+        res
+    }
+
+    fn configure(&self, child: &Child) -> Result<()> {
+        if self.disable_priority_boost {
+            child.set_priority_boost(false)?;
+        }
+
+        if let Some((min_bytes, max_bytes)) = self.working_set {
+            child.set_working_set(min_bytes, max_bytes, false)?;
+        }
+
+        if self.efficiency_mode {
+            child.set_efficiency_mode(true)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use crate::{pipe::owned, wide, Error, Result};
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    os::windows::io::{AsRawHandle, OwnedHandle},
+};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{LocalFree, HANDLE, HLOCAL, LUID},
+        Security::{
+            Authorization::ConvertStringSidToSidW, CreateRestrictedToken, LookupPrivilegeValueW,
+            CREATE_RESTRICTED_TOKEN_FLAGS, DISABLE_MAX_PRIVILEGE, LUID_AND_ATTRIBUTES, PSID,
+            SID_AND_ATTRIBUTES, TOKEN_ADJUST_DEFAULT, TOKEN_ASSIGN_PRIMARY, TOKEN_DUPLICATE,
+            TOKEN_PRIVILEGES_ATTRIBUTES, TOKEN_QUERY,
+        },
+        System::Threading::{GetCurrentProcess, OpenProcessToken},
+    },
+};
+
+// The SID of the Administrators group.
+const ADMINISTRATORS_SID: &str = "S-1-5-32-544";
+
+/// The restrictions of the token of a child process, used by
+/// [`Command::restricted`][crate::Command::restricted].
+///
+/// The child runs with the token of the calling process minus these
+/// restrictions. The disabled groups are only used to deny access: the child
+/// cannot access what a disabled group grants, but is still denied what it
+/// denies.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{Command, RestrictedTokenOptions};
+///
+/// let mut options = RestrictedTokenOptions::new();
+/// options
+///     .disable_max_privilege(true)
+///     .disable_administrators()
+///     .delete_privilege("SeShutdownPrivilege");
+///
+/// let status = Command::new("downloaded-tool.exe")
+///     .restricted(options)
+///     .status()
+///     .expect("the tool failed to start");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RestrictedTokenOptions {
+    disable_max_privilege: bool,
+    disabled_sids: Vec<OsString>,
+    deleted_privileges: Vec<OsString>,
+}
+
+impl RestrictedTokenOptions {
+    /// Creates options without restrictions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable/disable the deletion of all the privileges, except
+    /// `SeChangeNotifyPrivilege`.
+    ///
+    /// Equivalent to the `DISABLE_MAX_PRIVILEGE` flag of the
+    /// [`CreateRestrictedToken`][create-restricted-token] function.
+    ///
+    /// [create-restricted-token]: https://docs.microsoft.com/en-us/windows/win32/api/securitybaseapi/nf-securitybaseapi-createrestrictedtoken
+    pub fn disable_max_privilege(&mut self, disable: bool) -> &mut Self {
+        self.disable_max_privilege = disable;
+        self
+    }
+
+    /// Disables a group, given as a SID string like `S-1-5-32-544`.
+    pub fn disable_sid(&mut self, sid: impl Into<OsString>) -> &mut Self {
+        self.disabled_sids.push(sid.into());
+        self
+    }
+
+    /// Disables the Administrators group, so the child cannot use the rights
+    /// of an administrator even if the calling process is elevated.
+    pub fn disable_administrators(&mut self) -> &mut Self {
+        self.disable_sid(ADMINISTRATORS_SID)
+    }
+
+    /// Deletes a privilege, given by name like `SeDebugPrivilege`.
+    pub fn delete_privilege(&mut self, name: impl Into<OsString>) -> &mut Self {
+        self.deleted_privileges.push(name.into());
+        self
+    }
+}
+
+// A SID allocated by `ConvertStringSidToSidW`.
+struct LocalSid(PSID);
+
+impl Drop for LocalSid {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = LocalFree(HLOCAL(self.0 .0 as isize));
+        }
+    }
+}
+
+// Create a restricted primary token from the token of the calling process.
+pub(crate) fn restricted_token(options: &RestrictedTokenOptions) -> Result<OwnedHandle> {
+    let sids = options
+        .disabled_sids
+        .iter()
+        .map(|sid| string_to_sid(sid))
+        .collect::<Result<Vec<_>>>()?;
+    let sids_to_disable = sids
+        .iter()
+        .map(|sid| SID_AND_ATTRIBUTES {
+            Sid: sid.0,
+            Attributes: 0,
+        })
+        .collect::<Vec<_>>();
+    let privileges_to_delete = options
+        .deleted_privileges
+        .iter()
+        .map(|name| {
+            Ok(LUID_AND_ATTRIBUTES {
+                Luid: privilege_value(name)?,
+                Attributes: TOKEN_PRIVILEGES_ATTRIBUTES(0),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let flags = if options.disable_max_privilege {
+        DISABLE_MAX_PRIVILEGE
+    } else {
+        CREATE_RESTRICTED_TOKEN_FLAGS(0)
+    };
+
+    unsafe {
+        let mut process_token = HANDLE::default();
+
+        OpenProcessToken(
+            GetCurrentProcess(),
+            TOKEN_DUPLICATE | TOKEN_QUERY | TOKEN_ASSIGN_PRIMARY | TOKEN_ADJUST_DEFAULT,
+            &mut process_token,
+        )
+        .map_err(|_| Error::TokenFailed(io::Error::last_os_error()))?;
+
+        // Closed on every path.
+        let process_token = owned(process_token);
+        let mut token = HANDLE::default();
+
+        CreateRestrictedToken(
+            HANDLE(process_token.as_raw_handle() as isize),
+            flags,
+            (!sids_to_disable.is_empty()).then_some(sids_to_disable.as_slice()),
+            (!privileges_to_delete.is_empty()).then_some(privileges_to_delete.as_slice()),
+            None,
+            &mut token,
+        )
+        .map_err(|_| Error::TokenFailed(io::Error::last_os_error()))?;
+
+        Ok(owned(token))
+    }
+}
+
+fn string_to_sid(sid: &OsStr) -> Result<LocalSid> {
+    let sid_wide = wide::to_wide(sid)?;
+    let mut psid = PSID::default();
+
+    unsafe {
+        ConvertStringSidToSidW(PCWSTR(sid_wide.as_ptr()), &mut psid)
+            .map_err(|_| Error::TokenFailed(io::Error::last_os_error()))?;
+    }
+
+    Ok(LocalSid(psid))
+}
+
+fn privilege_value(name: &OsStr) -> Result<LUID> {
+    let name_wide = wide::to_wide(name)?;
+    let mut luid = LUID::default();
+
+    unsafe {
+        LookupPrivilegeValueW(PCWSTR::null(), PCWSTR(name_wide.as_ptr()), &mut luid)
+            .map_err(|_| Error::TokenFailed(io::Error::last_os_error()))?;
+    }
+
+    Ok(luid)
+}
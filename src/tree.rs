@@ -0,0 +1,322 @@
+use crate::{filetime_to_system_time, wait, Child, Error, ExitStatus, Result};
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    io,
+    mem::size_of,
+    os::windows::ffi::OsStringExt,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use windows::Win32::{
+    Foundation::{CloseHandle, FILETIME},
+    System::{
+        Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+            TH32CS_SNAPPROCESS,
+        },
+        Threading::{GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
+    },
+};
+
+/// A process that descends from a child process.
+///
+/// Returned by [`Child::descendants`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessEntry {
+    /// The process identifier.
+    pub pid: u32,
+    /// The process identifier of the parent process.
+    pub parent_pid: u32,
+    /// The name of the executable file of the process, like `cargo.exe`.
+    pub exe_name: OsString,
+    /// The time at which the process was created, `None` if the process
+    /// cannot be opened to query it.
+    pub creation_time: Option<SystemTime>,
+    /// The depth of the process in the tree, `1` for the direct children of
+    /// the child process.
+    pub depth: usize,
+}
+
+/// The result of [`Child::wait_tree_polling`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeStatus {
+    /// The exit status of the child process.
+    pub status: ExitStatus,
+    /// The number of descendants still running when the timeout elapsed,
+    /// `0` if they all exited.
+    pub stragglers: usize,
+}
+
+impl Child {
+    /// Returns the processes that descend from the child process.
+    ///
+    /// The tree is built from a snapshot of the processes of the system by
+    /// following the parent process identifiers. Since identifiers are reused,
+    /// a process that has been created before its supposed parent is ignored.
+    /// The entries are sorted by depth, so the direct children come first. If
+    /// the snapshot cannot be taken, a [`SnapshotFailed`][Error::SnapshotFailed]
+    /// error is returned.
+    ///
+    /// This is equivalent to calling the
+    /// [`CreateToolhelp32Snapshot`][create-toolhelp32-snapshot] function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("cargo.exe build").spawn().unwrap();
+    /// std::thread::sleep(std::time::Duration::from_secs(5));
+    ///
+    /// for process in child.descendants().unwrap() {
+    ///     let indent = "  ".repeat(process.depth);
+    ///     println!("{}{} ({})", indent, process.exe_name.to_string_lossy(), process.pid);
+    /// }
+    /// ```
+    ///
+    /// [create-toolhelp32-snapshot]: https://docs.microsoft.com/en-us/windows/win32/api/tlhelp32/nf-tlhelp32-createtoolhelp32snapshot
+    pub fn descendants(&self) -> Result<Vec<ProcessEntry>> {
+        let root_creation_time = self.process_times()?.creation_time;
+
+        descendants_of(self.id(), Some(root_creation_time))
+    }
+
+    /// Waits for the child process and all its descendants to exit, without
+    /// a job object, returning the exit status of the child.
+    ///
+    /// The processes of the system are listed every `poll_interval`, like
+    /// with [`descendants`][Child::descendants], until no descendant is
+    /// running. The descendants are tracked across the polls, so the ones
+    /// whose parent has exited are still waited on, but a descendant created
+    /// and orphaned between two polls is missed. Prefer a job object when the
+    /// calling process can use one.
+    ///
+    /// If the child is still running once `timeout` has elapsed, a
+    /// [`Timeout`][Error::Timeout] error is returned. If only descendants are,
+    /// the number of them is returned in
+    /// [`stragglers`][TreeStatus::stragglers]. If a snapshot cannot be taken,
+    /// a [`SnapshotFailed`][Error::SnapshotFailed] error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::time::Duration;
+    ///
+    /// let child = Command::new("install.exe").spawn().unwrap();
+    ///
+    /// let tree = child
+    ///     .wait_tree_polling(Duration::from_millis(250), Some(Duration::from_secs(600)))
+    ///     .unwrap();
+    ///
+    /// if tree.stragglers > 0 {
+    ///     println!("{} processes are still running", tree.stragglers);
+    /// }
+    /// println!("the installer exited with {}", tree.status);
+    /// ```
+    pub fn wait_tree_polling(
+        &self,
+        poll_interval: Duration,
+        timeout: Option<Duration>,
+    ) -> Result<TreeStatus> {
+        let deadline = wait::deadline(timeout);
+        let mut tracked = vec![(self.id(), Some(self.process_times()?.creation_time))];
+
+        let status = loop {
+            poll_descendants(&mut tracked)?;
+
+            if self.api.wait(
+                self.raw_process_handle(),
+                Some(next_poll(poll_interval, deadline)),
+            )? {
+                break self.wait()?;
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(Error::Timeout);
+            }
+        };
+
+        loop {
+            let stragglers = poll_descendants(&mut tracked)?;
+
+            if stragglers == 0 || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(TreeStatus { status, stragglers });
+            }
+
+            thread::sleep(next_poll(poll_interval, deadline));
+        }
+    }
+}
+
+// The delay until the next poll, which is at the deadline at the latest.
+fn next_poll(poll_interval: Duration, deadline: Option<Instant>) -> Duration {
+    match deadline {
+        Some(deadline) => poll_interval.min(deadline.saturating_duration_since(Instant::now())),
+        None => poll_interval,
+    }
+}
+
+// Add the new descendants of the root, `tracked[0]`, to the tracked processes
+// and return the number of them running. A process descends from the root if
+// its parent is tracked, even if it has exited, and it wasn't created before
+// its parent under a recycled identifier.
+fn poll_descendants(tracked: &mut Vec<(u32, Option<SystemTime>)>) -> Result<usize> {
+    let processes = snapshot_processes()?;
+    let root_pid = tracked[0].0;
+    let mut running = HashSet::new();
+
+    // The snapshot can list a process before its parent.
+    loop {
+        let mut added = false;
+
+        for (pid, ppid, _) in &processes {
+            // The identifier of the root cannot be reused while its handle is
+            // open.
+            if *pid == root_pid
+                || running.contains(pid)
+                || !tracked
+                    .iter()
+                    .any(|(tracked_pid, _)| tracked_pid == pid || tracked_pid == ppid)
+            {
+                continue;
+            }
+
+            let creation_time = process_creation_time(*pid);
+
+            if tracked.contains(&(*pid, creation_time)) {
+                running.insert(*pid);
+            } else if tracked.iter().any(|&(parent_pid, parent_creation_time)| {
+                parent_pid == *ppid
+                    && !matches!(
+                        (creation_time, parent_creation_time),
+                        (Some(creation_time), Some(parent_creation_time))
+                            if creation_time < parent_creation_time
+                    )
+            }) {
+                tracked.push((*pid, creation_time));
+                running.insert(*pid);
+                added = true;
+            }
+        }
+
+        if !added {
+            return Ok(running.len());
+        }
+    }
+}
+
+// Find the descendants of the process with the given identifier. The creation
+// time of the root, when known, is used to reject processes that were created
+// before it under a recycled identifier.
+pub(crate) fn descendants_of(
+    pid: u32,
+    creation_time: Option<SystemTime>,
+) -> Result<Vec<ProcessEntry>> {
+    let processes = snapshot_processes()?;
+
+    let mut descendants: Vec<ProcessEntry> = Vec::new();
+    let mut visited = HashSet::from([pid]);
+    let mut parents = vec![(pid, creation_time, 0)];
+
+    while let Some((parent_pid, parent_creation_time, depth)) = parents.pop() {
+        for (pid, ppid, exe_name) in &processes {
+            if *ppid != parent_pid || visited.contains(pid) {
+                continue;
+            }
+
+            let creation_time = process_creation_time(*pid);
+
+            if let (Some(creation_time), Some(parent_creation_time)) =
+                (creation_time, parent_creation_time)
+            {
+                if creation_time < parent_creation_time {
+                    continue;
+                }
+            }
+
+            visited.insert(*pid);
+            parents.push((*pid, creation_time, depth + 1));
+            descendants.push(ProcessEntry {
+                pid: *pid,
+                parent_pid,
+                exe_name: exe_name.clone(),
+                creation_time,
+                depth: depth + 1,
+            });
+        }
+    }
+
+    descendants.sort_by_key(|entry| entry.depth);
+
+    Ok(descendants)
+}
+
+// Take a snapshot of the processes of the system, returning the identifier,
+// parent identifier and executable name of each of them.
+fn snapshot_processes() -> Result<Vec<(u32, u32, OsString)>> {
+    let mut processes = Vec::new();
+
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)
+            .map_err(|_| Error::SnapshotFailed(io::Error::last_os_error()))?;
+
+        let mut entry = PROCESSENTRY32W {
+            dwSize: size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+
+        let mut res = Process32FirstW(snapshot, &mut entry);
+
+        if res.is_err() {
+            let err = io::Error::last_os_error();
+            let _ = CloseHandle(snapshot);
+            return Err(Error::SnapshotFailed(err));
+        }
+
+        while res.is_ok() {
+            let len = entry
+                .szExeFile
+                .iter()
+                .position(|c| *c == 0)
+                .unwrap_or(entry.szExeFile.len());
+
+            processes.push((
+                entry.th32ProcessID,
+                entry.th32ParentProcessID,
+                OsString::from_wide(&entry.szExeFile[..len]),
+            ));
+
+            res = Process32NextW(snapshot, &mut entry);
+        }
+
+        let _ = CloseHandle(snapshot);
+    }
+
+    Ok(processes)
+}
+
+// Get the creation time of a process, if it can be opened.
+pub(crate) fn process_creation_time(pid: u32) -> Option<SystemTime> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+
+        let mut creation_time = FILETIME::default();
+        let mut exit_time = FILETIME::default();
+        let mut kernel_time = FILETIME::default();
+        let mut user_time = FILETIME::default();
+
+        let res = GetProcessTimes(
+            process,
+            &mut creation_time,
+            &mut exit_time,
+            &mut kernel_time,
+            &mut user_time,
+        );
+
+        let _ = CloseHandle(process);
+
+        res.ok().map(|()| filetime_to_system_time(creation_time))
+    }
+}
@@ -0,0 +1,251 @@
+// Spawning a child on the desktop of the user logged on the console, from a
+// service running in session 0.
+//
+// The calls getting the token and the environment of the user are behind a
+// trait, so the sequence and its error handling don't depend on a session.
+
+use crate::{env, pipe::owned, Error, Result};
+use std::{
+    ffi::{c_void, OsString},
+    io,
+    os::windows::io::{AsRawHandle, OwnedHandle},
+};
+use windows::Win32::{
+    Foundation::{GetLastError, ERROR_NO_TOKEN, ERROR_PRIVILEGE_NOT_HELD, HANDLE, WIN32_ERROR},
+    Security::{DuplicateTokenEx, SecurityIdentification, TokenPrimary, TOKEN_ALL_ACCESS},
+    System::{
+        Environment::{CreateEnvironmentBlock, DestroyEnvironmentBlock},
+        RemoteDesktop::{ProcessIdToSessionId, WTSGetActiveConsoleSessionId, WTSQueryUserToken},
+    },
+};
+
+// The interactive desktop of the session.
+pub(crate) const DESKTOP: &str = r"winsta0\default";
+
+/// Returns the identifier of the Remote Desktop Services session of the
+/// process `pid`.
+///
+/// Session 0 is the one of the services, which cannot show a UI to the user.
+/// The identifier of a process can be reused once it has exited and all its
+/// handles have been closed: the session of another process may be returned.
+/// If the function fails, like when there is no process `pid`, a
+/// [`GetSessionIdFailed`][Error::GetSessionIdFailed] error is returned.
+///
+/// Equivalent to the [`ProcessIdToSessionId`][process-id-to-session-id]
+/// function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::session_of_pid;
+///
+/// if session_of_pid(std::process::id()).unwrap() == 0 {
+///     println!("running as a service, without UI");
+/// }
+/// ```
+///
+/// [process-id-to-session-id]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-processidtosessionid
+pub fn session_of_pid(pid: u32) -> Result<u32> {
+    let mut session_id = 0;
+
+    unsafe {
+        ProcessIdToSessionId(pid, &mut session_id)
+            .map_err(|_| Error::GetSessionIdFailed(io::Error::last_os_error()))?;
+    }
+
+    Ok(session_id)
+}
+
+// The calls of the sequence, returning the error codes of the system.
+pub(crate) trait SessionTokens {
+    // The identifier of the session attached to the console, `None` if there
+    // is none.
+    fn active_console_session(&self) -> Option<u32>;
+
+    // The token of the user logged on the session.
+    fn query_user_token(&self, session_id: u32) -> std::result::Result<OwnedHandle, u32>;
+
+    // A primary token duplicated from `token`.
+    fn duplicate_primary(&self, token: &OwnedHandle) -> std::result::Result<OwnedHandle, u32>;
+
+    // The environment of the user of `token`.
+    fn environment(
+        &self,
+        token: &OwnedHandle,
+    ) -> std::result::Result<Vec<(OsString, OsString)>, u32>;
+}
+
+// The token and the environment the child runs with.
+pub(crate) struct UserSession {
+    pub(crate) token: OwnedHandle,
+    pub(crate) environment: Vec<(OsString, OsString)>,
+}
+
+// Get the token and the environment of the user logged on the console. The
+// tokens are owned, so they are closed on every path.
+pub(crate) fn active_user_session(tokens: &impl SessionTokens) -> Result<UserSession> {
+    let session_id = tokens
+        .active_console_session()
+        .ok_or(Error::NoActiveSession)?;
+    let user_token =
+        tokens
+            .query_user_token(session_id)
+            .map_err(|code| match WIN32_ERROR(code) {
+                // The session exists but nobody is logged on.
+                ERROR_NO_TOKEN => Error::NoActiveSession,
+                ERROR_PRIVILEGE_NOT_HELD => Error::MissingTcbPrivilege,
+                _ => Error::UserTokenFailed(io::Error::from_raw_os_error(code as i32)),
+            })?;
+    let token = tokens
+        .duplicate_primary(&user_token)
+        .map_err(|code| Error::UserTokenFailed(io::Error::from_raw_os_error(code as i32)))?;
+    let environment = tokens
+        .environment(&token)
+        .map_err(|code| Error::UserTokenFailed(io::Error::from_raw_os_error(code as i32)))?;
+
+    Ok(UserSession { token, environment })
+}
+
+// The Win32 API.
+pub(crate) struct Win32SessionTokens;
+
+impl SessionTokens for Win32SessionTokens {
+    fn active_console_session(&self) -> Option<u32> {
+        // `0xFFFFFFFF` while the sessions are being attached or detached.
+        match unsafe { WTSGetActiveConsoleSessionId() } {
+            u32::MAX => None,
+            session_id => Some(session_id),
+        }
+    }
+
+    fn query_user_token(&self, session_id: u32) -> std::result::Result<OwnedHandle, u32> {
+        let mut token = HANDLE::default();
+
+        unsafe {
+            WTSQueryUserToken(session_id, &mut token).map_err(|_| GetLastError().0)?;
+
+            Ok(owned(token))
+        }
+    }
+
+    fn duplicate_primary(&self, token: &OwnedHandle) -> std::result::Result<OwnedHandle, u32> {
+        let mut primary = HANDLE::default();
+
+        unsafe {
+            DuplicateTokenEx(
+                HANDLE(token.as_raw_handle() as isize),
+                TOKEN_ALL_ACCESS,
+                None,
+                SecurityIdentification,
+                TokenPrimary,
+                &mut primary,
+            )
+            .map_err(|_| GetLastError().0)?;
+
+            Ok(owned(primary))
+        }
+    }
+
+    fn environment(
+        &self,
+        token: &OwnedHandle,
+    ) -> std::result::Result<Vec<(OsString, OsString)>, u32> {
+        let mut block: *mut c_void = std::ptr::null_mut();
+
+        unsafe {
+            CreateEnvironmentBlock(&mut block, HANDLE(token.as_raw_handle() as isize), false)
+                .map_err(|_| GetLastError().0)?;
+
+            let environment = env::parse_block(block as *const u16);
+
+            let _ = DestroyEnvironmentBlock(block);
+
+            Ok(environment)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    // Scripted results of the calls of the sequence.
+    struct FakeTokens {
+        session: Option<u32>,
+        query_error: Option<u32>,
+    }
+
+    // Any handle owned by the test, the tokens are only closed.
+    fn handle() -> OwnedHandle {
+        OwnedHandle::from(File::open(std::env::current_exe().unwrap()).unwrap())
+    }
+
+    impl SessionTokens for FakeTokens {
+        fn active_console_session(&self) -> Option<u32> {
+            self.session
+        }
+
+        fn query_user_token(&self, _session_id: u32) -> std::result::Result<OwnedHandle, u32> {
+            self.query_error.map_or_else(|| Ok(handle()), Err)
+        }
+
+        fn duplicate_primary(&self, _token: &OwnedHandle) -> std::result::Result<OwnedHandle, u32> {
+            Ok(handle())
+        }
+
+        fn environment(
+            &self,
+            _token: &OwnedHandle,
+        ) -> std::result::Result<Vec<(OsString, OsString)>, u32> {
+            Ok(vec![("USERNAME".into(), "user".into())])
+        }
+    }
+
+    fn session(session: Option<u32>, query_error: Option<u32>) -> Result<UserSession> {
+        active_user_session(&FakeTokens {
+            session,
+            query_error,
+        })
+    }
+
+    #[test]
+    fn session_has_the_environment_of_the_user() {
+        let session = session(Some(1), None).unwrap();
+
+        assert_eq!(
+            session.environment,
+            [(OsString::from("USERNAME"), OsString::from("user"))]
+        );
+    }
+
+    #[test]
+    fn missing_console_session_is_no_active_session() {
+        assert!(matches!(session(None, None), Err(Error::NoActiveSession)));
+    }
+
+    #[test]
+    fn session_without_user_is_no_active_session() {
+        assert!(matches!(
+            session(Some(1), Some(ERROR_NO_TOKEN.0)),
+            Err(Error::NoActiveSession)
+        ));
+    }
+
+    #[test]
+    fn missing_privilege_is_reported() {
+        assert!(matches!(
+            session(Some(1), Some(ERROR_PRIVILEGE_NOT_HELD.0)),
+            Err(Error::MissingTcbPrivilege)
+        ));
+    }
+
+    #[test]
+    fn other_failures_are_user_token_failures() {
+        // `ERROR_ACCESS_DENIED`
+        match session(Some(1), Some(5)) {
+            Err(Error::UserTokenFailed(err)) => assert_eq!(err.raw_os_error(), Some(5)),
+            res => panic!("unexpected result: {:?}", res.err()),
+        }
+    }
+}
@@ -0,0 +1,454 @@
+// Check the configuration of a command before spawning it.
+//
+// The builder methods don't fail, the problems are collected when spawning so
+// they can all be reported at once.
+
+use crate::{cmdline, crt_fds, path, Command, Error, Result};
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::Path,
+};
+use windows::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
+
+// The maximum length of the command line given to `CreateProcessW`, in UTF-16
+// units including the null terminator.
+const MAX_COMMAND_LINE_LEN: usize = 32_768;
+
+// The maximum length of an AppUserModelID, in UTF-16 units.
+const MAX_APP_USER_MODEL_ID_LEN: usize = 128;
+
+// Check the configuration of `command`, `capture` being `true` when the
+// standard streams default to the ones of `Command::output`. All the problems
+// are returned in a single `InvalidCommand` error.
+pub(crate) fn validate(command: &Command, capture: bool) -> Result<()> {
+    let problems = [
+        check_command_line(command),
+        check_application_name(command.application_name.as_deref()),
+        check_current_directory(command.current_directory.as_deref()),
+        check_cmd_directory(command),
+        check_stdio_inheritance(command, capture),
+        check_app_user_model_id(command.app_user_model_id.as_deref()),
+        check_hotkey(command, capture),
+        check_prevent_pinning(command),
+        check_crt_fds(command),
+        check_inherited_handles(command),
+        check_env(command),
+        check_user_session(command),
+        command
+            .working_set
+            .and_then(|(min_bytes, max_bytes)| check_working_set(min_bytes, max_bytes))
+            .map(|problem| format!("invalid working set: {}", problem)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidCommand(problems))
+    }
+}
+
+fn check_command_line(command: &Command) -> Option<String> {
+    let command_line = &command.command;
+    let len = command_line.encode_wide().count();
+
+    if len == 0 {
+        Some("the command line is empty".to_string())
+    } else if command_line.encode_wide().all(cmdline::is_whitespace) {
+        Some("the command line contains only whitespace".to_string())
+    } else if command.application_name.is_none() && program_and_args(command_line).0.is_empty() {
+        Some(format!(
+            "the program of the command line {:?} is empty",
+            command_line
+        ))
+    } else if command_line.encode_wide().any(|unit| unit == 0) {
+        Some("the command line contains a null character".to_string())
+    } else if len >= MAX_COMMAND_LINE_LEN {
+        Some(format!(
+            "the command line is {} characters long, the maximum is {}",
+            len,
+            MAX_COMMAND_LINE_LEN - 1
+        ))
+    } else {
+        None
+    }
+}
+
+// The first token of the command line, which is the program when there is no
+// application name, and the arguments that follow it. The whitespace before
+// it is ignored.
+pub(crate) fn program_and_args(command_line: &OsStr) -> (OsString, OsString) {
+    let command_line = command_line
+        .encode_wide()
+        .skip_while(|&unit| cmdline::is_whitespace(unit))
+        .collect::<Vec<_>>();
+
+    cmdline::program_and_args(&OsString::from_wide(&command_line))
+}
+
+fn check_application_name(application_name: Option<&Path>) -> Option<String> {
+    let application_name = application_name?;
+
+    if application_name.as_os_str().is_empty() {
+        Some("the application name is empty".to_string())
+    } else if application_name
+        .as_os_str()
+        .encode_wide()
+        .any(|unit| unit == 0)
+    {
+        Some("the application name contains a null character".to_string())
+    } else {
+        None
+    }
+}
+
+fn check_current_directory(current_directory: Option<&Path>) -> Option<String> {
+    let current_directory = current_directory?;
+
+    if current_directory.as_os_str().is_empty() {
+        Some("the working directory is empty".to_string())
+    } else if current_directory
+        .as_os_str()
+        .encode_wide()
+        .any(|unit| unit == 0)
+    {
+        Some("the working directory contains a null character".to_string())
+    } else {
+        None
+    }
+}
+
+// `cmd.exe` starts in the Windows directory when its working directory is a
+// UNC path, and runs the command there.
+fn check_cmd_directory(command: &Command) -> Option<String> {
+    let current_directory = command.current_directory.as_deref()?;
+    let program = program_and_args(&command.command).0;
+    let is_cmd = Path::new(&program)
+        .file_name()
+        .and_then(OsStr::to_str)
+        .is_some_and(|name| {
+            name.eq_ignore_ascii_case("cmd") || name.eq_ignore_ascii_case("cmd.exe")
+        });
+
+    (is_cmd && path::is_unc(current_directory)).then(|| {
+        format!(
+            "cmd.exe doesn't support the UNC working directory {}, use `pushd` in the command instead",
+            current_directory.display()
+        )
+    })
+}
+
+// The child inherits the handles of its standard streams, they are invalid if
+// the inheritance has been disabled.
+fn check_stdio_inheritance(command: &Command, capture: bool) -> Option<String> {
+    let stdio =
+        capture || command.stdin.is_some() || command.stdout.is_some() || command.stderr.is_some();
+
+    (stdio && command.inherit_handles == Some(false)).then(|| {
+        "the standard streams are configured but the inheritance of handles is disabled".to_string()
+    })
+}
+
+fn check_app_user_model_id(id: Option<&OsStr>) -> Option<String> {
+    let id = id?;
+    let len = id.encode_wide().count();
+
+    if len == 0 {
+        Some("the AppUserModelID is empty".to_string())
+    } else if len > MAX_APP_USER_MODEL_ID_LEN {
+        Some(format!(
+            "the AppUserModelID is {} characters long, the maximum is {}",
+            len, MAX_APP_USER_MODEL_ID_LEN
+        ))
+    } else if id
+        .encode_wide()
+        .any(|unit| unit == 0 || unit == b' ' as u16)
+    {
+        Some("the AppUserModelID contains a space or a null character".to_string())
+    } else {
+        None
+    }
+}
+
+// The hotkey is given in `hStdInput`, which is also the handle of the standard
+// input when the standard streams are configured.
+fn check_hotkey(command: &Command, capture: bool) -> Option<String> {
+    let (virtual_key, _) = command.hotkey?;
+    let stdio =
+        capture || command.stdin.is_some() || command.stdout.is_some() || command.stderr.is_some();
+
+    if virtual_key == 0 || virtual_key > 0xFF {
+        Some(format!(
+            "{:#x} is not a valid virtual-key code",
+            virtual_key
+        ))
+    } else if stdio {
+        Some("a hotkey cannot be combined with the standard streams".to_string())
+    } else {
+        None
+    }
+}
+
+fn check_prevent_pinning(command: &Command) -> Option<String> {
+    (command.prevent_pinning && command.app_user_model_id.is_none())
+        .then(|| "preventing the pinning requires an AppUserModelID".to_string())
+}
+
+// The handles of the file descriptors are inherited.
+fn check_crt_fds(command: &Command) -> Option<String> {
+    let mut fds = command
+        .crt_fds
+        .iter()
+        .map(|(fd, _)| *fd)
+        .collect::<Vec<_>>();
+
+    fds.sort_unstable();
+
+    if let Some(fd) = fds.last().filter(|fd| **fd > crt_fds::MAX_FD) {
+        Some(format!(
+            "the file descriptor {} is above the maximum of {}",
+            fd,
+            crt_fds::MAX_FD
+        ))
+    } else if let Some(fds) = fds.windows(2).find(|fds| fds[0] == fds[1]) {
+        Some(format!("the file descriptor {} is given twice", fds[0]))
+    } else if !fds.is_empty() && command.inherit_handles == Some(false) {
+        Some(
+            "file descriptors are given to the child but the inheritance of handles is disabled"
+                .to_string(),
+        )
+    } else {
+        None
+    }
+}
+
+// The names cannot contain `=`, except at the start like the hidden variables
+// holding the current directory of each drive, like `=C:`.
+fn check_env(command: &Command) -> Option<String> {
+    let invalid = command
+        .environment
+        .vars
+        .iter()
+        .filter(|(name, value)| {
+            let name = name.encode_wide().collect::<Vec<_>>();
+
+            name.is_empty()
+                || name[1..].contains(&(b'=' as u16))
+                || name.contains(&0)
+                || value.encode_wide().any(|unit| unit == 0)
+        })
+        .map(|(name, _)| format!("{:?}", name))
+        .collect::<Vec<_>>();
+
+    (!invalid.is_empty()).then(|| format!("invalid environment variables: {}", invalid.join(", ")))
+}
+
+fn check_inherited_handles(command: &Command) -> Option<String> {
+    (!command.inherited_handles.is_empty() && command.inherit_handles == Some(false)).then(|| {
+        "handles are given to the child but the inheritance of handles is disabled".to_string()
+    })
+}
+
+// The child gets the token of the user, it cannot be restricted too.
+fn check_user_session(command: &Command) -> Option<String> {
+    (command.user_session && command.restricted.is_some()).then(|| {
+        "the child cannot be both restricted and spawned in the active user session".to_string()
+    })
+}
+
+// Check that `command` only uses the options `ShellExecuteExW` supports, so it
+// can be spawned elevated. All the options that would be ignored are returned
+// in a single `InvalidCommand` error.
+pub(crate) fn validate_elevation(command: &Command) -> Result<()> {
+    let unsupported = [
+        (command.inherit_handles == Some(true), "inherit_handles"),
+        (!command.environment.is_inherited(), "env"),
+        (command.priority_class.is_some(), "priority_class"),
+        (command.startup_feedback.is_some(), "startup_feedback"),
+        (command.app_user_model_id.is_some(), "app_user_model_id"),
+        (command.hotkey.is_some(), "hotkey"),
+        (command.prevent_pinning, "prevent_pinning"),
+        (!command.crt_fds.is_empty(), "crt_inherit_fds"),
+        (!command.inherited_handles.is_empty(), "inherit_handle"),
+        (
+            command.suppress_error_dialogs.is_some(),
+            "suppress_error_dialogs",
+        ),
+        (command.inherit_parent_affinity, "inherit_parent_affinity"),
+        (
+            command.disable_wow64_redirection,
+            "disable_wow64_redirection",
+        ),
+        (command.disable_priority_boost, "disable_priority_boost"),
+        (command.working_set.is_some(), "working_set"),
+        (command.efficiency_mode, "efficiency_mode"),
+        (command.signature.is_some(), "verify_signature"),
+        (command.debug.is_some(), "debug"),
+        (command.protected.is_some(), "protected_process"),
+        (command.restricted.is_some(), "restricted"),
+        (command.user_session, "in_active_user_session"),
+        (command.stdin.is_some(), "stdin"),
+        (command.stdout.is_some(), "stdout"),
+        (command.stderr.is_some(), "stderr"),
+    ];
+
+    let problems = unsupported
+        .into_iter()
+        .filter(|&(set, _)| set)
+        .map(|(_, option)| format!("`{}` isn't supported when spawning elevated", option))
+        .collect::<Vec<_>>();
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidCommand(problems))
+    }
+}
+
+// Check working set limits, shared by `Command::working_set` and
+// `Child::set_working_set`. The system requires a maximum of at least 13
+// pages.
+pub(crate) fn check_working_set(min_bytes: usize, max_bytes: usize) -> Option<&'static str> {
+    let mut info = SYSTEM_INFO::default();
+
+    unsafe { GetSystemInfo(&mut info) };
+
+    if min_bytes == 0 {
+        Some("the minimum working set size cannot be zero")
+    } else if min_bytes > max_bytes {
+        Some("the minimum working set size is above the maximum")
+    } else if max_bytes < 13 * info.dwPageSize as usize {
+        Some("the maximum working set size is below 13 pages")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HotkeyModifiers, Stdio};
+
+    #[test]
+    fn command_line_is_checked() {
+        assert!(check_command_line(&Command::new("")).is_some());
+        assert!(check_command_line(&Command::new(" \t")).is_some());
+        assert!(check_command_line(&Command::new("cmd.exe\0/c")).is_some());
+        assert!(check_command_line(&Command::new("a".repeat(MAX_COMMAND_LINE_LEN))).is_some());
+        assert!(check_command_line(&Command::new("cmd.exe /c exit")).is_none());
+    }
+
+    #[test]
+    fn empty_command_line_is_rejected() {
+        assert_eq!(
+            check_command_line(&Command::new("")).as_deref(),
+            Some("the command line is empty")
+        );
+    }
+
+    #[test]
+    fn whitespace_command_line_is_rejected() {
+        assert_eq!(
+            check_command_line(&Command::new("   ")).as_deref(),
+            Some("the command line contains only whitespace")
+        );
+    }
+
+    #[test]
+    fn empty_quoted_program_is_rejected() {
+        assert_eq!(
+            check_command_line(&Command::new("\"\"")).as_deref(),
+            Some(r#"the program of the command line "\"\"" is empty"#)
+        );
+
+        // The program is the application name.
+        let mut command = Command::new("\"\" --check");
+        command.application_name(r"C:\tools\check.exe");
+        assert!(check_command_line(&command).is_none());
+    }
+
+    #[test]
+    fn paths_are_checked() {
+        assert!(check_application_name(Some(Path::new(""))).is_some());
+        assert!(check_application_name(Some(Path::new("cmd.exe\0"))).is_some());
+        assert!(check_application_name(None).is_none());
+        assert!(check_current_directory(Some(Path::new(""))).is_some());
+        assert!(check_current_directory(Some(Path::new(r"C:\Windows"))).is_none());
+    }
+
+    #[test]
+    fn cmd_rejects_a_unc_directory() {
+        let mut command = Command::new("cmd.exe /c dir");
+        command.current_dir(r"\\server\share");
+        assert!(check_cmd_directory(&command).is_some());
+
+        let mut command = Command::new("notepad.exe");
+        command.current_dir(r"\\server\share");
+        assert!(check_cmd_directory(&command).is_none());
+    }
+
+    #[test]
+    fn stdio_requires_the_inheritance() {
+        let mut command = Command::new("cmd.exe");
+        command.inherit_handles(false);
+        assert!(check_stdio_inheritance(&command, false).is_none());
+        assert!(check_stdio_inheritance(&command, true).is_some());
+
+        command.stdout(Stdio::piped());
+        assert!(check_stdio_inheritance(&command, false).is_some());
+    }
+
+    #[test]
+    fn app_user_model_id_is_checked() {
+        assert!(check_app_user_model_id(Some(OsStr::new(""))).is_some());
+        assert!(check_app_user_model_id(Some(OsStr::new("Contoso App"))).is_some());
+        assert!(check_app_user_model_id(Some(OsStr::new(&"a".repeat(129)))).is_some());
+        assert!(check_app_user_model_id(Some(OsStr::new("Contoso.App"))).is_none());
+    }
+
+    #[test]
+    fn hotkey_is_checked() {
+        let mut command = Command::new("notepad.exe");
+        command.hotkey(0x100, HotkeyModifiers::NONE);
+        assert!(check_hotkey(&command, false).is_some());
+
+        command.hotkey(0x41, HotkeyModifiers::CONTROL);
+        assert!(check_hotkey(&command, false).is_none());
+        assert!(check_hotkey(&command, true).is_some());
+    }
+
+    #[test]
+    fn env_names_are_checked() {
+        let mut command = Command::new("cmd.exe");
+        command.env("=C:", r"C:\Windows");
+        assert!(check_env(&command).is_none());
+
+        command.env("A=B", "value");
+        assert!(check_env(&command).is_some());
+    }
+
+    #[test]
+    fn working_set_is_checked() {
+        assert!(check_working_set(0, 64 << 20).is_some());
+        assert!(check_working_set(2 << 20, 1 << 20).is_some());
+        assert!(check_working_set(1, 1).is_some());
+        assert!(check_working_set(1 << 20, 64 << 20).is_none());
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let mut command = Command::new("cmd.exe /c dir");
+        command
+            .current_dir(r"\\server\share")
+            .app_user_model_id("Contoso App")
+            .hotkey(0, HotkeyModifiers::NONE);
+
+        match validate(&command, false) {
+            Err(Error::InvalidCommand(problems)) => assert_eq!(problems.len(), 3),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
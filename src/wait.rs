@@ -0,0 +1,374 @@
+use crate::{Child, Error, ExitStatus, Result};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+use windows::Win32::{
+    Foundation::{
+        HANDLE, WAIT_ABANDONED_0, WAIT_EVENT, WAIT_FAILED, WAIT_IO_COMPLETION, WAIT_OBJECT_0,
+        WAIT_TIMEOUT,
+    },
+    System::Threading::{WaitForMultipleObjects, WaitForSingleObjectEx, INFINITE},
+};
+
+/// How an alertable wait ended, returned by [`Child::wait_alertable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitOutcome {
+    /// The child exited with this status.
+    Exited(ExitStatus),
+    /// The timeout elapsed before the child exited.
+    TimedOut,
+    /// A user APC or an I/O completion routine ran on the waiting thread
+    /// before the child exited. The wait can be started again.
+    Interrupted,
+}
+
+impl Child {
+    /// Waits for the child to exit like [`wait`][Child::wait], in an alertable
+    /// state: the APCs queued to the calling thread run during the wait.
+    ///
+    /// This is needed when the waiting thread is cancelled with an APC queued
+    /// by `QueueUserAPC`, or completes overlapped I/O with completion
+    /// routines. Once an APC has run, the wait ends with
+    /// [`Interrupted`][WaitOutcome::Interrupted], so the caller can react and
+    /// wait again. [`TimedOut`][WaitOutcome::TimedOut] is returned if
+    /// `timeout` elapsed before the child exited, `None` waits forever.
+    ///
+    /// If the wait fails, a [`WaitFailed`][Error::WaitFailed] error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::{Command, WaitOutcome};
+    ///
+    /// let child = Command::new("cargo.exe build").spawn().unwrap();
+    ///
+    /// let status = loop {
+    ///     match child.wait_alertable(None).unwrap() {
+    ///         WaitOutcome::Exited(status) => break status,
+    ///         WaitOutcome::Interrupted => println!("an APC ran, still waiting"),
+    ///         WaitOutcome::TimedOut => unreachable!(),
+    ///     }
+    /// };
+    /// ```
+    ///
+    /// Equivalent to the [`WaitForSingleObjectEx`][wait-for-single-object-ex]
+    /// function with `bAlertable` set to `TRUE`.
+    ///
+    /// [wait-for-single-object-ex]: https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-waitforsingleobjectex
+    pub fn wait_alertable(&self, timeout: Option<Duration>) -> Result<WaitOutcome> {
+        if let Some(status) = self.exit_status.get() {
+            return Ok(WaitOutcome::Exited(*status));
+        }
+
+        let res = unsafe {
+            WaitForSingleObjectEx(
+                self.process_information.hProcess,
+                remaining_millis(deadline(timeout)),
+                true,
+            )
+        };
+
+        if res == WAIT_IO_COMPLETION {
+            return Ok(WaitOutcome::Interrupted);
+        }
+
+        match wait_result(res, 1)? {
+            // The handle is signaled, even an exit code of `STILL_ACTIVE` is
+            // the one of the exited process.
+            Some(_) => Ok(WaitOutcome::Exited(self.reap_after_external_wait()?)),
+            None => Ok(WaitOutcome::TimedOut),
+        }
+    }
+}
+
+// The maximum number of handles `WaitForMultipleObjects` can wait on.
+const MAXIMUM_WAIT_OBJECTS: usize = 64;
+
+// How long each group of handles is waited on when there are more handles
+// than `WaitForMultipleObjects` can take at once.
+const CHUNK_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Waits for one of the children to exit, returning its index in `children`
+/// and its exit status.
+///
+/// Only the child that exited is reaped. The children that have already been
+/// waited on are ignored, so this can be called in a loop to observe the
+/// children in the order they exit. `Ok(None)` is returned if `timeout`
+/// elapses first, or right away if all the children have already been waited
+/// on. A `None` timeout waits forever. If the function fail, a
+/// [`WaitFailed`][Error::WaitFailed] error is returned.
+///
+/// There is no limit on the number of children. Above 64 children, they are
+/// waited on in groups of 64 with a short timeout each, which delays the
+/// observation of an exit by a few milliseconds.
+///
+/// Equivalent to the [`WaitForMultipleObjects`][wait-for-multiple-objects]
+/// function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{wait_any, Command};
+///
+/// let children = [
+///     Command::new("cmd.exe /c timeout 3").spawn().unwrap(),
+///     Command::new("cmd.exe /c timeout 1").spawn().unwrap(),
+/// ];
+///
+/// while let Some((index, status)) = wait_any(&children, None).unwrap() {
+///     println!("child {} exited with {}", index, status);
+/// }
+/// ```
+///
+/// [wait-for-multiple-objects]: https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-waitformultipleobjects
+pub fn wait_any(
+    children: &[Child],
+    timeout: Option<Duration>,
+) -> Result<Option<(usize, ExitStatus)>> {
+    let pending = children
+        .iter()
+        .enumerate()
+        .filter(|(_, child)| child.exit_status.get().is_none())
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    if pending.is_empty() {
+        return Ok(None);
+    }
+
+    let handles = pending
+        .iter()
+        .map(|index| children[*index].process_information.hProcess)
+        .collect::<Vec<_>>();
+
+    match wait_any_handle(&handles, deadline(timeout))? {
+        // The process has exited, this doesn't block.
+        Some(index) => Ok(Some((pending[index], children[pending[index]].wait()?))),
+        None => Ok(None),
+    }
+}
+
+/// Waits for all the children to exit, returning their exit status in the
+/// same order as `children`.
+///
+/// `Ok(None)` is returned if `timeout` elapses first, in which case none of
+/// the children is reaped. A `None` timeout waits forever. The children that
+/// have already been waited on are included. If the function fail, a
+/// [`WaitFailed`][Error::WaitFailed] error is returned.
+///
+/// Equivalent to the [`WaitForMultipleObjects`][wait-for-multiple-objects]
+/// function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use CreateProcessW::{wait_all, Command};
+/// use std::time::Duration;
+///
+/// let children = [
+///     Command::new("cargo.exe build").spawn().unwrap(),
+///     Command::new("cargo.exe doc").spawn().unwrap(),
+/// ];
+///
+/// match wait_all(&children, Some(Duration::from_secs(60))).unwrap() {
+///     Some(statuses) => println!("exited with {:?}", statuses),
+///     None => println!("still running"),
+/// }
+/// ```
+///
+/// [wait-for-multiple-objects]: https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-waitformultipleobjects
+pub fn wait_all(children: &[Child], timeout: Option<Duration>) -> Result<Option<Vec<ExitStatus>>> {
+    let deadline = deadline(timeout);
+
+    let handles = children
+        .iter()
+        .filter(|child| child.exit_status.get().is_none())
+        .map(|child| child.process_information.hProcess)
+        .collect::<Vec<_>>();
+
+    // All the processes have to exit, the groups can be waited on one after
+    // the other.
+    for chunk in handles.chunks(MAXIMUM_WAIT_OBJECTS) {
+        let res = unsafe { WaitForMultipleObjects(chunk, true, remaining_millis(deadline)) };
+
+        if wait_result(res, chunk.len())?.is_none() {
+            return Ok(None);
+        }
+    }
+
+    children
+        .iter()
+        .map(Child::wait)
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
+
+// Wait until one of the handles is signaled, returning its index.
+pub(crate) fn wait_any_handle(
+    handles: &[HANDLE],
+    deadline: Option<Instant>,
+) -> Result<Option<usize>> {
+    loop {
+        for (chunk_index, chunk) in handles.chunks(MAXIMUM_WAIT_OBJECTS).enumerate() {
+            let timeout = if handles.len() <= MAXIMUM_WAIT_OBJECTS {
+                remaining_millis(deadline)
+            } else {
+                remaining_millis(deadline).min(CHUNK_TIMEOUT.as_millis() as u32)
+            };
+
+            let res = unsafe { WaitForMultipleObjects(chunk, false, timeout) };
+
+            if let Some(index) = wait_result(res, chunk.len())? {
+                return Ok(Some(chunk_index * MAXIMUM_WAIT_OBJECTS + index));
+            }
+        }
+
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Ok(None);
+        }
+    }
+}
+
+// Interpret the value returned by `WaitForSingleObject` or
+// `WaitForMultipleObjects` on `count` handles, returning the index of the
+// signaled handle or `None` if the timeout elapsed.
+//
+// Only `WAIT_FAILED` sets the last error, the other values must not consult
+// it.
+pub(crate) fn wait_result(res: WAIT_EVENT, count: usize) -> Result<Option<usize>> {
+    // `WAIT_OBJECT_0 + n` means the `n`-th handle is signaled,
+    // `WAIT_ABANDONED_0 + n` that it is a mutex abandoned by its owner.
+    let signaled = res.0.wrapping_sub(WAIT_OBJECT_0.0) as usize;
+    let abandoned = res.0.wrapping_sub(WAIT_ABANDONED_0.0) as usize;
+
+    if signaled < count {
+        Ok(Some(signaled))
+    } else if abandoned < count {
+        Err(Error::WaitAbandoned)
+    } else if res == WAIT_TIMEOUT {
+        Ok(None)
+    } else if res == WAIT_FAILED {
+        Err(Error::WaitFailed(io::Error::last_os_error()))
+    } else {
+        // Not a documented value, report it as is.
+        Err(Error::WaitFailed(io::Error::from_raw_os_error(
+            res.0 as i32,
+        )))
+    }
+}
+
+pub(crate) fn deadline(timeout: Option<Duration>) -> Option<Instant> {
+    // A timeout too large to be represented waits forever.
+    timeout.and_then(|timeout| Instant::now().checked_add(timeout))
+}
+
+// The time left until the deadline in milliseconds, rounded up so the wait
+// doesn't return before the deadline.
+pub(crate) fn remaining_millis(deadline: Option<Instant>) -> u32 {
+    match deadline {
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let millis = (remaining.as_nanos() + 999_999) / 1_000_000;
+
+            millis.min(u128::from(INFINITE - 1)) as u32
+        }
+        None => INFINITE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::os_code;
+    use windows::{
+        core::PCWSTR,
+        Win32::{
+            Foundation::{CloseHandle, SetLastError, ERROR_INVALID_HANDLE},
+            System::Threading::{CreateEventW, SetEvent},
+        },
+    };
+
+    #[test]
+    fn remaining_millis_are_bounded() {
+        assert_eq!(remaining_millis(None), INFINITE);
+        // Too far to be represented.
+        assert_eq!(remaining_millis(deadline(Some(Duration::MAX))), INFINITE);
+        // About 100 days, `INFINITE` would wait forever.
+        assert_eq!(
+            remaining_millis(deadline(Some(Duration::from_secs(8_640_000)))),
+            INFINITE - 1
+        );
+
+        if let Some(past) = Instant::now().checked_sub(Duration::from_secs(1)) {
+            assert_eq!(remaining_millis(Some(past)), 0);
+        }
+    }
+
+    #[test]
+    fn signaled_handle_is_returned() {
+        assert_eq!(wait_result(WAIT_OBJECT_0, 3).unwrap(), Some(0));
+        assert_eq!(
+            wait_result(WAIT_EVENT(WAIT_OBJECT_0.0 + 2), 3).unwrap(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn timeout_returns_none() {
+        assert_eq!(wait_result(WAIT_TIMEOUT, 3).unwrap(), None);
+    }
+
+    #[test]
+    fn failure_reports_the_last_error() {
+        unsafe { SetLastError(ERROR_INVALID_HANDLE) };
+
+        assert!(matches!(
+            wait_result(WAIT_FAILED, 3),
+            Err(Error::WaitFailed(ref err)) if os_code(err) == ERROR_INVALID_HANDLE.0
+        ));
+    }
+
+    #[test]
+    fn abandoned_mutex_is_an_error() {
+        assert!(matches!(
+            wait_result(WAIT_ABANDONED_0, 3),
+            Err(Error::WaitAbandoned)
+        ));
+        assert!(matches!(
+            wait_result(WAIT_EVENT(WAIT_ABANDONED_0.0 + 2), 3),
+            Err(Error::WaitAbandoned)
+        ));
+    }
+
+    #[test]
+    fn handles_beyond_the_first_group_are_waited_on() {
+        let events = (0..150)
+            .map(|_| unsafe { CreateEventW(None, true, false, PCWSTR::null()).unwrap() })
+            .collect::<Vec<_>>();
+
+        let timeout = deadline(Some(Duration::from_millis(50)));
+        assert_eq!(wait_any_handle(&events, timeout).unwrap(), None);
+
+        // In the third group of 64 handles.
+        unsafe { SetEvent(events[140]).unwrap() };
+        assert_eq!(wait_any_handle(&events, None).unwrap(), Some(140));
+
+        // The groups are waited on in order, so the lower index wins.
+        unsafe { SetEvent(events[70]).unwrap() };
+        assert_eq!(wait_any_handle(&events, None).unwrap(), Some(70));
+
+        for event in events {
+            unsafe { CloseHandle(event).unwrap() };
+        }
+    }
+
+    #[test]
+    fn index_beyond_the_handles_is_not_signaled() {
+        assert!(matches!(
+            wait_result(WAIT_EVENT(WAIT_OBJECT_0.0 + 3), 3),
+            Err(Error::WaitFailed(_))
+        ));
+    }
+}
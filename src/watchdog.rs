@@ -0,0 +1,120 @@
+// A thread terminating a child once a timeout has elapsed, unless the child
+// exits or the watchdog is disarmed before.
+//
+// The thread waits on a duplicate of the process handle, so the handle stays
+// valid even if the `Child` is dropped, and on an event signaled to disarm it.
+
+use crate::{pipe::owned, wait, Child, Error, Result};
+use std::{
+    io,
+    os::windows::io::{AsRawHandle, OwnedHandle},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{DuplicateHandle, DUPLICATE_SAME_ACCESS, HANDLE},
+        System::Threading::{CreateEventW, GetCurrentProcess, SetEvent, TerminateProcess},
+    },
+};
+
+/// A watchdog terminating a child process once its timeout has elapsed.
+///
+/// Returned by [`Command::spawn_with_timeout`][crate::Command::spawn_with_timeout].
+/// The watchdog stops once the child exits, it never terminates a child that
+/// has exited, even if its identifier has been reused.
+///
+/// Dropping the guard doesn't disarm the watchdog: the timeout is enforced
+/// even if the code holding the guard returns early or panics. Use
+/// [`disarm`][WatchdogGuard::disarm] to cancel it.
+#[derive(Debug)]
+pub struct WatchdogGuard {
+    disarm: Arc<OwnedHandle>,
+    thread: JoinHandle<bool>,
+}
+
+impl WatchdogGuard {
+    // Start the watchdog of `child`. The child is terminated with the exit
+    // code 1, like the children terminated by `kill_all_registered`.
+    pub(crate) fn arm(child: &Child, timeout: Duration) -> Result<Self> {
+        let mut process = HANDLE::default();
+
+        let (process, disarm) = unsafe {
+            DuplicateHandle(
+                GetCurrentProcess(),
+                child.process_information.hProcess,
+                GetCurrentProcess(),
+                &mut process,
+                0,
+                false,
+                DUPLICATE_SAME_ACCESS,
+            )
+            .map_err(|_| Error::DuplicateHandleFailed(io::Error::last_os_error()))?;
+
+            let process = owned(process);
+
+            let disarm = CreateEventW(None, true, false, PCWSTR::null())
+                .map_err(|_| Error::WatchdogFailed(io::Error::last_os_error()))?;
+
+            (process, Arc::new(owned(disarm)))
+        };
+
+        let thread_disarm = Arc::clone(&disarm);
+
+        let thread = thread::Builder::new()
+            .name("CreateProcessW watchdog".to_string())
+            .spawn(move || {
+                let handles = [handle(&process), handle(&thread_disarm)];
+                let expired = matches!(
+                    wait::wait_any_handle(&handles, wait::deadline(Some(timeout))),
+                    Ok(None)
+                );
+
+                if expired {
+                    unsafe {
+                        let _ = TerminateProcess(handles[0], 1);
+                    }
+                }
+
+                expired
+            })
+            .map_err(Error::WatchdogFailed)?;
+
+        Ok(Self { disarm, thread })
+    }
+
+    /// Disarms the watchdog, returning `true` if it terminated the child
+    /// before.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::time::Duration;
+    ///
+    /// let (child, watchdog) = Command::new("cargo.exe test")
+    ///     .spawn_with_timeout(Duration::from_secs(600))
+    ///     .unwrap();
+    ///
+    /// let status = child.wait().unwrap();
+    ///
+    /// if watchdog.disarm() {
+    ///     println!("the tests timed out");
+    /// } else {
+    ///     println!("the tests exited with {}", status.code());
+    /// }
+    /// ```
+    pub fn disarm(self) -> bool {
+        unsafe {
+            let _ = SetEvent(handle(&self.disarm));
+        }
+
+        self.thread.join().unwrap_or(false)
+    }
+}
+
+fn handle(handle: &OwnedHandle) -> HANDLE {
+    HANDLE(handle.as_raw_handle() as isize)
+}
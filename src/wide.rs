@@ -0,0 +1,77 @@
+//! Conversions between strings and the wide strings of the Win32 API.
+//!
+//! The `W` functions of the Win32 API take and return UTF-16 strings, usually
+//! terminated by a null character. [`OsStr`] and [`OsString`] can represent
+//! any of these strings, including the ones that aren't valid UTF-16.
+
+use crate::{Error, Result};
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    slice,
+};
+
+/// Converts a string into a wide string terminated by a null character.
+///
+/// A string containing a null character cannot be represented, since the
+/// string would end there: an [`InteriorNul`][Error::InteriorNul] error is
+/// returned.
+///
+/// # Examples
+///
+/// ```
+/// use CreateProcessW::wide;
+///
+/// assert_eq!(wide::to_wide("hi".as_ref()).unwrap(), [0x68, 0x69, 0]);
+/// assert!(wide::to_wide("a\0b".as_ref()).is_err());
+/// ```
+pub fn to_wide(s: &OsStr) -> Result<Vec<u16>> {
+    let mut wide = to_wide_unterminated(s);
+
+    if wide.contains(&0) {
+        return Err(Error::InteriorNul);
+    }
+
+    wide.push(0);
+
+    Ok(wide)
+}
+
+/// Converts a string into a wide string, without a null terminator.
+///
+/// This is for the functions taking the length of the string, the null
+/// characters are kept.
+pub fn to_wide_unterminated(s: &OsStr) -> Vec<u16> {
+    s.encode_wide().collect()
+}
+
+/// Converts a wide string into a string.
+///
+/// The whole slice is converted, including the null characters. The invalid
+/// UTF-16 sequences are kept, see [`OsStringExt::from_wide`].
+pub fn from_wide(wide: &[u16]) -> OsString {
+    OsString::from_wide(wide)
+}
+
+/// Converts a wide string terminated by a null character into a string, like
+/// the strings written by the Win32 API in a buffer.
+///
+/// A null pointer gives an empty string.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a valid wide string terminated by a null
+/// character.
+pub unsafe fn from_wide_until_nul(ptr: *const u16) -> OsString {
+    if ptr.is_null() {
+        return OsString::new();
+    }
+
+    let mut len = 0;
+
+    while *ptr.add(len) != 0 {
+        len += 1;
+    }
+
+    from_wide(slice::from_raw_parts(ptr, len))
+}
@@ -0,0 +1,344 @@
+use crate::{
+    wait::{deadline, remaining_millis, wait_result},
+    Child, Error, ExitStatus, Result,
+};
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+use windows::Win32::{
+    Foundation::{BOOL, HWND, LPARAM, RECT, WPARAM},
+    UI::WindowsAndMessaging::{
+        AllowSetForegroundWindow, DispatchMessageW, EnumWindows, GetClassNameW, GetWindowRect,
+        GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindowVisible,
+        MsgWaitForMultipleObjects, PeekMessageW, PostMessageW, PostQuitMessage,
+        SetForegroundWindow, ShowWindow, TranslateMessage, MSG, PM_REMOVE, QS_ALLINPUT, SW_RESTORE,
+        WM_CLOSE, WM_QUIT,
+    },
+};
+
+/// Information about a top-level window.
+///
+/// Returned by [`Child::windows`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowInfo {
+    /// The raw value of the window handle (`HWND`).
+    pub handle: isize,
+    /// The title of the window, empty if it has none.
+    pub title: String,
+    /// The name of the class of the window.
+    pub class_name: String,
+    /// Whether the window is visible.
+    pub visible: bool,
+}
+
+impl Child {
+    /// Returns the top-level windows that belong to the child process.
+    ///
+    /// An empty list is returned for processes without a user interface, like
+    /// console programs. Windows that are destroyed while the list is being
+    /// built are still returned, with an empty title and class name. If the
+    /// function fail, an [`EnumWindowsFailed`][Error::EnumWindowsFailed]
+    /// error is returned.
+    ///
+    /// This is equivalent to calling the [`EnumWindows`][enum-windows] and
+    /// [`GetWindowThreadProcessId`][get-window-thread-process-id] functions.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    /// std::thread::sleep(std::time::Duration::from_secs(1));
+    ///
+    /// for window in child.windows().unwrap() {
+    ///     println!("{} ({})", window.title, window.class_name);
+    /// }
+    /// ```
+    ///
+    /// [enum-windows]: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-enumwindows
+    /// [get-window-thread-process-id]: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getwindowthreadprocessid
+    pub fn windows(&self) -> Result<Vec<WindowInfo>> {
+        let mut search = WindowSearch {
+            process_id: self.id(),
+            windows: Vec::new(),
+        };
+
+        unsafe {
+            EnumWindows(
+                Some(enum_windows_callback),
+                LPARAM(&mut search as *mut WindowSearch as isize),
+            )
+            .map_err(|_| Error::EnumWindowsFailed(io::Error::last_os_error()))?;
+        }
+
+        Ok(search.windows)
+    }
+
+    /// Waits until the child process shows a visible top-level window,
+    /// returning it.
+    ///
+    /// The windows of the process are polled until one of them is visible. If
+    /// the child exits before, an [`Exited`][Error::Exited] error is returned.
+    /// If no window is visible after `timeout`, a [`Timeout`][Error::Timeout]
+    /// error is returned.
+    ///
+    /// Unlike waiting for the process to be idle, this works with
+    /// applications that create their windows lazily.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    /// use std::time::Duration;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    ///
+    /// let window = child
+    ///     .wait_for_window(Duration::from_secs(10))
+    ///     .expect("notepad didn't show a window");
+    ///
+    /// println!("notepad shows {}", window.title);
+    /// ```
+    pub fn wait_for_window(&self, timeout: Duration) -> Result<WindowInfo> {
+        let start = Instant::now();
+        let mut interval = Duration::from_millis(10);
+
+        loop {
+            if let Some(window) = self.windows()?.into_iter().find(|window| window.visible) {
+                return Ok(window);
+            }
+
+            if self.has_exited() {
+                return Err(Error::Exited);
+            }
+
+            let elapsed = start.elapsed();
+
+            if elapsed >= timeout {
+                return Err(Error::Timeout);
+            }
+
+            thread::sleep(interval.min(timeout - elapsed));
+            interval = (interval * 2).min(Duration::from_millis(250));
+        }
+    }
+
+    /// Brings the main window of the child process to the foreground.
+    ///
+    /// The main window is the largest visible top-level window of the
+    /// process. If the process doesn't have a visible window (yet), a
+    /// [`WindowNotFound`][Error::WindowNotFound] error is returned. Windows has
+    /// strict rules about which process can set the foreground window, so the
+    /// calling process allows the child to take the foreground first. If the
+    /// system still refuses, a [`ForegroundRefused`][Error::ForegroundRefused]
+    /// error is returned.
+    ///
+    /// This is equivalent to calling the
+    /// [`AllowSetForegroundWindow`][allow-set-foreground-window],
+    /// [`ShowWindow`][show-window] and [`SetForegroundWindow`][set-foreground-window]
+    /// functions.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// let child = Command::new("notepad.exe").spawn().unwrap();
+    /// std::thread::sleep(std::time::Duration::from_secs(1));
+    ///
+    /// child.bring_to_foreground().expect("cannot focus notepad");
+    /// ```
+    ///
+    /// [allow-set-foreground-window]: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-allowsetforegroundwindow
+    /// [show-window]: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-showwindow
+    /// [set-foreground-window]: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setforegroundwindow
+    pub fn bring_to_foreground(&self) -> Result<()> {
+        let window = self
+            .windows()?
+            .into_iter()
+            .filter(|window| window.visible)
+            .max_by_key(|window| unsafe { window_area(HWND(window.handle)) })
+            .ok_or(Error::WindowNotFound)?;
+
+        self.set_foreground(&window)
+    }
+
+    /// Brings the visible top-level window of the child process with the
+    /// given title to the foreground.
+    ///
+    /// See [`bring_to_foreground`][Child::bring_to_foreground] for more
+    /// information.
+    pub fn bring_to_foreground_with_title(&self, title: &str) -> Result<()> {
+        let window = self
+            .windows()?
+            .into_iter()
+            .find(|window| window.visible && window.title == title)
+            .ok_or(Error::WindowNotFound)?;
+
+        self.set_foreground(&window)
+    }
+
+    /// Waits for the child to exit like [`wait`][Child::wait], dispatching the
+    /// messages of the windows of the calling thread meanwhile.
+    ///
+    /// A thread that owns windows must keep dispatching their messages: if it
+    /// blocks in [`wait`][Child::wait], its user interface freezes, and a
+    /// child that sends it messages, like with COM or DDE, can deadlock.
+    /// `Ok(None)` is returned if `timeout` elapsed before the child exited,
+    /// `None` waits forever. A `WM_QUIT` message ends the wait too, returning
+    /// `Ok(None)`, and is posted again for the message loop of the thread.
+    ///
+    /// If the wait fails, a [`WaitFailed`][Error::WaitFailed] error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use CreateProcessW::Command;
+    ///
+    /// // From the thread of the user interface.
+    /// let child = Command::new("installer.exe").spawn().unwrap();
+    ///
+    /// match child.wait_pumping_messages(None).unwrap() {
+    ///     Some(status) => println!("installer exited with {}", status.code()),
+    ///     None => println!("the application is closing"),
+    /// }
+    /// ```
+    ///
+    /// Equivalent to the [`MsgWaitForMultipleObjects`][msg-wait-for-multiple-objects]
+    /// function with `QS_ALLINPUT`, the messages being dispatched with the
+    /// [`PeekMessageW`][peek-message-w], `TranslateMessage` and
+    /// `DispatchMessageW` functions.
+    ///
+    /// [msg-wait-for-multiple-objects]: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-msgwaitformultipleobjects
+    /// [peek-message-w]: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-peekmessagew
+    pub fn wait_pumping_messages(&self, timeout: Option<Duration>) -> Result<Option<ExitStatus>> {
+        let deadline = deadline(timeout);
+        let handles = [self.process_information.hProcess];
+
+        loop {
+            let mut msg = MSG::default();
+
+            // The messages already queued don't wake the wait, dispatch them
+            // first.
+            unsafe {
+                while PeekMessageW(&mut msg, HWND(0), 0, 0, PM_REMOVE).as_bool() {
+                    if msg.message == WM_QUIT {
+                        PostQuitMessage(msg.wParam.0 as i32);
+                        return Ok(None);
+                    }
+
+                    let _ = TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            }
+
+            let res = unsafe {
+                MsgWaitForMultipleObjects(
+                    Some(&handles),
+                    false,
+                    remaining_millis(deadline),
+                    QS_ALLINPUT,
+                )
+            };
+
+            // The index after the handles means that messages are queued.
+            match wait_result(res, handles.len() + 1)? {
+                // Like `wait_alertable`, the handle is signaled even if the
+                // exit code is `STILL_ACTIVE`.
+                Some(0) => return self.reap_after_external_wait().map(Some),
+                Some(_) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    // Ask the top-level windows of the child to close, returning how many
+    // have been asked.
+    pub(crate) fn close_windows(&self) -> Result<usize> {
+        let windows = self.windows()?;
+
+        Ok(windows
+            .iter()
+            .filter(|window| unsafe {
+                PostMessageW(HWND(window.handle), WM_CLOSE, WPARAM(0), LPARAM(0)).is_ok()
+            })
+            .count())
+    }
+
+    fn set_foreground(&self, window: &WindowInfo) -> Result<()> {
+        let hwnd = HWND(window.handle);
+
+        unsafe {
+            // This only succeeds if the calling process can set the foreground
+            // window itself, which is checked by `SetForegroundWindow` anyway.
+            let _ = AllowSetForegroundWindow(self.id());
+
+            if IsIconic(hwnd).as_bool() {
+                let _ = ShowWindow(hwnd, SW_RESTORE);
+            }
+
+            if SetForegroundWindow(hwnd).as_bool() {
+                Ok(())
+            } else {
+                Err(Error::ForegroundRefused)
+            }
+        }
+    }
+}
+
+unsafe fn window_area(hwnd: HWND) -> i64 {
+    let mut rect = RECT::default();
+
+    match GetWindowRect(hwnd, &mut rect) {
+        Ok(()) => i64::from(rect.right - rect.left) * i64::from(rect.bottom - rect.top),
+        Err(_) => 0,
+    }
+}
+
+struct WindowSearch {
+    process_id: u32,
+    windows: Vec<WindowInfo>,
+}
+
+unsafe extern "system" fn enum_windows_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let search = &mut *(lparam.0 as *mut WindowSearch);
+    let mut process_id: u32 = 0;
+
+    GetWindowThreadProcessId(hwnd, Some(&mut process_id));
+
+    if process_id == search.process_id {
+        search.windows.push(WindowInfo {
+            handle: hwnd.0,
+            title: window_title(hwnd),
+            class_name: window_class_name(hwnd),
+            visible: IsWindowVisible(hwnd).as_bool(),
+        });
+    }
+
+    // Continue the enumeration.
+    BOOL(1)
+}
+
+unsafe fn window_title(hwnd: HWND) -> String {
+    let length = GetWindowTextLengthW(hwnd);
+
+    if length <= 0 {
+        return String::new();
+    }
+
+    let mut buffer = vec![0u16; length as usize + 1];
+    let copied = GetWindowTextW(hwnd, &mut buffer);
+
+    String::from_utf16_lossy(&buffer[..copied.max(0) as usize])
+}
+
+unsafe fn window_class_name(hwnd: HWND) -> String {
+    // The maximum length of a class name is 256 characters.
+    let mut buffer = [0u16; 257];
+    let copied = GetClassNameW(hwnd, &mut buffer);
+
+    String::from_utf16_lossy(&buffer[..copied.max(0) as usize])
+}
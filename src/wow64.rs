@@ -0,0 +1,97 @@
+// The file system redirection of a 32-bit process on 64-bit Windows, which
+// sends the accesses to `System32` to `SysWOW64`.
+
+use std::{
+    ffi::{c_void, OsString},
+    os::windows::ffi::OsStringExt,
+    path::PathBuf,
+};
+use windows::Win32::{
+    Foundation::BOOL,
+    Storage::FileSystem::{Wow64DisableWow64FsRedirection, Wow64RevertWow64FsRedirection},
+    System::{
+        SystemInformation::GetSystemWindowsDirectoryW,
+        Threading::{GetCurrentProcess, IsWow64Process},
+    },
+};
+
+// Whether the calling process is a 32-bit process on 64-bit Windows.
+pub(crate) fn is_wow64() -> bool {
+    let mut wow64 = BOOL(0);
+
+    unsafe { IsWow64Process(GetCurrentProcess(), &mut wow64).is_ok() && wow64.as_bool() }
+}
+
+// The Windows directory, like `C:\Windows`.
+pub(crate) fn windows_directory() -> Option<PathBuf> {
+    let mut buffer = [0; 260];
+
+    let len = unsafe { GetSystemWindowsDirectoryW(Some(&mut buffer)) } as usize;
+
+    (len > 0 && len < buffer.len()).then(|| PathBuf::from(OsString::from_wide(&buffer[..len])))
+}
+
+// The redirection disabled for the calling thread, restored when dropped, even
+// when unwinding from a panic.
+pub(crate) struct RedirectionDisabled {
+    old_value: *mut c_void,
+}
+
+impl RedirectionDisabled {
+    // Disable the redirection, `None` if the calling process isn't redirected.
+    pub(crate) fn new() -> Option<Self> {
+        if !is_wow64() {
+            return None;
+        }
+
+        let mut old_value = std::ptr::null_mut();
+
+        unsafe { Wow64DisableWow64FsRedirection(&mut old_value) }
+            .ok()
+            .map(|()| Self { old_value })
+    }
+}
+
+impl Drop for RedirectionDisabled {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = Wow64RevertWow64FsRedirection(self.old_value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_directory_has_system32() {
+        let windows = windows_directory().unwrap();
+
+        assert!(windows.is_absolute());
+        assert!(windows.join("System32").is_dir());
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn native_process_isnt_redirected() {
+        assert!(!is_wow64());
+        assert!(RedirectionDisabled::new().is_none());
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn redirection_is_disabled_until_dropped() {
+        // Only redirected on 64-bit Windows.
+        if !is_wow64() {
+            return;
+        }
+
+        let sysnative = windows_directory().unwrap().join("SysNative");
+        assert!(sysnative.is_dir());
+
+        drop(RedirectionDisabled::new().unwrap());
+        // `SysNative` is only seen by the redirected accesses.
+        assert!(sysnative.is_dir());
+    }
+}
@@ -0,0 +1,74 @@
+use std::{env, io, process};
+use CreateProcessW::Command;
+
+const APP_PATHS: &str = r"HKCU\SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths";
+
+// A program registered in the `App Paths` key of the current user, removed
+// when dropped.
+struct Registered {
+    key: String,
+}
+
+impl Registered {
+    fn new(name: &str, program: &str, path: &str) -> Self {
+        let key = format!(r"{APP_PATHS}\{name}.exe");
+
+        reg(&["add", &key, "/ve", "/d", program, "/f"]);
+        reg(&["add", &key, "/v", "Path", "/d", path, "/f"]);
+
+        Self { key }
+    }
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        reg(&["delete", &self.key, "/f"]);
+    }
+}
+
+fn reg(args: &[&str]) {
+    let status = process::Command::new("reg.exe")
+        .args(args)
+        .stdout(process::Stdio::null())
+        .status()
+        .unwrap();
+
+    assert!(status.success(), "reg.exe {args:?} failed");
+}
+
+#[test]
+fn registered_program_is_resolved() {
+    let name = format!("create-process-w-{}", process::id());
+    let dir = env::temp_dir().join("create-process-w-app-path");
+    let comspec = env::var("ComSpec").unwrap();
+    let _registered = Registered::new(&name, &comspec, dir.to_str().unwrap());
+
+    let output = Command::new(format!("{name} /c echo %PATH%"))
+        .resolve_app_paths(true)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    // The directory is appended to the `PATH` of the child.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.trim_end().ends_with(&format!(";{}", dir.display())),
+        "{stdout}"
+    );
+
+    // The key is only looked up when enabled.
+    let err = Command::new(format!("{name} /c exit"))
+        .output()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn unregistered_program_is_not_found() {
+    let err = Command::new("missing-program-7f3a.exe")
+        .resolve_app_paths(true)
+        .spawn()
+        .unwrap_err();
+
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
@@ -0,0 +1,726 @@
+mod common;
+
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    io::{self, BufRead, Read, Write},
+    os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle, RawHandle},
+    path::PathBuf,
+    process,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use windows::Win32::{Foundation::HANDLE, System::Pipes::CreatePipe};
+use CreateProcessW::{
+    build_command_line, cmdline, session_of_pid, wait_any, Command, Error, ExitStatus,
+    PriorityClass, ProcessArch, Stdio,
+};
+
+#[test]
+fn helper() {
+    common::run_helper();
+}
+
+#[test]
+fn suspended_child_stops_writing() {
+    let mut child = common::helper("tick", &[])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let mut buf = [0; 4096];
+
+    // The helper is running once it writes.
+    assert_ne!(stderr.read(&mut buf).unwrap(), 0);
+
+    child.suspend().unwrap();
+    // Drain the output written before the suspension.
+    thread::sleep(Duration::from_millis(100));
+    while stderr.try_read(&mut buf).unwrap().is_some() {}
+
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(stderr.try_read(&mut buf).unwrap(), None);
+
+    child.resume().unwrap();
+    assert_ne!(stderr.read(&mut buf).unwrap(), 0);
+    assert!(matches!(child.resume(), Err(Error::NotSuspended)));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn try_read_of_a_slow_child_never_blocks() {
+    let mut child = common::helper("slow-lines", &[OsStr::new("5")])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let mut buf = [0; 4096];
+    let mut output = Vec::new();
+    let mut polls = 0;
+
+    loop {
+        let start = Instant::now();
+        let res = stderr.try_read(&mut buf).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        match res {
+            // The pipe is broken once the child exits.
+            Some(0) => break,
+            Some(len) => output.extend_from_slice(&buf[..len]),
+            None => polls += 1,
+        }
+
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    assert_eq!(
+        output
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .count(),
+        5
+    );
+    // Most polls found nothing while the child was sleeping.
+    assert!(polls > 10);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn output_is_captured_whatever_the_pipe_buffer_size() {
+    const LINES: usize = 16 * 1024;
+
+    let expected = (0..LINES)
+        .map(|index| format!("{index:015}\n"))
+        .collect::<String>();
+
+    for size in [1, 1024 * 1024] {
+        let output = common::helper("chatty", &[OsStr::new(&LINES.to_string())])
+            .pipe_buffer_size(size)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(
+            output.stderr.len(),
+            expected.len(),
+            "buffer of {size} bytes"
+        );
+        assert!(
+            output.stderr == expected.as_bytes(),
+            "buffer of {size} bytes"
+        );
+    }
+}
+
+#[test]
+fn large_pipe_buffer_holds_the_output_until_it_is_read() {
+    // 256 KiB, more than the default buffer of 64 KiB.
+    const LINES: usize = 16 * 1024;
+
+    let mut child = common::helper("chatty", &[OsStr::new(&LINES.to_string())])
+        .stderr(Stdio::piped())
+        .pipe_buffer_size(1024 * 1024)
+        .spawn()
+        .unwrap();
+
+    // The child doesn't block on its writes.
+    let start = Instant::now();
+    while child.try_wait().unwrap().is_none() {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut stderr = Vec::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_end(&mut stderr)
+        .unwrap();
+    assert_eq!(stderr.len(), LINES * 16);
+}
+
+#[test]
+fn null_dereference_is_an_access_violation() {
+    let status = common::helper("null-deref", &[])
+        .suppress_error_dialogs(true)
+        .status()
+        .unwrap();
+
+    assert!(status.crashed());
+    assert_eq!(status.exception_code(), Some(ExitStatus::ACCESS_VIOLATION));
+    assert_eq!(status.to_string(), "0xC0000005");
+    assert!(status.exit_ok().is_err());
+}
+
+#[test]
+fn crt_file_descriptor_is_read_by_the_child() {
+    let (mut read, mut write) = (HANDLE::default(), HANDLE::default());
+    unsafe { CreatePipe(&mut read, &mut write, None, 0).unwrap() };
+    let read = unsafe { OwnedHandle::from_raw_handle(read.0 as RawHandle) };
+    let write = unsafe { OwnedHandle::from_raw_handle(write.0 as RawHandle) };
+
+    let mut child = common::helper("crt-fd", &[])
+        .crt_inherit_fds(&[(3, read.as_raw_handle())])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The child has its own handle, the end of file is reached once the
+    // write end is closed.
+    drop(read);
+    fs::File::from(write).write_all(b"through fd 3").unwrap();
+
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    assert_eq!(stderr, "through fd 3");
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn child_has_the_priority_class_of_the_command() {
+    let child = common::sleeper(10)
+        .priority_class(PriorityClass::BelowNormal)
+        .spawn()
+        .unwrap();
+
+    assert_eq!(child.priority().unwrap(), PriorityClass::BelowNormal);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn priority_class_round_trips_through_set_priority() {
+    let child = common::sleeper(10).spawn().unwrap();
+
+    for priority_class in [
+        PriorityClass::Idle,
+        PriorityClass::BelowNormal,
+        PriorityClass::Normal,
+        PriorityClass::AboveNormal,
+        PriorityClass::High,
+    ] {
+        child.set_priority(priority_class).unwrap();
+        assert_eq!(child.priority().unwrap(), priority_class);
+    }
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn affinity_mask_is_read_back() {
+    let child = common::sleeper(10).spawn().unwrap();
+    let (process_mask, system_mask) = child.affinity().unwrap();
+    assert_eq!(process_mask & !system_mask, 0);
+
+    // Only the first available processor.
+    let mask = system_mask & system_mask.wrapping_neg();
+    child.set_affinity(mask).unwrap();
+    assert_eq!(child.affinity().unwrap(), (mask, system_mask));
+
+    assert!(matches!(child.set_affinity(0), Err(Error::InvalidInput(_))));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn process_times_are_ordered() {
+    let before = SystemTime::now();
+    let child = common::spinner().spawn().unwrap();
+
+    thread::sleep(Duration::from_millis(300));
+    let first = child.process_times().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    let second = child.process_times().unwrap();
+
+    // The clock of `GetProcessTimes` is coarser than the one of `SystemTime`.
+    assert!(first.creation_time + Duration::from_millis(100) >= before);
+    assert!(first.creation_time <= SystemTime::now());
+    assert_eq!(first.creation_time, second.creation_time);
+    assert_eq!(first.exit_time, None);
+    assert!(second.user_time + second.kernel_time > first.user_time + first.kernel_time);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let exited = child.process_times().unwrap();
+    assert!(exited.exit_time.unwrap() >= exited.creation_time);
+    assert!(exited.user_time >= second.user_time);
+}
+
+#[test]
+fn private_bytes_include_the_allocations_of_the_child() {
+    const LEN: usize = 64 * 1024 * 1024;
+
+    let mut child = common::helper("alloc", &[OsStr::new(&LEN.to_string())])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut ready = [0; 5];
+    child.stderr.take().unwrap().read_exact(&mut ready).unwrap();
+    assert_eq!(&ready, b"ready");
+
+    let memory_info = child.memory_info().unwrap();
+    assert!(memory_info.private_usage >= LEN as u64);
+    assert!(memory_info.peak_working_set_size >= memory_info.working_set_size);
+    assert!(memory_info.page_fault_count > 0);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn hard_working_set_cap_is_kept_while_touching_more_memory() {
+    const CAP: usize = 16 * 1024 * 1024;
+    const LEN: usize = 64 * 1024 * 1024;
+
+    let mut child = common::helper("alloc-on-input", &[OsStr::new(&LEN.to_string())])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.set_working_set(1024 * 1024, CAP, true).unwrap();
+    let limits = child.working_set().unwrap();
+    assert_eq!((limits.min_bytes, limits.max_bytes), (1024 * 1024, CAP));
+    assert!(limits.hard_max);
+    assert!(!limits.hard_min);
+
+    child.stdin.take().unwrap().write_all(b"go\n").unwrap();
+    let mut ready = [0; 5];
+    child.stderr.take().unwrap().read_exact(&mut ready).unwrap();
+    assert_eq!(&ready, b"ready");
+
+    // The pages above the cap are trimmed, not freed.
+    let memory_info = child.memory_info().unwrap();
+    assert!(memory_info.private_usage >= LEN as u64, "{memory_info:?}");
+    assert!(
+        memory_info.working_set_size <= (CAP + 1024 * 1024) as u64,
+        "{memory_info:?}"
+    );
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn working_set_is_set_before_the_child_runs() {
+    let child = common::sleeper(30)
+        .working_set(1024 * 1024, 32 * 1024 * 1024)
+        .spawn()
+        .unwrap();
+
+    let limits = child.working_set().unwrap();
+    assert_eq!(
+        (limits.min_bytes, limits.max_bytes),
+        (1024 * 1024, 32 * 1024 * 1024)
+    );
+    assert!(!limits.hard_max);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(matches!(
+        child.set_working_set(1024 * 1024, 32 * 1024 * 1024, true),
+        Err(Error::Exited)
+    ));
+}
+
+#[test]
+fn io_counters_include_the_bytes_written_to_a_file() {
+    const LEN: usize = 4 * 1024 * 1024;
+
+    let path = env::temp_dir().join(format!("create-process-w-{}.bin", process::id()));
+    let child = common::helper(
+        "write-file",
+        &[path.as_os_str(), OsStr::new(&LEN.to_string())],
+    )
+    .spawn()
+    .unwrap();
+    assert!(child.wait().unwrap().success());
+    fs::remove_file(&path).unwrap();
+
+    // The counters are still available until the child is dropped.
+    let counters = child.io_counters().unwrap();
+    assert!(counters.write_bytes >= LEN as u64);
+    assert!(counters.write_operations > 0);
+}
+
+#[test]
+fn cpu_usage_of_a_spinner_is_higher_than_of_a_sleeper() {
+    let spinner = common::spinner().spawn().unwrap();
+    let sleeper = common::sleeper(10).spawn().unwrap();
+
+    // The first call has nothing to compare with.
+    assert_eq!(spinner.cpu_usage(false).unwrap(), 0.0);
+    assert_eq!(sleeper.cpu_usage(false).unwrap(), 0.0);
+
+    thread::sleep(Duration::from_secs(1));
+
+    let spinning = spinner.cpu_usage(false).unwrap();
+    let sleeping = sleeper.cpu_usage(false).unwrap();
+    assert!(spinning > 50.0, "spinner: {spinning}%");
+    assert!(sleeping < 10.0, "sleeper: {sleeping}%");
+
+    for child in [spinner, sleeper] {
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
+
+#[test]
+fn wow64_child_has_a_different_architecture() {
+    let windows_dir = PathBuf::from(env::var_os("SystemRoot").unwrap());
+    let wow64_cmd = windows_dir.join(r"SysWOW64\cmd.exe");
+
+    // Only 64-bit systems have a 32-bit `cmd.exe`.
+    if !wow64_cmd.exists() {
+        return;
+    }
+
+    let native = Command::new(format!(
+        "{} /c pause",
+        windows_dir.join(r"System32\cmd.exe").display()
+    ))
+    .stdin(Stdio::piped())
+    .spawn()
+    .unwrap();
+    let wow64 = Command::new(format!("{} /c pause", wow64_cmd.display()))
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    assert_eq!(wow64.architecture().unwrap(), ProcessArch::X86);
+    assert_ne!(native.architecture().unwrap(), ProcessArch::X86);
+    assert_ne!(native.architecture().unwrap(), ProcessArch::Unknown);
+
+    for child in [native, wow64] {
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
+
+#[test]
+fn program_path_is_the_resolved_binary() {
+    let windows_dir = PathBuf::from(env::var_os("SystemRoot").unwrap());
+    let child = common::sleeper(10).spawn().unwrap();
+
+    let path = child.program_path().unwrap();
+    assert!(path.is_absolute());
+    assert!(path.file_name().unwrap().eq_ignore_ascii_case("ping.exe"));
+    assert!(path
+        .parent()
+        .unwrap()
+        .as_os_str()
+        .eq_ignore_ascii_case(windows_dir.join("System32")));
+
+    let native_path = child.native_program_path().unwrap();
+    assert!(native_path.starts_with(r"\Device"));
+    assert!(native_path
+        .file_name()
+        .unwrap()
+        .eq_ignore_ascii_case("ping.exe"));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn child_keeps_its_command_line_and_directory() {
+    let dir = env::temp_dir();
+    let args = ["-n", "11", "127.0.0.1"];
+    let child = Command::from_program("ping.exe", args)
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let args = args.map(OsStr::new);
+    assert_eq!(
+        child.command_line(),
+        build_command_line(OsStr::new("ping.exe"), &args)
+    );
+    assert_eq!(child.spawn_dir(), Some(dir.as_path()));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn duplicated_handle_can_be_waited_on_by_another_process() {
+    let worker = common::sleeper(30).spawn().unwrap();
+    let mut waiter = common::helper("wait-handle", &[])
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let handle = worker
+        .duplicate_handle_to(waiter.wait_handle(), None, false)
+        .unwrap();
+    let mut stdin = waiter.stdin.take().unwrap();
+    writeln!(stdin, "{}", handle as isize).unwrap();
+
+    // The waiter only exits once the worker does.
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(waiter.try_wait().unwrap(), None);
+    worker.kill().unwrap();
+    worker.wait().unwrap();
+
+    assert!(waiter.wait().unwrap().success());
+}
+
+#[test]
+fn child_runs_in_a_unc_working_directory() {
+    // The administrative share of the system drive, only available to the
+    // administrators.
+    let dir = PathBuf::from(r"\\localhost\C$\Windows");
+    if !dir.is_dir() {
+        eprintln!("skipped: {} isn't accessible", dir.display());
+        return;
+    }
+
+    for current_dir in [dir.clone(), PathBuf::from(r"\\?\UNC\localhost\C$\Windows")] {
+        let output = common::helper("cwd", &[])
+            .current_dir(&current_dir)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap().to_lowercase(),
+            dir.display().to_string().to_lowercase()
+        );
+    }
+}
+
+#[test]
+fn arguments_from_a_string_are_received_unchanged() {
+    let user_args = r#"--name "a b" --path C:\dir\ "say \"hi\"" "unclosed"#;
+
+    let output = common::helper("args", &[])
+        .args_from_str(user_args.as_ref())
+        .unwrap()
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let received = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        received.lines().collect::<Vec<_>>(),
+        [
+            "--name",
+            "a b",
+            "--path",
+            r"C:\dir\",
+            "say \"hi\"",
+            "unclosed"
+        ]
+    );
+
+    // Like the string is split.
+    let split = cmdline::split(format!("_ {user_args}").as_ref()).unwrap();
+    assert_eq!(received.lines().collect::<Vec<_>>(), split[1..]);
+}
+
+#[test]
+fn kill_after_the_exit_is_collected_is_already_reaped() {
+    let waited = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    waited.wait().unwrap();
+    assert!(matches!(waited.kill(), Err(Error::AlreadyReaped)));
+
+    let polled = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    while polled.try_wait().unwrap().is_none() {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(matches!(polled.kill(), Err(Error::AlreadyReaped)));
+
+    let killed = common::sleeper(30).spawn().unwrap();
+    killed.kill().unwrap();
+    killed.wait().unwrap();
+    assert!(matches!(killed.kill(), Err(Error::AlreadyReaped)));
+}
+
+#[test]
+fn exit_status_is_kept_by_each_collection() {
+    let waited = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    waited.wait().unwrap();
+    assert_eq!(waited.exit_status().map(|status| status.code()), Some(3));
+
+    let polled = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    let status = loop {
+        if let Some(status) = polled.try_wait().unwrap() {
+            break status;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(polled.exit_status(), Some(status));
+
+    let children = [Command::new("cmd.exe /c exit 3").spawn().unwrap()];
+    let (_, status) = wait_any(&children, None).unwrap().unwrap();
+    assert_eq!(children[0].exit_status(), Some(status));
+
+    // The callback sees the status without collecting it.
+    let watched = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let _watch = watched
+        .on_exit(move |status| sender.send(status).unwrap())
+        .unwrap();
+    let status = receiver.recv().unwrap();
+    assert_eq!(watched.exit_status(), None);
+
+    assert_eq!(watched.reap_after_external_wait().unwrap(), status);
+    assert_eq!(watched.exit_status(), Some(status));
+}
+
+#[test]
+fn exit_status_is_none_while_running() {
+    let child = common::sleeper(30).spawn().unwrap();
+    assert_eq!(child.exit_status(), None);
+    assert_eq!(child.try_wait().unwrap(), None);
+    assert_eq!(child.exit_status(), None);
+    assert!(matches!(
+        child.reap_after_external_wait(),
+        Err(Error::StillRunning)
+    ));
+    assert_eq!(child.exit_status(), None);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn gui_resources_are_counted_for_a_window() {
+    // Not notepad, which is only a stub starting the packaged application on
+    // recent versions of Windows.
+    let gui = common::helper("message-box", &[]).spawn().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let resources = loop {
+        let resources = gui.gui_resources().unwrap();
+        if resources.user_objects > 0 || Instant::now() >= deadline {
+            break resources;
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    assert!(resources.user_objects > 0, "{resources:?}");
+    assert!(resources.gdi_objects > 0, "{resources:?}");
+    assert!(resources.user_peak >= resources.user_objects);
+    assert!(resources.gdi_peak >= resources.gdi_objects);
+
+    let console = common::sleeper(30).spawn().unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let resources = console.gui_resources().unwrap();
+    assert_eq!(resources.gdi_objects, 0, "{resources:?}");
+    assert_eq!(resources.user_objects, 0, "{resources:?}");
+
+    for child in [gui, console] {
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
+
+#[test]
+fn handle_count_grows_with_the_opened_handles() {
+    let mut child = common::helper("events", &[OsStr::new("16")])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stderr = io::BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+
+    stderr.read_line(&mut line).unwrap();
+    assert_eq!(line, "before\n");
+    let before = child.handle_count().unwrap();
+
+    stdin.write_all(b"\n").unwrap();
+    line.clear();
+    stderr.read_line(&mut line).unwrap();
+    assert_eq!(line, "after\n");
+    let after = child.handle_count().unwrap();
+
+    assert_eq!(after - before, 16);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn child_runs_in_the_session_of_the_parent() {
+    let child = common::sleeper(30).spawn().unwrap();
+
+    assert_eq!(
+        child.session_id().unwrap(),
+        session_of_pid(process::id()).unwrap()
+    );
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    // The identifier cannot be reused while the handle is open.
+    assert_eq!(
+        child.session_id().unwrap(),
+        session_of_pid(process::id()).unwrap()
+    );
+}
+
+#[test]
+fn priority_boost_round_trip() {
+    let child = common::sleeper(30).spawn().unwrap();
+    assert!(child.priority_boost().unwrap());
+
+    child.set_priority_boost(false).unwrap();
+    assert!(!child.priority_boost().unwrap());
+
+    child.set_priority_boost(true).unwrap();
+    assert!(child.priority_boost().unwrap());
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(matches!(
+        child.set_priority_boost(false),
+        Err(Error::Exited)
+    ));
+}
+
+#[test]
+fn priority_boost_is_disabled_from_the_start() {
+    let child = common::sleeper(30)
+        .disable_priority_boost(true)
+        .spawn()
+        .unwrap();
+    assert!(!child.priority_boost().unwrap());
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn efficiency_mode_is_set_on_a_live_child() {
+    let child = common::sleeper(30).spawn().unwrap();
+
+    match child.set_efficiency_mode(true) {
+        Ok(()) => child.set_efficiency_mode(false).unwrap(),
+        // Before Windows 10 1709.
+        Err(Error::Unsupported(_)) => {}
+        Err(err) => panic!("{err}"),
+    }
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(matches!(
+        child.set_efficiency_mode(true),
+        Err(Error::Exited)
+    ));
+}
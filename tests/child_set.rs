@@ -0,0 +1,47 @@
+mod common;
+
+use std::time::Duration;
+use CreateProcessW::{ChildSet, Token};
+
+// Kill the child with `token` and check that it's the next one to exit.
+fn kill_next(set: &mut ChildSet, token: Token) {
+    set.get(token).unwrap().kill().unwrap();
+
+    let (exited, status) = set.next_exit(None).unwrap().unwrap();
+
+    assert_eq!(exited, token);
+    assert!(status.success());
+    assert!(set.get(token).is_none());
+}
+
+#[test]
+fn children_are_returned_in_exit_order_with_replacements() {
+    let mut set = ChildSet::new();
+    let mut tokens = (0..5)
+        .map(|_| set.insert(common::sleeper(30).spawn().unwrap()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        set.next_exit(Some(Duration::from_millis(100))).unwrap(),
+        None
+    );
+    assert_eq!(set.len(), 5);
+
+    kill_next(&mut set, tokens[3]);
+    kill_next(&mut set, tokens[0]);
+
+    // Replace the children that exited.
+    for _ in 0..2 {
+        let token = set.insert(common::sleeper(30).spawn().unwrap());
+        assert!(!tokens.contains(&token));
+        tokens.push(token);
+    }
+    assert_eq!(set.len(), 5);
+
+    for index in [6, 4, 1, 5, 2] {
+        kill_next(&mut set, tokens[index]);
+    }
+
+    assert!(set.is_empty());
+    assert_eq!(set.next_exit(None).unwrap(), None);
+}
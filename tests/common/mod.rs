@@ -0,0 +1,238 @@
+// Helpers shared by the integration tests that need a child with a specific
+// behavior. The child is the test binary itself, running its `helper` test
+// with the mode in `HELPER_VAR`.
+#![allow(dead_code)]
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, Read, Write},
+    mem::ManuallyDrop,
+    os::windows::io::{FromRawHandle, RawHandle},
+    process, thread,
+    time::Duration,
+};
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::{HANDLE, HWND, WAIT_OBJECT_0},
+        System::{
+            Console::{GenerateConsoleCtrlEvent, SetConsoleCtrlHandler, CTRL_C_EVENT},
+            Threading::{CreateEventW, WaitForSingleObject, INFINITE},
+        },
+        UI::WindowsAndMessaging::{MessageBoxW, MB_OK},
+    },
+};
+use CreateProcessW::{build_command_line, Command, Stdio};
+
+// The variable holding the mode of the helper in the child.
+pub const HELPER_VAR: &str = "CREATE_PROCESS_W_HELPER";
+
+// The arguments of the test binary running `helper`, before the ones of the
+// helper.
+pub const HELPER_TEST_ARGS: [&str; 4] = ["helper", "--exact", "--nocapture", "--"];
+
+// A command running the test binary as a helper in `mode`. The `args` are
+// passed after `--`, where libtest ignores them since `--exact` only matches
+// the `helper` test.
+pub fn helper(mode: &str, args: &[&OsStr]) -> Command {
+    let test_binary = env::current_exe().unwrap();
+    let mut helper_args = HELPER_TEST_ARGS.map(OsStr::new).to_vec();
+    helper_args.extend_from_slice(args);
+
+    let mut command = Command::new(build_command_line(test_binary.as_os_str(), &helper_args));
+    command.env(HELPER_VAR, mode).stdout(Stdio::null());
+    command
+}
+
+// The arguments given to `helper`.
+pub fn helper_args() -> Vec<OsString> {
+    env::args_os()
+        .skip_while(|arg| arg != "--")
+        .skip(1)
+        .collect()
+}
+
+// Run by the `helper` test of each test binary, does nothing unless the
+// binary has been spawned by `helper`. libtest prints to the standard output,
+// so the helpers write to the standard error and exit before the end of the
+// test.
+pub fn run_helper() {
+    let Ok(mode) = env::var(HELPER_VAR) else {
+        return;
+    };
+
+    match mode.as_str() {
+        // Write a line every 10 ms until killed.
+        "tick" => loop {
+            eprintln!("tick");
+            thread::sleep(Duration::from_millis(10));
+        },
+        // Allocate and touch the number of bytes given as argument, then write
+        // `ready` and sleep until killed.
+        "alloc" => {
+            let len = helper_args()[0].to_str().unwrap().parse().unwrap();
+            let memory = vec![1u8; len];
+            eprintln!("ready");
+            loop {
+                thread::sleep(Duration::from_secs(1));
+                assert_eq!(memory[len - 1], 1);
+            }
+        }
+        // Like `alloc`, once a line has been read from the standard input.
+        "alloc-on-input" => {
+            let len = helper_args()[0].to_str().unwrap().parse().unwrap();
+            io::stdin().read_line(&mut String::new()).unwrap();
+            let memory = vec![1u8; len];
+            eprintln!("ready");
+            loop {
+                thread::sleep(Duration::from_secs(1));
+                assert_eq!(memory[len - 1], 1);
+            }
+        }
+        // Write a file at the path given as first argument, of the number of
+        // bytes given as second argument.
+        "write-file" => {
+            let args = helper_args();
+            let len = args[1].to_str().unwrap().parse().unwrap();
+            fs::write(&args[0], vec![0u8; len]).unwrap();
+            exit(0);
+        }
+        // Wait on the handle whose value is read from the standard input.
+        "wait-handle" => {
+            let mut line = String::new();
+            io::stdin().read_line(&mut line).unwrap();
+            let handle = HANDLE(line.trim().parse().unwrap());
+
+            let res = unsafe { WaitForSingleObject(handle, INFINITE) };
+            exit(if res == WAIT_OBJECT_0 { 0 } else { 1 });
+        }
+        // Write the number of lines given as argument, each of them holding
+        // its index on 16 bytes.
+        "chatty" => {
+            let lines: usize = helper_args()[0].to_str().unwrap().parse().unwrap();
+            let mut stderr = io::BufWriter::new(io::stderr().lock());
+            for index in 0..lines {
+                writeln!(stderr, "{index:015}").unwrap();
+            }
+            stderr.flush().unwrap();
+            drop(stderr);
+            exit(0);
+        }
+        // Exit like an access violation until it has been run the number of
+        // times given as second argument, counted in the file given as first
+        // argument, then exit successfully.
+        "crash-until" => {
+            let args = helper_args();
+            let crashes: u32 = args[1].to_str().unwrap().parse().unwrap();
+            let runs = fs::read_to_string(&args[0]).map_or(0, |runs| runs.parse().unwrap());
+            fs::write(&args[0], (runs + 1).to_string()).unwrap();
+
+            exit(if runs < crashes {
+                CreateProcessW::ExitStatus::ACCESS_VIOLATION as i32
+            } else {
+                0
+            });
+        }
+        // Write the number of lines given as argument, one every 100 ms, then
+        // exit.
+        "slow-lines" => {
+            let lines: usize = helper_args()[0].to_str().unwrap().parse().unwrap();
+            for _ in 0..lines {
+                thread::sleep(Duration::from_millis(100));
+                eprintln!("line");
+            }
+            exit(0);
+        }
+        // Dereference a null pointer.
+        "null-deref" => {
+            let null = std::hint::black_box(std::ptr::null::<u8>());
+            let value = unsafe { std::ptr::read_volatile(null) };
+            exit(value.into());
+        }
+        // Send `Ctrl+C` to the processes of its console, itself included.
+        "ctrl-c" => {
+            unsafe {
+                // Let the default handler exit with `STATUS_CONTROL_C_EXIT`,
+                // even if `Ctrl+C` is ignored by the parent.
+                SetConsoleCtrlHandler(None, false).unwrap();
+                GenerateConsoleCtrlEvent(CTRL_C_EVENT, 0).unwrap();
+            }
+            thread::sleep(Duration::from_secs(10));
+            exit(0);
+        }
+        // Write what it reads from the file descriptor 3 of its C runtime.
+        "crt-fd" => {
+            extern "C" {
+                fn _get_osfhandle(fd: i32) -> isize;
+            }
+
+            let handle = unsafe { _get_osfhandle(3) };
+            assert_ne!(handle, -1, "the file descriptor 3 isn't open");
+
+            // The handle is owned by the C runtime.
+            let mut file =
+                ManuallyDrop::new(unsafe { fs::File::from_raw_handle(handle as RawHandle) });
+            let mut data = String::new();
+            file.read_to_string(&mut data).unwrap();
+            eprint!("{data}");
+            exit(0);
+        }
+        // Write each of its arguments on a line.
+        "args" => {
+            for arg in helper_args() {
+                eprintln!("{}", arg.to_str().unwrap());
+            }
+            exit(0);
+        }
+        // Show a message box until killed.
+        "message-box" => {
+            unsafe { MessageBoxW(HWND(0), w!("helper"), w!("helper"), MB_OK) };
+            exit(0);
+        }
+        // Write `before`, then once a line has been read from the standard
+        // input, open the number of events given as argument, write `after`
+        // and sleep until killed.
+        "events" => {
+            let count: usize = helper_args()[0].to_str().unwrap().parse().unwrap();
+            eprintln!("before");
+            io::stdin().read_line(&mut String::new()).unwrap();
+
+            let events = (0..count)
+                .map(|_| unsafe { CreateEventW(None, true, false, PCWSTR::null()).unwrap() })
+                .collect::<Vec<_>>();
+            eprintln!("after");
+
+            loop {
+                thread::sleep(Duration::from_secs(1));
+                assert_eq!(events.len(), count);
+            }
+        }
+        // Write its working directory.
+        "cwd" => {
+            eprint!("{}", env::current_dir().unwrap().display());
+            exit(0);
+        }
+        mode => panic!("unknown helper mode: {mode}"),
+    }
+}
+
+// The standard error is unbuffered, the explicit flush only makes sure
+// nothing is lost before `process::exit`.
+pub fn exit(code: i32) -> ! {
+    io::stderr().flush().unwrap();
+    process::exit(code)
+}
+
+// A command using a processor until killed.
+pub fn spinner() -> Command {
+    Command::new("cmd.exe /c for /l %i in (0,0,1) do @rem")
+}
+
+// A command sleeping for about `seconds` without output.
+pub fn sleeper(seconds: u32) -> Command {
+    let mut command = Command::new(format!("ping.exe -n {} 127.0.0.1", seconds + 1));
+    command.stdout(Stdio::null());
+    command
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+use CreateProcessW::Command;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn callback_is_called_once_the_child_exits() {
+    let child = common::sleeper(30).spawn().unwrap();
+    let (sender, receiver) = mpsc::channel();
+
+    let watch = child
+        .on_exit(move |status| sender.send(status).unwrap())
+        .unwrap();
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_millis(200)),
+        Err(RecvTimeoutError::Timeout)
+    );
+
+    child.kill().unwrap();
+    let status = receiver.recv_timeout(TIMEOUT).unwrap();
+
+    assert_eq!(status, child.wait().unwrap());
+    assert!(watch.cancel());
+}
+
+#[test]
+fn cancelled_callback_is_never_called() {
+    let child = common::sleeper(30).spawn().unwrap();
+    let (sender, receiver) = mpsc::channel();
+
+    let watch = child
+        .on_exit(move |status| sender.send(status).unwrap())
+        .unwrap();
+    assert!(!watch.cancel());
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    // The callback has been dropped with its sender.
+    assert_eq!(
+        receiver.recv_timeout(TIMEOUT),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn callback_of_an_exited_child_is_called_right_away() {
+    let child = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    assert_eq!(child.wait().unwrap().code(), 3);
+    let (sender, receiver) = mpsc::channel();
+
+    let _watch = child
+        .on_exit(move |status| sender.send(status).unwrap())
+        .unwrap();
+
+    assert_eq!(receiver.recv_timeout(TIMEOUT).unwrap().code(), 3);
+}
+
+#[test]
+fn exit_channel_receives_the_status_of_the_wait() {
+    let child = Command::new("cmd.exe /c ping.exe -n 2 127.0.0.1 >nul & exit 7")
+        .spawn()
+        .unwrap();
+    let exit = child.exit_channel().unwrap();
+
+    assert_eq!(
+        exit.recv_timeout(Duration::from_millis(100)),
+        Err(RecvTimeoutError::Timeout)
+    );
+
+    let status = exit.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(status.code(), 7);
+    assert_eq!(status, child.wait().unwrap());
+
+    // Exactly one message is sent.
+    assert_eq!(
+        exit.recv_timeout(TIMEOUT),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
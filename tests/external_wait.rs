@@ -0,0 +1,42 @@
+mod common;
+
+use std::os::windows::io::AsRawHandle;
+use windows::Win32::{
+    Foundation::{HANDLE, WAIT_OBJECT_0},
+    System::Threading::{WaitForSingleObject, INFINITE},
+};
+use CreateProcessW::{Child, Command, Error};
+
+// Wait on the handle of `child` like an external event loop would.
+fn wait_externally(child: &Child) {
+    let handle = HANDLE(child.wait_handle().as_raw_handle() as isize);
+
+    assert_eq!(
+        unsafe { WaitForSingleObject(handle, INFINITE) },
+        WAIT_OBJECT_0
+    );
+}
+
+#[test]
+fn child_is_reaped_after_an_external_wait() {
+    let child = common::sleeper(30).spawn().unwrap();
+    assert!(matches!(
+        child.reap_after_external_wait(),
+        Err(Error::StillRunning)
+    ));
+
+    child.kill().unwrap();
+    wait_externally(&child);
+
+    let status = child.reap_after_external_wait().unwrap();
+    assert_eq!(child.exit_status(), Some(status));
+    assert_eq!(child.wait().unwrap(), status);
+}
+
+#[test]
+fn exit_code_of_still_active_is_reaped_after_an_external_wait() {
+    let child = Command::new("cmd.exe /c exit 259").spawn().unwrap();
+    wait_externally(&child);
+
+    assert_eq!(child.reap_after_external_wait().unwrap().code(), 259);
+}
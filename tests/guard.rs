@@ -0,0 +1,53 @@
+use std::{panic, thread, time::Duration};
+use CreateProcessW::{Child, ChildIdentity, Command, Stdio};
+
+// A command sleeping for about 30 seconds.
+fn sleeper() -> Command {
+    let mut command = Command::new("ping.exe -n 31 127.0.0.1");
+    command.stdout(Stdio::null());
+    command
+}
+
+// Whether the process with `identity` is still running.
+fn is_running(identity: ChildIdentity) -> bool {
+    match Child::reattach(identity).unwrap() {
+        Some(child) => child.try_wait().unwrap().is_none(),
+        None => false,
+    }
+}
+
+#[test]
+fn dropped_guard_terminates_the_child() {
+    let guard = sleeper().spawn_guarded().unwrap();
+    let identity = guard.identity().unwrap();
+    assert!(is_running(identity));
+
+    drop(guard);
+    assert!(!is_running(identity));
+}
+
+#[test]
+fn released_child_keeps_running() {
+    let guard = sleeper().spawn_guarded().unwrap();
+    let identity = guard.identity().unwrap();
+
+    let child = guard.release();
+    thread::sleep(Duration::from_millis(100));
+    assert!(is_running(identity));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn child_is_terminated_when_the_scope_panics() {
+    let res = panic::catch_unwind(|| {
+        let guard = sleeper().spawn_guarded().unwrap();
+        let identity = guard.identity().unwrap();
+
+        panic::panic_any(identity);
+    });
+
+    let identity = *res.unwrap_err().downcast::<ChildIdentity>().unwrap();
+    assert!(!is_running(identity));
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+use CreateProcessW::{Child, ChildIdentity, Command, Stdio};
+
+// A child running for about a minute, until it is killed.
+fn spawn_worker() -> Child {
+    Command::new("ping.exe -n 60 127.0.0.1")
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+#[test]
+fn serialized_identity_is_reattached() {
+    let child = spawn_worker();
+    let json = serde_json::to_string(&child.identity().unwrap()).unwrap();
+
+    let identity: ChildIdentity = serde_json::from_str(&json).unwrap();
+    let reattached = Child::reattach(identity).unwrap().unwrap();
+
+    assert_eq!(reattached.id(), child.id());
+
+    reattached.kill().unwrap();
+    assert_eq!(child.wait().unwrap().code(), 0);
+    assert_eq!(reattached.wait().unwrap().code(), 0);
+}
+
+#[test]
+fn stale_identity_is_not_reattached() {
+    let child = spawn_worker();
+    let mut identity = child.identity().unwrap();
+
+    // Another process created with the same identifier.
+    identity.creation_time -= Duration::from_secs(1);
+    assert!(Child::reattach(identity).unwrap().is_none());
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let identity = child.identity().unwrap();
+    drop(child);
+
+    // The process is gone.
+    assert!(Child::reattach(identity).unwrap().is_none());
+}
+
+#[test]
+fn child_is_reattached_after_being_dropped() {
+    let child = spawn_worker();
+    let json = serde_json::to_string(&child.identity().unwrap()).unwrap();
+
+    // Dropping the child doesn't kill it.
+    drop(child);
+
+    let reattached = Child::reattach(serde_json::from_str(&json).unwrap())
+        .unwrap()
+        .unwrap();
+
+    assert!(reattached.try_wait().unwrap().is_none());
+
+    reattached.kill().unwrap();
+    assert_eq!(reattached.wait().unwrap().code(), 0);
+}
@@ -0,0 +1,59 @@
+use std::{
+    env,
+    ffi::OsStr,
+    os::windows::io::{AsHandle, AsRawHandle, FromRawHandle, OwnedHandle},
+};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{GetHandleInformation, HANDLE, HANDLE_FLAG_INHERIT, WAIT_OBJECT_0},
+        System::Threading::{CreateEventW, SetEvent, WaitForSingleObject},
+    },
+};
+use CreateProcessW::{build_command_line, Command, Stdio};
+
+// The variable holding the value of the event in the child.
+const EVENT_VAR: &str = "CREATE_PROCESS_W_INHERITED_EVENT";
+
+// Run in the child spawned by `child_signals_the_inherited_event`, does
+// nothing otherwise.
+#[test]
+fn signal_inherited_event() {
+    let Ok(value) = env::var(EVENT_VAR) else {
+        return;
+    };
+
+    unsafe { SetEvent(HANDLE(value.parse().unwrap())).unwrap() };
+}
+
+#[test]
+fn child_signals_the_inherited_event() {
+    let event = unsafe {
+        let event = CreateEventW(None, true, false, PCWSTR::null()).unwrap();
+        OwnedHandle::from_raw_handle(event.0 as _)
+    };
+
+    let test_binary = env::current_exe().unwrap();
+    let mut command = Command::new(build_command_line(
+        test_binary.as_os_str(),
+        &[OsStr::new("signal_inherited_event"), OsStr::new("--exact")],
+    ));
+    let token = command.inherit_handle(event.as_handle()).unwrap();
+
+    // The duplicate isn't inheritable until the child is created.
+    let mut flags = 0;
+    unsafe { GetHandleInformation(HANDLE(token.value()), &mut flags).unwrap() };
+    assert_eq!(flags & HANDLE_FLAG_INHERIT.0, 0);
+
+    let status = command
+        .env(EVENT_VAR, token.to_arg_string())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!(
+        unsafe { WaitForSingleObject(HANDLE(event.as_raw_handle() as isize), 0) },
+        WAIT_OBJECT_0
+    );
+}
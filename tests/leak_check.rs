@@ -0,0 +1,113 @@
+// The count of open handles is global, so the checks are in a single test.
+
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+use CreateProcessW::{
+    assert_no_leaks, kill_all_registered, open_handle_count, Child, Command, Stdio,
+};
+
+#[test]
+fn handles_of_children_are_closed() {
+    let child = Command::new("cmd.exe /c exit").spawn().unwrap();
+    assert_eq!(open_handle_count(), 2);
+
+    // The process handle of `child` keeps the process alive to reattach it.
+    let reattached = Child::reattach(child.identity().unwrap()).unwrap().unwrap();
+    assert_eq!(open_handle_count(), 3);
+
+    assert!(reattached.wait().unwrap().success());
+    drop(reattached);
+    assert_eq!(open_handle_count(), 2);
+
+    child.wait().unwrap();
+    drop(child);
+    assert_no_leaks();
+
+    Command::new("cmd.exe /c echo hello").output().unwrap();
+    assert_no_leaks();
+
+    // A failed spawn doesn't open anything.
+    Command::new("missing-program-7f3a.exe")
+        .spawn()
+        .unwrap_err();
+    assert_no_leaks();
+
+    Command::new("cmd.exe /c exit").spawn().unwrap().forget();
+    assert_no_leaks();
+
+    let child = Command::new("cmd.exe /c exit").spawn().unwrap();
+    while child.try_wait().unwrap().is_none() {
+        thread::sleep(Duration::from_millis(10));
+    }
+    drop(child);
+    assert_no_leaks();
+
+    // The registration of the callback holds its own handle.
+    let child = Command::new("cmd.exe /c exit").spawn().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let watch = child
+        .on_exit(move |status| sender.send(status).unwrap())
+        .unwrap();
+    assert_eq!(open_handle_count(), 3);
+
+    assert!(receiver.recv().unwrap().success());
+    drop(watch);
+    child.wait().unwrap();
+    drop(child);
+    // The registration is released once the callback has returned.
+    assert_no_leaks_eventually();
+
+    // A registration cancelled before the exit too.
+    let child = sleeper(false);
+    let watch = child.on_exit(|_| {}).unwrap();
+    drop(watch);
+    assert_eq!(open_handle_count(), 2);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    drop(child);
+    assert_no_leaks();
+
+    // The registry holds its own handle until the exit is collected.
+    let child = Command::new("cmd.exe /c exit")
+        .register(true)
+        .spawn()
+        .unwrap();
+    assert_eq!(open_handle_count(), 3);
+
+    child.wait().unwrap();
+    assert_eq!(open_handle_count(), 2);
+    drop(child);
+    assert_no_leaks();
+
+    // Or until the registered children are killed.
+    drop(sleeper(true));
+    assert_eq!(open_handle_count(), 1);
+
+    assert_eq!(kill_all_registered(), 1);
+    assert_no_leaks();
+}
+
+// A child sleeping for 30 seconds.
+fn sleeper(register: bool) -> Child {
+    Command::new("ping.exe -n 30 127.0.0.1")
+        .stdout(Stdio::null())
+        .register(register)
+        .spawn()
+        .unwrap()
+}
+
+// Like `assert_no_leaks`, for the handles released on another thread.
+#[track_caller]
+fn assert_no_leaks_eventually() {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while open_handle_count() != 0 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_no_leaks();
+}
@@ -0,0 +1,85 @@
+// The metrics are global, so the checks are in a single test.
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use CreateProcessW::{clear_metrics_hook, set_metrics_hook, Command, Metrics, MetricsEvent, Stdio};
+
+#[test]
+fn metrics_count_the_children() {
+    assert_eq!(Metrics::snapshot(), Metrics::default());
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let hook_events = Arc::clone(&events);
+    set_metrics_hook(move |event| hook_events.lock().unwrap().push(event.clone()));
+
+    let exited = Command::new("cmd.exe /c exit")
+        .tag("exited")
+        .spawn()
+        .unwrap();
+    let dropped = sleeper().spawn().unwrap();
+    let snapshot = Metrics::snapshot();
+    assert_eq!(snapshot.spawned, 2);
+    assert_eq!(snapshot.running, 2);
+
+    exited.wait().unwrap();
+    assert_eq!(Metrics::snapshot().running, 1);
+
+    // Killed but dropped before its exit status is collected.
+    dropped.kill().unwrap();
+    drop(dropped);
+    assert_eq!(Metrics::snapshot().running, 0);
+
+    Command::new("missing-program-7f3a.exe")
+        .spawn()
+        .unwrap_err();
+    let snapshot = Metrics::snapshot();
+    assert_eq!(snapshot.spawned, 2);
+    assert_eq!(snapshot.spawn_failures, 1);
+
+    // The processor time is added once the exit status is collected.
+    let spinner = Command::new("cmd.exe /c for /l %i in (0,0,1) do @rem")
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(500));
+    spinner.kill().unwrap();
+    spinner.wait().unwrap();
+
+    clear_metrics_hook();
+
+    let snapshot = Metrics::snapshot();
+    assert_eq!(snapshot.spawned, 3);
+    assert_eq!(snapshot.spawn_failures, 1);
+    assert_eq!(snapshot.running, 0);
+    assert!(snapshot.cpu_time > Duration::ZERO, "{snapshot:?}");
+
+    let events = events.lock().unwrap();
+    assert!(matches!(
+        &events[0],
+        MetricsEvent::Spawned { tag: Some(tag), .. } if &**tag == "exited"
+    ));
+    assert!(matches!(events[1], MetricsEvent::Spawned { tag: None, .. }));
+    assert!(matches!(
+        &events[2],
+        MetricsEvent::Exited { tag: Some(tag), .. } if &**tag == "exited"
+    ));
+    assert!(matches!(events[3], MetricsEvent::Dropped { .. }));
+    assert!(matches!(events[4], MetricsEvent::SpawnFailed));
+    assert!(matches!(
+        events[6],
+        MetricsEvent::Exited {
+            cpu_time: Some(cpu_time),
+            ..
+        } if cpu_time > Duration::ZERO
+    ));
+    assert_eq!(events.len(), 7);
+}
+
+// A child sleeping for 30 seconds.
+fn sleeper() -> Command {
+    let mut command = Command::new("ping.exe -n 30 127.0.0.1");
+    command.stdout(Stdio::null());
+    command
+}
@@ -0,0 +1,205 @@
+use std::{error::Error as _, io, sync::Arc};
+use CreateProcessW::{Child, Command, Error, MockProcessApi, Operation, ProtectedMode};
+
+#[test]
+fn spawn_failure_is_reported_with_the_command() {
+    let api = MockProcessApi::new();
+    // `ERROR_ACCESS_DENIED`
+    api.fail_create(5);
+
+    let err = Command::new("worker.exe --once")
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap_err();
+
+    assert_eq!(err.operation(), Some(Operation::Spawn));
+    assert_eq!(err.code(), 5);
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    assert!(err.to_string().contains("worker.exe --once"));
+    assert!(matches!(
+        err.source()
+            .and_then(|source| source.downcast_ref::<Error>()),
+        Some(Error::CreationFailed(_))
+    ));
+    assert_eq!(api.open_handles(), 0);
+}
+
+#[test]
+fn failed_wait_can_be_retried() {
+    let api = MockProcessApi::new();
+    let child = Command::new("worker.exe")
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap();
+    // `ERROR_INVALID_HANDLE`
+    api.fail_wait(6);
+
+    let err = child.wait().unwrap_err();
+
+    assert!(matches!(err, Error::WaitFailed(_)));
+    assert_eq!(err.code(), 6);
+    assert_eq!(child.exit_status(), None);
+
+    api.exit(child.id(), 1);
+
+    assert_eq!(child.wait().unwrap().code(), 1);
+}
+
+#[test]
+fn kill_failure_has_the_os_error_as_source() {
+    let api = MockProcessApi::new();
+    let child = Command::new("worker.exe")
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap();
+    // `ERROR_ACCESS_DENIED`
+    api.fail_terminate(5);
+
+    let err = child.kill().unwrap_err();
+
+    assert!(matches!(err, Error::KillFailed(_)));
+    assert_eq!(err.code(), 5);
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+    let os_error = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(os_error.raw_os_error(), Some(5));
+    assert_eq!(err.os_error().unwrap().raw_os_error(), Some(5));
+    // The message of the source isn't repeated.
+    assert!(!err.to_string().contains(&os_error.to_string()));
+
+    // The child is still running and can be killed again.
+    assert_eq!(child.try_wait().unwrap(), None);
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn kill_ends_the_child() {
+    let api = MockProcessApi::new();
+    let child = Command::new("worker.exe")
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap();
+
+    child.kill().unwrap();
+
+    assert_eq!(child.wait().unwrap().code(), 0);
+    assert!(matches!(child.kill(), Err(Error::AlreadyReaped)));
+
+    drop(child);
+    assert_eq!(api.open_handles(), 0);
+}
+
+#[test]
+fn kill_after_the_exit_is_collected_is_already_reaped() {
+    let api = MockProcessApi::new();
+
+    // Collected by `wait`.
+    let waited = spawn_mock(&api);
+    api.exit(waited.id(), 3);
+    waited.wait().unwrap();
+    assert!(matches!(waited.kill(), Err(Error::AlreadyReaped)));
+
+    // Collected by `try_wait`, not before.
+    let polled = spawn_mock(&api);
+    assert_eq!(polled.try_wait().unwrap(), None);
+    api.exit(polled.id(), 3);
+    assert!(polled.try_wait().unwrap().is_some());
+    assert!(matches!(polled.kill(), Err(Error::AlreadyReaped)));
+
+    // Exited without being collected, the termination is given to the
+    // system.
+    let exited = spawn_mock(&api);
+    api.exit(exited.id(), 3);
+    exited.kill().unwrap();
+    assert_eq!(exited.wait().unwrap().code(), 3);
+    assert!(matches!(exited.kill(), Err(Error::AlreadyReaped)));
+}
+
+fn spawn_mock(api: &MockProcessApi) -> Child {
+    Command::new("worker.exe")
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap()
+}
+
+#[test]
+fn external_wait_reaps_a_child_exiting_with_still_active() {
+    let api = MockProcessApi::new();
+    let child = Command::new("worker.exe")
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap();
+
+    assert!(matches!(
+        child.reap_after_external_wait(),
+        Err(Error::StillRunning)
+    ));
+
+    // `STILL_ACTIVE`
+    api.exit(child.id(), 259);
+
+    // The exit code alone cannot tell that the child has exited.
+    assert_eq!(child.try_wait().unwrap(), None);
+    assert_eq!(child.reap_after_external_wait().unwrap().code(), 259);
+    assert_eq!(child.exit_status().map(|status| status.code()), Some(259));
+    assert_eq!(
+        child.try_wait().unwrap().map(|status| status.code()),
+        Some(259)
+    );
+}
+
+#[test]
+fn denied_creation_is_a_protected_process_failure_only_when_protected() {
+    let api = MockProcessApi::new();
+    // `ERROR_INVALID_IMAGE_HASH`, then `ERROR_ACCESS_DENIED` twice.
+    api.fail_create(577);
+    api.fail_create(5);
+    api.fail_create(5);
+
+    for mode in [ProtectedMode::Full, ProtectedMode::Light] {
+        let err = Command::new("worker.exe")
+            .protected_process(mode)
+            .spawn_with_api(Arc::new(api.clone()))
+            .unwrap_err();
+
+        assert!(
+            matches!(
+                err.source()
+                    .and_then(|source| source.downcast_ref::<Error>()),
+                Some(Error::ProtectedProcessFailed(_))
+            ),
+            "{err}"
+        );
+    }
+
+    let err = Command::new("worker.exe")
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap_err();
+    assert!(matches!(
+        err.source()
+            .and_then(|source| source.downcast_ref::<Error>()),
+        Some(Error::CreationFailed(_))
+    ));
+}
+
+#[test]
+fn tag_is_in_the_spawn_failure_and_the_debug_output() {
+    let api = MockProcessApi::new();
+    // `ERROR_ACCESS_DENIED`
+    api.fail_create(5);
+
+    let mut command = Command::new("worker.exe");
+    command.tag("build-docs");
+
+    let err = command.spawn_with_api(Arc::new(api.clone())).unwrap_err();
+    assert_eq!(err.tag(), Some("build-docs"));
+    assert!(
+        err.to_string().contains("`worker.exe` [build-docs]"),
+        "{err}"
+    );
+
+    let child = command.spawn_with_api(Arc::new(api.clone())).unwrap();
+    assert_eq!(child.tag(), Some("build-docs"));
+    assert!(
+        format!("{child:?}").contains("tag: Some(\"build-docs\")"),
+        "{child:?}"
+    );
+
+    api.exit(child.id(), 0);
+    child.wait().unwrap();
+}
@@ -0,0 +1,27 @@
+use std::io::Read;
+use CreateProcessW::{Command, Stdio};
+
+#[test]
+fn filter_reads_the_output_of_the_generator() {
+    let mut filter = Command::new("findstr.exe t");
+    filter.stdout(Stdio::piped());
+
+    let mut children = Command::new("cmd.exe /c echo one& echo two& echo three")
+        .pipe_to(filter)
+        .spawn()
+        .unwrap();
+
+    let mut output = String::new();
+    children.children_mut()[1]
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+
+    let statuses = children.wait().unwrap();
+
+    assert_eq!(output, "two\r\nthree\r\n");
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses.iter().all(|status| status.success()));
+}
@@ -0,0 +1,40 @@
+// The count of open handles is global, so the checks are in a single test.
+
+use std::{sync::mpsc, time::Duration};
+use CreateProcessW::{assert_no_leaks, open_handle_count, process_handle_count, Command};
+
+const CHILDREN: usize = 300;
+
+#[test]
+fn reaped_children_release_their_handles() {
+    let baseline = process_handle_count();
+    let (sender, receiver) = mpsc::channel();
+
+    for _ in 0..CHILDREN {
+        let sender = sender.clone();
+
+        Command::new("cmd.exe /c exit 3")
+            .spawn()
+            .unwrap()
+            .reap_in_background_with(move |status| sender.send(status).unwrap())
+            .unwrap();
+    }
+
+    for _ in 0..CHILDREN {
+        let status = receiver.recv_timeout(Duration::from_secs(60)).unwrap();
+        assert_eq!(status.unwrap().code(), 3);
+    }
+
+    // The handles are closed before the callbacks are called.
+    assert_eq!(open_handle_count(), 0);
+    assert_no_leaks();
+
+    // The threads of the thread pool have handles too, but not one per child.
+    let count = process_handle_count();
+    assert!(
+        count < baseline + 50,
+        "{} handles after reaping, {} before",
+        count,
+        baseline
+    );
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use std::os::windows::io::AsRawHandle;
+use windows::Win32::{
+    Foundation::{HANDLE, WAIT_OBJECT_0},
+    System::Threading::{WaitForSingleObject, INFINITE},
+};
+use CreateProcessW::{kill_all_registered, Command, Stdio};
+
+// The registry is global, so all the cases are in a single test.
+#[test]
+fn only_the_running_registered_children_are_killed() {
+    let running = common::sleeper(30).register(true).spawn().unwrap();
+    let unregistered = common::sleeper(30).spawn().unwrap();
+
+    // Waited on, so removed from the registry.
+    let waited = Command::new("cmd.exe /c exit 0")
+        .register(true)
+        .spawn()
+        .unwrap();
+    waited.wait().unwrap();
+
+    // Exited without being waited on.
+    let exited = Command::new("cmd.exe /c exit 0")
+        .register(true)
+        .spawn()
+        .unwrap();
+    let handle = HANDLE(exited.wait_handle().as_raw_handle() as isize);
+    assert_eq!(
+        unsafe { WaitForSingleObject(handle, INFINITE) },
+        WAIT_OBJECT_0
+    );
+
+    // Forgotten on purpose, so removed from the registry.
+    let forgotten = common::sleeper(30).register(true).spawn().unwrap();
+    let forgotten_pid = forgotten.id();
+    forgotten.forget();
+
+    assert_eq!(kill_all_registered(), 1);
+    assert_eq!(running.wait().unwrap().code(), 1);
+    assert_eq!(unregistered.try_wait().unwrap(), None);
+    assert!(exited.wait().unwrap().success());
+
+    // The registry is empty afterwards.
+    assert_eq!(kill_all_registered(), 0);
+
+    unregistered.kill().unwrap();
+    unregistered.wait().unwrap();
+    Command::new(format!("taskkill.exe /f /pid {forgotten_pid}"))
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+use CreateProcessW::{ExitStatus, Output, ResourceUsage};
+
+#[test]
+fn output_round_trip_keeps_any_bytes() {
+    let output = Output {
+        status: ExitStatus::from_raw(1),
+        // Not UTF-8.
+        stdout: vec![0xFF, 0xFE, 0x00, b'\n'],
+        stderr: b"error\r\n".to_vec(),
+    };
+
+    let json = serde_json::to_string(&output).unwrap();
+    assert_eq!(
+        json,
+        r#"{"status":1,"stdout":"//4ACg==","stderr":"ZXJyb3INCg=="}"#
+    );
+
+    let round_trip: Output = serde_json::from_str(&json).unwrap();
+    assert_eq!(round_trip, output);
+}
+
+#[test]
+fn invalid_base64_is_rejected() {
+    let json = r#"{"status":0,"stdout":"not base64!","stderr":""}"#;
+
+    assert!(serde_json::from_str::<Output>(json).is_err());
+}
+
+#[test]
+fn exit_status_is_its_code() {
+    let status = ExitStatus::from_raw(ExitStatus::ACCESS_VIOLATION);
+
+    let json = serde_json::to_string(&status).unwrap();
+    assert_eq!(json, "3221225477");
+
+    assert_eq!(serde_json::from_str::<ExitStatus>(&json).unwrap(), status);
+}
+
+#[test]
+fn resource_usage_round_trip() {
+    let usage = ResourceUsage {
+        user_time: Some(Duration::from_millis(1500)),
+        kernel_time: None,
+        wall_time: Duration::new(2, 5),
+        peak_working_set: Some(4096),
+        io_read_bytes: Some(0),
+        io_write_bytes: None,
+    };
+
+    let json = serde_json::to_string(&usage).unwrap();
+    assert_eq!(
+        json,
+        concat!(
+            r#"{"user_time":{"secs":1,"nanos":500000000},"kernel_time":null,"#,
+            r#""wall_time":{"secs":2,"nanos":5},"peak_working_set":4096,"#,
+            r#""io_read_bytes":0,"io_write_bytes":null}"#
+        )
+    );
+
+    let round_trip: ResourceUsage = serde_json::from_str(&json).unwrap();
+    assert_eq!(round_trip, usage);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use std::time::Duration;
+use CreateProcessW::{Command, Error, InteractiveSession};
+
+#[test]
+fn helper() {
+    common::run_helper();
+}
+
+fn cmd() -> InteractiveSession {
+    let mut session = InteractiveSession::spawn(&mut Command::new("cmd.exe")).unwrap();
+    session
+        .strip_escapes(true)
+        .set_timeout(Duration::from_secs(10));
+    session
+}
+
+#[test]
+fn cmd_echoes_in_a_pseudoconsole() {
+    let mut session = cmd();
+
+    session.send_line("echo marker").unwrap();
+    // The console echoes the input, then the output follows.
+    session.expect("echo marker").unwrap();
+    session.expect("marker").unwrap();
+
+    session.send_line("exit").unwrap();
+    assert!(session.wait().unwrap().success());
+}
+
+#[test]
+fn timeout_keeps_the_output() {
+    let mut session = cmd();
+
+    // The prompt is printed, not the pattern.
+    match session.read_until("never printed", Duration::from_millis(500)) {
+        Err(Error::ExpectTimeout { pattern, output }) => {
+            assert_eq!(pattern, "never printed");
+            assert!(output.contains('>'), "{output:?}");
+            assert_eq!(session.buffered(), output);
+        }
+        res => panic!("unexpected result: {res:?}"),
+    }
+
+    session.send_line("exit").unwrap();
+    assert!(session.wait().unwrap().success());
+}
+
+#[test]
+fn exit_ends_the_search() {
+    let mut session = cmd();
+
+    session.send_line("exit 3").unwrap();
+    assert!(matches!(
+        session.expect("never printed"),
+        Err(Error::ExpectExited { .. })
+    ));
+    assert_eq!(session.wait().unwrap().code(), 3);
+}
+
+#[test]
+fn ctrl_c_event_interrupts_the_child() {
+    // The pseudoconsole keeps the event away from the test process.
+    let session = InteractiveSession::spawn(&mut common::helper("ctrl-c", &[])).unwrap();
+    let status = session.wait().unwrap();
+
+    assert!(status.is_interrupted(), "{status}");
+    assert!(!status.crashed());
+}
@@ -0,0 +1,42 @@
+use std::{env, error::Error as _, ffi::OsStr, fs, io};
+use CreateProcessW::{build_command_line, Command, Error, SignaturePolicy};
+
+#[test]
+fn signed_system_program_is_spawned() {
+    // `cmd.exe` is signed by a catalog of the system.
+    let status = Command::new("cmd.exe /c exit")
+        .verify_signature(SignaturePolicy::AnyValid)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+}
+
+#[test]
+fn unsigned_copy_is_rejected() {
+    // The test binary isn't signed.
+    let copy = env::temp_dir().join(format!(
+        "create-process-w-unsigned-{}.exe",
+        std::process::id()
+    ));
+    fs::copy(env::current_exe().unwrap(), &copy).unwrap();
+
+    // Runs no test if it is spawned anyway.
+    let res = Command::new(build_command_line(
+        copy.as_os_str(),
+        &[OsStr::new("--exact"), OsStr::new("no_test")],
+    ))
+    .verify_signature(SignaturePolicy::AnyValid)
+    .spawn();
+
+    fs::remove_file(&copy).unwrap();
+
+    let err = res.unwrap_err();
+
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    assert!(matches!(
+        err.source()
+            .and_then(|source| source.downcast_ref::<Error>()),
+        Some(Error::UntrustedProgram(_))
+    ));
+}
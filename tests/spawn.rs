@@ -0,0 +1,186 @@
+mod common;
+
+use std::{env, error::Error as _, fs, io, process};
+use CreateProcessW::{Command, Error, ProtectedMode, RestrictedTokenOptions, Stdio};
+
+#[test]
+fn helper() {
+    common::run_helper();
+}
+
+#[test]
+fn missing_program_is_not_found() {
+    let err = Command::new("missing-program-7f3a.exe")
+        .spawn()
+        .unwrap_err();
+
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    // `ERROR_FILE_NOT_FOUND`
+    assert_eq!(err.code(), 2);
+
+    let source = err.source().unwrap();
+    assert!(matches!(
+        source.downcast_ref::<Error>(),
+        Some(Error::CreationFailed(_))
+    ));
+
+    let os_error = source
+        .source()
+        .unwrap()
+        .downcast_ref::<io::Error>()
+        .unwrap();
+    assert_eq!(os_error.raw_os_error(), Some(2));
+
+    // Each message of the chain is printed once.
+    assert!(!err.to_string().contains(&source.to_string()));
+    assert!(!source.to_string().contains(&os_error.to_string()));
+}
+
+#[test]
+fn message_of_a_missing_program_has_the_command() {
+    let err = Command::new("missing-program-7f3a.exe --flag")
+        .spawn()
+        .unwrap_err();
+
+    assert!(matches!(err, Error::CommandFailed { .. }));
+    assert!(
+        err.to_string()
+            .contains("`missing-program-7f3a.exe --flag`"),
+        "{err}"
+    );
+}
+
+#[test]
+fn message_of_a_missing_directory_has_the_command_and_the_directory() {
+    let dir = env::temp_dir().join("missing-directory-7f3a");
+    let err = Command::new("cmd.exe /c exit")
+        .current_dir(&dir)
+        .spawn()
+        .unwrap_err();
+
+    let message = err.to_string();
+    assert!(message.contains("`cmd.exe /c exit`"), "{message}");
+    assert!(message.contains(&format!("{:?}", dir)), "{message}");
+    assert_eq!(err.current_directory(), Some(dir.as_path()));
+    assert_eq!(err.kind(), io::ErrorKind::NotADirectory);
+}
+
+#[test]
+fn text_file_renamed_to_exe_is_invalid_data() {
+    let path = env::temp_dir().join(format!("create-process-w-{}.exe", process::id()));
+    fs::write(&path, "not a program\r\n").unwrap();
+
+    let err = Command::new(format!("\"{}\"", path.display()))
+        .spawn()
+        .unwrap_err();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    // `ERROR_BAD_EXE_FORMAT`
+    assert_eq!(err.code(), 193);
+    assert!(err
+        .source()
+        .unwrap()
+        .to_string()
+        .contains("another architecture"));
+}
+
+#[test]
+fn unsigned_program_cannot_run_protected() {
+    let err = Command::new(format!("\"{}\"", env::current_exe().unwrap().display()))
+        .protected_process(ProtectedMode::Full)
+        .spawn()
+        .unwrap_err();
+
+    assert!(
+        matches!(
+            err.source()
+                .and_then(|source| source.downcast_ref::<Error>()),
+            Some(Error::ProtectedProcessFailed(_))
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn restricted_child_only_has_the_change_notify_privilege() {
+    let mut options = RestrictedTokenOptions::new();
+    options.disable_max_privilege(true);
+
+    let restricted = privileges(Command::new("whoami.exe /priv").restricted(options));
+    assert_eq!(restricted, ["SeChangeNotifyPrivilege"]);
+}
+
+#[test]
+fn deleted_privilege_is_missing_from_the_child() {
+    let mut options = RestrictedTokenOptions::new();
+    options.delete_privilege("SeShutdownPrivilege");
+
+    let mut expected = privileges(&mut Command::new("whoami.exe /priv"));
+    expected.retain(|privilege| privilege != "SeShutdownPrivilege");
+
+    let restricted = privileges(Command::new("whoami.exe /priv").restricted(options));
+    assert_eq!(restricted, expected);
+}
+
+// The names of the privileges listed by `whoami /priv`, enabled or not.
+fn privileges(command: &mut Command) -> Vec<String> {
+    let output = command.output().unwrap();
+    assert!(output.status.success());
+
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .filter(|word| word.starts_with("Se") && word.ends_with("Privilege"))
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn user_session_requires_the_tcb_privilege() {
+    // The tests don't run as `LocalSystem`.
+    let err = Command::new("cmd.exe /c exit")
+        .in_active_user_session()
+        .spawn()
+        .unwrap_err();
+
+    assert!(
+        matches!(
+            err.source()
+                .and_then(|source| source.downcast_ref::<Error>()),
+            Some(Error::MissingTcbPrivilege | Error::NoActiveSession)
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn program_with_spaces_in_its_path_is_quoted() {
+    let dir = env::temp_dir().join(format!("create process w {}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("helper copy.exe");
+    fs::copy(env::current_exe().unwrap(), &program).unwrap();
+
+    let args = common::HELPER_TEST_ARGS
+        .into_iter()
+        .chain(["a b", "\"quoted\"", r"C:\dir\"]);
+    let output = Command::from_program(&program, args)
+        .env(common::HELPER_VAR, "args")
+        .stdout(Stdio::null())
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(output.stderr, b"a b\n\"quoted\"\nC:\\dir\\\n");
+}
+
+#[test]
+fn missing_program_is_a_spawn_failure() {
+    let err = Command::new("missing-program-7f3a.exe")
+        .status()
+        .unwrap_err();
+
+    assert!(err.is_spawn_failure());
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert_eq!(err.pid(), None);
+}
@@ -0,0 +1,311 @@
+// What the builders of `Command` give to `CreateProcessW`, recorded by
+// `MockProcessApi` instead of spawning processes.
+
+use std::{
+    env,
+    error::Error as _,
+    ffi::{OsStr, OsString},
+    path::Path,
+    sync::Arc,
+};
+use windows::Win32::System::{
+    Diagnostics::Debug::GetErrorMode,
+    Threading::{
+        CREATE_DEFAULT_ERROR_MODE, DEBUG_ONLY_THIS_PROCESS, DEBUG_PROCESS, INHERIT_PARENT_AFFINITY,
+        STARTF_FORCEOFFFEEDBACK, STARTF_FORCEONFEEDBACK, STARTF_PREVENTPINNING,
+        STARTF_TITLEISAPPID, STARTF_USEHOTKEY,
+    },
+};
+use CreateProcessW::{
+    Command, DebugMode, EnvironmentSnapshot, Error, HotkeyModifiers, MockProcessApi, RecordedSpec,
+    RestrictedTokenOptions, Stdio,
+};
+
+// Spawn `command` with a fake and return what has been given to the creation.
+fn spec(command: &mut Command) -> RecordedSpec {
+    let api = MockProcessApi::new();
+    let child = command.spawn_with_api(Arc::new(api.clone())).unwrap();
+
+    api.exit(child.id(), 0);
+    child.wait().unwrap();
+
+    api.specs().remove(0)
+}
+
+#[test]
+fn startup_feedback_sets_one_of_the_feedback_flags() {
+    let feedback = STARTF_FORCEONFEEDBACK.0 | STARTF_FORCEOFFFEEDBACK.0;
+
+    let on = spec(Command::new("worker.exe").startup_feedback(true));
+    assert_eq!(on.startup_flags & feedback, STARTF_FORCEONFEEDBACK.0);
+
+    let off = spec(Command::new("worker.exe").startup_feedback(false));
+    assert_eq!(off.startup_flags & feedback, STARTF_FORCEOFFFEEDBACK.0);
+
+    // The system decides by default.
+    let default = spec(&mut Command::new("worker.exe"));
+    assert_eq!(default.startup_flags & feedback, 0);
+}
+
+#[test]
+fn app_user_model_id_is_the_title() {
+    let with_id = spec(Command::new("worker.exe").app_user_model_id("Contoso.Launcher.Helper"));
+
+    assert_eq!(
+        with_id.title.as_deref(),
+        Some(OsStr::new("Contoso.Launcher.Helper"))
+    );
+    assert_ne!(with_id.startup_flags & STARTF_TITLEISAPPID.0, 0);
+
+    let default = spec(&mut Command::new("worker.exe"));
+    assert_eq!(default.title, None);
+    assert_eq!(default.startup_flags & STARTF_TITLEISAPPID.0, 0);
+}
+
+#[test]
+fn invalid_app_user_model_id_is_rejected() {
+    let api = MockProcessApi::new();
+    let err = Command::new("worker.exe")
+        .app_user_model_id("with space")
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap_err();
+
+    assert!(is_invalid_command(&err), "{err}");
+    assert!(api.specs().is_empty());
+}
+
+// Whether the error is an `InvalidCommand` error of the spawn.
+fn is_invalid_command(err: &Error) -> bool {
+    matches!(
+        err.source()
+            .and_then(|source| source.downcast_ref::<Error>()),
+        Some(Error::InvalidCommand(_))
+    )
+}
+
+#[test]
+fn hotkey_is_given_in_place_of_the_standard_input() {
+    // `VK_F9` with CTRL+ALT.
+    let with_hotkey = spec(
+        Command::new("worker.exe").hotkey(0x78, HotkeyModifiers::CONTROL | HotkeyModifiers::ALT),
+    );
+
+    assert_ne!(with_hotkey.startup_flags & STARTF_USEHOTKEY.0, 0);
+    assert_eq!(with_hotkey.hotkey, Some(0x0678));
+    assert!(!with_hotkey.has_stdio);
+}
+
+#[test]
+fn hotkey_conflicts_with_the_standard_streams() {
+    let api = MockProcessApi::new();
+    let err = Command::new("worker.exe")
+        .hotkey(0x78, HotkeyModifiers::NONE)
+        .stdin(Stdio::null())
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap_err();
+
+    assert!(is_invalid_command(&err), "{err}");
+    assert!(err
+        .source()
+        .unwrap()
+        .to_string()
+        .contains("a hotkey cannot be combined with the standard streams"));
+    assert!(api.specs().is_empty());
+}
+
+#[test]
+fn prevent_pinning_is_set_with_the_app_user_model_id() {
+    let unpinnable = spec(
+        Command::new("worker.exe")
+            .app_user_model_id("Contoso.Launcher.Helper")
+            .prevent_pinning(true)
+            .startup_feedback(false),
+    );
+
+    // The flags of the other builders are kept.
+    let expected = STARTF_PREVENTPINNING.0 | STARTF_TITLEISAPPID.0 | STARTF_FORCEOFFFEEDBACK.0;
+    assert_eq!(unpinnable.startup_flags & expected, expected);
+}
+
+#[test]
+fn prevent_pinning_requires_an_app_user_model_id() {
+    let api = MockProcessApi::new();
+    let err = Command::new("worker.exe")
+        .prevent_pinning(true)
+        .spawn_with_api(Arc::new(api.clone()))
+        .unwrap_err();
+
+    assert!(is_invalid_command(&err), "{err}");
+    assert!(api.specs().is_empty());
+}
+
+#[test]
+fn error_dialogs_are_only_a_flag_when_enabled() {
+    let enabled = spec(Command::new("worker.exe").suppress_error_dialogs(false));
+    assert_ne!(enabled.creation_flags & CREATE_DEFAULT_ERROR_MODE.0, 0);
+
+    // Suppressing them changes the error mode inherited by the child instead,
+    // which is restored after the spawn.
+    let previous = unsafe { GetErrorMode() };
+    let suppressed = spec(Command::new("worker.exe").suppress_error_dialogs(true));
+    assert_eq!(suppressed.creation_flags & CREATE_DEFAULT_ERROR_MODE.0, 0);
+    assert_eq!(unsafe { GetErrorMode() }, previous);
+
+    let default = spec(&mut Command::new("worker.exe"));
+    assert_eq!(default.creation_flags & CREATE_DEFAULT_ERROR_MODE.0, 0);
+}
+
+#[test]
+fn parent_affinity_is_a_flag() {
+    let inherited = spec(Command::new("worker.exe").inherit_parent_affinity(true));
+    assert_ne!(inherited.creation_flags & INHERIT_PARENT_AFFINITY.0, 0);
+
+    let not_inherited = spec(Command::new("worker.exe").inherit_parent_affinity(false));
+    assert_eq!(not_inherited.creation_flags & INHERIT_PARENT_AFFINITY.0, 0);
+
+    let default = spec(&mut Command::new("worker.exe"));
+    assert_eq!(default.creation_flags & INHERIT_PARENT_AFFINITY.0, 0);
+}
+
+#[test]
+fn debug_mode_is_one_of_the_debug_flags() {
+    let debug = DEBUG_ONLY_THIS_PROCESS.0 | DEBUG_PROCESS.0;
+
+    let this_process = spec(Command::new("worker.exe").debug(DebugMode::ThisProcess));
+    assert_eq!(
+        this_process.creation_flags & debug,
+        DEBUG_ONLY_THIS_PROCESS.0
+    );
+
+    let process_tree = spec(Command::new("worker.exe").debug(DebugMode::ProcessTree));
+    assert_eq!(process_tree.creation_flags & debug, DEBUG_PROCESS.0);
+
+    let default = spec(&mut Command::new("worker.exe"));
+    assert_eq!(default.creation_flags & debug, 0);
+}
+
+#[test]
+fn restricted_child_is_given_a_token() {
+    let restricted = spec(Command::new("worker.exe").restricted(RestrictedTokenOptions::new()));
+    assert!(restricted.has_token);
+
+    let default = spec(&mut Command::new("worker.exe"));
+    assert!(!default.has_token);
+}
+
+#[test]
+fn clean_environment_only_has_the_allowed_variables() {
+    env::set_var("CREATE_PROCESS_W_CLEAN_ENV", "1");
+
+    let clean = spec(
+        Command::new("worker.exe")
+            .clean_env_with(["PATH"])
+            .env("SOURCE_DATE_EPOCH", "0"),
+    );
+    let allowed = [
+        "SYSTEMROOT",
+        "SYSTEMDRIVE",
+        "WINDIR",
+        "COMSPEC",
+        "PATHEXT",
+        "TEMP",
+        "TMP",
+        "NUMBER_OF_PROCESSORS",
+        "PATH",
+        "SOURCE_DATE_EPOCH",
+    ];
+
+    let names = names(&clean);
+    assert!(
+        names.iter().all(|name| allowed.contains(&name.as_str())),
+        "{names:?}"
+    );
+    assert!(names.iter().any(|name| name == "SYSTEMROOT"), "{names:?}");
+    assert!(
+        names.iter().any(|name| name == "SOURCE_DATE_EPOCH"),
+        "{names:?}"
+    );
+    assert!(!names
+        .iter()
+        .any(|name| name == "CREATE_PROCESS_W_CLEAN_ENV"));
+}
+
+// The upper case names of the variables of the environment block.
+fn names(spec: &RecordedSpec) -> Vec<String> {
+    spec.environment
+        .as_ref()
+        .expect("no environment block")
+        .iter()
+        .map(|var| {
+            let var = var.to_str().unwrap();
+            // The hidden variables like `=C:` start with `=`.
+            let end = var[1..].find('=').unwrap() + 1;
+            var[..end].to_uppercase()
+        })
+        .collect()
+}
+
+#[test]
+fn base_environment_is_isolated_from_later_changes() {
+    env::set_var("CREATE_PROCESS_W_SNAPSHOT_CHANGED", "before");
+    let snapshot = EnvironmentSnapshot::capture();
+    env::set_var("CREATE_PROCESS_W_SNAPSHOT_CHANGED", "after");
+    env::set_var("CREATE_PROCESS_W_SNAPSHOT_ADDED", "1");
+
+    assert_eq!(
+        snapshot.get("CREATE_PROCESS_W_SNAPSHOT_CHANGED"),
+        Some(OsStr::new("before"))
+    );
+    assert_eq!(snapshot.get("CREATE_PROCESS_W_SNAPSHOT_ADDED"), None);
+
+    let based = spec(
+        Command::new("worker.exe")
+            .base_env(&snapshot)
+            .env("WORKER_ID", "1"),
+    );
+    let environment = based.environment.unwrap();
+
+    assert!(environment.contains(&OsString::from("CREATE_PROCESS_W_SNAPSHOT_CHANGED=before")));
+    assert!(environment.contains(&OsString::from("WORKER_ID=1")));
+    assert!(!environment.iter().any(|var| var
+        .to_string_lossy()
+        .starts_with("CREATE_PROCESS_W_SNAPSHOT_ADDED=")));
+}
+
+#[test]
+fn verbatim_unc_directory_is_given_as_a_unc_path() {
+    let unc = spec(Command::new("worker.exe").current_dir(r"\\?\UNC\server\share\work"));
+
+    assert_eq!(
+        unc.current_directory.as_deref(),
+        Some(Path::new(r"\\server\share\work"))
+    );
+}
+
+#[test]
+fn cmd_rejects_a_unc_directory() {
+    for dir in [r"\\server\share", r"\\?\UNC\server\share"] {
+        let api = MockProcessApi::new();
+        let err = Command::new("cmd.exe /c dir")
+            .current_dir(dir)
+            .spawn_with_api(Arc::new(api.clone()))
+            .unwrap_err();
+
+        assert!(is_invalid_command(&err), "{err}");
+        assert!(err.source().unwrap().to_string().contains("pushd"), "{err}");
+        assert!(api.specs().is_empty());
+    }
+}
+
+#[test]
+fn empty_command_lines_are_rejected_before_the_creation() {
+    for command_line in ["", "   ", "\"\""] {
+        let api = MockProcessApi::new();
+        let err = Command::new(command_line)
+            .spawn_with_api(Arc::new(api.clone()))
+            .unwrap_err();
+
+        assert!(is_invalid_command(&err), "{command_line:?}: {err}");
+        assert!(api.specs().is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use std::{env, ffi::OsStr, fs, process, time::Duration};
+use CreateProcessW::{ExitStatus, RestartPolicy, Supervisor, SupervisorEvent, SupervisorStatus};
+
+#[test]
+fn helper() {
+    common::run_helper();
+}
+
+#[test]
+fn child_is_restarted_until_it_succeeds() {
+    let runs = env::temp_dir().join(format!("create-process-w-runs-{}", process::id()));
+    let _ = fs::remove_file(&runs);
+
+    let command = common::helper("crash-until", &[runs.as_os_str(), OsStr::new("3")]);
+    let policy = RestartPolicy {
+        backoff: Duration::from_millis(10),
+        ..RestartPolicy::default()
+    };
+    let supervisor = Supervisor::spawn(command, policy);
+
+    // The channel is closed once the child isn't restarted anymore.
+    let events = supervisor.events().iter().collect::<Vec<_>>();
+    fs::remove_file(&runs).unwrap();
+
+    let exits = events
+        .iter()
+        .filter_map(|event| match event {
+            SupervisorEvent::Exited(status) => Some(*status),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let starts = events
+        .iter()
+        .filter(|event| matches!(event, SupervisorEvent::Started(_)))
+        .count();
+
+    assert_eq!(starts, 4);
+    assert_eq!(exits.len(), 4);
+    assert!(exits[..3]
+        .iter()
+        .all(|status| status.exception_code() == Some(ExitStatus::ACCESS_VIOLATION)));
+    assert!(exits[3].success());
+    assert!(!events.contains(&SupervisorEvent::GaveUp));
+
+    assert_eq!(supervisor.restarts(), 3);
+    assert_eq!(supervisor.status(), SupervisorStatus::Exited(exits[3]));
+    supervisor.stop(Duration::ZERO).unwrap();
+}
+
+#[test]
+fn supervisor_gives_up_after_the_maximum_restarts() {
+    let runs = env::temp_dir().join(format!("create-process-w-crashes-{}", process::id()));
+    let _ = fs::remove_file(&runs);
+
+    let command = common::helper("crash-until", &[runs.as_os_str(), OsStr::new("10")]);
+    let policy = RestartPolicy {
+        max_restarts: 2,
+        backoff: Duration::from_millis(10),
+        ..RestartPolicy::default()
+    };
+    let supervisor = Supervisor::spawn(command, policy);
+
+    let events = supervisor.events().iter().collect::<Vec<_>>();
+    fs::remove_file(&runs).unwrap();
+
+    assert_eq!(events.last(), Some(&SupervisorEvent::GaveUp));
+    assert_eq!(supervisor.restarts(), 2);
+    assert_eq!(supervisor.status(), SupervisorStatus::GaveUp);
+}
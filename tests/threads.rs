@@ -0,0 +1,35 @@
+use std::{sync::Arc, thread};
+use CreateProcessW::{Command, Stdio};
+
+#[test]
+fn child_is_waited_in_another_thread() {
+    let child = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+
+    let status = thread::spawn(move || child.wait().unwrap()).join().unwrap();
+
+    assert_eq!(status.code(), 3);
+}
+
+#[test]
+fn id_is_read_while_waiting() {
+    // Runs for about a second.
+    let child = Arc::new(
+        Command::new("ping.exe -n 2 127.0.0.1")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    let pid = child.id();
+
+    let waiter = {
+        let child = Arc::clone(&child);
+        thread::spawn(move || child.wait().unwrap())
+    };
+
+    while !waiter.is_finished() {
+        assert_eq!(child.id(), pid);
+        thread::yield_now();
+    }
+
+    assert!(waiter.join().unwrap().success());
+}
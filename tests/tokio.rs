@@ -0,0 +1,60 @@
+mod common;
+
+use std::{ffi::OsStr, time::Duration};
+use CreateProcessW::Command;
+
+#[test]
+fn helper() {
+    common::run_helper();
+}
+
+#[tokio::test]
+async fn wait_async_returns_the_exit_status() {
+    let child = Command::new("cmd.exe /c exit 4").spawn().unwrap();
+
+    assert_eq!(child.wait_async().await.unwrap().code(), 4);
+    // Already reaped, this returns right away.
+    assert_eq!(child.wait_async().await.unwrap().code(), 4);
+}
+
+#[tokio::test]
+async fn wait_async_completes_once_the_child_is_killed() {
+    let child = common::sleeper(30).spawn().unwrap();
+
+    let (status, ()) = tokio::join!(child.wait_async(), async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        child.kill().unwrap();
+    });
+
+    assert_eq!(status.unwrap().code(), 0);
+}
+
+#[tokio::test]
+async fn dropped_wait_async_leaves_the_child_usable() {
+    let child = common::sleeper(30).spawn().unwrap();
+
+    let res = tokio::time::timeout(Duration::from_millis(100), child.wait_async()).await;
+    assert!(res.is_err());
+    assert_eq!(child.try_wait().unwrap(), None);
+
+    child.kill().unwrap();
+    assert_eq!(child.wait_async().await.unwrap(), child.wait().unwrap());
+}
+
+#[tokio::test]
+async fn output_async_captures_megabytes() {
+    // 4 MiB, much more than the buffer of the pipes.
+    const LINES: usize = 256 * 1024;
+
+    let output = common::helper("chatty", &[OsStr::new(&LINES.to_string())])
+        .output_async()
+        .await
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(output.stderr.len(), LINES * 16);
+    assert!(output
+        .stderr_lines()
+        .enumerate()
+        .all(|(index, line)| line == format!("{index:015}")));
+}
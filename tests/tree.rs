@@ -0,0 +1,122 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+use CreateProcessW::{Command, Error, ProcessEntry, Stdio};
+
+// Wait until `matches` finds a descendant of `child`.
+fn find_descendant(
+    child: &CreateProcessW::Child,
+    matches: impl Fn(&[ProcessEntry]) -> Option<ProcessEntry>,
+) -> (ProcessEntry, Vec<ProcessEntry>) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    loop {
+        let descendants = child.descendants().unwrap();
+
+        if let Some(entry) = matches(&descendants) {
+            return (entry, descendants);
+        }
+
+        assert!(Instant::now() < deadline, "not found in {descendants:?}");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn is_ping(entry: &ProcessEntry) -> bool {
+    entry.exe_name.eq_ignore_ascii_case("ping.exe")
+}
+
+fn kill_pid(pid: u32) {
+    Command::new(format!("taskkill.exe /f /pid {pid}"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+}
+
+#[test]
+fn grandchild_is_a_descendant_at_depth_two() {
+    let child = Command::new("cmd.exe /c cmd.exe /c ping.exe -n 30 127.0.0.1")
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let (grandchild, descendants) = find_descendant(&child, |descendants| {
+        descendants.iter().find(|entry| is_ping(entry)).cloned()
+    });
+
+    assert_eq!(grandchild.depth, 2);
+    let parent = descendants
+        .iter()
+        .find(|entry| entry.pid == grandchild.parent_pid)
+        .unwrap();
+    assert_eq!(parent.depth, 1);
+    assert_eq!(parent.parent_pid, child.id());
+    assert!(parent.exe_name.eq_ignore_ascii_case("cmd.exe"));
+    assert!(grandchild.creation_time.unwrap() >= parent.creation_time.unwrap());
+    // The direct children come first.
+    assert!(descendants
+        .windows(2)
+        .all(|pair| pair[0].depth <= pair[1].depth));
+
+    kill_pid(grandchild.pid);
+    child.wait().unwrap();
+}
+
+#[test]
+fn tree_wait_waits_for_the_grandchild_outliving_its_parent() {
+    let start = Instant::now();
+    let child = Command::new("cmd.exe /c start /b ping.exe -n 3 127.0.0.1")
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let tree = child
+        .wait_tree_polling(Duration::from_millis(100), Some(Duration::from_secs(30)))
+        .unwrap();
+
+    assert!(tree.status.success());
+    assert_eq!(tree.stragglers, 0);
+    // `cmd.exe` exits right away, the ping takes about two seconds.
+    assert!(start.elapsed() >= Duration::from_millis(1500));
+}
+
+#[test]
+fn tree_wait_reports_the_stragglers_on_timeout() {
+    let child = Command::new("cmd.exe /c start /b ping.exe -n 30 127.0.0.1")
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let tree = child
+        .wait_tree_polling(Duration::from_millis(100), Some(Duration::from_secs(1)))
+        .unwrap();
+
+    assert!(tree.status.success());
+    // The ping, and the console host of `cmd.exe` if it created a console.
+    assert!(tree.stragglers >= 1);
+
+    let descendants = child.descendants().unwrap();
+    assert!(descendants.iter().any(is_ping), "{descendants:?}");
+    for entry in descendants {
+        kill_pid(entry.pid);
+    }
+}
+
+#[test]
+fn tree_wait_times_out_while_the_child_runs() {
+    let child = Command::new("ping.exe -n 30 127.0.0.1")
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    assert!(matches!(
+        child.wait_tree_polling(Duration::from_millis(50), Some(Duration::from_millis(200))),
+        Err(Error::Timeout)
+    ));
+    assert_eq!(child.exit_status(), None);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
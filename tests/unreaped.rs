@@ -0,0 +1,83 @@
+use std::{
+    ffi::OsString,
+    sync::{Arc, Mutex},
+};
+use CreateProcessW::{set_metrics_hook, Child, Command, MetricsEvent, MockProcessApi};
+
+// The hook is global, so all the cases are in a single test.
+#[test]
+fn warning_fires_only_for_the_children_not_waited_on() {
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    {
+        let warnings = Arc::clone(&warnings);
+        set_metrics_hook(move |event| {
+            if let MetricsEvent::Unreaped { pid, command, tag } = event {
+                warnings
+                    .lock()
+                    .unwrap()
+                    .push((*pid, command.clone(), tag.clone()));
+            }
+        });
+    }
+
+    let api = MockProcessApi::new();
+    let spawn = |warn: bool| -> Child {
+        Command::new("worker.exe --once")
+            .warn_on_unreaped(warn)
+            .tag("worker")
+            .spawn_with_api(Arc::new(api.clone()))
+            .unwrap()
+    };
+    let take = || std::mem::take(&mut *warnings.lock().unwrap());
+
+    // Dropped while running.
+    let child = spawn(true);
+    let pid = child.id();
+    drop(child);
+    assert_eq!(
+        take(),
+        [(
+            pid,
+            OsString::from("worker.exe --once"),
+            Some(Arc::from("worker"))
+        )]
+    );
+
+    // Dropped after exiting, without being waited on.
+    let child = spawn(true);
+    api.exit(child.id(), 0);
+    drop(child);
+    assert_eq!(take().len(), 1);
+
+    // Waited on.
+    let child = spawn(true);
+    api.exit(child.id(), 0);
+    child.wait().unwrap();
+    drop(child);
+    assert!(take().is_empty());
+
+    // Reaped by `try_wait`.
+    let child = spawn(true);
+    assert_eq!(child.try_wait().unwrap(), None);
+    api.exit(child.id(), 0);
+    assert!(child.try_wait().unwrap().is_some());
+    drop(child);
+    assert!(take().is_empty());
+
+    // Killed and reaped.
+    let child = spawn(true);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    drop(child);
+    assert!(take().is_empty());
+
+    // Forgotten on purpose.
+    spawn(true).forget();
+    assert!(take().is_empty());
+
+    // Not enabled.
+    drop(spawn(false));
+    assert!(take().is_empty());
+
+    CreateProcessW::clear_metrics_hook();
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+use windows::Win32::System::Threading::{GetCurrentThread, QueueUserAPC};
+use CreateProcessW::{wait_all, wait_any, Command, WaitOutcome};
+
+#[test]
+fn wait_any_returns_the_children_in_exit_order() {
+    let children = [3, 1, 2].map(|seconds| common::sleeper(seconds).spawn().unwrap());
+
+    let mut order = Vec::new();
+    while let Some((index, status)) = wait_any(&children, None).unwrap() {
+        assert!(status.success());
+        order.push(index);
+    }
+
+    assert_eq!(order, [1, 2, 0]);
+    // All the children have been reaped.
+    assert!(children.iter().all(|child| child.exit_status().is_some()));
+}
+
+#[test]
+fn wait_all_returns_the_statuses_in_order() {
+    let children = [2, 1, 0].map(|seconds| common::sleeper(seconds).spawn().unwrap());
+
+    // None of the children is reaped on timeout.
+    assert_eq!(
+        wait_all(&children, Some(Duration::from_millis(100))).unwrap(),
+        None
+    );
+    assert!(children.iter().all(|child| child.exit_status().is_none()));
+
+    let start = Instant::now();
+    let statuses = wait_all(&children, None).unwrap().unwrap();
+
+    assert_eq!(statuses.len(), 3);
+    assert!(statuses.iter().all(|status| status.success()));
+    // The longest child took about two seconds.
+    assert!(start.elapsed() >= Duration::from_millis(1500));
+    assert!(children
+        .iter()
+        .zip(&statuses)
+        .all(|(child, status)| child.exit_status() == Some(*status)));
+}
+
+#[test]
+fn message_wait_times_out_like_the_alertable_wait() {
+    let child = common::sleeper(30).spawn().unwrap();
+    let timeout = Duration::from_millis(200);
+
+    let start = Instant::now();
+    assert!(matches!(
+        child.wait_alertable(Some(timeout)).unwrap(),
+        WaitOutcome::TimedOut
+    ));
+    assert!(start.elapsed() >= Duration::from_millis(150));
+
+    // There is no window, only the timeout ends the wait.
+    let start = Instant::now();
+    assert_eq!(child.wait_pumping_messages(Some(timeout)).unwrap(), None);
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(150), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    assert_eq!(child.exit_status(), None);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn message_wait_reaps_the_child_like_the_alertable_wait() {
+    let alertable = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    let status = match alertable
+        .wait_alertable(Some(Duration::from_secs(10)))
+        .unwrap()
+    {
+        WaitOutcome::Exited(status) => status,
+        outcome => panic!("unexpected outcome: {outcome:?}"),
+    };
+    assert_eq!(status.code(), 3);
+    assert_eq!(alertable.exit_status(), Some(status));
+
+    let pumping = Command::new("cmd.exe /c exit 3").spawn().unwrap();
+    let status = pumping
+        .wait_pumping_messages(Some(Duration::from_secs(10)))
+        .unwrap()
+        .unwrap();
+    assert_eq!(status.code(), 3);
+    assert_eq!(pumping.exit_status(), Some(status));
+}
+
+#[test]
+fn queued_apc_interrupts_the_alertable_wait() {
+    static RAN: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "system" fn apc(_data: usize) {
+        RAN.store(true, Ordering::SeqCst);
+    }
+
+    let child = common::sleeper(30).spawn().unwrap();
+
+    // Queued to the calling thread, it runs as soon as the wait starts.
+    assert_ne!(unsafe { QueueUserAPC(Some(apc), GetCurrentThread(), 0) }, 0);
+    assert!(matches!(
+        child.wait_alertable(None).unwrap(),
+        WaitOutcome::Interrupted
+    ));
+    assert!(RAN.load(Ordering::SeqCst));
+
+    // The wait can be started again.
+    assert!(matches!(
+        child
+            .wait_alertable(Some(Duration::from_millis(50)))
+            .unwrap(),
+        WaitOutcome::TimedOut
+    ));
+    assert_eq!(child.exit_status(), None);
+
+    child.kill().unwrap();
+    assert!(matches!(
+        child.wait_alertable(None).unwrap(),
+        WaitOutcome::Exited(_)
+    ));
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+use CreateProcessW::Command;
+
+#[test]
+fn expired_watchdog_terminates_the_child() {
+    let start = Instant::now();
+    let (child, watchdog) = common::sleeper(30)
+        .spawn_with_timeout(Duration::from_millis(200))
+        .unwrap();
+
+    assert_eq!(child.wait().unwrap().code(), 1);
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert!(watchdog.disarm());
+}
+
+#[test]
+fn disarmed_watchdog_leaves_the_child_running() {
+    let (child, watchdog) = common::sleeper(30)
+        .spawn_with_timeout(Duration::from_millis(300))
+        .unwrap();
+
+    assert!(!watchdog.disarm());
+    thread::sleep(Duration::from_millis(600));
+    assert_eq!(child.try_wait().unwrap(), None);
+
+    child.kill().unwrap();
+    assert_eq!(child.wait().unwrap().code(), 0);
+}
+
+#[test]
+fn watchdog_stops_once_the_child_exits() {
+    let start = Instant::now();
+    let (child, watchdog) = Command::new("cmd.exe /c exit 3")
+        .spawn_with_timeout(Duration::from_secs(60))
+        .unwrap();
+
+    assert_eq!(child.wait().unwrap().code(), 3);
+    assert!(!watchdog.disarm());
+    assert!(start.elapsed() < Duration::from_secs(10));
+}